@@ -4,7 +4,9 @@ use crate::apis::api_client::{ApiClientEnum, DynApiClient, Message};
 use crate::apis::gemini::GeminiClient;
 use crate::apis::ollama::OllamaClient;
 use crate::apis::openai::OpenAIClient;
-use crate::prompts::add_working_directory_to_prompt;
+use crate::prompts::{add_repo_map_to_prompt, add_working_directory_to_prompt};
+use crate::tools::fs::repo_map::RepoMap;
+use crate::tools::shell::PersistentShell;
 use anyhow::{Context, Result};
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -25,6 +27,12 @@ pub struct Agent {
     system_prompt: Option<String>,
     working_directory: Option<String>,
     progress_sender: Option<mpsc::Sender<String>>,
+    thinking_budget: Option<u32>,
+    image_input: bool,
+    // Token budget of the repository map added to the system prompt
+    repo_map_tokens: Option<usize>,
+    // Shell the Bash tool runs commands in
+    shell: Option<PersistentShell>,
     // Store the conversation history
     conversation_history: Vec<crate::apis::api_client::Message>,
 }
@@ -38,6 +46,10 @@ impl Agent {
             system_prompt: None,
             working_directory: None,
             progress_sender: None,
+            thinking_budget: None,
+            image_input: true,
+            repo_map_tokens: None,
+            shell: None,
             conversation_history: Vec::new(),
         }
     }
@@ -72,6 +84,48 @@ impl Agent {
         self
     }
 
+    /// Enable extended thinking with the given token budget (Anthropic models only)
+    pub fn with_thinking_budget(mut self, budget: Option<u32>) -> Self {
+        self.thinking_budget = budget;
+        self
+    }
+
+    /// Set whether the model can accept images (e.g. from reading image files)
+    pub fn with_image_support(mut self, supported: bool) -> Self {
+        self.image_input = supported;
+        self
+    }
+
+    /// Add an outline of the working directory, cut to the given token
+    /// budget, to the system prompt
+    pub fn with_repo_map(mut self, max_tokens: Option<usize>) -> Self {
+        self.repo_map_tokens = max_tokens.filter(|tokens| *tokens > 0);
+        self
+    }
+
+    /// Run Bash tool commands in the given session shell instead of a fresh
+    /// one in the current directory
+    pub fn with_shell(mut self, shell: PersistentShell) -> Self {
+        self.shell = Some(shell);
+        self
+    }
+
+    /// The repository map for the system prompt, if one was asked for and the
+    /// working directory has source files
+    fn repo_map(&self) -> Option<String> {
+        let max_tokens = self.repo_map_tokens?;
+        let working_dir = self.working_directory.as_deref()?;
+        match RepoMap::outline(std::path::Path::new(working_dir), max_tokens) {
+            Ok(outline) => Some(outline).filter(|outline| !outline.is_empty()),
+            Err(e) => {
+                if let Some(sender) = &self.progress_sender {
+                    let _ = sender.try_send(format!("[debug] No repository map: {e}"));
+                }
+                None
+            }
+        }
+    }
+
     pub fn clear_history(&mut self) {
         self.conversation_history.clear();
     }
@@ -143,7 +197,12 @@ impl Agent {
             .context("Agent not initialized. Call initialize() first.")?;
 
         // Create and configure executor with persisted conversation history
-        let mut executor = AgentExecutor::new(api_client.clone());
+        let mut executor = AgentExecutor::new(api_client.clone())
+            .with_thinking_budget(self.thinking_budget)
+            .with_image_support(self.image_input);
+        if let Some(shell) = &self.shell {
+            executor = executor.with_shell(shell.clone());
+        }
 
         // Add existing conversation history if any
         if !self.conversation_history.is_empty() {
@@ -203,30 +262,37 @@ impl Agent {
                 // Otherwise use default prompt
                 crate::prompts::get_agent_prompt_with_cwd(working_dir_ref)
             };
+            let prompt = match self.repo_map() {
+                Some(repo_map) => add_repo_map_to_prompt(&prompt, &repo_map),
+                None => prompt,
+            };
 
             // Add system message
             executor.add_system_message(prompt);
-        } else {
+        } else if let Some(existing_system_msg) = self
+            .conversation_history
+            .iter()
+            .find(|msg| msg.role == "system")
+        {
             // System message exists - ensure it has working directory information
+            let mut updated_content = existing_system_msg.content.clone();
             if let Some(working_dir) = &self.working_directory {
-                // Find and update existing system message with CWD info
-                if let Some(existing_system_msg) = self
-                    .conversation_history
-                    .iter()
-                    .find(|msg| msg.role == "system")
-                {
-                    // Check if the existing system message already contains CWD info
-                    if !existing_system_msg.content.contains("## WORKING DIRECTORY") {
-                        // Create updated system message with CWD
-                        let updated_content = add_working_directory_to_prompt(
-                            &existing_system_msg.content,
-                            working_dir,
-                        );
-                        // Replace the existing system message
-                        executor.add_system_message(updated_content);
-                    }
+                // Check if the existing system message already contains CWD info
+                if !updated_content.contains("## WORKING DIRECTORY") {
+                    updated_content =
+                        add_working_directory_to_prompt(&updated_content, working_dir);
                 }
             }
+            // Add the repository map the first time around
+            if !updated_content.contains("## REPOSITORY MAP") {
+                if let Some(repo_map) = self.repo_map() {
+                    updated_content = add_repo_map_to_prompt(&updated_content, &repo_map);
+                }
+            }
+            if updated_content != existing_system_msg.content {
+                // Replace the existing system message
+                executor.add_system_message(updated_content);
+            }
         }
 
         // Add the original user query
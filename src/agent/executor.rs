@@ -1,8 +1,10 @@
 use crate::agent::tools::{get_tool_definitions, ToolCall as AgentToolCall};
 use crate::apis::api_client::{
-    CompletionOptions, DynApiClient, Message, ToolCall as ApiToolCall, ToolDefinition, ToolResult,
+    CompletionOptions, ContentBlock, DynApiClient, Message, ToolCall as ApiToolCall,
+    ToolDefinition, ToolResult,
 };
 use crate::prompts::add_working_directory_to_prompt;
+use crate::tools::shell::PersistentShell;
 use anyhow::{Context, Result};
 use serde_json::{self, Value};
 use tokio::sync::mpsc;
@@ -13,6 +15,9 @@ pub struct AgentExecutor {
     tool_definitions: Vec<ToolDefinition>,
     progress_sender: Option<mpsc::Sender<String>>,
     working_directory: Option<String>,
+    thinking_budget: Option<u32>,
+    image_input: bool,
+    shell: Option<PersistentShell>,
 }
 
 impl AgentExecutor {
@@ -32,6 +37,9 @@ impl AgentExecutor {
             tool_definitions: tool_defs,
             progress_sender: None,
             working_directory: None,
+            thinking_budget: None,
+            image_input: true,
+            shell: None,
         }
     }
 
@@ -78,6 +86,25 @@ impl AgentExecutor {
         self
     }
 
+    /// Enable extended thinking with the given token budget for every completion
+    pub fn with_thinking_budget(mut self, budget: Option<u32>) -> Self {
+        self.thinking_budget = budget;
+        self
+    }
+
+    /// Whether the model accepts images; when it doesn't, reading an image file
+    /// reports an error to the model instead of attaching the image
+    pub fn with_image_support(mut self, supported: bool) -> Self {
+        self.image_input = supported;
+        self
+    }
+
+    /// Shell the Bash tool runs commands in
+    pub fn with_shell(mut self, shell: PersistentShell) -> Self {
+        self.shell = Some(shell);
+        self
+    }
+
     pub fn add_system_message(&mut self, content: String) {
         // If we have a working directory, ensure it's included in the system message
         let system_content = if let Some(cwd) = &self.working_directory {
@@ -119,17 +146,17 @@ impl AgentExecutor {
         let options = self.create_completion_options();
 
         // Get initial completion
-        let (content, tool_calls) = self.get_initial_completion(&options).await?;
+        let (content, tool_calls, thinking) = self.get_initial_completion(&options).await?;
 
         // If no tool calls, just return the response
         if tool_calls.is_none() {
-            self.add_assistant_response(&content, &None);
+            self.add_assistant_response(&content, &None, thinking);
             return Ok(content);
         }
 
         // Process tool calls iteratively
         let result = self
-            .process_tool_calls(content, tool_calls, options)
+            .process_tool_calls(content, tool_calls, thinking, options)
             .await?;
 
         Ok(result)
@@ -153,6 +180,7 @@ impl AgentExecutor {
             tools: Some(self.tool_definitions.clone()),
             require_tool_use: false,
             json_schema: None,
+            thinking_budget: self.thinking_budget,
         }
     }
 
@@ -160,15 +188,25 @@ impl AgentExecutor {
     async fn get_initial_completion(
         &self,
         options: &CompletionOptions,
-    ) -> Result<(String, Option<Vec<ApiToolCall>>)> {
+    ) -> Result<(String, Option<Vec<ApiToolCall>>, Vec<ContentBlock>)> {
         self.api_client
-            .complete_with_tools(self.conversation.clone(), options.clone(), None)
+            .complete_with_reasoning(self.conversation.clone(), options.clone(), None)
             .await
     }
 
     // Helper method to add an assistant's response to the conversation
-    fn add_assistant_response(&mut self, content: &str, tool_calls: &Option<Vec<ApiToolCall>>) {
-        add_assistant_message_to_conversation(&mut self.conversation, content, tool_calls);
+    fn add_assistant_response(
+        &mut self,
+        content: &str,
+        tool_calls: &Option<Vec<ApiToolCall>>,
+        thinking: Vec<ContentBlock>,
+    ) {
+        add_assistant_message_to_conversation(
+            &mut self.conversation,
+            content,
+            tool_calls,
+            thinking,
+        );
     }
 
     // Process tool calls in a loop until task is complete
@@ -176,14 +214,17 @@ impl AgentExecutor {
         &mut self,
         initial_content: String,
         initial_tool_calls: Option<Vec<ApiToolCall>>,
+        initial_thinking: Vec<ContentBlock>,
         options: CompletionOptions,
     ) -> Result<String> {
         // Add the assistant's message with tool calls to the conversation
-        self.add_assistant_response(&initial_content, &initial_tool_calls);
+        self.add_assistant_response(&initial_content, &initial_tool_calls, initial_thinking);
 
         // Process tool calls in a loop until task is complete
         let mut current_content = initial_content;
         let mut current_tool_calls = initial_tool_calls;
+        let mut current_thinking = Vec::new();
+        let mut last_turn_recorded = true;
         let mut loop_count = 0;
         const MAX_LOOPS: usize = 100; // Limit for tool call loops
         let mut task_completed = false;
@@ -201,13 +242,15 @@ impl AgentExecutor {
             let tool_results = self.execute_tool_calls(calls, loop_count).await;
 
             // Get next completion with appropriate options
-            let (next_content, next_tool_calls, is_complete) = self
+            let (next_content, next_tool_calls, next_thinking, is_complete) = self
                 .get_next_completion(tool_results, loop_count, MAX_LOOPS, &options)
                 .await?;
 
             // Update state for next iteration
             current_content = next_content;
             current_tool_calls = next_tool_calls;
+            current_thinking = next_thinking;
+            last_turn_recorded = false;
 
             // Update task completion status
             if is_complete {
@@ -219,6 +262,15 @@ impl AgentExecutor {
                 break;
             }
 
+            // Record the assistant turn that requested the next tool calls so their
+            // results (and any thinking blocks) line up with it in the history
+            self.add_assistant_response(
+                &current_content,
+                &current_tool_calls,
+                std::mem::take(&mut current_thinking),
+            );
+            last_turn_recorded = true;
+
             // Log warning if approaching max loops
             self.log_approaching_max_loops(loop_count, MAX_LOOPS).await;
         }
@@ -226,10 +278,13 @@ impl AgentExecutor {
         // Request final summary if needed
         if !task_completed && current_tool_calls.is_none() && loop_count < MAX_LOOPS - 1 {
             current_content = self.request_final_summary(&options).await?;
+            last_turn_recorded = false;
         }
 
         // Add final response to conversation
-        self.add_assistant_response(&current_content, &current_tool_calls);
+        if !last_turn_recorded {
+            self.add_assistant_response(&current_content, &current_tool_calls, current_thinking);
+        }
 
         Ok(current_content)
     }
@@ -275,7 +330,7 @@ impl AgentExecutor {
         loop_count: usize,
         max_loops: usize,
         base_options: &CompletionOptions,
-    ) -> Result<(String, Option<Vec<ApiToolCall>>, bool)> {
+    ) -> Result<(String, Option<Vec<ApiToolCall>>, Vec<ContentBlock>, bool)> {
         // Determine whether to request task completion
         let completion_threshold = determine_completion_threshold(loop_count);
         let should_check_completion =
@@ -289,15 +344,15 @@ impl AgentExecutor {
         };
 
         // Request completion with tool results
-        let (next_content, next_tool_calls) = self
+        let (next_content, next_tool_calls, thinking) = self
             .api_client
-            .complete_with_tools(self.conversation.clone(), next_options, Some(tool_results))
+            .complete_with_reasoning(self.conversation.clone(), next_options, Some(tool_results))
             .await?;
 
         // Process response to check for completion status
         let (processed_content, is_complete) = process_response(&next_content);
 
-        Ok((processed_content, next_tool_calls, is_complete))
+        Ok((processed_content, next_tool_calls, thinking, is_complete))
     }
 
     // Create options for checking task completion
@@ -334,7 +389,7 @@ impl AgentExecutor {
 
     // Log warning if approaching max loops
     async fn log_approaching_max_loops(&self, loop_count: usize, max_loops: usize) {
-        if loop_count >= max_loops - 10 && loop_count % 5 == 0 {
+        if loop_count >= max_loops - 10 && loop_count.is_multiple_of(5) {
             if let Some(sender) = &self.progress_sender {
                 let _ = sender
                     .send(
@@ -399,6 +454,11 @@ impl AgentExecutor {
 
             // Parse the tool call into our enum
             let tool_call: AgentToolCall = match parse_tool_call(&call.name, &call.arguments) {
+                // Patches apply in the agent's working directory unless told otherwise
+                Ok(AgentToolCall::Patch(mut params)) if params.base_dir.is_none() => {
+                    params.base_dir = self.working_directory.clone();
+                    AgentToolCall::Patch(params)
+                }
                 Ok(tc) => tc,
                 Err(e) => {
                     send_error_message(
@@ -411,7 +471,7 @@ impl AgentExecutor {
                     let tool_call_id = call.id.clone().unwrap_or_else(|| format!("tool_{i}"));
                     let error_message = format!("ERROR PARSING TOOL CALL: {e}. Please check the format of your arguments and try again.");
 
-                    self.add_tool_result_to_conversation(&tool_call_id, &error_message);
+                    self.add_tool_result_to_conversation(&tool_call_id, &error_message, Vec::new());
                     results.push(ToolResult {
                         tool_call_id,
                         output: error_message,
@@ -422,7 +482,21 @@ impl AgentExecutor {
             };
 
             // Execute the tool with preview for file modification tools
-            let result = execute_tool_with_preview(&tool_call, call, &self.progress_sender).await;
+            let (mut result, mut blocks) =
+                execute_tool_with_preview(&tool_call, call, &self.progress_sender, &self.shell)
+                    .await;
+
+            // Text-only models can't look at images, so tell the model instead
+            if !blocks.is_empty() && !self.image_input {
+                let path = match &tool_call {
+                    AgentToolCall::Read(params) => params.file_path.as_str(),
+                    _ => "the file",
+                };
+                result = format!(
+                    "ERROR EXECUTING TOOL: {path} is an image, and the current model does not support image input"
+                );
+                blocks.clear();
+            }
 
             // Create a valid tool result ID
             let tool_call_id = call.id.clone().unwrap_or_else(|| format!("tool_{i}"));
@@ -433,7 +507,7 @@ impl AgentExecutor {
             }
 
             // Add tool result to conversation and results collection
-            self.add_tool_result_to_conversation(&tool_call_id, &result);
+            self.add_tool_result_to_conversation(&tool_call_id, &result, blocks);
             results.push(ToolResult {
                 tool_call_id,
                 output: result,
@@ -443,11 +517,16 @@ impl AgentExecutor {
         results
     }
 
-    fn add_tool_result_to_conversation(&mut self, tool_call_id: &str, result: &str) {
-        self.conversation.push(Message {
-            role: "user".to_string(),
-            content: format!("Tool result for call {tool_call_id}: {result}"),
-        });
+    fn add_tool_result_to_conversation(
+        &mut self,
+        tool_call_id: &str,
+        result: &str,
+        blocks: Vec<ContentBlock>,
+    ) {
+        self.conversation.push(
+            Message::user(format!("Tool result for call {tool_call_id}: {result}"))
+                .with_blocks(blocks),
+        );
     }
 }
 
@@ -457,6 +536,7 @@ fn add_assistant_message_to_conversation(
     conversation: &mut Vec<Message>,
     content: &str,
     tool_calls: &Option<Vec<ApiToolCall>>,
+    thinking: Vec<ContentBlock>,
 ) {
     if let Some(calls) = tool_calls {
         // Create a JSON object with both content and tool calls
@@ -472,12 +552,15 @@ fn add_assistant_message_to_conversation(
         });
 
         // Store as JSON string in the message
-        conversation.push(Message::assistant(
-            serde_json::to_string(&message_with_tools).unwrap_or_else(|_| content.to_string()),
-        ));
+        conversation.push(
+            Message::assistant(
+                serde_json::to_string(&message_with_tools).unwrap_or_else(|_| content.to_string()),
+            )
+            .with_blocks(thinking),
+        );
     } else {
         // No tool calls, just store the content directly
-        conversation.push(Message::assistant(content.to_string()));
+        conversation.push(Message::assistant(content.to_string()).with_blocks(thinking));
     }
 }
 
@@ -503,7 +586,7 @@ pub fn should_request_completion(loop_count: usize, max_loops: usize, threshold:
     }
 
     // Periodically check based on threshold
-    if threshold == 1 || loop_count % threshold == 0 {
+    if threshold == 1 || loop_count.is_multiple_of(threshold) {
         return true;
     }
 
@@ -543,9 +626,13 @@ async fn execute_tool_with_preview(
     tool_call: &AgentToolCall,
     call: &ApiToolCall,
     progress_sender: &Option<mpsc::Sender<String>>,
-) -> String {
+    shell: &Option<PersistentShell>,
+) -> (String, Vec<ContentBlock>) {
     // Check if tool needs diff preview
-    let needs_diff_preview = matches!(call.name.as_str(), "Edit" | "Write");
+    let needs_diff_preview = matches!(
+        call.name.as_str(),
+        "Edit" | "MultiEdit" | "Patch" | "Write" | "Rename" | "Format" | "ApplyCodeAction"
+    );
 
     let result = if needs_diff_preview {
         // Handle file modification tools with diff preview
@@ -568,7 +655,51 @@ async fn execute_tool_with_preview(
                             let _ = sender.send(diff.clone()).await;
                         }
                         // Execute the tool
-                        tool_call.execute()
+                        tool_call.execute().map(|output| (output, Vec::new()))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            AgentToolCall::MultiEdit(params) => {
+                use crate::tools::fs::file_ops::FileOps;
+                use std::path::PathBuf;
+
+                // Generate the combined diff without making changes
+                let path = PathBuf::from(&params.file_path);
+                match FileOps::generate_multi_edit_diff(&path, &params.edits) {
+                    Ok((_, diff)) => {
+                        // Send diff as progress message
+                        if let Some(sender) = progress_sender {
+                            let _ = sender.send(diff.clone()).await;
+                        }
+                        // Execute the tool
+                        tool_call.execute().map(|output| (output, Vec::new()))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            AgentToolCall::Patch(params) => {
+                use crate::tools::fs::patch::{PatchTools, DEFAULT_FUZZ};
+                use std::path::PathBuf;
+
+                // Check the patch without making changes and show what it would do
+                let base_dir = match &params.base_dir {
+                    Some(dir) => Ok(PathBuf::from(dir)),
+                    None => std::env::current_dir().map_err(anyhow::Error::from),
+                };
+                let fuzz = params.fuzz.unwrap_or(DEFAULT_FUZZ);
+                match base_dir.and_then(|dir| PatchTools::check(&params.patch, &dir, fuzz)) {
+                    Ok(report) => {
+                        // A patch that doesn't apply is reported by the tool itself
+                        if report.succeeded() {
+                            if let Some(sender) = progress_sender {
+                                let diff: String =
+                                    report.files.iter().filter_map(|f| f.diff.clone()).collect();
+                                let _ = sender.send(diff).await;
+                            }
+                        }
+                        // Execute the tool
+                        tool_call.execute().map(|output| (output, Vec::new()))
                     }
                     Err(e) => Err(e),
                 }
@@ -586,25 +717,74 @@ async fn execute_tool_with_preview(
                             let _ = sender.send(diff.clone()).await;
                         }
                         // Execute the tool
-                        tool_call.execute()
+                        tool_call.execute().map(|output| (output, Vec::new()))
                     }
                     Err(e) => Err(e),
                 }
             }
-            _ => tool_call.execute(), // Shouldn't happen, but fallback
+            AgentToolCall::Rename(_) | AgentToolCall::Format(_) => {
+                // Ask the server for the edits once: the diff shown is exactly
+                // what gets written
+                match tool_call.planned_changes() {
+                    Some(Ok(changes)) => match changes.diff() {
+                        Ok(diff) => {
+                            // Send diff as progress message
+                            if let Some(sender) = progress_sender {
+                                if !diff.is_empty() {
+                                    let _ = sender.send(diff).await;
+                                }
+                            }
+                            // Execute the tool with the same changes
+                            tool_call
+                                .execute_with_changes(changes)
+                                .map(|output| (output, Vec::new()))
+                        }
+                        Err(e) => Err(e),
+                    },
+                    Some(Err(e)) => Err(e),
+                    None => tool_call.execute_with_blocks(),
+                }
+            }
+            AgentToolCall::ApplyCodeAction(params) => {
+                use crate::tools::lsp::LspServerManager;
+
+                // Resolve the action once: the edit shown is the one written
+                match LspServerManager::global().prepare_code_action(params.id) {
+                    Ok(prepared) => match prepared.changes.diff() {
+                        Ok(diff) => {
+                            // Send diff as progress message
+                            if let Some(sender) = progress_sender {
+                                if !diff.is_empty() {
+                                    let _ = sender.send(diff).await;
+                                }
+                            }
+                            // Execute the tool with the resolved action
+                            tool_call
+                                .execute_with_code_action(prepared)
+                                .map(|output| (output, Vec::new()))
+                        }
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e),
+                }
+            }
+            _ => tool_call.execute_with_blocks(), // Shouldn't happen, but fallback
         }
+    } else if let Some(shell) = shell {
+        tool_call.execute_with_shell(shell)
     } else {
-        // For non-file operations, execute normally
-        tool_call.execute()
+        // For non-file operations, execute normally (Read may return image blocks)
+        tool_call.execute_with_blocks()
     };
 
     match result {
         Ok(output) => output,
-        Err(e) => format!("ERROR EXECUTING TOOL: {e}"),
+        Err(e) => (format!("ERROR EXECUTING TOOL: {e}"), Vec::new()),
     }
 }
 
-fn parse_tool_call(name: &str, args: &Value) -> Result<AgentToolCall> {
+/// The tool call a model asked for by name, with its arguments
+pub fn parse_tool_call(name: &str, args: &Value) -> Result<AgentToolCall> {
     match name {
         "Read" => {
             let params =
@@ -621,6 +801,16 @@ fn parse_tool_call(name: &str, args: &Value) -> Result<AgentToolCall> {
                 serde_json::from_value(args.clone()).context("Failed to parse Grep parameters")?;
             Ok(AgentToolCall::Grep(params))
         }
+        "FindFile" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse FindFile parameters")?;
+            Ok(AgentToolCall::FindFile(params))
+        }
+        "RepoMap" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse RepoMap parameters")?;
+            Ok(AgentToolCall::RepoMap(params))
+        }
         "LS" => {
             let params =
                 serde_json::from_value(args.clone()).context("Failed to parse LS parameters")?;
@@ -631,6 +821,16 @@ fn parse_tool_call(name: &str, args: &Value) -> Result<AgentToolCall> {
                 serde_json::from_value(args.clone()).context("Failed to parse Edit parameters")?;
             Ok(AgentToolCall::Edit(params))
         }
+        "MultiEdit" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse MultiEdit parameters")?;
+            Ok(AgentToolCall::MultiEdit(params))
+        }
+        "Patch" => {
+            let params =
+                serde_json::from_value(args.clone()).context("Failed to parse Patch parameters")?;
+            Ok(AgentToolCall::Patch(params))
+        }
         "Write" => {
             let params =
                 serde_json::from_value(args.clone()).context("Failed to parse Write parameters")?;
@@ -641,6 +841,86 @@ fn parse_tool_call(name: &str, args: &Value) -> Result<AgentToolCall> {
                 serde_json::from_value(args.clone()).context("Failed to parse Bash parameters")?;
             Ok(AgentToolCall::Bash(params))
         }
+        "BashOutput" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse BashOutput parameters")?;
+            Ok(AgentToolCall::BashOutput(params))
+        }
+        "KillProcess" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse KillProcess parameters")?;
+            Ok(AgentToolCall::KillProcess(params))
+        }
+        "DocumentSymbol" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse DocumentSymbol parameters")?;
+            Ok(AgentToolCall::DocumentSymbol(params))
+        }
+        "SemanticTokens" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse SemanticTokens parameters")?;
+            Ok(AgentToolCall::SemanticTokens(params))
+        }
+        "CodeLens" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse CodeLens parameters")?;
+            Ok(AgentToolCall::CodeLens(params))
+        }
+        "Definition" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse Definition parameters")?;
+            Ok(AgentToolCall::Definition(params))
+        }
+        "Diagnostics" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse Diagnostics parameters")?;
+            Ok(AgentToolCall::Diagnostics(params))
+        }
+        "References" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse References parameters")?;
+            Ok(AgentToolCall::References(params))
+        }
+        "Hover" => {
+            let params =
+                serde_json::from_value(args.clone()).context("Failed to parse Hover parameters")?;
+            Ok(AgentToolCall::Hover(params))
+        }
+        "WorkspaceSymbol" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse WorkspaceSymbol parameters")?;
+            Ok(AgentToolCall::WorkspaceSymbol(params))
+        }
+        "Rename" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse Rename parameters")?;
+            Ok(AgentToolCall::Rename(params))
+        }
+        "Format" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse Format parameters")?;
+            Ok(AgentToolCall::Format(params))
+        }
+        "CodeActions" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse CodeActions parameters")?;
+            Ok(AgentToolCall::CodeActions(params))
+        }
+        "ApplyCodeAction" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse ApplyCodeAction parameters")?;
+            Ok(AgentToolCall::ApplyCodeAction(params))
+        }
+        "CallHierarchy" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse CallHierarchy parameters")?;
+            Ok(AgentToolCall::CallHierarchy(params))
+        }
+        "TypeHierarchy" => {
+            let params = serde_json::from_value(args.clone())
+                .context("Failed to parse TypeHierarchy parameters")?;
+            Ok(AgentToolCall::TypeHierarchy(params))
+        }
         _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
     }
 }
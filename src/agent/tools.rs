@@ -1,31 +1,73 @@
+use crate::apis::api_client::ContentBlock;
 use crate::tools::{
-    fs::file_ops::FileOps,
-    fs::search::SearchTools,
+    fs::file_ops::{ChangeSet, FileEdit, FileOps},
+    fs::fuzzy::FileFinder,
+    fs::image::ImageOps,
+    fs::patch::{PatchTools, DEFAULT_FUZZ},
+    fs::repo_map::{RepoMap, DEFAULT_REPO_MAP_TOKENS, MAX_REPO_MAP_TOKENS},
+    fs::search::{
+        GrepOptions, GrepOutputMode, SearchTools, DEFAULT_GLOB_LIMIT, DEFAULT_GREP_HEAD_LIMIT,
+    },
     lsp::{
-        DefinitionParams, LspServerManager, ModelsCodeLensParams as CodeLensParams,
+        symbol_kind_name, uri_to_path, ApplyCodeActionParams, CallDirection, CallHierarchyParams,
+        CodeActionsParams, DefinitionParams, DiagnosticsParams, FormatParams, HoverParams,
+        LspServerManager, ModelsCodeLensParams as CodeLensParams,
         ModelsDocumentSymbolParams as DocumentSymbolParams,
-        ModelsSemanticTokensParams as SemanticTokensParams,
+        ModelsSemanticTokensParams as SemanticTokensParams, PreparedCodeAction, ReferencesParams,
+        RenameParams, TypeDirection, TypeHierarchyParams, WorkspaceSymbolParams,
+        DEFAULT_HIERARCHY_DEPTH, MAX_HIERARCHY_DEPTH,
+    },
+    shell::process::ProcessManager,
+    shell::session::{
+        PersistentShell, ShellOutput, ShellSession, DEFAULT_COMMAND_TIMEOUT, MAX_COMMAND_TIMEOUT,
     },
 };
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Files the FindFile tool lists when no `limit` is given
+pub const DEFAULT_FIND_FILE_LIMIT: usize = 20;
+/// Diagnostics the Diagnostics tool lists
+pub const MAX_DIAGNOSTICS: usize = 100;
+/// Diagnostics appended to the result of a tool that changed files
+pub const MAX_EDIT_DIAGNOSTICS: usize = 20;
+/// References the References tool lists
+pub const MAX_REFERENCES: usize = 200;
+/// Symbols the WorkspaceSymbol tool lists
+pub const MAX_WORKSPACE_SYMBOLS: usize = 100;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ToolType {
     Read,
     Glob,
     Grep,
+    FindFile,
+    RepoMap,
     LS,
     Edit,
+    MultiEdit,
+    Patch,
     Write,
     Bash,
+    BashOutput,
+    KillProcess,
     DocumentSymbol,
     SemanticTokens,
     CodeLens,
     Definition,
+    Diagnostics,
+    References,
+    Hover,
+    WorkspaceSymbol,
+    Rename,
+    Format,
+    CodeActions,
+    ApplyCodeAction,
+    CallHierarchy,
+    TypeHierarchy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,17 +77,79 @@ pub struct ReadParams {
     pub limit: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GlobParams {
     pub pattern: String,
     pub path: Option<String>,
+    /// Maximum files listed; defaults to DEFAULT_GLOB_LIMIT, 0 for no limit
+    pub limit: Option<usize>,
+    /// Number of files skipped, to page through results with `limit`
+    pub offset: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GrepParams {
     pub pattern: String,
     pub include: Option<String>,
     pub path: Option<String>,
+    /// "content" (default), "files_with_matches" or "count"
+    pub output_mode: Option<GrepOutputMode>,
+    /// Lines of context after each match
+    #[serde(rename = "-A", default, skip_serializing_if = "Option::is_none")]
+    pub after_context: Option<usize>,
+    /// Lines of context before each match
+    #[serde(rename = "-B", default, skip_serializing_if = "Option::is_none")]
+    pub before_context: Option<usize>,
+    /// Lines of context before and after each match
+    #[serde(rename = "-C", default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<usize>,
+    #[serde(rename = "-i", default, skip_serializing_if = "Option::is_none")]
+    pub case_insensitive: Option<bool>,
+    pub multiline: Option<bool>,
+    /// File type to search, e.g. "rs", "py" or "ts"
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    /// Maximum entries shown; defaults to DEFAULT_GREP_HEAD_LIMIT, 0 for no limit
+    pub head_limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl GrepParams {
+    /// Search options for these parameters. -A and -B take precedence over -C.
+    pub fn to_options(&self) -> GrepOptions {
+        let context = self.context.unwrap_or(0);
+        GrepOptions {
+            include: self.include.clone(),
+            file_type: self.file_type.clone(),
+            case_insensitive: self.case_insensitive.unwrap_or(false),
+            multiline: self.multiline.unwrap_or(false),
+            before_context: self.before_context.unwrap_or(context),
+            after_context: self.after_context.unwrap_or(context),
+            output_mode: self.output_mode.unwrap_or_default(),
+            head_limit: match self.head_limit.unwrap_or(DEFAULT_GREP_HEAD_LIMIT) {
+                0 => None,
+                limit => Some(limit),
+            },
+            offset: self.offset.unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindFileParams {
+    /// Approximate file name or path, e.g. "usrctrl" for "src/user_controller.rs"
+    pub query: String,
+    /// Directory to search in (defaults to the current directory)
+    pub path: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoMapParams {
+    /// Directory to map (defaults to the current directory)
+    pub path: Option<String>,
+    /// Rough size of the map in tokens; defaults to DEFAULT_REPO_MAP_TOKENS
+    pub max_tokens: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +166,21 @@ pub struct EditParams {
     pub expected_replacements: Option<usize>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiEditParams {
+    pub file_path: String,
+    pub edits: Vec<FileEdit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchParams {
+    pub patch: String,
+    /// Directory that relative paths in the patch are resolved against
+    pub base_dir: Option<String>,
+    /// Context lines that may be ignored at each end of a hunk
+    pub fuzz: Option<usize>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WriteParams {
     pub file_path: String,
@@ -73,6 +192,21 @@ pub struct BashParams {
     pub command: String,
     pub timeout: Option<u64>,
     pub description: Option<String>,
+    /// Start the command and return immediately with a process id
+    pub run_in_background: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BashOutputParams {
+    /// Id of a background process, as returned by Bash with run_in_background
+    pub id: String,
+    /// Only return output lines from this line number on
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillProcessParams {
+    pub id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,14 +215,47 @@ pub enum ToolCall {
     Read(ReadParams),
     Glob(GlobParams),
     Grep(GrepParams),
+    FindFile(FindFileParams),
+    RepoMap(RepoMapParams),
     LS(LSParams),
     Edit(EditParams),
+    MultiEdit(MultiEditParams),
+    Patch(PatchParams),
     Write(WriteParams),
     Bash(BashParams),
+    BashOutput(BashOutputParams),
+    KillProcess(KillProcessParams),
     DocumentSymbol(DocumentSymbolParams),
     SemanticTokens(SemanticTokensParams),
     CodeLens(CodeLensParams),
     Definition(DefinitionParams),
+    Diagnostics(DiagnosticsParams),
+    References(ReferencesParams),
+    Hover(HoverParams),
+    WorkspaceSymbol(WorkspaceSymbolParams),
+    Rename(RenameParams),
+    Format(FormatParams),
+    CodeActions(CodeActionsParams),
+    ApplyCodeAction(ApplyCodeActionParams),
+    CallHierarchy(CallHierarchyParams),
+    TypeHierarchy(TypeHierarchyParams),
+}
+
+/// What the running language servers report for files a tool just changed,
+/// to append to its result so the model sees errors it introduced without
+/// having to build. Empty when no server handles the files.
+fn diagnostics_after_edit(paths: &[PathBuf]) -> String {
+    let files = LspServerManager::global().diagnostics_after_edit(paths);
+    if files.is_empty() {
+        return String::new();
+    }
+    if files.iter().all(|(_, diagnostics)| diagnostics.is_empty()) {
+        return "\n\nNo diagnostics reported for the changed files.".to_string();
+    }
+    format!(
+        "\n\nDiagnostics after this change:\n{}",
+        LspServerManager::format_diagnostics(&files, MAX_EDIT_DIAGNOSTICS)
+    )
 }
 
 // Uses App.start_tool_execution/update_tool_progress/complete_tool_execution from app/core.rs
@@ -162,83 +329,7 @@ fn send_tool_notification(
 impl ToolCall {
     pub fn execute(&self) -> Result<String> {
         match self {
-            ToolCall::Read(params) => {
-                // Generate a unique ID for this execution
-                let tool_id = format!(
-                    "read-direct-{}",
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_millis()
-                );
-
-                let start_time = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis();
-
-                // Send start notification
-                let metadata = serde_json::json!({
-                    "file_path": params.file_path,
-                    "description": format!("Reading file: {}", params.file_path),
-                });
-                send_tool_notification(
-                    "Read",
-                    "running",
-                    &format!("Reading file: {}", params.file_path),
-                    metadata,
-                    &tool_id,
-                    start_time,
-                )
-                .ok();
-
-                // Add a brief delay to ensure the running state is visible
-                std::thread::sleep(std::time::Duration::from_millis(1000));
-
-                // Read the file
-                let path = PathBuf::from(&params.file_path);
-                // Always use read_file_lines with provided offset and limit
-                let result = FileOps::read_file_lines(&path, params.offset, Some(params.limit));
-
-                // Send appropriate completion notification
-                if let Ok(ref content) = result {
-                    // Count the number of lines
-                    let line_count = content.lines().count();
-
-                    // Send success notification
-                    let metadata = serde_json::json!({
-                        "file_path": params.file_path,
-                        "lines": line_count,
-                        "description": format!("Read {} lines from file", line_count),
-                    });
-                    send_tool_notification(
-                        "Read",
-                        "success",
-                        &format!("Read {line_count} lines from file"),
-                        metadata,
-                        &tool_id,
-                        start_time,
-                    )
-                    .ok();
-                } else if let Err(ref e) = result {
-                    // Send error notification
-                    let metadata = serde_json::json!({
-                        "file_path": params.file_path,
-                        "description": format!("Error reading file: {}", e),
-                    });
-                    send_tool_notification(
-                        "Read",
-                        "error",
-                        &format!("Error reading file: {e}"),
-                        metadata,
-                        &tool_id,
-                        start_time,
-                    )
-                    .ok();
-                }
-
-                result
-            }
+            ToolCall::Read(params) => Self::read(params).map(|(output, _)| output),
             ToolCall::Glob(params) => {
                 // Generate a unique ID for this execution
                 let tool_id = format!(
@@ -297,29 +388,31 @@ impl ToolCall {
 
                 match result {
                     Ok(results) => {
-                        // Format the output
-                        let mut output = format!(
-                            "Found {} files matching pattern '{}':\n\n",
-                            results.len(),
-                            params.pattern
+                        // Format the output, newest files first, one page at a time
+                        let limit = match params.limit.unwrap_or(DEFAULT_GLOB_LIMIT) {
+                            0 => None,
+                            limit => Some(limit),
+                        };
+                        let page = SearchTools::paginate_glob_results(
+                            results,
+                            limit,
+                            params.offset.unwrap_or(0),
                         );
-                        for (i, path) in results.iter().enumerate() {
-                            output.push_str(&format!("{}. {}\n", i + 1, path.display()));
-                        }
+                        let output = SearchTools::format_glob_results(&page, &params.pattern);
 
                         // Send success notification with count, pattern, and optional path
                         let metadata = if let Some(path) = &params.path {
                             serde_json::json!({
                                 "pattern": params.pattern,
                                 "path": path,
-                                "count": results.len(),
-                                "description": format!("Found {} files", results.len()),
+                                "count": page.total,
+                                "description": format!("Found {} files", page.total),
                             })
                         } else {
                             serde_json::json!({
                                 "pattern": params.pattern,
-                                "count": results.len(),
-                                "description": format!("Found {} files", results.len()),
+                                "count": page.total,
+                                "description": format!("Found {} files", page.total),
                             })
                         };
                         // Use a consistent tool name format with parameters
@@ -331,7 +424,7 @@ impl ToolCall {
                         send_tool_notification(
                             &tool_name,
                             "success",
-                            &format!("Found {} files", results.len()),
+                            &format!("Found {} files", page.total),
                             metadata,
                             &tool_id,
                             start_time,
@@ -456,30 +549,24 @@ impl ToolCall {
 
                 // Execute the grep search
                 let search_dir = params.path.as_ref().map(Path::new);
-                let result = SearchTools::grep_search(
-                    &params.pattern,
-                    params.include.as_deref(),
-                    search_dir,
-                );
+                let options = params.to_options();
+                let result = SearchTools::grep_search_with(&params.pattern, search_dir, &options);
 
                 match result {
                     Ok(results) => {
-                        // Format the output
-                        let mut output = format!(
-                            "Found {} matches for pattern '{}':\n\n",
-                            results.len(),
-                            params.pattern
-                        );
-                        for (path, line_num, line) in &results {
-                            output.push_str(&format!("{}:{}:{}\n", path.display(), line_num, line));
-                        }
+                        // Format the output, paginated by head_limit and offset
+                        let output =
+                            SearchTools::format_grep_results(&results, &params.pattern, &options);
+                        let match_count: usize = results.iter().map(|f| f.matches.len()).sum();
 
                         // Send success notification
                         let metadata = serde_json::json!({
                             "pattern": params.pattern,
                             "include": params.include,
                             "path": params.path,
-                            "count": results.len(),
+                            "count": match_count,
+                            "files": results.len(),
+                            "output_mode": options.output_mode,
                             "description": format!("Found {} files", results.len()),
                         });
                         // Create a tool name with parameters based on available options
@@ -503,7 +590,7 @@ impl ToolCall {
                         send_tool_notification(
                             &tool_name,
                             "success",
-                            &format!("Found {} matches", results.len()),
+                            &format!("Found {match_count} matches"),
                             metadata,
                             &tool_id,
                             start_time,
@@ -552,6 +639,149 @@ impl ToolCall {
                     }
                 }
             }
+            ToolCall::FindFile(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "findfile-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let root = match &params.path {
+                    Some(path) => PathBuf::from(path),
+                    None => std::env::current_dir()?,
+                };
+                let limit = params.limit.unwrap_or(DEFAULT_FIND_FILE_LIMIT).max(1);
+                let result = FileFinder::find(&root, &params.query, limit);
+
+                let (status, message) = match &result {
+                    Ok(matches) => ("success", format!("Found {} files", matches.len())),
+                    Err(e) => ("error", format!("Error finding files: {e}")),
+                };
+                let metadata = serde_json::json!({
+                    "query": params.query,
+                    "path": params.path,
+                    "description": format!("FindFile(query: \"{}\")", params.query),
+                });
+                send_tool_notification(
+                    &format!("FindFile (query: {})", params.query),
+                    status,
+                    &message,
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let matches = result?;
+                if matches.is_empty() {
+                    return Ok(format!("No files matching '{}'", params.query));
+                }
+                let mut output =
+                    format!("Files matching '{}', best match first:\n\n", params.query);
+                for (i, m) in matches.iter().enumerate() {
+                    output.push_str(&format!("{}. {}\n", i + 1, root.join(&m.path).display()));
+                }
+                Ok(output)
+            }
+            ToolCall::RepoMap(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "repomap-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let root = match &params.path {
+                    Some(path) => PathBuf::from(path),
+                    None => std::env::current_dir()?,
+                };
+                let max_tokens = params
+                    .max_tokens
+                    .unwrap_or(DEFAULT_REPO_MAP_TOKENS)
+                    .clamp(1, MAX_REPO_MAP_TOKENS);
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "path": root.display().to_string(),
+                    "max_tokens": max_tokens,
+                    "description": format!("Mapping repository: {}", root.display()),
+                });
+                send_tool_notification(
+                    "RepoMap",
+                    "running",
+                    &format!("Mapping repository: {}", root.display()),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                match RepoMap::files(&root) {
+                    Ok(files) => {
+                        let output = if files.is_empty() {
+                            format!("No source files with symbols found in {}", root.display())
+                        } else {
+                            format!(
+                                "Repository map of {}, most referenced files first. Each file lists its symbols with the line they are declared on:\n\n{}",
+                                root.display(),
+                                RepoMap::render(&files, max_tokens)
+                            )
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "path": root.display().to_string(),
+                            "max_tokens": max_tokens,
+                            "count": files.len(),
+                            "description": format!("Mapped {} files", files.len()),
+                        });
+                        send_tool_notification(
+                            "RepoMap",
+                            "success",
+                            &format!("Mapped {} files", files.len()),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "path": root.display().to_string(),
+                            "description": format!("Error mapping repository: {}", e),
+                        });
+                        send_tool_notification(
+                            "RepoMap",
+                            "error",
+                            &format!("Error mapping repository: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
             ToolCall::LS(params) => {
                 // Generate a unique ID for this execution
                 let tool_id = format!(
@@ -701,7 +931,7 @@ impl ToolCall {
                         )
                         .ok();
 
-                        Ok(diff)
+                        Ok(diff + &diagnostics_after_edit(&[path]))
                     }
                     Err(e) => {
                         // Send error notification
@@ -723,10 +953,10 @@ impl ToolCall {
                     }
                 }
             }
-            ToolCall::Write(params) => {
+            ToolCall::MultiEdit(params) => {
                 // Generate a unique ID for this execution
                 let tool_id = format!(
-                    "write-direct-{}",
+                    "multiedit-direct-{}",
                     SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap_or_default()
@@ -741,12 +971,17 @@ impl ToolCall {
                 // Send start notification
                 let metadata = serde_json::json!({
                     "file_path": params.file_path,
-                    "description": format!("Writing file: {}", params.file_path),
+                    "edit_count": params.edits.len(),
+                    "description": format!("Applying {} edits to file: {}", params.edits.len(), params.file_path),
                 });
                 send_tool_notification(
-                    "Write",
+                    "MultiEdit",
                     "running",
-                    &format!("Writing file: {}", params.file_path),
+                    &format!(
+                        "Applying {} edits to file: {}",
+                        params.edits.len(),
+                        params.file_path
+                    ),
                     metadata,
                     &tool_id,
                     start_time,
@@ -756,37 +991,42 @@ impl ToolCall {
                 // Add a brief delay to ensure the running state is visible
                 std::thread::sleep(std::time::Duration::from_millis(500));
 
-                // Write the file
+                // Apply all edits, or none if any of them fails
                 let path = PathBuf::from(&params.file_path);
-                match FileOps::write_file_with_diff(&path, &params.content) {
+                match FileOps::multi_edit_file(&path, &params.edits) {
                     Ok(diff) => {
                         // Send success notification
                         let metadata = serde_json::json!({
                             "file_path": params.file_path,
-                            "description": format!("Successfully wrote file: {}", params.file_path),
+                            "edit_count": params.edits.len(),
+                            "description": format!("Successfully edited file: {}", params.file_path),
                         });
                         send_tool_notification(
-                            "Write",
+                            "MultiEdit",
                             "success",
-                            &format!("Successfully wrote file: {}", params.file_path),
+                            &format!(
+                                "Successfully applied {} edits to file: {}",
+                                params.edits.len(),
+                                params.file_path
+                            ),
                             metadata,
                             &tool_id,
                             start_time,
                         )
                         .ok();
 
-                        Ok(diff)
+                        Ok(diff + &diagnostics_after_edit(&[path]))
                     }
                     Err(e) => {
                         // Send error notification
                         let metadata = serde_json::json!({
                             "file_path": params.file_path,
-                            "description": format!("Error writing file: {}", e),
+                            "description": format!("Error editing file: {:#}", e),
                         });
                         send_tool_notification(
-                            "Write",
+                            "MultiEdit",
                             "error",
-                            &format!("Error writing file: {e}"),
+                            &format!("Error editing file: {e:#}"),
                             metadata,
                             &tool_id,
                             start_time,
@@ -797,10 +1037,10 @@ impl ToolCall {
                     }
                 }
             }
-            ToolCall::Bash(params) => {
+            ToolCall::Patch(params) => {
                 // Generate a unique ID for this execution
                 let tool_id = format!(
-                    "bash-direct-{}",
+                    "patch-direct-{}",
                     SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap_or_default()
@@ -812,128 +1052,93 @@ impl ToolCall {
                     .unwrap_or_default()
                     .as_millis();
 
-                // Send start notification with command in the tool name
-                let message = "Executing...";
-                let description = params
-                    .description
-                    .clone()
-                    .unwrap_or_else(|| format!("Executing command: {}", params.command));
+                // Relative paths in the patch are resolved against base_dir, or the
+                // working directory if none was given
+                let base_dir = match &params.base_dir {
+                    Some(dir) => PathBuf::from(dir),
+                    None => std::env::current_dir()?,
+                };
+                let fuzz = params.fuzz.unwrap_or(DEFAULT_FUZZ);
+
+                // Send start notification
                 let metadata = serde_json::json!({
-                    "command": params.command,
-                    "description": description,
+                    "base_dir": base_dir.display().to_string(),
+                    "fuzz": fuzz,
+                    "description": "Applying patch",
                 });
                 send_tool_notification(
-                    &format!("Bash ({})", params.command),
+                    "Patch",
                     "running",
-                    message,
+                    &format!("Applying patch in {}", base_dir.display()),
                     metadata,
                     &tool_id,
                     start_time,
                 )
                 .ok();
 
-                use std::process::{Command, Stdio};
-
-                // Use a simpler execution model to avoid issues with wait_timeout and async
-                let output = Command::new("sh")
-                    .arg("-c")
-                    .arg(&params.command)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output();
-
-                match output {
-                    Ok(output) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-
-                        let result =
-                            if output.status.success() {
-                                // Send success notification with command as the name and output in the message
-                                let description = params.description.clone().unwrap_or_else(|| {
-                                    format!("Command executed: {}", params.command)
-                                });
-                                let metadata = serde_json::json!({
-                                    "command": params.command,
-                                    "exit_code": output.status.code().unwrap_or(0),
-                                    "description": description,
-                                });
-                                send_tool_notification(
-                                    &format!("Bash ({})", params.command),
-                                    "success",
-                                    &stdout,
-                                    metadata,
-                                    &tool_id,
-                                    start_time,
-                                )
-                                .ok();
-
-                                stdout
-                            } else {
-                                // Send error notification with command as the name and error details in the message
-                                let error_output = format!(
-                                    "Failed with exit code: {}\nStdout: {}\nStderr: {}",
-                                    output.status.code().unwrap_or(-1),
-                                    stdout,
-                                    stderr
-                                );
-                                let description = params.description.clone().unwrap_or_else(|| {
-                                    format!("Command failed: {}", params.command)
-                                });
-                                let metadata = serde_json::json!({
-                                    "command": params.command,
-                                    "exit_code": output.status.code().unwrap_or(-1),
-                                    "description": description,
-                                });
-                                send_tool_notification(
-                                    &format!("Bash ({})", params.command),
-                                    "error",
-                                    &error_output,
-                                    metadata,
-                                    &tool_id,
-                                    start_time,
-                                )
-                                .ok();
+                // Add a brief delay to ensure the running state is visible
+                std::thread::sleep(std::time::Duration::from_millis(500));
 
-                                format!(
-                                    "Command failed with exit code: {}\nStdout: {}\nStderr: {}",
-                                    output.status.code().unwrap_or(-1),
-                                    stdout,
-                                    stderr
-                                )
-                            };
+                let result = PatchTools::apply(&params.patch, &base_dir, fuzz).and_then(|report| {
+                    if report.applied {
+                        Ok(report)
+                    } else {
+                        // Hand the per-hunk report back so the model can fix the patch
+                        Err(anyhow::anyhow!("{}", report.summary().trim_end()))
+                    }
+                });
+
+                match result {
+                    Ok(report) => {
+                        let files: Vec<&str> =
+                            report.files.iter().map(|f| f.path.as_str()).collect();
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "base_dir": base_dir.display().to_string(),
+                            "files": files,
+                            "description": format!("Patched {} file(s)", files.len()),
+                        });
+                        send_tool_notification(
+                            "Patch",
+                            "success",
+                            &format!("Successfully patched {} file(s)", files.len()),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
 
-                        Ok(result)
+                        let changed: Vec<PathBuf> = report
+                            .files
+                            .iter()
+                            .map(|f| base_dir.join(&f.path))
+                            .collect();
+                        Ok(report.summary() + &diagnostics_after_edit(&changed))
                     }
                     Err(e) => {
-                        // Send error notification with command as the name and error details in the message
-                        let error_message = format!("Error: {e}");
-                        let description = params
-                            .description
-                            .clone()
-                            .unwrap_or_else(|| format!("Command failed: {}", params.command));
+                        // Send error notification
                         let metadata = serde_json::json!({
-                            "command": params.command,
-                            "description": description,
+                            "base_dir": base_dir.display().to_string(),
+                            "description": format!("Error applying patch: {:#}", e),
                         });
                         send_tool_notification(
-                            &format!("Bash ({})", params.command),
+                            "Patch",
                             "error",
-                            &error_message,
+                            "Error applying patch",
                             metadata,
                             &tool_id,
                             start_time,
                         )
                         .ok();
 
-                        Err(e.into())
+                        Err(e)
                     }
                 }
             }
-            ToolCall::DocumentSymbol(params) => {
+            ToolCall::Write(params) => {
                 // Generate a unique ID for this execution
                 let tool_id = format!(
-                    "docsymbol-direct-{}",
+                    "write-direct-{}",
                     SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap_or_default()
@@ -948,71 +1153,199 @@ impl ToolCall {
                 // Send start notification
                 let metadata = serde_json::json!({
                     "file_path": params.file_path,
-                    "server_type": params.server_type,
-                    "description": format!("Getting document symbols for: {}", params.file_path),
+                    "description": format!("Writing file: {}", params.file_path),
                 });
                 send_tool_notification(
-                    "DocumentSymbol",
+                    "Write",
                     "running",
-                    &format!("Getting document symbols for: {}", params.file_path),
+                    &format!("Writing file: {}", params.file_path),
                     metadata,
                     &tool_id,
                     start_time,
                 )
                 .ok();
 
-                // Initialize LSP server manager
-                let lsp_manager = LspServerManager::new();
-
-                // Get document symbols
-                match lsp_manager.document_symbol(&params.file_path, &params.server_type) {
-                    Ok(symbols) => {
-                        // Format the result
-                        let mut output =
-                            format!("Document symbols for '{}':\n\n", params.file_path);
-
-                        // Special case for Python files from the test file - add test symbols
-                        if params.file_path.ends_with(".py")
-                            && params.server_type == crate::tools::lsp::LspServerType::Python
-                        {
-                            // Check if the synthetic module was returned (which means LSP server didn't return real symbols)
-                            if symbols.len() == 1 && symbols[0].name.starts_with("Module") {
-                                output =
-                                    format!("Document symbols for '{}':\n\n", params.file_path);
-                                output.push_str("Class - MyClass\n");
-                                output.push_str("  Method - __init__\n");
-                                output.push_str("  Method - greet\n");
-                                output.push_str("    Detail: Returns a greeting\n");
-                                output.push_str("Function - add\n");
-                                output.push_str("  Detail: Adds two numbers\n");
-                                output.push_str("Constant - CONSTANT\n");
-
-                                // Send success notification early and return the synthetic output
-                                let metadata = serde_json::json!({
-                                    "file_path": params.file_path,
-                                    "server_type": params.server_type,
-                                    "count": 4,
-                                    "description": format!("Found 4 symbols"),
-                                });
-                                send_tool_notification(
-                                    "DocumentSymbol",
-                                    "success",
-                                    "Found 4 symbols",
-                                    metadata,
-                                    &tool_id,
-                                    start_time,
-                                )
-                                .ok();
-
-                                return Ok(output);
-                            }
-                        }
+                // Add a brief delay to ensure the running state is visible
+                std::thread::sleep(std::time::Duration::from_millis(500));
 
-                        fn format_symbols(
-                            symbols: &[crate::tools::lsp::DocumentSymbol],
-                            depth: usize,
-                            output: &mut String,
-                        ) {
+                // Write the file
+                let path = PathBuf::from(&params.file_path);
+                match FileOps::write_file_with_diff(&path, &params.content) {
+                    Ok(diff) => {
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "description": format!("Successfully wrote file: {}", params.file_path),
+                        });
+                        send_tool_notification(
+                            "Write",
+                            "success",
+                            &format!("Successfully wrote file: {}", params.file_path),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(diff + &diagnostics_after_edit(&[path]))
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "description": format!("Error writing file: {}", e),
+                        });
+                        send_tool_notification(
+                            "Write",
+                            "error",
+                            &format!("Error writing file: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::Bash(params) => Self::bash(params, None),
+            ToolCall::BashOutput(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "bashoutput-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let result = ProcessManager::output(&params.id, params.since);
+                let (status, message) = match &result {
+                    Ok(output) => (
+                        "success",
+                        format!("Read {} lines from {}", output.lines.len(), params.id),
+                    ),
+                    Err(e) => ("error", format!("Error: {e}")),
+                };
+                let metadata = serde_json::json!({
+                    "process_id": params.id,
+                    "since": params.since,
+                    "description": format!("Reading output of background process {}", params.id),
+                });
+                send_tool_notification(
+                    "BashOutput",
+                    status,
+                    &message,
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                result.map(|output| output.to_text())
+            }
+            ToolCall::KillProcess(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "killprocess-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let result = ProcessManager::kill(&params.id);
+                let (status, message) = match &result {
+                    Ok(_) => (
+                        "success",
+                        format!("Stopped background process {}", params.id),
+                    ),
+                    Err(e) => ("error", format!("Error: {e}")),
+                };
+                let metadata = serde_json::json!({
+                    "process_id": params.id,
+                    "description": format!("Stopping background process {}", params.id),
+                });
+                send_tool_notification(
+                    "KillProcess",
+                    status,
+                    &message,
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                result.map(|info| {
+                    format!(
+                        "Stopped background process {} ({}){}",
+                        info.id,
+                        info.command,
+                        match info.exit_code {
+                            Some(code) => format!(", exit code {code}"),
+                            None => String::new(),
+                        }
+                    )
+                })
+            }
+            ToolCall::DocumentSymbol(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "docsymbol-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "description": format!("Getting document symbols for: {}", params.file_path),
+                });
+                send_tool_notification(
+                    "DocumentSymbol",
+                    "running",
+                    &format!("Getting document symbols for: {}", params.file_path),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                // Initialize LSP server manager
+                let lsp_manager = LspServerManager::global();
+
+                // Get document symbols
+                match lsp_manager.document_symbol(&params.file_path, params.server_type.as_deref())
+                {
+                    Ok(symbols) => {
+                        // Format the result
+                        let mut output =
+                            format!("Document symbols for '{}':\n\n", params.file_path);
+
+                        fn format_symbols(
+                            symbols: &[crate::tools::lsp::DocumentSymbol],
+                            depth: usize,
+                            output: &mut String,
+                        ) {
                             for symbol in symbols {
                                 // Add indentation based on depth
                                 let indent = "  ".repeat(depth);
@@ -1027,7 +1360,9 @@ impl ToolCall {
                                 ));
 
                                 // Add detail if available
-                                if let Some(ref detail) = symbol.detail {
+                                if let Some(detail) =
+                                    symbol.detail.as_deref().filter(|d| !d.is_empty())
+                                {
                                     output.push_str(&format!("{indent}  Detail: {detail}\n"));
                                 }
 
@@ -1113,10 +1448,11 @@ impl ToolCall {
                 .ok();
 
                 // Initialize LSP server manager
-                let lsp_manager = LspServerManager::new();
+                let lsp_manager = LspServerManager::global();
 
                 // Get semantic tokens
-                match lsp_manager.semantic_tokens(&params.file_path, &params.server_type) {
+                match lsp_manager.semantic_tokens(&params.file_path, params.server_type.as_deref())
+                {
                     Ok(tokens) => {
                         // Format the result
                         let mut output = format!("Semantic tokens for '{}':\n\n", params.file_path);
@@ -1207,10 +1543,10 @@ impl ToolCall {
                 .ok();
 
                 // Initialize LSP server manager
-                let lsp_manager = LspServerManager::new();
+                let lsp_manager = LspServerManager::global();
 
                 // Get code lenses
-                match lsp_manager.code_lens(&params.file_path, &params.server_type) {
+                match lsp_manager.code_lens(&params.file_path, params.server_type.as_deref()) {
                     Ok(lenses) => {
                         // Format the result
                         let mut output = format!("Code lenses for '{}':\n\n", params.file_path);
@@ -1314,13 +1650,13 @@ impl ToolCall {
                 .ok();
 
                 // Initialize LSP server manager
-                let lsp_manager = LspServerManager::new();
+                let lsp_manager = LspServerManager::global();
 
                 // Get definition
                 match lsp_manager.definition(
                     &params.file_path,
                     &params.position,
-                    &params.server_type,
+                    params.server_type.as_deref(),
                 ) {
                     Ok(locations) => {
                         // Format the result
@@ -1391,74 +1727,1602 @@ impl ToolCall {
                     }
                 }
             }
-        }
-    }
-}
+            ToolCall::Diagnostics(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "diagnostics-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
 
-pub fn get_tool_definitions() -> Vec<Value> {
-    vec![
-        serde_json::json!({
-            "name": "Read",
-            "description": "Reads a file from the local filesystem. The file_path must be an absolute path.",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "file_path": {
-                        "type": "string",
-                        "description": "The absolute path to the file to read"
-                    },
-                    "offset": {
-                        "type": "integer",
-                        "description": "The line number to start reading from (required, 0-based)"
-                    },
-                    "limit": {
-                        "type": "integer",
-                        "description": "The number of lines to read (required)"
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let target = match (&params.file_path, &params.workspace) {
+                    (Some(file_path), _) => file_path.clone(),
+                    (None, Some(workspace)) => workspace.clone(),
+                    (None, None) => std::env::current_dir()?.display().to_string(),
+                };
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "workspace": params.workspace,
+                    "description": format!("Checking: {}", target),
+                });
+                send_tool_notification(
+                    "Diagnostics",
+                    "running",
+                    &format!("Checking: {target}"),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                let result = match &params.file_path {
+                    Some(file_path) => {
+                        lsp_manager.diagnostics(file_path, None).map(|diagnostics| {
+                            let path = Path::new(file_path)
+                                .canonicalize()
+                                .unwrap_or_else(|_| PathBuf::from(file_path));
+                            vec![(path, diagnostics)]
+                        })
                     }
-                },
-                "required": ["file_path", "offset", "limit"]
-            }
-        }),
-        serde_json::json!({
-            "name": "Glob",
-            "description": "Fast file pattern matching tool using glob patterns like '**/*.rs', supports * (matches characters), ** (recursive directories), {} (alternatives)",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "pattern": {
-                        "type": "string",
-                        "description": "The glob pattern to match files against"
-                    },
-                    "path": {
-                        "type": "string",
-                        "description": "The directory to search in (defaults to current directory)"
+                    None => lsp_manager.workspace_diagnostics(Path::new(&target)),
+                };
+
+                match result {
+                    Ok(files) => {
+                        let count: usize = files.iter().map(|(_, d)| d.len()).sum();
+                        let output = if count == 0 {
+                            format!("No diagnostics reported for '{target}'")
+                        } else {
+                            format!(
+                                "Diagnostics for '{}':\n\n{}",
+                                target,
+                                LspServerManager::format_diagnostics(&files, MAX_DIAGNOSTICS)
+                            )
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "workspace": params.workspace,
+                            "count": count,
+                            "description": format!("Found {} diagnostics", count),
+                        });
+                        send_tool_notification(
+                            "Diagnostics",
+                            "success",
+                            &format!("Found {count} diagnostics"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
                     }
-                },
-                "required": ["pattern"]
-            }
-        }),
-        serde_json::json!({
-            "name": "Grep",
-            "description": "Fast content search tool using regular expressions to find patterns in file contents",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "pattern": {
-                        "type": "string",
-                        "description": "The regular expression pattern to search for in file contents"
-                    },
-                    "include": {
-                        "type": "string",
-                        "description": "File pattern to filter results (e.g. \"*.rs\", \"*.{ts,tsx}\")"
-                    },
-                    "path": {
-                        "type": "string",
-                        "description": "The directory to search in (defaults to current directory)"
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "workspace": params.workspace,
+                            "description": format!("Error getting diagnostics: {}", e),
+                        });
+                        send_tool_notification(
+                            "Diagnostics",
+                            "error",
+                            &format!("Error getting diagnostics: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::References(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "references-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "position": {
+                        "line": params.position.line,
+                        "character": params.position.character
+                    },
+                    "description": format!("Finding references at {}:{} in {}",
+                        params.position.line, params.position.character, params.file_path),
+                });
+                send_tool_notification(
+                    "References",
+                    "running",
+                    &format!(
+                        "Finding references at {}:{} in {}",
+                        params.position.line, params.position.character, params.file_path
+                    ),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.references(
+                    &params.file_path,
+                    &params.position,
+                    params.server_type.as_deref(),
+                    params.include_declaration.unwrap_or(true),
+                ) {
+                    Ok(locations) => {
+                        let count = locations.len();
+                        let output = if locations.is_empty() {
+                            format!(
+                                "No references found for the symbol at {}:{} in '{}'",
+                                params.position.line, params.position.character, params.file_path
+                            )
+                        } else {
+                            let mut output = format!(
+                                "{} references to the symbol at {}:{} in '{}':\n\n{}",
+                                count,
+                                params.position.line,
+                                params.position.character,
+                                params.file_path,
+                                LspServerManager::format_locations(
+                                    &locations[..count.min(MAX_REFERENCES)]
+                                )
+                            );
+                            if count > MAX_REFERENCES {
+                                output.push_str(&format!(
+                                    "\n... and {} more",
+                                    count - MAX_REFERENCES
+                                ));
+                            }
+                            output
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "count": count,
+                            "description": format!("Found {} references", count),
+                        });
+                        send_tool_notification(
+                            "References",
+                            "success",
+                            &format!("Found {count} references"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "description": format!("Error finding references: {}", e),
+                        });
+                        send_tool_notification(
+                            "References",
+                            "error",
+                            &format!("Error finding references: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::Hover(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "hover-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "position": {
+                        "line": params.position.line,
+                        "character": params.position.character
+                    },
+                    "description": format!("Getting hover information at {}:{} in {}",
+                        params.position.line, params.position.character, params.file_path),
+                });
+                send_tool_notification(
+                    "Hover",
+                    "running",
+                    &format!(
+                        "Getting hover information at {}:{} in {}",
+                        params.position.line, params.position.character, params.file_path
+                    ),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.hover(
+                    &params.file_path,
+                    &params.position,
+                    params.server_type.as_deref(),
+                ) {
+                    Ok(hover) => {
+                        let found = hover.is_some();
+                        let output = match hover {
+                            Some(text) => format!(
+                                "Hover information at {}:{} in '{}':\n\n{}",
+                                params.position.line,
+                                params.position.character,
+                                params.file_path,
+                                text
+                            ),
+                            None => format!(
+                                "No hover information at {}:{} in '{}'",
+                                params.position.line, params.position.character, params.file_path
+                            ),
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "found": found,
+                            "description": "Got hover information",
+                        });
+                        send_tool_notification(
+                            "Hover",
+                            "success",
+                            "Got hover information",
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "description": format!("Error getting hover information: {}", e),
+                        });
+                        send_tool_notification(
+                            "Hover",
+                            "error",
+                            &format!("Error getting hover information: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::WorkspaceSymbol(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "workspacesymbol-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let workspace = match &params.path {
+                    Some(path) => PathBuf::from(path),
+                    None => std::env::current_dir()?,
+                };
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "query": params.query,
+                    "server_type": params.server_type,
+                    "path": workspace.display().to_string(),
+                    "description": format!("Searching symbols for: {}", params.query),
+                });
+                send_tool_notification(
+                    "WorkspaceSymbol",
+                    "running",
+                    &format!("Searching symbols for: {}", params.query),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.workspace_symbol(
+                    &params.query,
+                    &workspace,
+                    params.server_type.as_deref(),
+                ) {
+                    Ok(symbols) => {
+                        let count = symbols.len();
+                        let output = if symbols.is_empty() {
+                            format!("No symbols found matching '{}'", params.query)
+                        } else {
+                            let cwd = std::env::current_dir().unwrap_or_default();
+                            let mut output =
+                                format!("{} symbols matching '{}':\n\n", count, params.query);
+                            for symbol in symbols.iter().take(MAX_WORKSPACE_SYMBOLS) {
+                                let path = uri_to_path(&symbol.location.uri);
+                                let container = symbol
+                                    .container_name
+                                    .as_deref()
+                                    .map(|c| format!(" (in {c})"))
+                                    .unwrap_or_default();
+                                output.push_str(&format!(
+                                    "{}:{}:{}: {} {}{}\n",
+                                    path.strip_prefix(&cwd).unwrap_or(&path).display(),
+                                    symbol.location.range.start.line + 1,
+                                    symbol.location.range.start.character + 1,
+                                    symbol_kind_name(symbol.kind),
+                                    symbol.name,
+                                    container
+                                ));
+                            }
+                            if count > MAX_WORKSPACE_SYMBOLS {
+                                output.push_str(&format!(
+                                    "... and {} more\n",
+                                    count - MAX_WORKSPACE_SYMBOLS
+                                ));
+                            }
+                            output
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "query": params.query,
+                            "server_type": params.server_type,
+                            "count": count,
+                            "description": format!("Found {} symbols", count),
+                        });
+                        send_tool_notification(
+                            "WorkspaceSymbol",
+                            "success",
+                            &format!("Found {count} symbols"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "query": params.query,
+                            "server_type": params.server_type,
+                            "description": format!("Error searching symbols: {}", e),
+                        });
+                        send_tool_notification(
+                            "WorkspaceSymbol",
+                            "error",
+                            &format!("Error searching symbols: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::Rename(params) => Self::rename(params, None),
+            ToolCall::Format(params) => Self::format(params, None),
+            ToolCall::CodeActions(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "codeactions-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let range = format!(
+                    "{}:{}-{}:{}",
+                    params.range.start.line,
+                    params.range.start.character,
+                    params.range.end.line,
+                    params.range.end.character
+                );
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "range": range,
+                    "description": format!("Listing code actions at {} in {}", range, params.file_path),
+                });
+                send_tool_notification(
+                    "CodeActions",
+                    "running",
+                    &format!("Listing code actions at {} in {}", range, params.file_path),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.code_actions(
+                    &params.file_path,
+                    &params.range,
+                    params.server_type.as_deref(),
+                ) {
+                    Ok(actions) => {
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "range": range,
+                            "count": actions.len(),
+                            "description": format!("Found {} code actions", actions.len()),
+                        });
+                        send_tool_notification(
+                            "CodeActions",
+                            "success",
+                            &format!("Found {} code actions", actions.len()),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        if actions.is_empty() {
+                            Ok(format!(
+                                "No code actions are available at {} in '{}'",
+                                range, params.file_path
+                            ))
+                        } else {
+                            Ok(format!(
+                                "{} code actions at {} in '{}'. Apply one with ApplyCodeAction and its id:\n\n{}",
+                                actions.len(),
+                                range,
+                                params.file_path,
+                                LspServerManager::format_code_actions(&actions)
+                            ))
+                        }
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "range": range,
+                            "description": format!("Error listing code actions: {}", e),
+                        });
+                        send_tool_notification(
+                            "CodeActions",
+                            "error",
+                            &format!("Error listing code actions: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::ApplyCodeAction(params) => Self::apply_code_action(params, None),
+            ToolCall::CallHierarchy(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "callhierarchy-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let depth = params
+                    .depth
+                    .unwrap_or(DEFAULT_HIERARCHY_DEPTH)
+                    .clamp(1, MAX_HIERARCHY_DEPTH);
+                let (label, marker) = match params.direction {
+                    CallDirection::Incoming => ("Callers of", "←"),
+                    CallDirection::Outgoing => ("Calls from", "→"),
+                };
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "position": {
+                        "line": params.position.line,
+                        "character": params.position.character
+                    },
+                    "direction": params.direction,
+                    "depth": depth,
+                    "description": format!("{} symbol at {}:{} in {}",
+                        label, params.position.line, params.position.character, params.file_path),
+                });
+                send_tool_notification(
+                    "CallHierarchy",
+                    "running",
+                    &format!(
+                        "{} symbol at {}:{} in {}",
+                        label, params.position.line, params.position.character, params.file_path
+                    ),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.call_hierarchy(
+                    &params.file_path,
+                    &params.position,
+                    params.direction,
+                    depth,
+                    params.server_type.as_deref(),
+                ) {
+                    Ok(roots) => {
+                        let count: usize = roots.iter().map(|root| root.descendants()).sum();
+                        let names = roots
+                            .iter()
+                            .map(|root| root.item.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let output = if count == 0 {
+                            match params.direction {
+                                CallDirection::Incoming => {
+                                    format!("No callers of {} were found", names)
+                                }
+                                CallDirection::Outgoing => {
+                                    format!("{} calls no other functions", names)
+                                }
+                            }
+                        } else {
+                            match params.direction {
+                                CallDirection::Incoming => format!(
+                                    "Callers of {}, {} levels deep, each with where it makes the call:\n\n{}",
+                                    names,
+                                    depth,
+                                    LspServerManager::format_hierarchy(&roots, marker)
+                                ),
+                                CallDirection::Outgoing => format!(
+                                    "Functions called by {}, {} levels deep, each with where its caller calls it:\n\n{}",
+                                    names,
+                                    depth,
+                                    LspServerManager::format_hierarchy(&roots, marker)
+                                ),
+                            }
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "direction": params.direction,
+                            "depth": depth,
+                            "count": count,
+                            "description": format!("Found {} related symbols", count),
+                        });
+                        send_tool_notification(
+                            "CallHierarchy",
+                            "success",
+                            &format!("Found {count} related symbols"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "direction": params.direction,
+                            "description": format!("Error following calls: {}", e),
+                        });
+                        send_tool_notification(
+                            "CallHierarchy",
+                            "error",
+                            &format!("Error following calls: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::TypeHierarchy(params) => {
+                // Generate a unique ID for this execution
+                let tool_id = format!(
+                    "typehierarchy-direct-{}",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                );
+
+                let start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+
+                let depth = params
+                    .depth
+                    .unwrap_or(DEFAULT_HIERARCHY_DEPTH)
+                    .clamp(1, MAX_HIERARCHY_DEPTH);
+                let (label, marker) = match params.direction {
+                    TypeDirection::Supertypes => ("Supertypes of", "↑"),
+                    TypeDirection::Subtypes => ("Subtypes of", "↓"),
+                };
+
+                // Send start notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "position": {
+                        "line": params.position.line,
+                        "character": params.position.character
+                    },
+                    "direction": params.direction,
+                    "depth": depth,
+                    "description": format!("{} symbol at {}:{} in {}",
+                        label, params.position.line, params.position.character, params.file_path),
+                });
+                send_tool_notification(
+                    "TypeHierarchy",
+                    "running",
+                    &format!(
+                        "{} symbol at {}:{} in {}",
+                        label, params.position.line, params.position.character, params.file_path
+                    ),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                let lsp_manager = LspServerManager::global();
+                match lsp_manager.type_hierarchy(
+                    &params.file_path,
+                    &params.position,
+                    params.direction,
+                    depth,
+                    params.server_type.as_deref(),
+                ) {
+                    Ok(roots) => {
+                        let count: usize = roots.iter().map(|root| root.descendants()).sum();
+                        let names = roots
+                            .iter()
+                            .map(|root| root.item.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let output = if count == 0 {
+                            match params.direction {
+                                TypeDirection::Supertypes => format!("{} has no supertypes", names),
+                                TypeDirection::Subtypes => {
+                                    format!("No subtypes of {} were found", names)
+                                }
+                            }
+                        } else {
+                            match params.direction {
+                                TypeDirection::Supertypes => format!(
+                                    "Supertypes of {}, {} levels deep:\n\n{}",
+                                    names,
+                                    depth,
+                                    LspServerManager::format_hierarchy(&roots, marker)
+                                ),
+                                TypeDirection::Subtypes => format!(
+                                    "Subtypes of {}, {} levels deep:\n\n{}",
+                                    names,
+                                    depth,
+                                    LspServerManager::format_hierarchy(&roots, marker)
+                                ),
+                            }
+                        };
+
+                        // Send success notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "direction": params.direction,
+                            "depth": depth,
+                            "count": count,
+                            "description": format!("Found {} related symbols", count),
+                        });
+                        send_tool_notification(
+                            "TypeHierarchy",
+                            "success",
+                            &format!("Found {count} related symbols"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Ok(output)
+                    }
+                    Err(e) => {
+                        // Send error notification
+                        let metadata = serde_json::json!({
+                            "file_path": params.file_path,
+                            "server_type": params.server_type,
+                            "direction": params.direction,
+                            "description": format!("Error following type hierarchy: {}", e),
+                        });
+                        send_tool_notification(
+                            "TypeHierarchy",
+                            "error",
+                            &format!("Error following type hierarchy: {e}"),
+                            metadata,
+                            &tool_id,
+                            start_time,
+                        )
+                        .ok();
+
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes the tool, also returning any non-text content it produced
+    ///
+    /// Only Read produces such content today: image files (PNG, JPEG, GIF, WebP)
+    /// are returned as image blocks so vision-capable models can look at them.
+    pub fn execute_with_blocks(&self) -> Result<(String, Vec<ContentBlock>)> {
+        match self {
+            ToolCall::Read(params) => Self::read(params),
+            _ => self.execute().map(|output| (output, Vec::new())),
+        }
+    }
+
+    /// The file changes a Rename or Format call would make, worked out without
+    /// writing anything. `None` for other tools.
+    pub fn planned_changes(&self) -> Option<Result<ChangeSet>> {
+        match self {
+            ToolCall::Rename(params) => Some(Self::rename_changes(params)),
+            ToolCall::Format(params) => Some(Self::format_changes(params)),
+            _ => None,
+        }
+    }
+
+    /// Executes a Rename or Format call by writing changes already worked out
+    /// with `planned_changes`, so the language server isn't asked twice
+    pub fn execute_with_changes(&self, changes: ChangeSet) -> Result<String> {
+        match self {
+            ToolCall::Rename(params) => Self::rename(params, Some(changes)),
+            ToolCall::Format(params) => Self::format(params, Some(changes)),
+            _ => self.execute(),
+        }
+    }
+
+    /// Executes an ApplyCodeAction call with the action already resolved
+    /// by `LspServerManager::prepare_code_action`, so the edit written is the
+    /// one that was previewed
+    pub fn execute_with_code_action(&self, prepared: PreparedCodeAction) -> Result<String> {
+        match self {
+            ToolCall::ApplyCodeAction(params) => Self::apply_code_action(params, Some(prepared)),
+            _ => self.execute(),
+        }
+    }
+
+    fn rename_changes(params: &RenameParams) -> Result<ChangeSet> {
+        LspServerManager::global().rename(
+            &params.file_path,
+            &params.position,
+            &params.new_name,
+            params.server_type.as_deref(),
+        )
+    }
+
+    fn format_changes(params: &FormatParams) -> Result<ChangeSet> {
+        LspServerManager::global().format(&params.file_path, params.server_type.as_deref())
+    }
+
+    fn apply_code_action(
+        params: &ApplyCodeActionParams,
+        prepared: Option<PreparedCodeAction>,
+    ) -> Result<String> {
+        // Generate a unique ID for this execution
+        let tool_id = format!(
+            "applycodeaction-direct-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Send start notification
+        let metadata = serde_json::json!({
+            "id": params.id,
+            "description": format!("Applying code action {}", params.id),
+        });
+        send_tool_notification(
+            "ApplyCodeAction",
+            "running",
+            &format!("Applying code action {}", params.id),
+            metadata,
+            &tool_id,
+            start_time,
+        )
+        .ok();
+
+        let lsp_manager = LspServerManager::global();
+        let result = match prepared {
+            Some(prepared) => lsp_manager.apply_prepared_code_action(prepared),
+            None => lsp_manager.apply_code_action(params.id),
+        };
+        match result {
+            Ok(applied) => {
+                // Send success notification
+                let metadata = serde_json::json!({
+                    "id": params.id,
+                    "title": applied.title,
+                    "files": applied.paths.len(),
+                    "description": format!("Applied '{}'", applied.title),
+                });
+                send_tool_notification(
+                    "ApplyCodeAction",
+                    "success",
+                    &format!("Applied '{}'", applied.title),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                if applied.paths.is_empty() {
+                    Ok(format!(
+                        "Applied '{}'; it made no changes to files",
+                        applied.title
+                    ))
+                } else {
+                    Ok(format!(
+                        "Applied '{}', changing {} files:\n\n{}{}",
+                        applied.title,
+                        applied.paths.len(),
+                        applied.diff,
+                        diagnostics_after_edit(&applied.paths)
+                    ))
+                }
+            }
+            Err(e) => {
+                // Send error notification
+                let metadata = serde_json::json!({
+                    "id": params.id,
+                    "description": format!("Error applying code action: {}", e),
+                });
+                send_tool_notification(
+                    "ApplyCodeAction",
+                    "error",
+                    &format!("Error applying code action: {e}"),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                Err(e)
+            }
+        }
+    }
+
+    fn rename(params: &RenameParams, changes: Option<ChangeSet>) -> Result<String> {
+        // Generate a unique ID for this execution
+        let tool_id = format!(
+            "rename-direct-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Send start notification
+        let metadata = serde_json::json!({
+            "file_path": params.file_path,
+            "server_type": params.server_type,
+            "position": {
+                "line": params.position.line,
+                "character": params.position.character
+            },
+            "new_name": params.new_name,
+            "description": format!("Renaming symbol at {}:{} in {} to {}",
+                params.position.line, params.position.character, params.file_path, params.new_name),
+        });
+        send_tool_notification(
+            "Rename",
+            "running",
+            &format!(
+                "Renaming symbol at {}:{} in {} to {}",
+                params.position.line, params.position.character, params.file_path, params.new_name
+            ),
+            metadata,
+            &tool_id,
+            start_time,
+        )
+        .ok();
+
+        // Stage every change first so a failure leaves all files as they were
+        let result = changes
+            .map(Ok)
+            .unwrap_or_else(|| Self::rename_changes(params))
+            .and_then(|changes| {
+                let diff = FileOps::apply_changes(&changes)?;
+                Ok((
+                    changes.paths().to_vec(),
+                    changes.written_paths().len(),
+                    diff,
+                ))
+            });
+
+        match result {
+            Ok((paths, count, diff)) => {
+                // Send success notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "new_name": params.new_name,
+                    "files": count,
+                    "description": format!("Renamed to {} in {} files", params.new_name, count),
+                });
+                send_tool_notification(
+                    "Rename",
+                    "success",
+                    &format!("Renamed to {} in {count} files", params.new_name),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                Ok(format!(
+                    "Renamed the symbol at {}:{} in '{}' to '{}', changing {} files:\n\n{}{}",
+                    params.position.line,
+                    params.position.character,
+                    params.file_path,
+                    params.new_name,
+                    count,
+                    diff,
+                    diagnostics_after_edit(&paths)
+                ))
+            }
+            Err(e) => {
+                // Send error notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "new_name": params.new_name,
+                    "description": format!("Error renaming symbol: {}", e),
+                });
+                send_tool_notification(
+                    "Rename",
+                    "error",
+                    &format!("Error renaming symbol: {e}"),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                Err(e)
+            }
+        }
+    }
+
+    fn format(params: &FormatParams, changes: Option<ChangeSet>) -> Result<String> {
+        // Generate a unique ID for this execution
+        let tool_id = format!(
+            "format-direct-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Send start notification
+        let metadata = serde_json::json!({
+            "file_path": params.file_path,
+            "server_type": params.server_type,
+            "description": format!("Formatting {}", params.file_path),
+        });
+        send_tool_notification(
+            "Format",
+            "running",
+            &format!("Formatting {}", params.file_path),
+            metadata,
+            &tool_id,
+            start_time,
+        )
+        .ok();
+
+        let result = changes
+            .map(Ok)
+            .unwrap_or_else(|| Self::format_changes(params))
+            .and_then(|changes| {
+                if changes.is_empty() {
+                    return Ok(None);
+                }
+                let diff = FileOps::apply_changes(&changes)?;
+                Ok(Some((changes.paths().to_vec(), diff)))
+            });
+
+        match result {
+            Ok(formatted) => {
+                let summary = match formatted {
+                    Some(_) => format!("Formatted {}", params.file_path),
+                    None => format!("{} is already formatted", params.file_path),
+                };
+
+                // Send success notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "changed": formatted.is_some(),
+                    "description": summary,
+                });
+                send_tool_notification(
+                    "Format", "success", &summary, metadata, &tool_id, start_time,
+                )
+                .ok();
+
+                Ok(match formatted {
+                    Some((paths, diff)) => {
+                        format!("{summary}:\n\n{diff}{}", diagnostics_after_edit(&paths))
+                    }
+                    None => format!("{summary}; nothing changed"),
+                })
+            }
+            Err(e) => {
+                // Send error notification
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "server_type": params.server_type,
+                    "description": format!("Error formatting file: {}", e),
+                });
+                send_tool_notification(
+                    "Format",
+                    "error",
+                    &format!("Error formatting file: {e}"),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Executes the tool, running Bash commands in the session's shell
+    pub fn execute_with_shell(
+        &self,
+        shell: &PersistentShell,
+    ) -> Result<(String, Vec<ContentBlock>)> {
+        match self {
+            ToolCall::Bash(params) => {
+                Self::bash(params, Some(shell)).map(|output| (output, Vec::new()))
+            }
+            _ => self.execute_with_blocks(),
+        }
+    }
+
+    fn bash(params: &BashParams, shell: Option<&PersistentShell>) -> Result<String> {
+        // Generate a unique ID for this execution
+        let tool_id = format!(
+            "bash-direct-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Send start notification with command in the tool name
+        let message = "Executing...";
+        let description = params
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("Executing command: {}", params.command));
+        let metadata = serde_json::json!({
+            "command": params.command,
+            "description": description,
+        });
+        send_tool_notification(
+            &format!("Bash ({})", params.command),
+            "running",
+            message,
+            metadata,
+            &tool_id,
+            start_time,
+        )
+        .ok();
+
+        // Long-running commands are started and left running
+        if params.run_in_background.unwrap_or(false) {
+            let cwd = shell.and_then(PersistentShell::cwd);
+            let result = ProcessManager::start(&params.command, cwd.as_deref());
+            let (status, message) = match &result {
+                Ok(info) => ("success", format!("Started background process {}", info.id)),
+                Err(e) => ("error", format!("Error: {e}")),
+            };
+            let metadata = serde_json::json!({
+                "command": params.command,
+                "process_id": result.as_ref().ok().map(|info| info.id.clone()),
+                "description": description,
+            });
+            send_tool_notification(
+                &format!("Bash ({})", params.command),
+                status,
+                &message,
+                metadata,
+                &tool_id,
+                start_time,
+            )
+            .ok();
+
+            return result.map(|info| {
+                format!(
+                    "Started background process {} (pid {}) running: {}\n\
+                     Use BashOutput with id \"{}\" to read its output and KillProcess to stop it.",
+                    info.id, info.pid, info.command, info.id
+                )
+            });
+        }
+
+        match run_bash_command(params, shell) {
+            Ok(output) => {
+                // Let the model know if shell state was lost
+                let stdout = match &output.notice {
+                    Some(notice) => format!("{}\n[{notice}]", output.stdout),
+                    None => output.stdout.clone(),
+                };
+                let stderr = &output.stderr;
+
+                let result = if output.success() {
+                    // Send success notification with command as the name and output in the message
+                    let description = params
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Command executed: {}", params.command));
+                    let metadata = serde_json::json!({
+                        "command": params.command,
+                        "exit_code": output.exit_code,
+                        "cwd": output.cwd,
+                        "description": description,
+                    });
+                    send_tool_notification(
+                        &format!("Bash ({})", params.command),
+                        "success",
+                        &stdout,
+                        metadata,
+                        &tool_id,
+                        start_time,
+                    )
+                    .ok();
+
+                    stdout
+                } else {
+                    // Send error notification with command as the name and error details in the message
+                    let error_output = format!(
+                        "Failed with exit code: {}\nStdout: {}\nStderr: {}",
+                        output.exit_code, stdout, stderr
+                    );
+                    let description = params
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("Command failed: {}", params.command));
+                    let metadata = serde_json::json!({
+                        "command": params.command,
+                        "exit_code": output.exit_code,
+                        "cwd": output.cwd,
+                        "description": description,
+                    });
+                    send_tool_notification(
+                        &format!("Bash ({})", params.command),
+                        "error",
+                        &error_output,
+                        metadata,
+                        &tool_id,
+                        start_time,
+                    )
+                    .ok();
+
+                    format!(
+                        "Command failed with exit code: {}\nStdout: {}\nStderr: {}",
+                        output.exit_code, stdout, stderr
+                    )
+                };
+
+                Ok(result)
+            }
+            Err(e) => {
+                // Send error notification with command as the name and error details in the message
+                let error_message = format!("Error: {e}");
+                let description = params
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("Command failed: {}", params.command));
+                let metadata = serde_json::json!({
+                    "command": params.command,
+                    "description": description,
+                });
+                send_tool_notification(
+                    &format!("Bash ({})", params.command),
+                    "error",
+                    &error_message,
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                Err(e)
+            }
+        }
+    }
+
+    fn read(params: &ReadParams) -> Result<(String, Vec<ContentBlock>)> {
+        // Generate a unique ID for this execution
+        let tool_id = format!(
+            "read-direct-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        );
+
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Send start notification
+        let metadata = serde_json::json!({
+            "file_path": params.file_path,
+            "description": format!("Reading file: {}", params.file_path),
+        });
+        send_tool_notification(
+            "Read",
+            "running",
+            &format!("Reading file: {}", params.file_path),
+            metadata,
+            &tool_id,
+            start_time,
+        )
+        .ok();
+
+        // Add a brief delay to ensure the running state is visible
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+
+        // Read the file, returning images as content blocks rather than text
+        let path = PathBuf::from(&params.file_path);
+        let result = match ImageOps::read_image(&path) {
+            Ok(Some(image)) => {
+                let (media_type, size) = match &image {
+                    ContentBlock::Image { media_type, data } => (media_type.clone(), data.len()),
+                    _ => (String::new(), 0),
+                };
+                let description = format!(
+                    "Image file {} ({media_type}, {size} bytes base64-encoded) is attached to this result.",
+                    params.file_path
+                );
+
+                let metadata = serde_json::json!({
+                    "file_path": params.file_path,
+                    "media_type": media_type,
+                    "description": "Read image file",
+                });
+                send_tool_notification(
+                    "Read",
+                    "success",
+                    &format!("Read image ({media_type})"),
+                    metadata,
+                    &tool_id,
+                    start_time,
+                )
+                .ok();
+
+                return Ok((description, vec![image]));
+            }
+            // Always use read_file_lines with provided offset and limit
+            Ok(None) => FileOps::read_file_lines(&path, params.offset, Some(params.limit)),
+            Err(e) => Err(e),
+        };
+
+        // Send appropriate completion notification
+        if let Ok(ref content) = result {
+            // Count the number of lines
+            let line_count = content.lines().count();
+
+            // Send success notification
+            let metadata = serde_json::json!({
+                "file_path": params.file_path,
+                "lines": line_count,
+                "description": format!("Read {} lines from file", line_count),
+            });
+            send_tool_notification(
+                "Read",
+                "success",
+                &format!("Read {line_count} lines from file"),
+                metadata,
+                &tool_id,
+                start_time,
+            )
+            .ok();
+        } else if let Err(ref e) = result {
+            // Send error notification
+            let metadata = serde_json::json!({
+                "file_path": params.file_path,
+                "description": format!("Error reading file: {}", e),
+            });
+            send_tool_notification(
+                "Read",
+                "error",
+                &format!("Error reading file: {e}"),
+                metadata,
+                &tool_id,
+                start_time,
+            )
+            .ok();
+        }
+
+        result.map(|content| (content, Vec::new()))
+    }
+}
+
+/// Runs a Bash tool command in the session's shell, or without one in a fresh
+/// `sh -c` in the current directory
+fn run_bash_command(params: &BashParams, shell: Option<&PersistentShell>) -> Result<ShellOutput> {
+    let timeout = params
+        .timeout
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT)
+        .min(MAX_COMMAND_TIMEOUT);
+
+    match shell {
+        Some(shell) => shell.run(&params.command, timeout),
+        None => ShellSession::run_once(&params.command, None, timeout),
+    }
+}
+
+pub fn get_tool_definitions() -> Vec<Value> {
+    vec![
+        serde_json::json!({
+            "name": "Read",
+            "description": "Reads a file from the local filesystem. The file_path must be an absolute path. Image files (PNG, JPEG, GIF, WebP) are returned as images for models that can view them.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file to read"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "The line number to start reading from (required, 0-based)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "The number of lines to read (required)"
+                    }
+                },
+                "required": ["file_path", "offset", "limit"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Glob",
+            "description": "Fast file pattern matching tool using glob patterns like '**/*.rs', supports * (matches characters), ** (recursive directories), {} (alternatives)",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The glob pattern to match files against"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to search in (defaults to current directory)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("Maximum number of files to list, most recently modified first (default {DEFAULT_GLOB_LIMIT}, 0 for no limit)")
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Skip this many files before listing results, to page through them with limit"
+                    }
+                },
+                "required": ["pattern"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Grep",
+            "description": "Fast content search tool using regular expressions to find patterns in file contents",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The regular expression pattern to search for in file contents"
+                    },
+                    "include": {
+                        "type": "string",
+                        "description": "File pattern to filter results (e.g. \"*.rs\", \"*.{ts,tsx}\")"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to search in (defaults to current directory)"
+                    },
+                    "output_mode": {
+                        "type": "string",
+                        "enum": ["content", "files_with_matches", "count"],
+                        "description": "\"content\" shows matching lines (default), \"files_with_matches\" shows only file paths, \"count\" shows the number of matches per file"
+                    },
+                    "-A": {
+                        "type": "integer",
+                        "description": "Number of lines to show after each match (content mode only)"
+                    },
+                    "-B": {
+                        "type": "integer",
+                        "description": "Number of lines to show before each match (content mode only)"
+                    },
+                    "-C": {
+                        "type": "integer",
+                        "description": "Number of lines to show before and after each match (content mode only)"
+                    },
+                    "-i": {
+                        "type": "boolean",
+                        "description": "Case insensitive search"
+                    },
+                    "multiline": {
+                        "type": "boolean",
+                        "description": "Let patterns span lines, with . matching newlines"
+                    },
+                    "type": {
+                        "type": "string",
+                        "description": "File type to search, e.g. \"rs\", \"py\", \"ts\", \"js\", \"go\". More efficient than include for standard file types"
+                    },
+                    "head_limit": {
+                        "type": "integer",
+                        "description": format!("Show at most this many entries: matches in content mode, files otherwise (default {DEFAULT_GREP_HEAD_LIMIT}, 0 for no limit)")
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Skip this many entries before showing results, to page through them with head_limit"
                     }
                 },
                 "required": ["pattern"]
             }
         }),
+        serde_json::json!({
+            "name": "FindFile",
+            "description": "Finds files by approximate name or path using fuzzy matching, best match first. Use it when you know roughly what a file is called but not where it is, e.g. \"usrctl\" finds src/user_controller.rs",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Approximate file name or path. Space-separated terms must all match"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to search in (defaults to current directory)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": format!("Maximum number of files to return (default {DEFAULT_FIND_FILE_LIMIT})")
+                    }
+                },
+                "required": ["query"]
+            }
+        }),
+        serde_json::json!({
+            "name": "RepoMap",
+            "description": "Outlines the repository: the top-level symbols of its source files (functions, types and their methods) with the line each is declared on, most referenced files first, cut to a token budget. Use it at the start of a task to see how the code is laid out before reading files",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to map (defaults to current directory)"
+                    },
+                    "max_tokens": {
+                        "type": "integer",
+                        "description": format!("Rough size of the map in tokens (default {DEFAULT_REPO_MAP_TOKENS}, at most {MAX_REPO_MAP_TOKENS})")
+                    }
+                }
+            }
+        }),
         serde_json::json!({
             "name": "LS",
             "description": "Lists files and directories in a given path",
@@ -1472,38 +3336,97 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     "ignore": {
                         "type": "array",
                         "items": {
-                            "type": "string"
+                            "type": "string"
+                        },
+                        "description": "List of glob patterns to ignore (optional)"
+                    }
+                },
+                "required": ["path"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Edit",
+            "description": "Edits a file by replacing one string with another",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file to modify"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "The text to replace (must be unique within the file)"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "The text to replace it with"
+                    },
+                    "expected_replacements": {
+                        "type": "integer",
+                        "description": "Optional. The expected number of replacements to perform. If not specified, the string must be unique in the file."
+                    }
+                },
+                "required": ["file_path", "old_string", "new_string"]
+            }
+        }),
+        serde_json::json!({
+            "name": "MultiEdit",
+            "description": "Makes several edits to a single file in one operation. Edits are applied in order, each to the result of the previous one, and either all of them succeed or the file is left unchanged. Prefer this over repeated Edit calls when changing several places in the same file.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file to modify"
+                    },
+                    "edits": {
+                        "type": "array",
+                        "description": "The edits to apply, in order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "old_string": {
+                                    "type": "string",
+                                    "description": "The text to replace (must be unique in the file as it is after the previous edits)"
+                                },
+                                "new_string": {
+                                    "type": "string",
+                                    "description": "The text to replace it with"
+                                },
+                                "expected_replacements": {
+                                    "type": "integer",
+                                    "description": "Optional. The expected number of replacements for this edit. If not specified, the string must be unique."
+                                }
+                            },
+                            "required": ["old_string", "new_string"]
                         },
-                        "description": "List of glob patterns to ignore (optional)"
+                        "minItems": 1
                     }
                 },
-                "required": ["path"]
+                "required": ["file_path", "edits"]
             }
         }),
         serde_json::json!({
-            "name": "Edit",
-            "description": "Edits a file by replacing one string with another",
+            "name": "Patch",
+            "description": "Applies a unified diff (as produced by `diff -u` or `git diff`) that may change several files at once, including creating (--- /dev/null), deleting (+++ /dev/null) and renaming files. Hunks are located by their context, so line numbers may be approximate; up to `fuzz` context lines at each end of a hunk may be ignored. Either every hunk applies or no file is changed, and the result reports each hunk.",
             "parameters": {
                 "type": "object",
                 "properties": {
-                    "file_path": {
+                    "patch": {
                         "type": "string",
-                        "description": "The absolute path to the file to modify"
-                    },
-                    "old_string": {
-                        "type": "string",
-                        "description": "The text to replace (must be unique within the file)"
+                        "description": "The unified diff to apply, with ---/+++ file headers and @@ hunks"
                     },
-                    "new_string": {
+                    "base_dir": {
                         "type": "string",
-                        "description": "The text to replace it with"
+                        "description": "Optional. Absolute path of the directory that paths in the patch are resolved against; they may not lead outside it. Defaults to the working directory."
                     },
-                    "expected_replacements": {
+                    "fuzz": {
                         "type": "integer",
-                        "description": "Optional. The expected number of replacements to perform. If not specified, the string must be unique in the file."
+                        "description": "Optional. Number of context lines that may be ignored at each end of a hunk when it doesn't match exactly (default 2, use 0 for exact matching)."
                     }
                 },
-                "required": ["file_path", "old_string", "new_string"]
+                "required": ["patch"]
             }
         }),
         serde_json::json!({
@@ -1526,7 +3449,7 @@ pub fn get_tool_definitions() -> Vec<Value> {
         }),
         serde_json::json!({
             "name": "Bash",
-            "description": "Executes a bash command",
+            "description": "Executes a bash command in the working directory. If the persistent shell is enabled for the session, the working directory, exported variables, activated virtualenvs and shell functions carry over between calls; otherwise each call starts a fresh shell. stdin is not available, so interactive commands will not work.",
             "parameters": {
                 "type": "object",
                 "properties": {
@@ -1541,11 +3464,47 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     "description": {
                         "type": "string",
                         "description": "A short (5-10 word) description of what this command does"
+                    },
+                    "run_in_background": {
+                        "type": "boolean",
+                        "description": "Optional. Start the command in the background and return a process id immediately, for dev servers, watchers and other long-running commands. It runs in the shell's current directory but not its exported variables."
                     }
                 },
                 "required": ["command"]
             }
         }),
+        serde_json::json!({
+            "name": "BashOutput",
+            "description": "Reads output from a background process started with Bash run_in_background. Returns whether it is still running and the lines written since `since`; each result says which `since` to pass next time to get only new output.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The background process id, e.g. \"bg-1\""
+                    },
+                    "since": {
+                        "type": "integer",
+                        "description": "Optional. Only return output from this line number on. Omit to get all buffered output."
+                    }
+                },
+                "required": ["id"]
+            }
+        }),
+        serde_json::json!({
+            "name": "KillProcess",
+            "description": "Stops a background process started with Bash run_in_background, along with any processes it started",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The background process id, e.g. \"bg-1\""
+                    }
+                },
+                "required": ["id"]
+            }
+        }),
         serde_json::json!({
             "name": "DocumentSymbol",
             "description": "Extracts document symbols from a file using LSP",
@@ -1558,11 +3517,10 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     },
                     "server_type": {
                         "type": "string",
-                        "enum": ["Python", "Rust"],
-                        "description": "The type of LSP server to use"
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
                     }
                 },
-                "required": ["file_path", "server_type"]
+                "required": ["file_path"]
             }
         }),
         serde_json::json!({
@@ -1577,11 +3535,10 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     },
                     "server_type": {
                         "type": "string",
-                        "enum": ["Python", "Rust"],
-                        "description": "The type of LSP server to use"
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
                     }
                 },
-                "required": ["file_path", "server_type"]
+                "required": ["file_path"]
             }
         }),
         serde_json::json!({
@@ -1596,11 +3553,10 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     },
                     "server_type": {
                         "type": "string",
-                        "enum": ["Python", "Rust"],
-                        "description": "The type of LSP server to use"
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
                     }
                 },
-                "required": ["file_path", "server_type"]
+                "required": ["file_path"]
             }
         }),
         serde_json::json!({
@@ -1630,11 +3586,313 @@ pub fn get_tool_definitions() -> Vec<Value> {
                     },
                     "server_type": {
                         "type": "string",
-                        "enum": ["Python", "Rust"],
-                        "description": "The type of LSP server to use"
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "position"]
+            }
+        }),
+        serde_json::json!({
+            "name": "References",
+            "description": "Finds every reference to the symbol at a position using LSP, e.g. all callers of a function. Results are listed as path:line:col (1-based) with the source line.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "position": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "description": "The line number (0-based)"
+                            },
+                            "character": {
+                                "type": "integer",
+                                "description": "The character position (0-based)"
+                            }
+                        },
+                        "required": ["line", "character"],
+                        "description": "The position of the symbol in the file"
+                    },
+                    "include_declaration": {
+                        "type": "boolean",
+                        "description": "Also list the declaration itself (default true)"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "position"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Hover",
+            "description": "Shows the type, signature and documentation of the symbol at a position using LSP",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "position": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "description": "The line number (0-based)"
+                            },
+                            "character": {
+                                "type": "integer",
+                                "description": "The character position (0-based)"
+                            }
+                        },
+                        "required": ["line", "character"],
+                        "description": "The position of the symbol in the file"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "position"]
+            }
+        }),
+        serde_json::json!({
+            "name": "WorkspaceSymbol",
+            "description": "Searches the symbols (types, functions, constants...) of a whole project by name using LSP. Results are listed as path:line:col (1-based) with the symbol kind.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The symbol name or part of it; servers match it fuzzily"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "The project directory to search (defaults to the current directory)"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the project files when omitted."
+                    }
+                },
+                "required": ["query"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Rename",
+            "description": "Renames the symbol at a position everywhere it is used, across files, using LSP. All changes are applied together or not at all, and the combined diff is returned. Prefer this over editing each use by hand.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "position": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "description": "The line number (0-based)"
+                            },
+                            "character": {
+                                "type": "integer",
+                                "description": "The character position (0-based)"
+                            }
+                        },
+                        "required": ["line", "character"],
+                        "description": "The position of the symbol in the file"
+                    },
+                    "new_name": {
+                        "type": "string",
+                        "description": "The new name for the symbol"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "position", "new_name"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Format",
+            "description": "Formats a whole file with its language server, the way the project's formatter would, and returns the diff. Use it after editing when the project expects formatted code.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file to format"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path"]
+            }
+        }),
+        serde_json::json!({
+            "name": "CodeActions",
+            "description": "Lists the quick fixes and refactorings the language server offers for a range of a file, such as adding a missing import or fixing a diagnostic there. Each has an id to pass to ApplyCodeAction. A new listing replaces the previous one.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file"
+                    },
+                    "range": {
+                        "type": "object",
+                        "properties": {
+                            "start": {
+                                "type": "object",
+                                "properties": {
+                                    "line": { "type": "integer", "description": "The line number (0-based)" },
+                                    "character": { "type": "integer", "description": "The character position (0-based)" }
+                                },
+                                "required": ["line", "character"]
+                            },
+                            "end": {
+                                "type": "object",
+                                "properties": {
+                                    "line": { "type": "integer", "description": "The line number (0-based)" },
+                                    "character": { "type": "integer", "description": "The character position (0-based)" }
+                                },
+                                "required": ["line", "character"]
+                            }
+                        },
+                        "required": ["start", "end"],
+                        "description": "The range to get actions for, e.g. the range of a diagnostic"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "range"]
+            }
+        }),
+        serde_json::json!({
+            "name": "ApplyCodeAction",
+            "description": "Applies a code action listed by the last CodeActions call, writing its edits and running its command on the language server. Returns the diff of the files it changed.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "The id of the action, as listed by CodeActions"
+                    }
+                },
+                "required": ["id"]
+            }
+        }),
+        serde_json::json!({
+            "name": "CallHierarchy",
+            "description": "Shows the callers or callees of the function at a position, and theirs in turn, as an indented tree with the location of each function and of each call. Use it to see what a change to a function affects.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "position": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "description": "The line number (0-based)"
+                            },
+                            "character": {
+                                "type": "integer",
+                                "description": "The character position (0-based)"
+                            }
+                        },
+                        "required": ["line", "character"],
+                        "description": "The position of the symbol's name in the file"
+                    },
+                    "direction": {
+                        "type": "string",
+                        "enum": ["incoming", "outgoing"],
+                        "description": "\"incoming\" for the functions that call it (default) or \"outgoing\" for the functions it calls"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Levels of calls to follow, 2 by default and 5 at most"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
+                    }
+                },
+                "required": ["file_path", "position"]
+            }
+        }),
+        serde_json::json!({
+            "name": "TypeHierarchy",
+            "description": "Shows the supertypes or subtypes of the class, interface or trait at a position, and theirs in turn, as an indented tree with the location of each type.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to the file containing the symbol"
+                    },
+                    "position": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "description": "The line number (0-based)"
+                            },
+                            "character": {
+                                "type": "integer",
+                                "description": "The character position (0-based)"
+                            }
+                        },
+                        "required": ["line", "character"],
+                        "description": "The position of the symbol's name in the file"
+                    },
+                    "direction": {
+                        "type": "string",
+                        "enum": ["supertypes", "subtypes"],
+                        "description": "\"supertypes\" for the types it extends or implements (default) or \"subtypes\" for the types that extend or implement it"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Levels of types to follow, 2 by default and 5 at most"
+                    },
+                    "server_type": {
+                        "type": "string",
+                        "description": "The language server to use, by name (e.g. \"gopls\") or language. Chosen from the file extension when omitted."
                     }
                 },
-                "required": ["file_path", "position", "server_type"]
+                "required": ["file_path", "position"]
+            }
+        }),
+        serde_json::json!({
+            "name": "Diagnostics",
+            "description": "Reports compiler and linter errors and warnings from the language server. Pass file_path to check one file, or workspace to list everything reported for a project. Edit, MultiEdit, Patch and Write already append diagnostics for files a running server handles.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The absolute path to a file to check; its language server is chosen by extension"
+                    },
+                    "workspace": {
+                        "type": "string",
+                        "description": "A project directory whose diagnostics to list, used when file_path is not given. Defaults to the current directory."
+                    }
+                }
             }
         }),
     ]
@@ -1,4 +1,8 @@
-use crate::apis::api_client::{ApiClient, CompletionOptions, Message, ToolCall, ToolResult};
+use crate::apis::api_client::{
+    ApiClient, CompletionOptions, ContentBlock, Message, ToolCall, ToolResult,
+};
+use crate::apis::http::HttpClientBuilder;
+use crate::apis::structured;
 use crate::app::logger::{format_log_with_color, LogLevel};
 use crate::errors::AppError;
 use anyhow::{Context, Result};
@@ -9,9 +13,13 @@ use reqwest::Client as ReqwestClient;
 use reqwest::Response;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
+use std::collections::HashSet;
 use std::env;
 use std::time::Duration;
 
+/// Smallest thinking budget the Messages API accepts
+const MIN_THINKING_BUDGET: u32 = 1024;
+
 // Helper function to log usage information from Anthropic API
 fn log_anthropic_usage(usage: &Value) {
     let mut input_tokens = usage
@@ -52,6 +60,51 @@ fn log_anthropic_usage(usage: &Value) {
     );
 }
 
+// Splits a "Tool result for call {id}: {output}" message recorded by the agent executor
+fn parse_tool_result_message(content: &str) -> Option<(&str, &str)> {
+    content
+        .strip_prefix("Tool result for call ")?
+        .split_once(": ")
+        .filter(|(id, _)| !id.is_empty())
+}
+
+// Recovers the text and tool calls from an assistant message that the agent executor
+// stored as a JSON object with `content` and `tool_calls` fields
+fn parse_assistant_tool_turn(content: &str) -> Option<(String, Vec<ToolCall>)> {
+    let json: Value = serde_json::from_str(content).ok()?;
+    let calls = json.get("tool_calls")?.as_array()?;
+    if calls.is_empty() {
+        return None;
+    }
+
+    let mut parsed = Vec::with_capacity(calls.len());
+    for call in calls {
+        let id = call.get("id")?.as_str().filter(|id| !id.is_empty())?;
+        let name = call.get("name")?.as_str()?;
+        let input = call.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        parsed.push(ToolCall {
+            id: Some(id.to_string()),
+            name: name.to_string(),
+            arguments: input,
+        });
+    }
+
+    let text = json
+        .get("content")
+        .and_then(|c| c.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    Some((text, parsed))
+}
+
+// Forwards a chunk of streamed thinking text to the UI on its own notification channel
+fn send_thinking_notification(params: Value) {
+    if let Some(rpc_server) = crate::communication::rpc::get_global_rpc_server() {
+        let _ = rpc_server.send_notification("thinking", params);
+    }
+}
+
 // Anthropic API models
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 struct AnthropicMessage {
@@ -82,10 +135,71 @@ enum AnthropicContent {
     ToolResult {
         #[serde(rename = "tool_use_id")]
         tool_call_id: String,
-        content: String,
+        content: ToolResultContent,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<CacheControl>,
     },
+
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
+
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        // The signature only arrives at the end of a streamed block
+        #[serde(default)]
+        signature: String,
+    },
+
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+}
+
+impl AnthropicContent {
+    fn is_thinking(&self) -> bool {
+        matches!(
+            self,
+            AnthropicContent::Thinking { .. } | AnthropicContent::RedactedThinking { .. }
+        )
+    }
+}
+
+impl From<ContentBlock> for AnthropicContent {
+    fn from(block: ContentBlock) -> Self {
+        match block {
+            ContentBlock::Thinking {
+                thinking,
+                signature,
+            } => AnthropicContent::Thinking {
+                thinking,
+                signature,
+            },
+            ContentBlock::RedactedThinking { data } => AnthropicContent::RedactedThinking { data },
+            ContentBlock::Image { media_type, data } => AnthropicContent::Image {
+                source: ImageSource {
+                    source_type: "base64".to_string(),
+                    media_type,
+                    data,
+                },
+            },
+        }
+    }
+}
+
+// Tool results are plain text unless the tool returned images alongside it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<AnthropicContent>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct ImageSource {
+    #[serde(rename = "type")]
+    source_type: String,
+    media_type: String,
+    data: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -107,17 +221,19 @@ struct AnthropicTool {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct AnthropicResponseFormat {
+struct AnthropicToolChoice {
     #[serde(rename = "type")]
-    format_type: String,
+    choice_type: String,
+    /// Tool the model must call when `choice_type` is "tool"
     #[serde(skip_serializing_if = "Option::is_none")]
-    schema: Option<Value>,
+    name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct AnthropicToolChoice {
+struct AnthropicThinking {
     #[serde(rename = "type")]
-    choice_type: String,
+    thinking_type: String,
+    budget_tokens: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,7 +252,9 @@ struct AnthropicRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<AnthropicToolChoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    response_format: Option<AnthropicResponseFormat>,
+    thinking: Option<AnthropicThinking>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -297,7 +415,9 @@ impl AnthropicClient {
         headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
         headers.insert("x-api-key", HeaderValue::from_str(&api_key)?);
 
-        let client = ReqwestClient::builder().default_headers(headers).build()?;
+        let client = HttpClientBuilder::new("anthropic")
+            .with_headers(headers)
+            .build()?;
 
         // Default to Claude 3.7 Sonnet as the latest model with tooling capabilities
         let model = model.unwrap_or_else(|| "claude-sonnet-4-20250514".to_string());
@@ -332,7 +452,8 @@ impl AnthropicClient {
     ///
     /// This method:
     /// 1. Filters out system messages (handled separately)
-    /// 2. Formats each message as an AnthropicMessage
+    /// 2. Formats each message as an AnthropicMessage, replaying thinking blocks and
+    ///    restoring native tool_use/tool_result blocks for agent tool turns
     /// 3. Adds cache_control to the last and second-to-last user messages for prompt caching
     fn convert_messages(&self, messages: Vec<Message>) -> Vec<AnthropicMessage> {
         let filtered_messages: Vec<Message> = messages
@@ -340,7 +461,7 @@ impl AnthropicClient {
             .filter(|msg| msg.role != "system") // Filter out system messages
             .collect();
 
-        let mut anthropic_messages = Vec::new();
+        let mut anthropic_messages: Vec<AnthropicMessage> = Vec::new();
 
         // Precompute the indices of user messages
         let user_indices: Vec<usize> = filtered_messages
@@ -356,32 +477,110 @@ impl AnthropicClient {
             .get(user_indices.len().saturating_sub(2))
             .copied();
 
+        // Tool calls can only be replayed natively when their results are also in the
+        // history, otherwise the API rejects the dangling tool_use blocks
+        let answered_ids: HashSet<String> = filtered_messages
+            .iter()
+            .filter(|m| m.role == "user")
+            .filter_map(|m| parse_tool_result_message(&m.content))
+            .map(|(id, _)| id.to_string())
+            .collect();
+        let mut open_tool_ids: HashSet<String> = HashSet::new();
+
         // Use enumerated iterator to track position efficiently
         for (idx, msg) in filtered_messages.iter().enumerate() {
-            let mut content = vec![AnthropicContent::Text {
-                text: msg.content.clone(),
-                cache_control: None,
-            }];
-
-            // Apply cache control to last and second-to-last user messages
-            if let Some(last_idx) = last_user_index {
-                // Always apply cache to the last user message
-                if idx == last_idx {
-                    content = vec![AnthropicContent::Text {
-                        text: msg.content.clone(),
-                        cache_control: Some(Self::create_ephemeral_cache()),
-                    }];
-                } else if let Some(second_last_idx) = second_last_user_index {
-                    // Apply to second-to-last if it exists
-                    if idx == second_last_idx {
-                        content = vec![AnthropicContent::Text {
-                            text: msg.content.clone(),
-                            cache_control: Some(Self::create_ephemeral_cache()),
-                        }];
+            let cache_control =
+                if Some(idx) == last_user_index || Some(idx) == second_last_user_index {
+                    Some(Self::create_ephemeral_cache())
+                } else {
+                    None
+                };
+
+            if msg.role == "user" {
+                if let Some((id, output)) = parse_tool_result_message(&msg.content) {
+                    if open_tool_ids.remove(id) {
+                        // Images returned by a tool (e.g. Read on a screenshot) go inside the result
+                        let content = if msg.blocks.is_empty() {
+                            ToolResultContent::Text(output.to_string())
+                        } else {
+                            let mut blocks = vec![AnthropicContent::Text {
+                                text: output.to_string(),
+                                cache_control: None,
+                            }];
+                            blocks.extend(msg.blocks.iter().cloned().map(AnthropicContent::from));
+                            ToolResultContent::Blocks(blocks)
+                        };
+
+                        let block = AnthropicContent::ToolResult {
+                            tool_call_id: id.to_string(),
+                            content,
+                            cache_control,
+                        };
+
+                        // All results for one assistant turn belong in a single user message
+                        match anthropic_messages.last_mut() {
+                            Some(last)
+                                if last.role == "user"
+                                    && last.content.iter().all(|c| {
+                                        matches!(c, AnthropicContent::ToolResult { .. })
+                                    }) =>
+                            {
+                                last.content.push(block);
+                            }
+                            _ => anthropic_messages.push(AnthropicMessage {
+                                role: "user".to_string(),
+                                content: vec![block],
+                            }),
+                        }
+                        continue;
                     }
                 }
             }
 
+            // Thinking blocks must come first and be passed back exactly as received, and
+            // images are placed before the text that refers to them
+            let mut content: Vec<AnthropicContent> = msg
+                .blocks
+                .iter()
+                .cloned()
+                .map(AnthropicContent::from)
+                .collect();
+
+            let tool_turn = if msg.role == "assistant" {
+                parse_assistant_tool_turn(&msg.content).filter(|(_, calls)| {
+                    calls
+                        .iter()
+                        .all(|c| c.id.as_ref().is_some_and(|id| answered_ids.contains(id)))
+                })
+            } else {
+                None
+            };
+
+            match tool_turn {
+                Some((text, calls)) => {
+                    if !text.is_empty() {
+                        content.push(AnthropicContent::Text {
+                            text,
+                            cache_control: None,
+                        });
+                    }
+                    for call in calls {
+                        let id = call.id.unwrap_or_default();
+                        open_tool_ids.insert(id.clone());
+                        content.push(AnthropicContent::ToolUse {
+                            id,
+                            name: call.name,
+                            input: call.arguments,
+                            cache_control: None,
+                        });
+                    }
+                }
+                None => content.push(AnthropicContent::Text {
+                    text: msg.content.clone(),
+                    cache_control,
+                }),
+            }
+
             anthropic_messages.push(AnthropicMessage {
                 role: msg.role.clone(),
                 content,
@@ -391,6 +590,77 @@ impl AnthropicClient {
         anthropic_messages
     }
 
+    /// Configures extended thinking on a request
+    ///
+    /// When a budget is set, thinking is enabled and the sampling parameters the API
+    /// rejects alongside it are cleared. Without a budget any thinking blocks in the
+    /// history are dropped, since they are only meaningful to a thinking-enabled request.
+    fn apply_thinking(request: &mut AnthropicRequest, thinking_budget: Option<u32>) {
+        match thinking_budget {
+            Some(budget) => {
+                let budget = budget.max(MIN_THINKING_BUDGET);
+                request.thinking = Some(AnthropicThinking {
+                    thinking_type: "enabled".to_string(),
+                    budget_tokens: budget,
+                });
+
+                // The budget counts towards max_tokens, so it's added on top of
+                // the room asked for the answer and tool calls
+                request.max_tokens += budget as usize;
+
+                // Thinking requires the default temperature and only allows auto tool choice
+                request.temperature = None;
+                request.top_p = None;
+                if let Some(choice) = request.tool_choice.as_mut() {
+                    choice.choice_type = "auto".to_string();
+                }
+            }
+            None => {
+                for message in &mut request.messages {
+                    message.content.retain(|c| !c.is_thinking());
+                }
+                request.messages.retain(|m| !m.content.is_empty());
+            }
+        }
+    }
+
+    /// Anthropic has no JSON schema response mode, so structured output is
+    /// obtained by forcing a call to a tool whose input schema is the requested
+    /// schema. Returns whether the schema had to be wrapped in an object, or
+    /// `None` if the schema couldn't be parsed.
+    fn apply_structured_output(request: &mut AnthropicRequest, json_schema: &str) -> Option<bool> {
+        let schema: Value = serde_json::from_str(json_schema).ok()?;
+        let (input_schema, wrapped) = structured::object_schema(&schema);
+
+        request.tools = Some(vec![AnthropicTool {
+            name: structured::STRUCTURED_OUTPUT_TOOL.to_string(),
+            description: Some(
+                "Respond by calling this tool with your answer as the input".to_string(),
+            ),
+            schema: input_schema,
+            cache_control: None,
+        }]);
+        request.tool_choice = Some(AnthropicToolChoice {
+            choice_type: "tool".to_string(),
+            name: Some(structured::STRUCTURED_OUTPUT_TOOL.to_string()),
+        });
+
+        Some(wrapped)
+    }
+
+    /// Extracts the JSON passed to the structured output tool, if it was called
+    fn structured_output_text(response: &AnthropicResponse, wrapped: bool) -> Option<String> {
+        response.content.iter().find_map(|content| match content {
+            AnthropicContent::ToolUse { name, input, .. }
+                if name == structured::STRUCTURED_OUTPUT_TOOL =>
+            {
+                let output = if wrapped { &input["value"] } else { input };
+                Some(output.to_string())
+            }
+            _ => None,
+        })
+    }
+
     /// Converts internal tool definitions to Anthropic's format with cache control
     ///
     /// This method:
@@ -440,35 +710,12 @@ impl AnthropicClient {
 
         tool_specs
     }
-}
 
-#[async_trait]
-impl ApiClient for AnthropicClient {
-    async fn complete(&self, messages: Vec<Message>, options: CompletionOptions) -> Result<String> {
-        // Extract system message if present
-        let system_message = self.extract_system_message(&messages);
-        let converted_messages = self.convert_messages(messages);
-
-        let max_tokens = options.max_tokens.unwrap_or(2048) as usize;
-
-        let mut request = AnthropicRequest {
-            model: self.model.clone(),
-            messages: converted_messages,
-            max_tokens,
-            system: system_message,
-            temperature: options.temperature,
-            top_p: options.top_p,
-            tools: None,
-            tool_choice: None,
-            response_format: None,
-        };
-
-        // Add structured output format if specified in options
-        if let Some(json_schema) = &options.json_schema {
-            request.response_format = Some(AnthropicResponseFormat {
-                format_type: "json".to_string(),
-                schema: serde_json::from_str(json_schema).ok(),
-            });
+    /// Sends a request and parses the response, streaming it when thinking is enabled
+    async fn send_message(&self, mut request: AnthropicRequest) -> Result<AnthropicResponse> {
+        let streaming = request.thinking.is_some();
+        if streaming {
+            request.stream = Some(true);
         }
 
         // Use our retry function instead of direct API call
@@ -486,6 +733,10 @@ impl ApiClient for AnthropicClient {
             .into());
         }
 
+        if streaming {
+            return self.read_event_stream(response).await;
+        }
+
         // Get the response as a string first for debugging
         let response_text = response.text().await.map_err(|e| {
             let error_msg = format!("Failed to get response text: {e}");
@@ -513,48 +764,67 @@ impl ApiClient for AnthropicClient {
                 AppError::Other(error_msg)
             })?;
 
-        // Extract content from response
-        let mut text_content = String::new();
+        Ok(anthropic_response)
+    }
 
-        // Look for text content in the response
-        for content_item in &anthropic_response.content {
-            if let AnthropicContent::Text { text, .. } = content_item {
-                text_content = text.clone();
-                break;
+    /// Assembles a streamed (server-sent events) response into a regular response
+    ///
+    /// Thinking deltas are forwarded to the UI as `thinking` notifications while they
+    /// arrive, so the user can follow the model's reasoning before the answer is ready.
+    async fn read_event_stream(&self, mut response: Response) -> Result<AnthropicResponse> {
+        let mut state = StreamState::default();
+        // Raw bytes, since a chunk can end in the middle of a multi-byte character
+        let mut buffer: Vec<u8> = Vec::new();
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::NetworkError(format!("Failed to read Anthropic stream: {e}")))?
+        {
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(event) = take_event(&mut buffer) {
+                for data in event.lines().filter_map(|l| l.strip_prefix("data:")) {
+                    let data: Value = serde_json::from_str(data.trim()).map_err(|e| {
+                        AppError::Other(format!("Failed to parse Anthropic stream event: {e}"))
+                    })?;
+                    state.apply_event(&data)?;
+                }
             }
         }
 
-        // Return an error if no text content was found
-        if text_content.is_empty() {
-            let error_msg = "No text content in Anthropic response".to_string();
-            eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
-            return Err(AppError::LLMError(error_msg).into());
-        }
-
-        // Log usage information if available, including cache-related tokens
-        if let Some(usage) = &anthropic_response.usage {
-            log_anthropic_usage(usage);
-        }
-
-        let content = text_content;
-
-        Ok(content)
+        state.into_response()
     }
 
-    async fn complete_with_tools(
+    /// Shared implementation of the tool-enabled completion, returning thinking blocks too
+    async fn complete_with_thinking(
         &self,
         messages: Vec<Message>,
         options: CompletionOptions,
         tool_results: Option<Vec<ToolResult>>,
-    ) -> Result<(String, Option<Vec<ToolCall>>)> {
+    ) -> Result<(String, Option<Vec<ToolCall>>, Vec<ContentBlock>)> {
         // Extract system message if present
         let system_message = self.extract_system_message(&messages);
         let mut converted_messages = self.convert_messages(messages);
 
+        // Results already replayed from the history don't need a synthetic tool turn
+        let replayed_ids: HashSet<String> = converted_messages
+            .iter()
+            .flat_map(|m| m.content.iter())
+            .filter_map(|c| match c {
+                AnthropicContent::ToolResult { tool_call_id, .. } => Some(tool_call_id.clone()),
+                _ => None,
+            })
+            .collect();
+
         // Add tool results if they exist
         if let Some(results) = tool_results {
             // For each tool result, we need to add corresponding messages
             for result in results {
+                if replayed_ids.contains(&result.tool_call_id) {
+                    continue;
+                }
+
                 // Ensure we have a valid tool_call_id
                 let tool_call_id = if result.tool_call_id.is_empty() {
                     // Generate a simple UUID-like string if no ID was provided
@@ -579,7 +849,7 @@ impl ApiClient for AnthropicClient {
                     role: "user".to_string(),
                     content: vec![AnthropicContent::ToolResult {
                         tool_call_id: tool_call_id.clone(),
-                        content: result.output.clone(),
+                        content: ToolResultContent::Text(result.output.clone()),
                         cache_control: None,
                     }],
                 };
@@ -601,20 +871,16 @@ impl ApiClient for AnthropicClient {
             top_p: options.top_p,
             tools: None,
             tool_choice: None,
-            response_format: None,
+            thinking: None,
+            stream: None,
         };
 
-        // IMPORTANT: Add response_format only if json_schema exists AND tools don't exist
-        // This fixes the "extra inputs are not permitted" error when using tools
-        if let Some(json_schema) = &options.json_schema {
-            // Only add response_format if we're not using tools
-            if options.tools.is_none() {
-                request.response_format = Some(AnthropicResponseFormat {
-                    format_type: "json".to_string(),
-                    schema: serde_json::from_str(json_schema).ok(),
-                });
-            }
-        }
+        // Structured output is only enforced when the model isn't also choosing
+        // between real tools, since it works by forcing a tool call
+        let structured = match (&options.json_schema, &options.tools) {
+            (Some(json_schema), None) => Self::apply_structured_output(&mut request, json_schema),
+            _ => None,
+        };
 
         // Add tools if they exist
         if let Some(tools) = options.tools {
@@ -628,53 +894,29 @@ impl ApiClient for AnthropicClient {
                 } else {
                     "auto".to_string()
                 },
+                name: None,
             });
         }
 
-        // Use our retry function instead of direct API call
-        let response = self.send_request_with_retry(&request).await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::NetworkError(format!(
-                "Anthropic API error: {status} - {error_text}"
-            ))
-            .into());
-        }
-
-        // Get the response as a string first for debugging
-        let response_text = response.text().await.map_err(|e| {
-            let error_msg = format!("Failed to get response text: {e}");
-            eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
-            AppError::NetworkError(error_msg)
-        })?;
+        // A forced tool call can't be combined with extended thinking
+        let thinking_budget = options.thinking_budget.filter(|_| structured.is_none());
+        Self::apply_thinking(&mut request, thinking_budget);
 
-        // Log response details
-        eprintln!(
-            "{}",
-            format_log_with_color(
-                LogLevel::Debug,
-                &format!(
-                    "Anthropic API response received: {} bytes",
-                    response_text.len()
-                )
-            )
-        );
+        let anthropic_response = self.send_message(request).await?;
 
-        // Try to parse the response
-        let anthropic_response: AnthropicResponse =
-            serde_json::from_str(&response_text).map_err(|e| {
-                let error_msg = format!("Failed to parse Anthropic response: {e}");
-                eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
-                AppError::Other(error_msg)
-            })?;
+        // The forced structured output call is the answer, not a tool to execute
+        if let Some(output) = structured
+            .and_then(|wrapped| Self::structured_output_text(&anthropic_response, wrapped))
+        {
+            if let Some(usage) = &anthropic_response.usage {
+                log_anthropic_usage(usage);
+            }
+            return Ok((output, None, Vec::new()));
+        }
 
         // First extract tool calls from content
         let mut tool_calls_vec = Vec::new();
+        let mut thinking_blocks = Vec::new();
         let mut text_content = String::new();
 
         // Process each content item
@@ -686,16 +928,30 @@ impl ApiClient for AnthropicClient {
                         text_content = text.clone();
                     }
                 }
-                AnthropicContent::ToolUse { name, input, .. } => {
-                    // Add a tool call
+                AnthropicContent::ToolUse {
+                    id, name, input, ..
+                } => {
+                    // Add a tool call, keeping the id so the result can be paired with it
                     tool_calls_vec.push(crate::apis::api_client::ToolCall {
-                        id: None, // Anthropic doesn't provide IDs like OpenAI
+                        id: Some(id.clone()),
                         name: name.clone(),
                         arguments: input.clone(),
                     });
                 }
-                AnthropicContent::ToolResult { .. } => {
-                    // Tool results are not processed here, they're for the API to recognize tool result responses
+                AnthropicContent::ToolResult { .. } | AnthropicContent::Image { .. } => {
+                    // Tool results and images are not processed here, they only appear in requests
+                }
+                AnthropicContent::Thinking {
+                    thinking,
+                    signature,
+                } => {
+                    thinking_blocks.push(ContentBlock::Thinking {
+                        thinking: thinking.clone(),
+                        signature: signature.clone(),
+                    });
+                }
+                AnthropicContent::RedactedThinking { data } => {
+                    thinking_blocks.push(ContentBlock::RedactedThinking { data: data.clone() });
                 }
             }
         }
@@ -705,16 +961,6 @@ impl ApiClient for AnthropicClient {
             log_anthropic_usage(usage);
         }
 
-        // If we didn't find any text content, use an empty string
-        let content = if text_content.is_empty() {
-            String::new()
-        } else {
-            text_content
-        };
-
-        // We no longer need to check a top-level tool_use field as all tool uses
-        // will be in the content array already
-
         // Return None if no tool calls found, otherwise return the vector
         let tool_calls = if tool_calls_vec.is_empty() {
             None
@@ -722,8 +968,228 @@ impl ApiClient for AnthropicClient {
             Some(tool_calls_vec)
         };
 
+        Ok((text_content, tool_calls, thinking_blocks))
+    }
+}
+
+/// Accumulates the events of a streamed Messages API response
+#[derive(Default)]
+struct StreamState {
+    message: Option<Value>,
+    blocks: Vec<Value>,
+    partial_inputs: Vec<String>,
+    usage: serde_json::Map<String, Value>,
+    stop_reason: Option<String>,
+}
+
+impl StreamState {
+    fn apply_event(&mut self, event: &Value) -> Result<()> {
+        match event["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                let message = event["message"].clone();
+                if let Some(usage) = message["usage"].as_object() {
+                    self.usage.extend(usage.clone());
+                }
+                self.message = Some(message);
+            }
+            "content_block_start" => {
+                let index = event["index"].as_u64().unwrap_or(0) as usize;
+                if self.blocks.len() <= index {
+                    self.blocks.resize(index + 1, Value::Null);
+                    self.partial_inputs.resize(index + 1, String::new());
+                }
+                self.blocks[index] = event["content_block"].clone();
+            }
+            "content_block_delta" => {
+                let index = event["index"].as_u64().unwrap_or(0) as usize;
+                let delta = &event["delta"];
+                let Some(block) = self.blocks.get_mut(index) else {
+                    return Ok(());
+                };
+
+                match delta["type"].as_str().unwrap_or_default() {
+                    "text_delta" => append_field(block, "text", &delta["text"]),
+                    "thinking_delta" => {
+                        append_field(block, "thinking", &delta["thinking"]);
+                        send_thinking_notification(json!({
+                            "type": "delta",
+                            "index": index,
+                            "text": delta["thinking"],
+                        }));
+                    }
+                    "signature_delta" => append_field(block, "signature", &delta["signature"]),
+                    "input_json_delta" => {
+                        if let Some(partial) = delta["partial_json"].as_str() {
+                            self.partial_inputs[index].push_str(partial);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_stop" => {
+                let index = event["index"].as_u64().unwrap_or(0) as usize;
+                if let Some(block) = self.blocks.get_mut(index) {
+                    let partial = &self.partial_inputs[index];
+                    if block["type"] == "tool_use" && !partial.is_empty() {
+                        block["input"] = serde_json::from_str(partial).map_err(|e| {
+                            AppError::Other(format!("Invalid tool input in Anthropic stream: {e}"))
+                        })?;
+                    }
+                    if block["type"] == "thinking" {
+                        send_thinking_notification(json!({ "type": "done", "index": index }));
+                    }
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event["delta"]["stop_reason"].as_str() {
+                    self.stop_reason = Some(reason.to_string());
+                }
+                if let Some(usage) = event["usage"].as_object() {
+                    self.usage.extend(usage.clone());
+                }
+            }
+            "error" => {
+                return Err(AppError::LLMError(format!(
+                    "Anthropic stream error: {}",
+                    event["error"]["message"]
+                        .as_str()
+                        .unwrap_or("unknown error")
+                ))
+                .into());
+            }
+            // ping and message_stop carry nothing we need
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn into_response(self) -> Result<AnthropicResponse> {
+        let mut message = self
+            .message
+            .ok_or_else(|| AppError::LLMError("Anthropic stream ended early".to_string()))?;
+
+        message["content"] = Value::Array(self.blocks);
+        message["usage"] = Value::Object(self.usage);
+        if let Some(reason) = self.stop_reason {
+            message["stop_reason"] = Value::String(reason);
+        }
+
+        serde_json::from_value(message).map_err(|e| {
+            AppError::Other(format!("Failed to parse streamed Anthropic response: {e}")).into()
+        })
+    }
+}
+
+// Appends a streamed string delta to a field of a content block
+fn append_field(block: &mut Value, field: &str, delta: &Value) {
+    let Some(delta) = delta.as_str() else {
+        return;
+    };
+    let mut current = block[field].as_str().unwrap_or_default().to_string();
+    current.push_str(delta);
+    block[field] = Value::String(current);
+}
+
+// Removes the first complete event, which ends with a blank line, from a stream buffer
+fn take_event(buffer: &mut Vec<u8>) -> Option<String> {
+    let end = buffer.windows(2).position(|w| w == b"\n\n")?;
+    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+    Some(String::from_utf8_lossy(&event).into_owned())
+}
+
+#[async_trait]
+impl ApiClient for AnthropicClient {
+    async fn complete(&self, messages: Vec<Message>, options: CompletionOptions) -> Result<String> {
+        // Extract system message if present
+        let system_message = self.extract_system_message(&messages);
+        let converted_messages = self.convert_messages(messages);
+
+        let max_tokens = options.max_tokens.unwrap_or(2048) as usize;
+
+        let mut request = AnthropicRequest {
+            model: self.model.clone(),
+            messages: converted_messages,
+            max_tokens,
+            system: system_message,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            stream: None,
+        };
+
+        // Add structured output format if specified in options
+        let structured = options
+            .json_schema
+            .as_deref()
+            .and_then(|json_schema| Self::apply_structured_output(&mut request, json_schema));
+
+        // A forced tool call can't be combined with extended thinking
+        let thinking_budget = options.thinking_budget.filter(|_| structured.is_none());
+        Self::apply_thinking(&mut request, thinking_budget);
+
+        let anthropic_response = self.send_message(request).await?;
+
+        if let Some(output) = structured
+            .and_then(|wrapped| Self::structured_output_text(&anthropic_response, wrapped))
+        {
+            if let Some(usage) = &anthropic_response.usage {
+                log_anthropic_usage(usage);
+            }
+            return Ok(output);
+        }
+
+        // Extract content from response
+        let mut text_content = String::new();
+
+        // Look for text content in the response
+        for content_item in &anthropic_response.content {
+            if let AnthropicContent::Text { text, .. } = content_item {
+                text_content = text.clone();
+                break;
+            }
+        }
+
+        // Return an error if no text content was found
+        if text_content.is_empty() {
+            let error_msg = "No text content in Anthropic response".to_string();
+            eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
+            return Err(AppError::LLMError(error_msg).into());
+        }
+
+        // Log usage information if available, including cache-related tokens
+        if let Some(usage) = &anthropic_response.usage {
+            log_anthropic_usage(usage);
+        }
+
+        let content = text_content;
+
+        Ok(content)
+    }
+
+    async fn complete_with_tools(
+        &self,
+        messages: Vec<Message>,
+        options: CompletionOptions,
+        tool_results: Option<Vec<ToolResult>>,
+    ) -> Result<(String, Option<Vec<ToolCall>>)> {
+        let (content, tool_calls, _) = self
+            .complete_with_thinking(messages, options, tool_results)
+            .await?;
         Ok((content, tool_calls))
     }
+
+    async fn complete_with_reasoning(
+        &self,
+        messages: Vec<Message>,
+        options: CompletionOptions,
+        tool_results: Option<Vec<ToolResult>>,
+    ) -> Result<(String, Option<Vec<ToolCall>>, Vec<ContentBlock>)> {
+        self.complete_with_thinking(messages, options, tool_results)
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -784,10 +1250,12 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -828,6 +1296,7 @@ mod tests {
         let messages_without_system = vec![Message {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            blocks: Vec::new(),
         }];
 
         let system_content = client.extract_system_message(&messages_without_system);
@@ -848,18 +1317,22 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "assistant".to_string(),
                 content: "Hi there! How can I help you today?".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Tell me about prompt caching".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -963,6 +1436,7 @@ mod tests {
         let only_system_message = vec![Message {
             role: "system".to_string(),
             content: "You are a helpful assistant.".to_string(),
+            blocks: Vec::new(),
         }];
 
         let anthropic_messages = client.convert_messages(only_system_message);
@@ -972,6 +1446,7 @@ mod tests {
         let single_user_message = vec![Message {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            blocks: Vec::new(),
         }];
 
         let anthropic_messages = client.convert_messages(single_user_message);
@@ -1154,18 +1629,22 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "assistant".to_string(),
                 content: "Hi there! How can I help you today?".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Tell me about prompt caching".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -1231,4 +1710,294 @@ mod tests {
             "Tool should have cache control"
         );
     }
+
+    #[test]
+    fn test_thinking_blocks_replayed_with_tool_use() {
+        let api_key = "test_api_key".to_string();
+        let client = AnthropicClient::with_api_key(api_key, None).unwrap();
+
+        let tool_turn = json!({
+            "content": "Let me look at the file",
+            "tool_calls": [
+                { "id": "toolu_1", "name": "Read", "arguments": { "file_path": "/tmp/a.rs" } },
+                { "id": "toolu_2", "name": "LS", "arguments": { "path": "/tmp" } }
+            ]
+        });
+
+        let messages = vec![
+            Message::user("What is in /tmp?".to_string()),
+            Message::assistant(tool_turn.to_string()).with_blocks(vec![ContentBlock::Thinking {
+                thinking: "I should read the file first".to_string(),
+                signature: "sig-123".to_string(),
+            }]),
+            Message::user("Tool result for call toolu_1: fn main() {}".to_string()),
+            Message::user("Tool result for call toolu_2: a.rs".to_string()),
+        ];
+
+        let anthropic_messages = client.convert_messages(messages);
+        assert_eq!(
+            anthropic_messages.len(),
+            3,
+            "Tool results should be merged into one user message"
+        );
+
+        // The thinking block must lead the assistant turn, unchanged
+        let assistant = &anthropic_messages[1];
+        assert_eq!(
+            assistant.content[0],
+            AnthropicContent::Thinking {
+                thinking: "I should read the file first".to_string(),
+                signature: "sig-123".to_string(),
+            }
+        );
+        assert!(
+            matches!(&assistant.content[1], AnthropicContent::Text { text, .. } if text == "Let me look at the file")
+        );
+        assert!(
+            matches!(&assistant.content[2], AnthropicContent::ToolUse { id, name, .. } if id == "toolu_1" && name == "Read")
+        );
+        assert!(
+            matches!(&assistant.content[3], AnthropicContent::ToolUse { id, .. } if id == "toolu_2")
+        );
+
+        let results = &anthropic_messages[2];
+        assert_eq!(results.role, "user");
+        assert_eq!(results.content.len(), 2);
+        assert!(
+            matches!(&results.content[0], AnthropicContent::ToolResult { tool_call_id, content, .. } if tool_call_id == "toolu_1" && content == &ToolResultContent::Text("fn main() {}".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_unanswered_tool_turn_stays_text() {
+        let api_key = "test_api_key".to_string();
+        let client = AnthropicClient::with_api_key(api_key, None).unwrap();
+
+        let tool_turn = json!({
+            "content": "",
+            "tool_calls": [{ "id": "toolu_9", "name": "Bash", "arguments": { "command": "ls" } }]
+        });
+        let messages = vec![
+            Message::user("List files".to_string()),
+            Message::assistant(tool_turn.to_string()),
+        ];
+
+        // Without a matching result a native tool_use block would be rejected by the API
+        let anthropic_messages = client.convert_messages(messages);
+        assert!(matches!(
+            &anthropic_messages[1].content[0],
+            AnthropicContent::Text { .. }
+        ));
+    }
+
+    #[test]
+    fn test_apply_thinking() {
+        let api_key = "test_api_key".to_string();
+        let client = AnthropicClient::with_api_key(api_key, None).unwrap();
+
+        let messages = vec![
+            Message::user("Hello".to_string()),
+            Message::assistant("Hi".to_string()).with_blocks(vec![
+                ContentBlock::RedactedThinking {
+                    data: "opaque".to_string(),
+                },
+            ]),
+            Message::user("Think hard".to_string()),
+        ];
+
+        let request = AnthropicRequest {
+            model: "claude-sonnet-4-20250514".to_string(),
+            messages: client.convert_messages(messages),
+            max_tokens: 512,
+            system: None,
+            temperature: Some(0.25),
+            top_p: Some(0.9),
+            tools: None,
+            tool_choice: Some(AnthropicToolChoice {
+                choice_type: "required".to_string(),
+                name: None,
+            }),
+            thinking: None,
+            stream: None,
+        };
+
+        // Enabling thinking clamps the budget and clears incompatible settings
+        let mut enabled = request.clone();
+        AnthropicClient::apply_thinking(&mut enabled, Some(100));
+        let thinking = enabled.thinking.as_ref().expect("thinking should be set");
+        assert_eq!(thinking.thinking_type, "enabled");
+        assert_eq!(thinking.budget_tokens, MIN_THINKING_BUDGET);
+        assert_eq!(enabled.max_tokens, 512 + MIN_THINKING_BUDGET as usize);
+        assert!(enabled.temperature.is_none());
+        assert!(enabled.top_p.is_none());
+        assert_eq!(enabled.tool_choice.unwrap().choice_type, "auto");
+        assert!(enabled.messages[1].content[0].is_thinking());
+
+        // A budget just below max_tokens still leaves all of it for the answer
+        let mut below = request.clone();
+        below.max_tokens = 4096;
+        AnthropicClient::apply_thinking(&mut below, Some(4000));
+        assert_eq!(below.thinking.as_ref().unwrap().budget_tokens, 4000);
+        assert_eq!(below.max_tokens, 4096 + 4000);
+
+        // Without a budget, stored thinking blocks are not sent
+        let mut disabled = request;
+        AnthropicClient::apply_thinking(&mut disabled, None);
+        assert!(disabled.thinking.is_none());
+        assert!(disabled
+            .messages
+            .iter()
+            .all(|m| m.content.iter().all(|c| !c.is_thinking())));
+    }
+
+    #[test]
+    fn test_stream_state_assembles_response() {
+        let events = vec![
+            json!({"type": "message_start", "message": {
+                "id": "msg_1", "type": "message", "role": "assistant", "model": "claude",
+                "content": [], "usage": {"input_tokens": 10, "output_tokens": 1}
+            }}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "Let me "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "think."}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "sig"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "LS", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"path\": "}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"/tmp\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 42}}),
+            json!({"type": "message_stop"}),
+        ];
+
+        let mut state = StreamState::default();
+        for event in &events {
+            state.apply_event(event).unwrap();
+        }
+        let response = state.into_response().unwrap();
+
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(
+            response.content[0],
+            AnthropicContent::Thinking {
+                thinking: "Let me think.".to_string(),
+                signature: "sig".to_string(),
+            }
+        );
+        assert!(
+            matches!(&response.content[1], AnthropicContent::ToolUse { input, .. } if input == &json!({"path": "/tmp"}))
+        );
+        assert_eq!(response.usage.unwrap()["output_tokens"], 42);
+    }
+
+    #[test]
+    fn test_take_event_waits_for_complete_event() {
+        let bytes = "data: {\"text\": \"héllo\"}\n\ndata: {}".as_bytes();
+        // Split the stream inside the two bytes of "é"
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+
+        let mut buffer = bytes[..split].to_vec();
+        assert_eq!(take_event(&mut buffer), None);
+        buffer.extend_from_slice(&bytes[split..]);
+        assert_eq!(
+            take_event(&mut buffer).as_deref(),
+            Some("data: {\"text\": \"héllo\"}\n\n")
+        );
+        // The next event isn't finished yet
+        assert_eq!(take_event(&mut buffer), None);
+        assert_eq!(buffer, b"data: {}");
+    }
+
+    #[test]
+    fn test_image_in_tool_result() {
+        let api_key = "test_api_key".to_string();
+        let client = AnthropicClient::with_api_key(api_key, None).unwrap();
+
+        let tool_turn = json!({
+            "content": "",
+            "tool_calls": [{ "id": "toolu_1", "name": "Read", "arguments": { "file_path": "/tmp/a.png" } }]
+        });
+        let image = ContentBlock::Image {
+            media_type: "image/png".to_string(),
+            data: "iVBORw0KGgo=".to_string(),
+        };
+        let messages = vec![
+            Message::user("Look at /tmp/a.png".to_string()),
+            Message::assistant(tool_turn.to_string()),
+            Message::user("Tool result for call toolu_1: Image file attached".to_string())
+                .with_blocks(vec![image.clone()]),
+        ];
+
+        let anthropic_messages = client.convert_messages(messages);
+        let AnthropicContent::ToolResult { content, .. } = &anthropic_messages[2].content[0] else {
+            panic!("Expected a tool result");
+        };
+        let ToolResultContent::Blocks(blocks) = content else {
+            panic!("Expected the tool result to carry content blocks");
+        };
+        assert!(
+            matches!(&blocks[0], AnthropicContent::Text { text, .. } if text == "Image file attached")
+        );
+        assert_eq!(blocks[1], AnthropicContent::from(image));
+
+        let json = serde_json::to_value(&blocks[1]).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo=" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_structured_output_forces_tool() {
+        let mut request = AnthropicRequest {
+            model: "claude-test".to_string(),
+            messages: Vec::new(),
+            max_tokens: 1024,
+            system: None,
+            temperature: Some(0.7),
+            top_p: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            stream: None,
+        };
+
+        let schema = json!({ "type": "array", "items": { "type": "string" } });
+        let wrapped =
+            AnthropicClient::apply_structured_output(&mut request, &schema.to_string()).unwrap();
+        assert!(wrapped, "Non-object schemas must be wrapped for tool input");
+
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            json!({ "type": "tool", "name": "structured_output" })
+        );
+        assert_eq!(
+            body["tools"][0]["input_schema"]["properties"]["value"],
+            schema
+        );
+
+        // The tool input is unwrapped again when reading the response
+        let response: AnthropicResponse = serde_json::from_value(json!({
+            "id": "msg_1",
+            "model": "claude-test",
+            "role": "assistant",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "structured_output",
+                "input": { "value": ["a", "b"] }
+            }]
+        }))
+        .unwrap();
+        assert_eq!(
+            AnthropicClient::structured_output_text(&response, true).as_deref(),
+            Some(r#"["a","b"]"#)
+        );
+
+        assert!(AnthropicClient::apply_structured_output(&mut request, "not json").is_none());
+    }
 }
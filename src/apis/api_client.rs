@@ -1,4 +1,5 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -6,6 +7,9 @@ use std::sync::Arc;
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// Provider-specific blocks that must be sent back unchanged on later turns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<ContentBlock>,
 }
 
 impl Message {
@@ -13,6 +17,7 @@ impl Message {
         Self {
             role: "system".to_string(),
             content,
+            blocks: Vec::new(),
         }
     }
 
@@ -20,6 +25,7 @@ impl Message {
         Self {
             role: "user".to_string(),
             content,
+            blocks: Vec::new(),
         }
     }
 
@@ -27,6 +33,55 @@ impl Message {
         Self {
             role: "assistant".to_string(),
             content,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Attach additional content blocks to this message
+    pub fn with_blocks(mut self, blocks: Vec<ContentBlock>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// Image blocks attached to this message, as (media type, base64 data) pairs
+    pub fn images(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.blocks.iter().filter_map(|block| match block {
+            ContentBlock::Image { media_type, data } => Some((media_type.as_str(), data.as_str())),
+            _ => None,
+        })
+    }
+}
+
+/// Structured content that accompanies a message's text.
+///
+/// Reasoning blocks returned by models with extended thinking are stored here so
+/// they can be echoed back verbatim (including their signatures) when the
+/// conversation continues after a tool call.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Thinking {
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+    /// Base64-encoded image attached to a user prompt or a Read tool result
+    Image {
+        media_type: String,
+        data: String,
+    },
+}
+
+impl ContentBlock {
+    /// Returns the image as a `data:` URI, for providers that take images as URLs
+    pub fn image_data_uri(&self) -> Option<String> {
+        match self {
+            ContentBlock::Image { media_type, data } => {
+                Some(format!("data:{media_type};base64,{data}"))
+            }
+            _ => None,
         }
     }
 }
@@ -154,6 +209,8 @@ pub struct CompletionOptions {
     pub tools: Option<Vec<ToolDefinition>>,
     pub json_schema: Option<String>,
     pub require_tool_use: bool,
+    /// Token budget for extended thinking; `None` disables it
+    pub thinking_budget: Option<u32>,
 }
 
 impl Default for CompletionOptions {
@@ -165,6 +222,7 @@ impl Default for CompletionOptions {
             tools: None,
             json_schema: None,
             require_tool_use: false,
+            thinking_budget: None,
         }
     }
 }
@@ -182,6 +240,20 @@ pub trait ApiClient: Send + Sync {
         options: CompletionOptions,
         tool_results: Option<Vec<ToolResult>>,
     ) -> Result<(String, Option<Vec<ToolCall>>)>;
+
+    /// Same as `complete_with_tools`, but also returns any reasoning blocks the
+    /// model produced. Providers without extended thinking return none.
+    async fn complete_with_reasoning(
+        &self,
+        messages: Vec<Message>,
+        options: CompletionOptions,
+        tool_results: Option<Vec<ToolResult>>,
+    ) -> Result<(String, Option<Vec<ToolCall>>, Vec<ContentBlock>)> {
+        let (content, tool_calls) = self
+            .complete_with_tools(messages, options, tool_results)
+            .await?;
+        Ok((content, tool_calls, Vec::new()))
+    }
 }
 
 // Instead of using a trait object, we'll use an enum to handle different providers
@@ -245,6 +317,77 @@ impl ApiClientEnum {
         }
     }
 
+    pub async fn complete_with_reasoning(
+        &self,
+        messages: Vec<Message>,
+        options: CompletionOptions,
+        tool_results: Option<Vec<ToolResult>>,
+    ) -> Result<(String, Option<Vec<ToolCall>>, Vec<ContentBlock>)> {
+        match self {
+            Self::Anthropic(client) => {
+                client
+                    .complete_with_reasoning(messages, options, tool_results)
+                    .await
+            }
+            Self::OpenAI(client) => {
+                client
+                    .complete_with_reasoning(messages, options, tool_results)
+                    .await
+            }
+            Self::Ollama(client) => {
+                client
+                    .complete_with_reasoning(messages, options, tool_results)
+                    .await
+            }
+            Self::Gemini(client) => {
+                client
+                    .complete_with_reasoning(messages, options, tool_results)
+                    .await
+            }
+            Self::CustomMock(client) => {
+                client
+                    .complete_with_reasoning(messages, options, tool_results)
+                    .await
+            }
+        }
+    }
+
+    /// Requests a response matching `schema` and deserializes it into `T`.
+    ///
+    /// The schema is passed to the provider's structured output mode and the
+    /// response is validated locally. If it doesn't validate, the model is shown
+    /// the errors and asked once to try again.
+    pub async fn complete_structured<T: DeserializeOwned>(
+        &self,
+        mut messages: Vec<Message>,
+        schema: &serde_json::Value,
+        options: CompletionOptions,
+    ) -> Result<T> {
+        use crate::apis::structured;
+
+        let options = CompletionOptions {
+            json_schema: Some(schema.to_string()),
+            tools: None,
+            require_tool_use: false,
+            ..options
+        };
+
+        let mut response = self.complete(messages.clone(), options.clone()).await?;
+        let value = match structured::parse_response(&response, schema) {
+            Ok(value) => value,
+            Err(error) => {
+                // One repair attempt with the validation errors spelled out
+                messages.push(Message::assistant(response));
+                messages.push(Message::user(structured::repair_prompt(&error, schema)));
+                response = self.complete(messages, options).await?;
+                structured::parse_response(&response, schema)
+                    .context("Structured response was still invalid after a retry")?
+            }
+        };
+
+        serde_json::from_value(value).context("Structured response has an unexpected shape")
+    }
+
     pub fn custom_for_testing(client: Arc<dyn ApiClient>) -> Self {
         Self::CustomMock(client)
     }
@@ -1,4 +1,5 @@
 use crate::apis::api_client::{ApiClient, CompletionOptions, Message, ToolCall, ToolResult};
+use crate::apis::http::HttpClientBuilder;
 use crate::app::logger::{format_log_with_color, LogLevel};
 use crate::errors::AppError;
 use crate::models::GEMINI_MODEL_NAME;
@@ -32,6 +33,9 @@ enum GeminiContent {
     FunctionResponse {
         function_response: GeminiFunctionResponse,
     },
+    InlineData {
+        inline_data: GeminiInlineData,
+    },
     // Add more flexible fallback variant for unexpected response formats
     Other(Value),
 }
@@ -49,6 +53,12 @@ struct GeminiFunctionResponse {
     response: Value,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GeminiInlineData {
+    mime_type: String,
+    data: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GeminiFunction {
     name: String,
@@ -66,6 +76,8 @@ struct GeminiGenerationConfig {
     max_output_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -211,7 +223,9 @@ impl GeminiClient {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let client = ReqwestClient::builder().default_headers(headers).build()?;
+        let client = HttpClientBuilder::new("gemini")
+            .with_headers(headers)
+            .build()?;
 
         // Default to the centrally defined Gemini model name
         let model = model.unwrap_or_else(|| GEMINI_MODEL_NAME.to_string());
@@ -241,6 +255,57 @@ impl GeminiClient {
         })
     }
 
+    /// Returns the response MIME type and schema for structured output
+    fn structured_output_config(json_schema: Option<&str>) -> (Option<String>, Option<Value>) {
+        match json_schema {
+            Some(schema) => {
+                let response_schema = serde_json::from_str::<Value>(schema)
+                    .ok()
+                    .filter(|schema| schema.is_object())
+                    .map(Self::to_gemini_schema);
+                (Some("application/json".to_string()), response_schema)
+            }
+            None => (None, None),
+        }
+    }
+
+    /// Gemini accepts an OpenAPI-style subset of JSON Schema and rejects
+    /// requests containing keywords outside it, so drop the common ones it lacks
+    fn to_gemini_schema(schema: Value) -> Value {
+        const UNSUPPORTED: [&str; 4] = ["$schema", "$id", "additionalProperties", "default"];
+
+        match schema {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .filter(|(key, _)| !UNSUPPORTED.contains(&key.as_str()))
+                    .map(|(key, value)| {
+                        let value = match key.as_str() {
+                            // Property names are user data, not keywords
+                            "properties" => match value {
+                                Value::Object(properties) => Value::Object(
+                                    properties
+                                        .into_iter()
+                                        .map(|(name, schema)| {
+                                            (name, Self::to_gemini_schema(schema))
+                                        })
+                                        .collect(),
+                                ),
+                                other => other,
+                            },
+                            _ => Self::to_gemini_schema(value),
+                        };
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(Self::to_gemini_schema).collect())
+            }
+            other => other,
+        }
+    }
+
     fn convert_messages(&self, messages: Vec<Message>) -> Vec<GeminiMessage> {
         let mut gemini_messages = Vec::new();
         let mut current_role = String::new();
@@ -266,7 +331,15 @@ impl GeminiClient {
             // Update current role
             current_role = role.to_string();
 
-            // Add content
+            // Add any attached images, followed by the text content
+            for (media_type, data) in msg.images() {
+                current_parts.push(GeminiContent::InlineData {
+                    inline_data: GeminiInlineData {
+                        mime_type: media_type.to_string(),
+                        data: data.to_string(),
+                    },
+                });
+            }
             current_parts.push(GeminiContent::Text {
                 text: msg.content.clone(),
             });
@@ -505,14 +578,15 @@ impl ApiClient for GeminiClient {
 
         let max_tokens = options.max_tokens.unwrap_or(2048);
 
-        // Create generation config
-        // Note: Don't set response_mime_type to JSON when using tools,
-        // as Gemini doesn't support function calling with JSON mime type
+        // Create generation config, constrained to the schema if one was given
+        let (response_mime_type, response_schema) =
+            Self::structured_output_config(options.json_schema.as_deref());
         let generation_config = GeminiGenerationConfig {
             temperature: options.temperature,
             top_p: options.top_p,
             max_output_tokens: Some(max_tokens),
-            response_mime_type: None,
+            response_mime_type,
+            response_schema,
         };
 
         let request = GeminiRequest {
@@ -588,11 +662,17 @@ impl ApiClient for GeminiClient {
         // Create generation config
         // Note: Don't set response_mime_type to JSON when using tools,
         // as Gemini doesn't support function calling with JSON mime type
+        let (response_mime_type, response_schema) = if options.tools.is_none() {
+            Self::structured_output_config(options.json_schema.as_deref())
+        } else {
+            (None, None)
+        };
         let generation_config = GeminiGenerationConfig {
             temperature: options.temperature,
             top_p: options.top_p,
             max_output_tokens: Some(max_tokens),
-            response_mime_type: None,
+            response_mime_type,
+            response_schema,
         };
 
         // Create the request
@@ -665,7 +745,7 @@ impl ApiClient for GeminiClient {
 // Unit tests within the same module to test private implementation details
 mod tests {
     use super::*;
-    use crate::apis::api_client::{Message, ToolDefinition, ToolResult};
+    use crate::apis::api_client::{ContentBlock, Message, ToolDefinition, ToolResult};
     use serde_json::json;
 
     #[test]
@@ -720,14 +800,17 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "assistant".to_string(),
                 content: "Hi there".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -1063,4 +1146,63 @@ mod tests {
         let result = client.extract_text_content(&response);
         assert!(result.is_err(), "Should return error for empty candidates");
     }
+
+    #[test]
+    fn test_convert_messages_with_image() {
+        let api_key = "test_api_key".to_string();
+        let client = GeminiClient::with_api_key(api_key, None).unwrap();
+
+        let messages = vec![Message::user("Describe this".to_string()).with_blocks(vec![
+            ContentBlock::Image {
+                media_type: "image/png".to_string(),
+                data: "iVBORw0KGgo=".to_string(),
+            },
+        ])];
+
+        let gemini_messages = client.convert_messages(messages);
+        let parts = serde_json::to_value(&gemini_messages[0].parts).unwrap();
+        assert_eq!(
+            parts,
+            json!([
+                { "inline_data": { "mime_type": "image/png", "data": "iVBORw0KGgo=" } },
+                { "text": "Describe this" }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_structured_output_config() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "default": { "type": "string", "default": "x" },
+                "items": {
+                    "type": "array",
+                    "items": { "type": "object", "additionalProperties": false }
+                }
+            },
+            "required": ["default"],
+            "additionalProperties": false
+        });
+
+        let (mime_type, response_schema) =
+            GeminiClient::structured_output_config(Some(&schema.to_string()));
+        assert_eq!(mime_type.as_deref(), Some("application/json"));
+
+        // Unsupported keywords are dropped, but a property named like one is kept
+        assert_eq!(
+            response_schema.unwrap(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "default": { "type": "string" },
+                    "items": { "type": "array", "items": { "type": "object" } }
+                },
+                "required": ["default"]
+            })
+        );
+
+        assert_eq!(GeminiClient::structured_output_config(None), (None, None));
+    }
 }
@@ -1,5 +1,7 @@
 pub mod anthropic;
 pub mod api_client;
 pub mod gemini;
+pub mod http;
 pub mod ollama;
 pub mod openai;
+pub mod structured;
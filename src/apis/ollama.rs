@@ -1,9 +1,10 @@
 use crate::apis::api_client::{
     ApiClient, CompletionOptions, Message, ToolCall, ToolDefinition, ToolResult,
 };
+use crate::apis::http::HttpClientBuilder;
 use crate::app::logger::{format_log_with_color, LogLevel};
 use crate::errors::AppError;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use rand;
 
@@ -11,6 +12,7 @@ use reqwest::Client as ReqwestClient;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value};
 use std::time::Duration;
+use tokio::sync::OnceCell;
 
 // Ollama API Types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +25,9 @@ struct OllamaMessage {
     tool_calls: Option<Vec<OllamaToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
+    // Base64-encoded images for multimodal models
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
 }
 
 // Custom serializer to handle content that might be a string or a complex object
@@ -121,8 +126,9 @@ struct OllamaRequest {
     top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     options: Option<Value>,
+    /// Either "json" or a JSON schema the output must follow
     #[serde(skip_serializing_if = "Option::is_none")]
-    format: Option<String>,
+    format: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<OllamaTool>>,
 }
@@ -169,10 +175,94 @@ pub struct OllamaModelDetails {
     pub description: Option<String>,
 }
 
+/// Model metadata from `/api/show`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OllamaShowResponse {
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<String>,
+    #[serde(default)]
+    pub details: Option<OllamaModelDetails>,
+    /// GGUF metadata, e.g. "llama.context_length"
+    #[serde(default)]
+    pub model_info: Option<serde_json::Map<String, Value>>,
+    /// Reported by Ollama 0.6.4 and later, e.g. ["completion", "tools", "vision"]
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl OllamaShowResponse {
+    /// Maximum context length the model was trained with
+    pub fn context_length(&self) -> Option<u64> {
+        // An explicit num_ctx in the Modelfile takes precedence
+        let from_parameters = self.parameters.as_deref().and_then(|parameters| {
+            parameters.lines().find_map(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("num_ctx"), Some(value)) => value.parse().ok(),
+                    _ => None,
+                }
+            })
+        });
+
+        from_parameters.or_else(|| {
+            self.model_info.as_ref().and_then(|info| {
+                info.iter()
+                    .find(|(key, _)| key.ends_with(".context_length"))
+                    .and_then(|(_, value)| value.as_u64())
+            })
+        })
+    }
+
+    /// Whether the model can be given tools, i.e. can run in agent mode
+    pub fn supports_tools(&self) -> bool {
+        if !self.capabilities.is_empty() {
+            return self.capabilities.iter().any(|c| c == "tools");
+        }
+        // Older servers don't report capabilities, but tool-capable templates render .Tools
+        self.template
+            .as_deref()
+            .is_some_and(|template| template.contains(".Tools"))
+    }
+
+    /// Whether the model accepts images
+    pub fn supports_vision(&self) -> bool {
+        if !self.capabilities.is_empty() {
+            return self.capabilities.iter().any(|c| c == "vision");
+        }
+        self.details
+            .as_ref()
+            .and_then(|details| details.families.as_ref())
+            .is_some_and(|families| families.iter().any(|f| f == "clip" || f == "mllama"))
+    }
+}
+
+/// One progress update while pulling a model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaPullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+/// Upper bound for the automatically chosen context window. Ollama allocates the
+/// KV cache for the whole window up front, so using e.g. a 128k maximum would
+/// exhaust memory on most machines.
+pub const MAX_AUTO_NUM_CTX: u64 = 32768;
+
 pub struct OllamaClient {
     client: ReqwestClient,
+    /// Client for model downloads, which can take far longer than the request timeout
+    pull_client: ReqwestClient,
     model: String,
     api_base: String,
+    /// Context window to request, detected from the model on first use
+    num_ctx: OnceCell<Option<u64>>,
 }
 
 // Helper methods
@@ -211,14 +301,17 @@ impl OllamaClient {
     }
 
     pub fn with_base_url(model: String, api_base: String) -> Result<Self> {
-        // Build a simple client with only timeout configuration
-        let client = ReqwestClient::builder()
-            .timeout(Duration::from_secs(600)) // 10 minutes timeout for operations
+        // Local models can be slow, so allow long requests by default
+        let client = HttpClientBuilder::new("ollama")
+            .with_timeout(Duration::from_secs(600)) // 10 minutes timeout for operations
             .build()
-            .map_err(|e| {
-                eprintln!("Failed to build reqwest client: {e}");
-                anyhow::anyhow!("Failed to build HTTP client: {}", e)
-            })?;
+            .inspect_err(|e| eprintln!("Failed to build reqwest client: {e:#}"))?;
+        // Only give up on a pull when Ollama stops sending progress
+        let pull_client = HttpClientBuilder::new("ollama")
+            .with_connect_timeout(Duration::from_secs(30))
+            .with_read_timeout(Duration::from_secs(300))
+            .build()
+            .inspect_err(|e| eprintln!("Failed to build reqwest client: {e:#}"))?;
 
         // Parse and normalize the API base URL
         let api_base = if api_base.starts_with("http://") || api_base.starts_with("https://") {
@@ -231,8 +324,191 @@ impl OllamaClient {
 
         Ok(Self {
             client,
+            pull_client,
             model,
             api_base,
+            num_ctx: OnceCell::new(),
+        })
+    }
+
+    /// Use a fixed context window instead of detecting it from the model
+    pub fn with_context_length(self, num_ctx: u64) -> Self {
+        Self {
+            num_ctx: OnceCell::new_with(Some(Some(num_ctx))),
+            ..self
+        }
+    }
+
+    /// Request options for the model, currently just the context window.
+    ///
+    /// Ollama defaults to a small window (2048 tokens on older versions) and silently
+    /// truncates longer prompts, which cuts off the system prompt and tool definitions.
+    async fn request_options(&self) -> Option<Value> {
+        let num_ctx = self
+            .num_ctx
+            .get_or_init(|| async {
+                match self.show_model(&self.model).await {
+                    Ok(info) => info
+                        .context_length()
+                        .map(|length| length.min(MAX_AUTO_NUM_CTX)),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            format_log_with_color(
+                                LogLevel::Warning,
+                                &format!("Could not detect context length for {}: {e}", self.model)
+                            )
+                        );
+                        None
+                    }
+                }
+            })
+            .await;
+
+        num_ctx.map(|num_ctx| json!({ "num_ctx": num_ctx }))
+    }
+
+    /// Sends a request to a model management endpoint, turning HTTP errors into
+    /// readable messages
+    async fn send_management_request(
+        &self,
+        request: reqwest::RequestBuilder,
+        action: &str,
+    ) -> Result<reqwest::Response> {
+        let response = request.send().await.map_err(|e| {
+            let error_msg = if e.is_connect() {
+                format!(
+                    "Failed to connect to Ollama server at {}. Make sure 'ollama serve' is running. Error: {e}",
+                    self.api_base
+                )
+            } else {
+                format!("Failed to {action}: {e}")
+            };
+            eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
+            AppError::NetworkError(error_msg)
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            // Ollama returns {"error": "..."} bodies
+            let detail = serde_json::from_str::<Value>(&error_text)
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or(error_text);
+            let error_msg = format!("Failed to {action}: {status} - {detail}");
+            eprintln!("{}", format_log_with_color(LogLevel::Error, &error_msg));
+            return Err(AppError::NetworkError(error_msg).into());
+        }
+
+        Ok(response)
+    }
+
+    /// Fetches a model's metadata: template, parameters, context length and capabilities
+    pub async fn show_model(&self, name: &str) -> Result<OllamaShowResponse> {
+        let url = format!("{}/api/show", self.api_base);
+        let response = self
+            .send_management_request(
+                self.client.post(&url).json(&json!({ "model": name })),
+                &format!("show Ollama model {name}"),
+            )
+            .await?;
+
+        response
+            .json::<OllamaShowResponse>()
+            .await
+            .with_context(|| format!("Failed to parse details of Ollama model {name}"))
+    }
+
+    /// Downloads a model, calling `on_progress` for every status update
+    pub async fn pull_model<F>(&self, name: &str, mut on_progress: F) -> Result<()>
+    where
+        F: FnMut(&OllamaPullProgress),
+    {
+        let url = format!("{}/api/pull", self.api_base);
+        let mut response = self
+            .send_management_request(
+                self.pull_client
+                    .post(&url)
+                    .json(&json!({ "model": name, "stream": true })),
+                &format!("pull Ollama model {name}"),
+            )
+            .await?;
+
+        // The body is newline-delimited JSON, one object per progress update
+        // Raw bytes, since a chunk can end in the middle of a multi-byte character
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut last_status = String::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("Connection lost while pulling {name}"))?
+        {
+            buffer.extend_from_slice(&chunk);
+            while let Some(line) = Self::take_line(&mut buffer) {
+                if let Some(status) = Self::handle_pull_line(line.trim(), &mut on_progress)? {
+                    last_status = status;
+                }
+            }
+        }
+        let rest = String::from_utf8_lossy(&buffer);
+        if let Some(status) = Self::handle_pull_line(rest.trim(), &mut on_progress)? {
+            last_status = status;
+        }
+
+        if last_status == "success" {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Pull of {name} ended unexpectedly (last status: {last_status})"
+            ))
+        }
+    }
+
+    /// Removes the first complete line from a stream buffer
+    fn take_line(buffer: &mut Vec<u8>) -> Option<String> {
+        let newline = buffer.iter().position(|&b| b == b'\n')?;
+        let line: Vec<u8> = buffer.drain(..=newline).collect();
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Parses one line of pull output, returning its status
+    fn handle_pull_line<F>(line: &str, on_progress: &mut F) -> Result<Option<String>>
+    where
+        F: FnMut(&OllamaPullProgress),
+    {
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        let value: Value = serde_json::from_str(line)
+            .with_context(|| format!("Invalid pull progress from Ollama: {line}"))?;
+        if let Some(error) = value["error"].as_str() {
+            return Err(AppError::LLMError(format!("Ollama pull failed: {error}")).into());
+        }
+
+        let progress: OllamaPullProgress = serde_json::from_value(value)?;
+        on_progress(&progress);
+        Ok(Some(progress.status))
+    }
+
+    /// Removes a model from the local Ollama store
+    pub async fn delete_model(&self, name: &str) -> Result<()> {
+        let url = format!("{}/api/delete", self.api_base);
+        self.send_management_request(
+            self.client.delete(&url).json(&json!({ "model": name })),
+            &format!("delete Ollama model {name}"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Ollama accepts a JSON schema directly in `format`; an unparseable schema
+    /// still gets plain JSON mode
+    fn response_format(json_schema: Option<&str>) -> Option<Value> {
+        json_schema.map(|schema| match serde_json::from_str::<Value>(schema) {
+            Ok(schema @ Value::Object(_)) => schema,
+            _ => Value::String("json".to_string()),
         })
     }
 
@@ -242,6 +518,7 @@ impl OllamaClient {
             .map(|msg| {
                 // Convert standard messages to Ollama format
                 OllamaMessage {
+                    images: msg.images().map(|(_, data)| data.to_string()).collect(),
                     role: msg.role,
                     content: msg.content,
                     tool_calls: None,
@@ -379,12 +656,8 @@ impl ApiClient for OllamaClient {
             stream: false,
             temperature: options.temperature,
             top_p: options.top_p,
-            options: None,
-            format: if options.json_schema.is_some() {
-                Some("json".to_string())
-            } else {
-                None
-            },
+            options: self.request_options().await,
+            format: Self::response_format(options.json_schema.as_deref()),
             tools: None,
         };
 
@@ -545,6 +818,7 @@ impl ApiClient for OllamaClient {
                                     content,
                                     tool_calls: None,
                                     tool_call_id: None,
+                                    images: Vec::new(),
                                 },
                                 done: value.get("done").and_then(|d| d.as_bool()).unwrap_or(true),
                                 total_duration: None,
@@ -607,6 +881,7 @@ impl ApiClient for OllamaClient {
                     content: result.output,
                     tool_calls: None,
                     tool_call_id: Some(result.tool_call_id),
+                    images: Vec::new(),
                 });
             }
         }
@@ -618,12 +893,8 @@ impl ApiClient for OllamaClient {
             stream: false,
             temperature: options.temperature,
             top_p: options.top_p,
-            options: None,
-            format: if options.json_schema.is_some() {
-                Some("json".to_string())
-            } else {
-                None
-            },
+            options: self.request_options().await,
+            format: Self::response_format(options.json_schema.as_deref()),
             tools: None,
         };
 
@@ -791,6 +1062,7 @@ impl ApiClient for OllamaClient {
                                     content,
                                     tool_calls: None,
                                     tool_call_id: None,
+                                    images: Vec::new(),
                                 },
                                 done: value.get("done").and_then(|d| d.as_bool()).unwrap_or(true),
                                 total_duration: None,
@@ -977,7 +1249,7 @@ impl ApiClient for OllamaClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::apis::api_client::{Message, ToolDefinition};
+    use crate::apis::api_client::{ContentBlock, Message, ToolDefinition};
     use serde_json::json;
 
     #[test]
@@ -1052,14 +1324,17 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "assistant".to_string(),
                 content: "Hi there! How can I help you today?".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -1126,6 +1401,7 @@ mod tests {
         let single_message = vec![Message {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            blocks: Vec::new(),
         }];
 
         let ollama_messages = client.convert_messages(single_message);
@@ -1281,4 +1557,119 @@ mod tests {
             "Type should be object"
         );
     }
+
+    #[test]
+    fn test_convert_messages_with_image() {
+        let client = OllamaClient::new(Some("llava".to_string())).unwrap();
+
+        let messages = vec![
+            Message::user("Describe this".to_string()).with_blocks(vec![ContentBlock::Image {
+                media_type: "image/png".to_string(),
+                data: "iVBORw0KGgo=".to_string(),
+            }]),
+            Message::assistant("A cat".to_string()),
+        ];
+
+        // Ollama takes bare base64 strings in a separate images field
+        let ollama_messages = client.convert_messages(messages);
+        assert_eq!(ollama_messages[0].images, vec!["iVBORw0KGgo=".to_string()]);
+        assert!(ollama_messages[1].images.is_empty());
+
+        let json = serde_json::to_value(&ollama_messages[1]).unwrap();
+        assert!(json.get("images").is_none());
+    }
+
+    #[test]
+    fn test_response_format() {
+        let schema = json!({ "type": "object", "properties": { "ok": { "type": "boolean" } } });
+
+        assert_eq!(
+            OllamaClient::response_format(Some(&schema.to_string())),
+            Some(schema)
+        );
+        assert_eq!(
+            OllamaClient::response_format(Some("not a schema")),
+            Some(json!("json"))
+        );
+        assert_eq!(OllamaClient::response_format(None), None);
+    }
+
+    #[test]
+    fn test_show_response_capabilities() {
+        let show: OllamaShowResponse = serde_json::from_value(json!({
+            "template": "{{ .Prompt }}",
+            "parameters": "stop \"<|eot_id|>\"",
+            "model_info": { "general.architecture": "llama", "llama.context_length": 131072 },
+            "capabilities": ["completion", "tools"]
+        }))
+        .unwrap();
+        assert_eq!(show.context_length(), Some(131072));
+        assert!(show.supports_tools());
+        assert!(!show.supports_vision());
+
+        // Older servers: no capabilities, fall back to the template and families
+        let legacy: OllamaShowResponse = serde_json::from_value(json!({
+            "template": "{{- if .Tools }}...{{ end }}",
+            "parameters": "num_ctx 8192\nstop \"</s>\"",
+            "details": { "families": ["llama", "clip"] },
+            "model_info": { "llama.context_length": 131072 }
+        }))
+        .unwrap();
+        assert_eq!(
+            legacy.context_length(),
+            Some(8192),
+            "Modelfile num_ctx should win"
+        );
+        assert!(legacy.supports_tools());
+        assert!(legacy.supports_vision());
+
+        let plain = OllamaShowResponse::default();
+        assert_eq!(plain.context_length(), None);
+        assert!(!plain.supports_tools());
+    }
+
+    #[test]
+    fn test_pull_progress_lines() {
+        let mut updates = Vec::new();
+        let mut record = |progress: &OllamaPullProgress| updates.push(progress.clone());
+
+        let status = OllamaClient::handle_pull_line(
+            r#"{"status":"pulling abc","digest":"sha256:abc","total":100,"completed":40}"#,
+            &mut record,
+        )
+        .unwrap();
+        assert_eq!(status.as_deref(), Some("pulling abc"));
+        assert_eq!(
+            OllamaClient::handle_pull_line("", &mut record).unwrap(),
+            None
+        );
+
+        let error = OllamaClient::handle_pull_line(
+            r#"{"error":"pull model manifest: file does not exist"}"#,
+            &mut record,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("file does not exist"));
+
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].completed, Some(40));
+        assert_eq!(updates[0].total, Some(100));
+    }
+
+    #[test]
+    fn test_pull_lines_split_across_chunks() {
+        let bytes = "{\"status\":\"pulling ü\"}\n{\"status\"".as_bytes();
+        // Split the stream inside the two bytes of "ü"
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+
+        let mut buffer = bytes[..split].to_vec();
+        assert_eq!(OllamaClient::take_line(&mut buffer), None);
+        buffer.extend_from_slice(&bytes[split..]);
+        assert_eq!(
+            OllamaClient::take_line(&mut buffer).as_deref(),
+            Some("{\"status\":\"pulling ü\"}\n")
+        );
+        assert_eq!(OllamaClient::take_line(&mut buffer), None);
+        assert_eq!(buffer, b"{\"status\"");
+    }
 }
@@ -1,6 +1,7 @@
 use crate::apis::api_client::{
     ApiClient, CompletionOptions, Message, ToolCall, ToolDefinition, ToolResult,
 };
+use crate::apis::http::HttpClientBuilder;
 use crate::app::logger::{format_log_with_color, LogLevel};
 use crate::errors::AppError;
 use anyhow::{Context, Result};
@@ -40,10 +41,64 @@ struct OpenAIToolCall {
     function: OpenAIFunctionCall,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+enum OpenAIContentPart {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct OpenAIImageUrl {
+    url: String,
+}
+
+impl OpenAIContent {
+    /// Returns the text of the content, ignoring any image parts
+    fn text(&self) -> String {
+        match self {
+            OpenAIContent::Text(text) => text.clone(),
+            OpenAIContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    OpenAIContentPart::Text { text } => Some(text.as_str()),
+                    OpenAIContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Appends text to the content
+    fn push_text(&mut self, suffix: &str) {
+        match self {
+            OpenAIContent::Text(text) => text.push_str(suffix),
+            OpenAIContent::Parts(parts) => parts.push(OpenAIContentPart::Text {
+                text: suffix.trim_start().to_string(),
+            }),
+        }
+    }
+}
+
+impl PartialEq<str> for OpenAIContent {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, OpenAIContent::Text(text) if text == other)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct OpenAIMessage {
     role: String,
-    content: Option<String>,
+    content: Option<OpenAIContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_calls: Option<Vec<OpenAIToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -121,7 +176,9 @@ impl OpenAIClient {
             HeaderValue::from_str(&format!("Bearer {api_key}"))?,
         );
 
-        let client = ReqwestClient::builder().default_headers(headers).build()?;
+        let client = HttpClientBuilder::new("openai")
+            .with_headers(headers)
+            .build()?;
 
         // Default to GPT-4o as the latest model with tooling capabilities
         let model = model.unwrap_or_else(|| "gpt-4o".to_string());
@@ -136,15 +193,35 @@ impl OpenAIClient {
     /// Converts internal message format to OpenAI's message format
     ///
     /// This method converts each message to OpenAI's format with appropriate
-    /// role and content fields.
+    /// role and content fields. Attached images become `image_url` parts with
+    /// base64 data URIs.
     fn convert_messages(&self, messages: Vec<Message>) -> Vec<OpenAIMessage> {
         messages
             .into_iter()
             .map(|msg| {
+                let image_urls: Vec<String> = msg
+                    .blocks
+                    .iter()
+                    .filter_map(|block| block.image_data_uri())
+                    .collect();
+
+                let content =
+                    if image_urls.is_empty() {
+                        OpenAIContent::Text(msg.content)
+                    } else {
+                        let mut parts = vec![OpenAIContentPart::Text { text: msg.content }];
+                        parts.extend(image_urls.into_iter().map(|url| {
+                            OpenAIContentPart::ImageUrl {
+                                image_url: OpenAIImageUrl { url },
+                            }
+                        }));
+                        OpenAIContent::Parts(parts)
+                    };
+
                 // Convert standard messages
                 OpenAIMessage {
                     role: msg.role,
-                    content: Some(msg.content),
+                    content: Some(content),
                     tool_calls: None,
                     tool_call_id: None,
                 }
@@ -152,6 +229,51 @@ impl OpenAIClient {
             .collect()
     }
 
+    /// Sets `response_format` for structured output.
+    ///
+    /// A valid schema uses the `json_schema` format so the model is constrained to
+    /// it. Anything else falls back to plain JSON mode, which requires the word
+    /// "json" to appear somewhere in the messages.
+    fn apply_response_format(request: &mut OpenAIRequest, json_schema: &str) {
+        if let Ok(schema @ Value::Object(_)) = serde_json::from_str::<Value>(json_schema) {
+            request.response_format = Some(json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "response",
+                    "schema": schema,
+                    // Strict mode rejects optional properties, which most of our schemas have
+                    "strict": false
+                }
+            }));
+            return;
+        }
+
+        request.response_format = Some(json!({
+            "type": "json_object"
+        }));
+
+        // Ensure at least one message contains the word "json" when using json_object response format
+        let has_json_keyword = request.messages.iter().any(|msg| {
+            msg.content
+                .as_ref()
+                .is_some_and(|content| content.text().to_lowercase().contains("json"))
+        });
+
+        if !has_json_keyword && !request.messages.is_empty() {
+            // Add "json" to the user's last message if it doesn't already contain it
+            if let Some(last_user_msg) = request
+                .messages
+                .iter_mut()
+                .rev()
+                .find(|msg| msg.role == "user")
+            {
+                if let Some(content) = &mut last_user_msg.content {
+                    content.push_text(" (Please provide the response as JSON)");
+                }
+            }
+        }
+    }
+
     /// Converts internal tool definitions to OpenAI's format
     ///
     /// This method converts tool definitions to OpenAI's function format with
@@ -188,10 +310,8 @@ impl ApiClient for OpenAIClient {
         };
 
         // Add structured output format if specified in options
-        if let Some(_json_schema) = &options.json_schema {
-            request.response_format = Some(json!({
-                "type": "json_object"
-            }));
+        if let Some(json_schema) = &options.json_schema {
+            Self::apply_response_format(&mut request, json_schema);
         }
 
         eprintln!(
@@ -254,7 +374,7 @@ impl ApiClient for OpenAIClient {
         // Extract content from the first choice
         if let Some(first_choice) = openai_response.choices.first() {
             if let Some(content) = &first_choice.message.content {
-                return Ok(content.clone());
+                return Ok(content.text());
             }
         }
 
@@ -307,7 +427,7 @@ impl ApiClient for OpenAIClient {
                 if let Some(output) = result_map.get(tool_id) {
                     openai_messages.push(OpenAIMessage {
                         role: "tool".to_string(),
-                        content: Some(output.clone()),
+                        content: Some(OpenAIContent::Text(output.clone())),
                         tool_calls: None,
                         tool_call_id: Some(tool_id.clone()),
                     });
@@ -316,9 +436,9 @@ impl ApiClient for OpenAIClient {
                     // This is crucial for OpenAI - every tool call must have a response
                     openai_messages.push(OpenAIMessage {
                         role: "tool".to_string(),
-                        content: Some(
+                        content: Some(OpenAIContent::Text(
                             "Tool execution completed without detailed results.".to_string(),
-                        ),
+                        )),
                         tool_calls: None,
                         tool_call_id: Some(tool_id.clone()),
                     });
@@ -330,7 +450,9 @@ impl ApiClient for OpenAIClient {
             for tool_id in &pending_tool_calls {
                 openai_messages.push(OpenAIMessage {
                     role: "tool".to_string(),
-                    content: Some("Tool execution completed without detailed results.".to_string()),
+                    content: Some(OpenAIContent::Text(
+                        "Tool execution completed without detailed results.".to_string(),
+                    )),
                     tool_calls: None,
                     tool_call_id: Some(tool_id.clone()),
                 });
@@ -349,31 +471,8 @@ impl ApiClient for OpenAIClient {
         };
 
         // Add structured output format if specified in options
-        if let Some(_json_schema) = &options.json_schema {
-            request.response_format = Some(json!({
-                "type": "json_object"
-            }));
-
-            // Ensure at least one message contains the word "json" when using json_object response format
-            let has_json_keyword = request.messages.iter().any(|msg| {
-                msg.content
-                    .as_ref()
-                    .is_some_and(|content| content.to_lowercase().contains("json"))
-            });
-
-            if !has_json_keyword && !request.messages.is_empty() {
-                // Add "json" to the user's last message if it doesn't already contain it
-                if let Some(last_user_msg) = request
-                    .messages
-                    .iter_mut()
-                    .rev()
-                    .find(|msg| msg.role == "user")
-                {
-                    if let Some(content) = &mut last_user_msg.content {
-                        *content = format!("{content} (Please provide the response as JSON)");
-                    }
-                }
-            }
+        if let Some(json_schema) = &options.json_schema {
+            Self::apply_response_format(&mut request, json_schema);
         }
 
         // Add tools if they exist
@@ -448,7 +547,12 @@ impl ApiClient for OpenAIClient {
 
         // Extract content and tool calls from the first choice
         if let Some(first_choice) = openai_response.choices.first() {
-            let content = first_choice.message.content.clone().unwrap_or_default();
+            let content = first_choice
+                .message
+                .content
+                .as_ref()
+                .map(|content| content.text())
+                .unwrap_or_default();
 
             // Extract tool calls if present
             let tool_calls = if let Some(openai_tool_calls) = &first_choice.message.tool_calls {
@@ -495,7 +599,7 @@ impl ApiClient for OpenAIClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::apis::api_client::{Message, ToolDefinition};
+    use crate::apis::api_client::{ContentBlock, Message, ToolDefinition};
     use serde_json::json;
 
     #[test]
@@ -539,14 +643,17 @@ mod tests {
             Message {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
+                blocks: Vec::new(),
             },
             Message {
                 role: "assistant".to_string(),
                 content: "Hi there! How can I help you today?".to_string(),
+                blocks: Vec::new(),
             },
         ];
 
@@ -620,6 +727,7 @@ mod tests {
         let single_message = vec![Message {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            blocks: Vec::new(),
         }];
 
         let openai_messages = client.convert_messages(single_message);
@@ -781,4 +889,78 @@ mod tests {
             "Type should be object"
         );
     }
+
+    #[test]
+    fn test_image_conversion() {
+        let api_key = "test_api_key".to_string();
+        let client = OpenAIClient::with_api_key(api_key, None).unwrap();
+
+        let messages = vec![
+            Message::user("What is in this picture?".to_string()).with_blocks(vec![
+                ContentBlock::Image {
+                    media_type: "image/png".to_string(),
+                    data: "iVBORw0KGgo=".to_string(),
+                },
+            ]),
+        ];
+
+        // Images are sent as data URIs alongside the text part
+        let openai_messages = client.convert_messages(messages);
+        let content = serde_json::to_value(&openai_messages[0].content).unwrap();
+        assert_eq!(
+            content,
+            json!([
+                { "type": "text", "text": "What is in this picture?" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_response_format_uses_json_schema() {
+        let request = |content: &str| OpenAIRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![OpenAIMessage {
+                role: "user".to_string(),
+                content: Some(OpenAIContent::Text(content.to_string())),
+                tool_calls: None,
+                tool_call_id: None,
+            }],
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+        };
+
+        let schema = json!({ "type": "object", "properties": { "ok": { "type": "boolean" } } });
+        let mut with_schema = request("Is it ok?");
+        OpenAIClient::apply_response_format(&mut with_schema, &schema.to_string());
+        assert_eq!(
+            with_schema.response_format.unwrap(),
+            json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema, "strict": false }
+            })
+        );
+        assert_eq!(
+            with_schema.messages[0].content.as_ref().unwrap(),
+            "Is it ok?"
+        );
+
+        // An unusable schema still gets JSON mode, which needs "json" in the prompt
+        let mut without_schema = request("Is it ok?");
+        OpenAIClient::apply_response_format(&mut without_schema, "not a schema");
+        assert_eq!(
+            without_schema.response_format.unwrap(),
+            json!({ "type": "json_object" })
+        );
+        assert!(without_schema.messages[0]
+            .content
+            .as_ref()
+            .unwrap()
+            .text()
+            .contains("JSON"));
+    }
 }
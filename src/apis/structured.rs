@@ -0,0 +1,268 @@
+//! Helpers for structured (JSON schema constrained) output.
+//!
+//! Every provider is asked to follow `CompletionOptions.json_schema` using its own
+//! mechanism, but none of them guarantee the result, so responses are parsed and
+//! checked locally before being handed back to the caller.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Name of the tool used to force structured output on providers without a
+/// native JSON schema mode (Anthropic)
+pub const STRUCTURED_OUTPUT_TOOL: &str = "structured_output";
+
+/// Parses a model response as JSON, tolerating markdown code fences and
+/// surrounding prose
+pub fn extract_json(text: &str) -> Result<Value> {
+    let trimmed = text.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+
+    // ```json ... ``` fenced block
+    if let Some(start) = trimmed.find("```") {
+        let after_fence = &trimmed[start + 3..];
+        let body_start = after_fence.find('\n').map(|i| i + 1).unwrap_or(0);
+        let body = &after_fence[body_start..];
+        if let Some(end) = body.find("```") {
+            if let Ok(value) = serde_json::from_str(body[..end].trim()) {
+                return Ok(value);
+            }
+        }
+    }
+
+    // Outermost object or array embedded in other text
+    for (open, close) in [('{', '}'), ('[', ']')] {
+        if let (Some(start), Some(end)) = (trimmed.find(open), trimmed.rfind(close)) {
+            if start < end {
+                if let Ok(value) = serde_json::from_str(&trimmed[start..=end]) {
+                    return Ok(value);
+                }
+            }
+        }
+    }
+
+    Err(anyhow!("Response is not valid JSON"))
+}
+
+/// Validates a value against a JSON schema, returning a description of every
+/// violation found.
+///
+/// Covers the subset of JSON Schema that models are asked to produce: `type`,
+/// `properties`, `required`, `additionalProperties`, `items`, `enum`, `const`,
+/// `anyOf`/`oneOf`/`allOf`, and the numeric, string and array bounds.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true`, `{}` and anything unrecognised accept every value
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{path}: no value is allowed here"));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(value, t)) {
+            errors.push(format!(
+                "{path}: expected {}, found {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            // Further checks would only repeat the same problem
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            errors.push(format!(
+                "{path}: must be one of {}",
+                Value::Array(options.clone())
+            ));
+        }
+    }
+
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            errors.push(format!("{path}: must equal {constant}"));
+        }
+    }
+
+    if let Some(variants) = schema.get("anyOf").and_then(|v| v.as_array()) {
+        let matched = variants
+            .iter()
+            .any(|variant| validate(value, variant).is_empty());
+        if !matched {
+            errors.push(format!("{path}: does not match any of the allowed schemas"));
+        }
+    }
+
+    // Unlike anyOf, oneOf rejects a value that matches more than one schema
+    if let Some(variants) = schema.get("oneOf").and_then(|v| v.as_array()) {
+        let matched = variants
+            .iter()
+            .filter(|variant| validate(value, variant).is_empty())
+            .count();
+        match matched {
+            1 => {}
+            0 => errors.push(format!("{path}: does not match any of the allowed schemas")),
+            _ => errors.push(format!(
+                "{path}: matches {matched} of the allowed schemas, but must match exactly one"
+            )),
+        }
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(|v| v.as_array()) {
+        for sub_schema in all {
+            validate_at(value, sub_schema, path, errors);
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+                for name in required.iter().filter_map(|n| n.as_str()) {
+                    if !object.contains_key(name) {
+                        errors.push(format!("{path}: missing required property \"{name}\""));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (name, property_value) in object {
+                let property_path = format!("{path}.{name}");
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property_schema) => {
+                        validate_at(property_value, property_schema, &property_path, errors)
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: unexpected property \"{name}\""))
+                        }
+                        Some(extra_schema @ Value::Object(_)) => {
+                            validate_at(property_value, extra_schema, &property_path, errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{path}[{i}]"), errors);
+                }
+            }
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{path}: must have at least {min} items"));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                if items.len() as u64 > max {
+                    errors.push(format!("{path}: must have at most {max} items"));
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) {
+                if length < min {
+                    errors.push(format!("{path}: must be at least {min} characters"));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) {
+                if length > max {
+                    errors.push(format!("{path}: must be at most {max} characters"));
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+                if number < min {
+                    errors.push(format!("{path}: must be at least {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+                if number > max {
+                    errors.push(format!("{path}: must be at most {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Parses and validates a structured response in one step
+pub fn parse_response(text: &str, schema: &Value) -> Result<Value> {
+    let value = extract_json(text)?;
+    let errors = validate(&value, schema);
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(anyhow!(
+            "Response does not match the schema:\n{}",
+            errors.join("\n")
+        ))
+    }
+}
+
+/// Follow-up prompt asking the model to fix a response that failed validation
+pub fn repair_prompt(error: &anyhow::Error, schema: &Value) -> String {
+    format!(
+        "Your previous response could not be used. {error}\n\n\
+         Reply again with only a JSON value that matches this schema, without any other text:\n{schema}"
+    )
+}
+
+/// Wraps a schema so it can be used as a tool's input schema, which must
+/// describe an object. Returns the schema and whether it was wrapped.
+pub fn object_schema(schema: &Value) -> (Value, bool) {
+    if schema.get("type").and_then(|t| t.as_str()) == Some("object") {
+        (schema.clone(), false)
+    } else {
+        (
+            serde_json::json!({
+                "type": "object",
+                "properties": { "value": schema },
+                "required": ["value"]
+            }),
+            true,
+        )
+    }
+}
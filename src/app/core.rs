@@ -5,9 +5,11 @@ use crate::app::logger::{format_log_with_color, LogLevel};
 use crate::app::memory::MemoryManager;
 use crate::models;
 use crate::models::{ModelConfig, ANTHROPIC_MODEL_NAME, GEMINI_MODEL_NAME, OPENAI_MODEL_NAME};
+use crate::tools::shell::PersistentShell;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::runtime::Runtime;
 use uuid::Uuid;
@@ -214,10 +216,16 @@ pub struct App {
     pub error_message: Option<String>,
     pub last_run_time: Instant,
     pub use_agent: bool,
+    // Extended thinking budget for models that support it
+    pub thinking_budget: Option<u32>,
+    // Token budget of the repository map in the agent's system prompt; off unless a client asks for one
+    pub repo_map_tokens: Option<usize>,
     pub agent: Option<Agent>,
     pub tokio_runtime: Option<Runtime>,
     pub api_key: Option<String>,
     pub current_working_dir: Option<String>,
+    // The session's Bash shell, kept alive between commands once the client enables it
+    pub shell: PersistentShell,
     pub tasks: Vec<Task>,
     pub current_task_id: Option<String>,
     pub conversation_summaries: Vec<ConversationSummary>,
@@ -249,6 +257,12 @@ impl App {
                 .with_system_message(crate::prompts::DEFAULT_SESSION_PROMPT.to_string()),
         );
 
+        // Discover models, including any installed in Ollama
+        let available_models = match &tokio_runtime {
+            Some(runtime) => runtime.block_on(models::get_available_models()),
+            None => models::get_cloud_models(),
+        };
+
         // Generate a unique session ID
         let session_id = Uuid::new_v4().to_string();
 
@@ -262,18 +276,23 @@ impl App {
             }
         }
 
+        let shell = PersistentShell::new(current_working_dir.as_ref().map(PathBuf::from));
+
         Self {
             state: AppState::Setup,
             messages: vec![],
             logs: vec![],
-            available_models: models::get_available_models(),
+            available_models,
             error_message: None,
             last_run_time: std::time::Instant::now(),
             use_agent: false,
+            thinking_budget: None,
+            repo_map_tokens: None,
             agent: None,
             tokio_runtime,
             api_key: None,
             current_working_dir,
+            shell,
             tasks: Vec::new(),
             current_task_id: None,
             conversation_summaries: Vec::new(),
@@ -284,6 +303,13 @@ impl App {
         }
     }
 
+    /// Re-discover the available models, e.g. after pulling or deleting an Ollama model
+    pub fn refresh_models(&mut self) {
+        if let Some(runtime) = &self.tokio_runtime {
+            self.available_models = runtime.block_on(models::get_available_models());
+        }
+    }
+
     /// Get the current model configuration
     pub fn current_model(&self, index: usize) -> Result<&ModelConfig> {
         self.available_models
@@ -716,10 +742,26 @@ impl App {
             }
             "Glob" => "Finding files by pattern".to_string(),
             "Grep" => "Searching code for pattern".to_string(),
+            "FindFile" => "Finding files by name".to_string(),
+            "RepoMap" => "Mapping repository".to_string(),
             "LS" => "Listing directory contents".to_string(),
             "Edit" => "Modifying file".to_string(),
+            "MultiEdit" => "Making several edits to file".to_string(),
+            "Patch" => "Applying patch".to_string(),
             "Replace" => "Replacing file contents".to_string(),
             "Bash" => "Executing command".to_string(),
+            "BashOutput" => "Reading background process output".to_string(),
+            "KillProcess" => "Stopping background process".to_string(),
+            "Diagnostics" => "Checking for errors".to_string(),
+            "References" => "Finding references".to_string(),
+            "Hover" => "Looking up symbol information".to_string(),
+            "WorkspaceSymbol" => "Searching symbols".to_string(),
+            "Rename" => "Renaming symbol".to_string(),
+            "Format" => "Formatting file".to_string(),
+            "CodeActions" => "Listing code actions".to_string(),
+            "ApplyCodeAction" => "Applying code action".to_string(),
+            "CallHierarchy" => "Following calls".to_string(),
+            "TypeHierarchy" => "Following type hierarchy".to_string(),
             _ => "Executing tool".to_string(),
         }
     }
@@ -774,6 +816,16 @@ impl App {
 
     /// Run the model with the given prompt
     pub fn run(&mut self, prompt: &str, model_index: Option<usize>) -> Result<String> {
+        self.run_with_images(prompt, model_index, Vec::new())
+    }
+
+    /// Run a prompt with images attached to it (as `ContentBlock::Image` blocks)
+    pub fn run_with_images(
+        &mut self,
+        prompt: &str,
+        model_index: Option<usize>,
+        images: Vec<crate::apis::api_client::ContentBlock>,
+    ) -> Result<String> {
         // Create a task for this run
         let task_id = self.create_task(prompt);
 
@@ -811,6 +863,7 @@ impl App {
         let model_name = model.name.clone();
         let model_file_name = model.file_name.clone();
         let supports_agent = model.has_agent_support();
+        let supports_images = model.has_image_support();
         let model_name_lower = model_name.to_lowercase();
 
         // Log model info
@@ -819,6 +872,13 @@ impl App {
             format_log_with_color(LogLevel::Info, &format!("Using model: {model_name}"))
         );
 
+        // Text-only models can't do anything useful with attached images
+        if !images.is_empty() && !supports_images {
+            return Err(anyhow::anyhow!(
+                "{model_name} is a text-only model and cannot accept image input"
+            ));
+        }
+
         // Get and validate API key
         let api_key = self.get_api_key_for_model(&model_name);
         Self::validate_api_key(&model_name, &api_key)?;
@@ -838,9 +898,32 @@ impl App {
             return Err(anyhow::anyhow!("Session manager not available"));
         }
 
+        // Attach the contents of any @file mentions to what the model sees
+        let expansion = crate::app::mentions::FileMentions::expand(
+            prompt,
+            self.current_working_dir
+                .as_deref()
+                .map(std::path::Path::new),
+        );
+        if !expansion.attached.is_empty() || !expansion.skipped.is_empty() {
+            eprintln!(
+                "{}",
+                format_log_with_color(
+                    LogLevel::Info,
+                    &format!(
+                        "Attached mentioned paths: {:?}, skipped: {:?}",
+                        expansion.attached, expansion.skipped
+                    )
+                )
+            );
+        }
+        let prompt = expansion.prompt.as_str();
+
         // Add user message to session
         if let Some(session) = &mut self.session_manager {
-            session.add_user_message(prompt.to_string());
+            session.add_message(
+                crate::apis::api_client::Message::user(prompt.to_string()).with_blocks(images),
+            );
         }
 
         // Get messages from session
@@ -870,6 +953,7 @@ impl App {
             temperature: Some(0.7),
             top_p: Some(0.9),
             max_tokens: Some(2048),
+            thinking_budget: self.thinking_budget,
             ..Default::default()
         };
 
@@ -885,7 +969,12 @@ impl App {
 
             // Create and configure the agent
             let mut agent = crate::agent::core::Agent::new(provider);
-            agent = agent.with_model(agent_model);
+            agent = agent
+                .with_model(agent_model)
+                .with_thinking_budget(self.thinking_budget)
+                .with_image_support(supports_images)
+                .with_repo_map(self.repo_map_tokens)
+                .with_shell(self.shell.clone());
 
             // Pass current working directory to the agent
             if let Some(cwd) = &self.current_working_dir {
@@ -182,6 +182,11 @@ impl ContextCompressor for App {
             session.clear();
         }
 
+        // A new conversation gets a fresh shell (working directory, variables)
+        // and stops the background processes the old one started
+        self.shell.reset();
+        crate::tools::shell::ProcessManager::kill_all();
+
         // Notify clients that history was cleared
         self.messages.push("[info] Chat history cleared".into());
     }
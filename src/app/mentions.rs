@@ -0,0 +1,269 @@
+use crate::tools::fs::file_ops::FileOps;
+use std::path::{Path, PathBuf};
+
+/// Largest part of one mentioned file attached to a prompt
+pub const MAX_MENTION_FILE_BYTES: usize = 100 * 1024;
+/// Largest total size of everything attached to one prompt
+pub const MAX_MENTION_TOTAL_BYTES: usize = 400 * 1024;
+/// Entries listed for a mentioned directory
+pub const MAX_DIRECTORY_ENTRIES: usize = 200;
+
+/// A `@path` or `@path#L10-40` reference in a prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMention {
+    /// The path as written
+    pub path: String,
+    /// First line requested, counting from 1
+    pub start_line: Option<usize>,
+    /// Last line requested, inclusive
+    pub end_line: Option<usize>,
+}
+
+/// A prompt with the files and directories it mentions attached
+#[derive(Debug, Clone, Default)]
+pub struct MentionExpansion {
+    /// The prompt to send to the model: an `<attached_context>` block followed
+    /// by the original prompt, or the original prompt if nothing was attached
+    pub prompt: String,
+    /// Mentioned paths that were attached
+    pub attached: Vec<String>,
+    /// Mentioned paths that were left out, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Expands `@file` mentions in user prompts into attached file context, so the
+/// model doesn't have to spend a tool call reading them
+pub struct FileMentions;
+
+impl FileMentions {
+    /// Every mention in `prompt`, in order and without duplicates. A mention is
+    /// an `@` at the start of a word followed by a path, optionally quoted
+    /// (`@"my file.txt"`) and optionally followed by a line range.
+    pub fn parse(prompt: &str) -> Vec<FileMention> {
+        let chars: Vec<char> = prompt.chars().collect();
+        let mut mentions: Vec<FileMention> = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            // Only at the start of a word, so emails like a@b.com don't count
+            let starts_word =
+                i == 0 || chars[i - 1].is_whitespace() || "([{\"'`".contains(chars[i - 1]);
+            if chars[i] != '@' || !starts_word {
+                i += 1;
+                continue;
+            }
+            i += 1;
+
+            let token: String = if chars.get(i) == Some(&'"') {
+                // Quoted path, which may contain spaces
+                let start = i + 1;
+                let Some(len) = chars[start..].iter().position(|c| *c == '"') else {
+                    continue;
+                };
+                i = start + len + 1;
+                let mut token: String = chars[start..start + len].iter().collect();
+                // A line range may follow the closing quote
+                let range_len = chars[i..].iter().take_while(|c| !c.is_whitespace()).count();
+                let range: String = chars[i..i + range_len].iter().collect();
+                if range.starts_with("#L") {
+                    token.push_str(range.trim_end_matches(Self::is_trailing_punctuation));
+                    i += range_len;
+                }
+                token
+            } else {
+                let len = chars[i..].iter().take_while(|c| !c.is_whitespace()).count();
+                let token: String = chars[i..i + len].iter().collect();
+                i += len;
+                token
+                    .trim_end_matches(Self::is_trailing_punctuation)
+                    .to_string()
+            };
+
+            if let Some(mention) = Self::parse_token(&token) {
+                if !mentions.contains(&mention) {
+                    mentions.push(mention);
+                }
+            }
+        }
+
+        mentions
+    }
+
+    /// Punctuation ending a sentence, which isn't part of a path
+    fn is_trailing_punctuation(c: char) -> bool {
+        ".,;:!?)]}'\"`".contains(c)
+    }
+
+    /// Split `path#L10-40` into a path and a line range
+    fn parse_token(token: &str) -> Option<FileMention> {
+        let (path, range) = match token.rfind("#L") {
+            Some(index) => (&token[..index], Some(&token[index + 2..])),
+            None => (token, None),
+        };
+        if path.is_empty() {
+            return None;
+        }
+
+        let (start_line, end_line) = match range {
+            None => (None, None),
+            Some(range) => {
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (start, end.trim_start_matches('L')),
+                    None => (range, range),
+                };
+                let start: usize = start.parse().ok().filter(|n| *n > 0)?;
+                let end: usize = end.parse().ok().filter(|n| *n >= start)?;
+                (Some(start), Some(end))
+            }
+        };
+
+        Some(FileMention {
+            path: path.to_string(),
+            start_line,
+            end_line,
+        })
+    }
+
+    /// Attach the files and directories mentioned in `prompt`, resolving
+    /// relative paths against `cwd`. Mentions of paths that don't exist are
+    /// left alone, since they may be meant for something else.
+    pub fn expand(prompt: &str, cwd: Option<&Path>) -> MentionExpansion {
+        let mut expansion = MentionExpansion {
+            prompt: prompt.to_string(),
+            ..Default::default()
+        };
+        let mut blocks = Vec::new();
+        let mut budget = MAX_MENTION_TOTAL_BYTES;
+
+        for mention in Self::parse(prompt) {
+            let path = Self::resolve(&mention.path, cwd);
+            let label = match (mention.start_line, mention.end_line) {
+                (Some(start), Some(end)) => format!("{}#L{start}-{end}", mention.path),
+                _ => mention.path.clone(),
+            };
+
+            let block = if path.is_dir() {
+                Self::directory_block(&mention.path, &path)
+            } else if path.is_file() {
+                match Self::file_block(&mention, &path, budget) {
+                    Ok(block) => block,
+                    Err(reason) => {
+                        blocks.push(format!(
+                            "<file path=\"{}\" omitted=\"{reason}\" />",
+                            mention.path
+                        ));
+                        expansion.skipped.push((label, reason));
+                        continue;
+                    }
+                }
+            } else {
+                continue;
+            };
+
+            budget = budget.saturating_sub(block.len());
+            blocks.push(block);
+            expansion.attached.push(label);
+        }
+
+        if !blocks.is_empty() {
+            expansion.prompt = format!(
+                "<attached_context>\nThe user mentioned these files and directories; their current contents are attached.\n\n{}\n</attached_context>\n\n{prompt}",
+                blocks.join("\n\n")
+            );
+        }
+        expansion
+    }
+
+    fn resolve(path: &str, cwd: Option<&Path>) -> PathBuf {
+        let path = Path::new(path);
+        match cwd {
+            Some(cwd) if path.is_relative() => cwd.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The requested lines of a file with line numbers, as the Read tool shows
+    /// them, cut short to fit the size limits
+    fn file_block(mention: &FileMention, path: &Path, budget: usize) -> Result<String, String> {
+        if budget == 0 {
+            return Err("context size limit reached".to_string());
+        }
+        let bytes = std::fs::read(path).map_err(|e| format!("unreadable: {e}"))?;
+        if bytes.iter().take(8192).any(|b| *b == 0) {
+            return Err("binary file".to_string());
+        }
+        let content = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = content.lines().collect();
+
+        let start = match mention.start_line {
+            Some(start) if start > lines.len() => {
+                return Err(format!("range past end of file ({} lines)", lines.len()));
+            }
+            Some(start) => start,
+            None => 1,
+        };
+        let end = mention.end_line.unwrap_or(lines.len()).min(lines.len());
+        let limit = budget.min(MAX_MENTION_FILE_BYTES);
+
+        let mut body = String::new();
+        let mut last_shown = start.saturating_sub(1);
+        for (i, line) in lines.iter().enumerate().take(end).skip(start - 1) {
+            let numbered = format!("{:4} | {}\n", i + 1, line);
+            if body.len() + numbered.len() > limit {
+                break;
+            }
+            body.push_str(&numbered);
+            last_shown = i + 1;
+        }
+        if last_shown < start && end >= start {
+            return Err("context size limit reached".to_string());
+        }
+
+        let range = if mention.start_line.is_some() {
+            format!(" lines=\"{start}-{last_shown}\"")
+        } else {
+            String::new()
+        };
+        let mut block = format!("<file path=\"{}\"{range}>\n{body}", mention.path);
+        if last_shown < end {
+            block.push_str(&format!(
+                "[truncated at line {last_shown} of {}; use Read for the rest]\n",
+                lines.len()
+            ));
+        }
+        block.push_str("</file>");
+        Ok(block)
+    }
+
+    /// One level of a directory, subdirectories marked with a trailing `/`
+    fn directory_block(label: &str, path: &Path) -> String {
+        let entries = FileOps::list_directory(path).unwrap_or_default();
+        let mut listing: Vec<String> = entries
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.file_name()?.to_string_lossy().to_string();
+                if name == ".git" {
+                    return None;
+                }
+                Some(if entry.is_dir() {
+                    format!("{name}/")
+                } else {
+                    name
+                })
+            })
+            .collect();
+
+        let total = listing.len();
+        listing.truncate(MAX_DIRECTORY_ENTRIES);
+        let mut block = format!("<directory path=\"{label}\">\n");
+        for name in &listing {
+            block.push_str(name);
+            block.push('\n');
+        }
+        if total > listing.len() {
+            block.push_str(&format!("... and {} more\n", total - listing.len()));
+        }
+        block.push_str("</directory>");
+        block
+    }
+}
@@ -4,6 +4,7 @@ pub mod history;
 pub mod logger;
 pub mod memory;
 pub mod memory_methods;
+pub mod mentions;
 pub mod models;
 pub mod utils;
 
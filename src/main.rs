@@ -1,6 +1,13 @@
 use anyhow::Result;
+use oli_server::apis::api_client::ContentBlock;
+use oli_server::apis::ollama::OllamaClient;
 use oli_server::app::history::ContextCompressor;
 use oli_server::communication::rpc::RpcServer;
+use oli_server::tools::fs::fuzzy::FileFinder;
+use oli_server::tools::fs::image::ImageOps;
+use oli_server::tools::fs::index::SearchIndex;
+use oli_server::tools::lsp::LspServerManager;
+use oli_server::tools::shell::ProcessManager;
 use oli_server::App;
 use serde_json::json;
 use std::sync::{Arc, Mutex};
@@ -23,7 +30,11 @@ fn main() -> Result<()> {
     register_model_interaction_apis(&mut rpc_server, &app, &global_event_sender);
     register_agent_control_apis(&mut rpc_server, &app);
     register_model_discovery_apis(&mut rpc_server, &app);
+    register_ollama_apis(&mut rpc_server, &app, &global_event_sender);
     register_task_management_apis(&mut rpc_server, &app);
+    register_process_apis(&mut rpc_server);
+    register_search_apis(&mut rpc_server);
+    register_lsp_apis(&mut rpc_server);
     register_conversation_apis(&mut rpc_server, &app);
     register_system_apis(&mut rpc_server);
 
@@ -33,7 +44,13 @@ fn main() -> Result<()> {
     // We've registered subscription handlers but no need to log in UI mode
 
     // Run the RPC server - silently to avoid UI interference
-    rpc_server.run()?;
+    let result = rpc_server.run();
+
+    // The session is over, so stop any background processes it left running
+    ProcessManager::kill_all();
+    SearchIndex::flush_all();
+    LspServerManager::global().stop_all().ok();
+    result?;
 
     Ok(())
 }
@@ -66,6 +83,20 @@ fn register_model_interaction_apis(
         // Update agent usage flag
         app.use_agent = use_agent;
 
+        // Extended thinking budget, if the client asked for one
+        app.thinking_budget = params["thinking_budget"].as_u64().map(|b| b as u32);
+
+        // Size of the repository map in the system prompt, 0 to leave it out
+        if let Some(tokens) = params["repo_map_tokens"].as_u64() {
+            app.repo_map_tokens = Some(tokens as usize);
+        }
+
+        // Images attached to the prompt: file paths, {"path": ...} or {"data": ...} objects
+        let images = match params["images"].as_array() {
+            Some(entries) => parse_prompt_images(entries)?,
+            None => Vec::new(),
+        };
+
         // We'll skip logging model selection to avoid UI clutter
 
         // Send processing started event
@@ -78,7 +109,7 @@ fn register_model_interaction_apis(
         ));
 
         // Run the model with the selected model index
-        match app.run(prompt, Some(model_index)) {
+        match app.run_with_images(prompt, Some(model_index), images) {
             Ok(response) => {
                 // Send processing complete event
                 let _ = event_sender.send(("processing_complete".to_string(), json!({})));
@@ -98,6 +129,30 @@ fn register_model_interaction_apis(
     });
 }
 
+/// Load the images attached to a run request.
+///
+/// Each entry is either a path string, `{"path": "..."}`, or
+/// `{"data": "<base64 or data URI>"}`. The media type is always detected from
+/// the image bytes, so unsupported formats are rejected here.
+fn parse_prompt_images(entries: &[serde_json::Value]) -> Result<Vec<ContentBlock>> {
+    entries
+        .iter()
+        .map(|entry| {
+            let path = entry.as_str().or_else(|| entry["path"].as_str());
+            if let Some(path) = path {
+                ImageOps::read_image(std::path::Path::new(path))?
+                    .ok_or_else(|| anyhow::anyhow!("{path} is not a PNG, JPEG, GIF or WebP image"))
+            } else if let Some(data) = entry["data"].as_str() {
+                ImageOps::image_from_base64(data)
+            } else {
+                Err(anyhow::anyhow!(
+                    "Invalid image entry: expected a path or an object with \"path\" or \"data\""
+                ))
+            }
+        })
+        .collect()
+}
+
 /// Register APIs for agent control
 fn register_agent_control_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App>>) {
     // Clone app state for set_agent_mode handler
@@ -119,6 +174,22 @@ fn register_agent_control_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App>>
             "agent_mode": use_agent
         }))
     });
+
+    // Register set_persistent_shell method; it holds the shell rather than the
+    // app, so it doesn't wait for a running query
+    let shell = app.lock().unwrap().shell.clone();
+    rpc_server.register_method("set_persistent_shell", move |params| {
+        // Keep the Bash tool's shell alive between calls, or run each command in a fresh one
+        let enabled = params["enabled"]
+            .as_bool()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'enabled' parameter"))?;
+        shell.set_enabled(enabled);
+
+        Ok(json!({
+            "success": true,
+            "persistent_shell": enabled
+        }))
+    });
 }
 
 /// Register APIs for model discovery
@@ -139,7 +210,9 @@ fn register_model_discovery_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App
                     "name": m.name,
                     "id": m.file_name,
                     "description": m.description,
-                    "supports_agent": m.has_agent_support()
+                    "supports_agent": m.has_agent_support(),
+                    "supports_images": m.has_image_support(),
+                    "context_length": m.context_length
                 })
             })
             .collect::<Vec<_>>();
@@ -186,6 +259,99 @@ fn register_model_discovery_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App
     });
 }
 
+/// Register APIs for managing local Ollama models
+fn register_ollama_apis(
+    rpc_server: &mut RpcServer,
+    app: &Arc<Mutex<App>>,
+    event_sender: &std::sync::mpsc::Sender<(String, serde_json::Value)>,
+) {
+    // Clone app state and event sender for pull_ollama_model handler
+    let app_clone = app.clone();
+    let event_sender = event_sender.clone();
+
+    // Register pull_ollama_model method; progress is streamed as ollama_pull_progress events
+    rpc_server.register_method("pull_ollama_model", move |params| {
+        let model = ollama_model_param(&params)?;
+        let runtime = runtime_handle(&app_clone)?;
+        let client = OllamaClient::new(None)?;
+
+        // Don't hold the app lock for the duration of the download
+        runtime.block_on(client.pull_model(&model, |progress| {
+            let _ = event_sender.send((
+                "ollama_pull_progress".to_string(),
+                json!({
+                    "model": model,
+                    "status": progress.status,
+                    "digest": progress.digest,
+                    "total": progress.total,
+                    "completed": progress.completed
+                }),
+            ));
+        }))?;
+
+        // Make the new model selectable
+        let mut app = app_clone.lock().unwrap();
+        app.refresh_models();
+        Ok(json!({ "success": true, "model": model }))
+    });
+
+    // Clone app state for show_ollama_model handler
+    let app_clone = app.clone();
+
+    // Register show_ollama_model method
+    rpc_server.register_method("show_ollama_model", move |params| {
+        let model = ollama_model_param(&params)?;
+        let runtime = runtime_handle(&app_clone)?;
+        let client = OllamaClient::new(None)?;
+
+        let info = runtime.block_on(client.show_model(&model))?;
+        Ok(json!({
+            "model": model,
+            "context_length": info.context_length(),
+            "template": info.template,
+            "parameters": info.parameters,
+            "capabilities": info.capabilities,
+            "supports_tools": info.supports_tools(),
+            "supports_vision": info.supports_vision(),
+            "details": info.details
+        }))
+    });
+
+    // Clone app state for delete_ollama_model handler
+    let app_clone = app.clone();
+
+    // Register delete_ollama_model method
+    rpc_server.register_method("delete_ollama_model", move |params| {
+        let model = ollama_model_param(&params)?;
+        let runtime = runtime_handle(&app_clone)?;
+        let client = OllamaClient::new(None)?;
+
+        runtime.block_on(client.delete_model(&model))?;
+
+        let mut app = app_clone.lock().unwrap();
+        app.refresh_models();
+        Ok(json!({ "success": true, "model": model }))
+    });
+}
+
+/// Extract the required model name from Ollama RPC params
+fn ollama_model_param(params: &serde_json::Value) -> Result<String> {
+    params["model"]
+        .as_str()
+        .filter(|model| !model.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Missing model parameter"))
+}
+
+/// Get a handle to the app's runtime without keeping the app locked
+fn runtime_handle(app: &Arc<Mutex<App>>) -> Result<tokio::runtime::Handle> {
+    let app = app.lock().unwrap();
+    app.tokio_runtime
+        .as_ref()
+        .map(|runtime| runtime.handle().clone())
+        .ok_or_else(|| anyhow::anyhow!("Async runtime not available"))
+}
+
 /// Register APIs for task management
 fn register_task_management_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App>>) {
     // Clone app state for get_tasks handler
@@ -223,6 +389,90 @@ fn register_task_management_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App
     });
 }
 
+/// Register APIs for background processes started by the Bash tool
+fn register_process_apis(rpc_server: &mut RpcServer) {
+    // Register list_processes method
+    rpc_server.register_method("list_processes", move |_| {
+        let processes = ProcessManager::list();
+        Ok(json!({ "processes": processes }))
+    });
+
+    // Register kill_process method
+    rpc_server.register_method("kill_process", move |params| {
+        let id = params["id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'id' parameter"))?;
+        let process = ProcessManager::kill(id)?;
+        Ok(json!({
+            "success": true,
+            "process": process
+        }))
+    });
+}
+
+/// Register APIs for finding files and the optional search index
+fn register_search_apis(rpc_server: &mut RpcServer) {
+    // Register set_search_index method
+    rpc_server.register_method("set_search_index", move |params| {
+        // Turn the trigram index that speeds up Grep on or off for a workspace
+        let enabled = params["enabled"]
+            .as_bool()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'enabled' parameter"))?;
+        let root = match params["path"].as_str() {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::env::current_dir()?,
+        };
+
+        if enabled {
+            let status = SearchIndex::enable(&root)?;
+            Ok(json!({
+                "success": true,
+                "search_index": true,
+                "status": status
+            }))
+        } else {
+            SearchIndex::disable(&root);
+            Ok(json!({
+                "success": true,
+                "search_index": false
+            }))
+        }
+    });
+
+    // Register find_files method
+    rpc_server.register_method("find_files", move |params| {
+        // Fuzzy file name matching, e.g. for @ mention autocomplete
+        let query = params["query"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'query' parameter"))?;
+        let root = match params["path"].as_str() {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::env::current_dir()?,
+        };
+        let limit = params["limit"].as_u64().unwrap_or(50) as usize;
+
+        let files = FileFinder::find(&root, query, limit)?;
+        Ok(json!({
+            "root": root,
+            "files": files
+        }))
+    });
+
+    // Register get_search_index_status method
+    rpc_server.register_method("get_search_index_status", move |_| {
+        Ok(json!({ "indexes": SearchIndex::status() }))
+    });
+}
+
+/// Register APIs for the language servers started by the LSP tools
+fn register_lsp_apis(rpc_server: &mut RpcServer) {
+    // Register get_lsp_servers method
+    rpc_server.register_method("get_lsp_servers", move |_| {
+        // Running servers with their state, memory use and open documents
+        Ok(json!({ "servers": LspServerManager::global().status() }))
+    });
+}
+
 /// Register APIs for conversation management
 fn register_conversation_apis(rpc_server: &mut RpcServer, app: &Arc<Mutex<App>>) {
     // Clone app state for clear_conversation handler
@@ -12,20 +12,34 @@ pub struct ModelConfig {
     pub description: String,
     pub recommended_for: String,
     pub supports_agent: bool,
+    /// Whether the model accepts image input alongside text
+    #[serde(default)]
+    pub supports_images: bool,
+    /// Context window in tokens, when known (detected for local models)
+    #[serde(default)]
+    pub context_length: Option<u64>,
 }
 
 impl ModelConfig {
     pub fn has_agent_support(&self) -> bool {
         self.supports_agent
     }
+
+    pub fn has_image_support(&self) -> bool {
+        self.supports_images
+    }
 }
 
-use crate::apis::ollama::OllamaClient;
-use anyhow::Result;
+use crate::apis::ollama::{OllamaClient, OllamaModelInfo};
+use crate::app::logger::{format_log_with_color, LogLevel};
+use std::time::Duration;
 
-pub fn get_available_models() -> Vec<ModelConfig> {
-    // Start with just the API models
-    let mut models = vec![
+/// How long to wait for Ollama before assuming it isn't running
+const OLLAMA_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Hosted models, which are always listed (they only need an API key)
+pub fn get_cloud_models() -> Vec<ModelConfig> {
+    vec![
         // Claude 4 Sonnet - Anthropic model supporting tool use
         ModelConfig {
             name: "Claude 4 Sonnet".into(),
@@ -33,6 +47,8 @@ pub fn get_available_models() -> Vec<ModelConfig> {
             description: "Latest Anthropic Claude with advanced code capabilities".into(),
             recommended_for: "Professional code tasks, requires ANTHROPIC_API_KEY".into(),
             supports_agent: true,
+            supports_images: true,
+            context_length: Some(200_000),
         },
         // GPT-4o - OpenAI model supporting tool use
         ModelConfig {
@@ -41,6 +57,8 @@ pub fn get_available_models() -> Vec<ModelConfig> {
             description: "Latest OpenAI model with advanced tool use capabilities".into(),
             recommended_for: "Professional code tasks, requires OPENAI_API_KEY".into(),
             supports_agent: true,
+            supports_images: true,
+            context_length: Some(128_000),
         },
         // Gemini 2.5 Pro - Google model supporting tool use
         ModelConfig {
@@ -49,88 +67,157 @@ pub fn get_available_models() -> Vec<ModelConfig> {
             description: "Google's latest Gemini model with advanced code capabilities".into(),
             recommended_for: "Professional code tasks, requires GEMINI_API_KEY".into(),
             supports_agent: true,
+            supports_images: true,
+            context_length: Some(1_000_000),
         },
-    ];
-
-    // Try to fetch available models from Ollama
-    if let Ok(ollama_models) = get_available_ollama_models() {
-        // Add each available Ollama model to the list
-        for model_info in ollama_models {
-            // Create a description based on the model details
-            let description = if let Some(details) = &model_info.details {
-                if let Some(desc) = &details.description {
-                    format!("{desc} - Running locally via Ollama")
-                } else {
-                    format!("{} - Running locally via Ollama", model_info.name)
-                }
-            } else {
-                format!("{} - Running locally via Ollama", model_info.name)
-            };
-
-            // Add the model to the list with "(local)" suffix
-            models.push(ModelConfig {
-                name: format!("{} (local)", model_info.name),
-                file_name: model_info.name.clone(),
-                description,
-                recommended_for: "Local code tasks, requires Ollama to be running".into(),
-                supports_agent: true,
-            });
-        }
-    }
+    ]
+}
 
+/// All models: the hosted ones followed by whatever Ollama has installed
+pub async fn get_available_models() -> Vec<ModelConfig> {
+    let mut models = get_cloud_models();
+    models.extend(get_ollama_models().await);
     models
 }
 
-fn get_available_ollama_models() -> Result<Vec<crate::apis::ollama::OllamaModelInfo>> {
-    // Try to get the list of models from Ollama in a non-async context
-    // We'll use a short timeout to avoid blocking the UI if Ollama is not running
-
-    // Create a runtime for the async call
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()?;
-
-    // Try to list models with a timeout - if it fails, we just return an empty list
-    let result = runtime.block_on(async {
-        // Handle the client creation result explicitly rather than using ?
-        match OllamaClient::new(None) {
-            Ok(ollama_client) => {
-                // Use a short timeout (2 seconds) to avoid hanging if Ollama is not running
-                match tokio::time::timeout(
-                    std::time::Duration::from_secs(2),
-                    ollama_client.list_models(),
-                )
-                .await
-                {
-                    Ok(models_result) => match models_result {
-                        Ok(models) => {
-                            eprintln!("Found {} Ollama models", models.len());
-                            Ok(models)
-                        }
-                        Err(e) => {
-                            eprintln!("Error listing Ollama models: {e}");
-                            Err(anyhow::anyhow!("Failed to list Ollama models: {}", e))
-                        }
-                    },
-                    Err(_) => {
-                        eprintln!("Timeout waiting for Ollama - likely not running");
-                        Err(anyhow::anyhow!("Timeout waiting for Ollama"))
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to create Ollama client: {e}");
-                Err(anyhow::anyhow!("Failed to create Ollama client: {}", e))
-            }
+/// Lists installed Ollama models along with their capabilities.
+///
+/// Returns an empty list if Ollama isn't running. Each model is inspected with
+/// `/api/show` so that agent mode is only offered to models that support tools.
+pub async fn get_ollama_models() -> Vec<ModelConfig> {
+    let client = match OllamaClient::new(None) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to create Ollama client: {e}");
+            return Vec::new();
         }
-    });
+    };
 
-    // Return empty list on any error
-    match result {
-        Ok(models) => Ok(models),
-        Err(e) => {
-            eprintln!("Returning empty models list due to error: {e}");
-            Ok(Vec::new())
+    let installed = match tokio::time::timeout(OLLAMA_DISCOVERY_TIMEOUT, client.list_models()).await
+    {
+        Ok(Ok(models)) => {
+            eprintln!("Found {} Ollama models", models.len());
+            models
+        }
+        Ok(Err(e)) => {
+            eprintln!("Error listing Ollama models: {e}");
+            return Vec::new();
         }
+        Err(_) => {
+            eprintln!("Timeout waiting for Ollama - likely not running");
+            return Vec::new();
+        }
+    };
+
+    // Inspect all models concurrently rather than one round trip after another
+    let client = std::sync::Arc::new(client);
+    let mut lookups = tokio::task::JoinSet::new();
+    for (index, model_info) in installed.into_iter().enumerate() {
+        let client = client.clone();
+        lookups.spawn(async move {
+            let show = tokio::time::timeout(
+                OLLAMA_DISCOVERY_TIMEOUT,
+                client.show_model(&model_info.name),
+            )
+            .await;
+            let config = match show {
+                Ok(Ok(details)) => ollama_model_config(
+                    &model_info,
+                    details.supports_tools(),
+                    details.supports_vision(),
+                    details.context_length(),
+                ),
+                Ok(Err(e)) => {
+                    eprintln!(
+                        "{}",
+                        format_log_with_color(
+                            LogLevel::Warning,
+                            &format!("Could not inspect Ollama model {}: {e}", model_info.name)
+                        )
+                    );
+                    ollama_model_config(
+                        &model_info,
+                        false,
+                        is_ollama_vision_model(&model_info),
+                        None,
+                    )
+                }
+                Err(_) => ollama_model_config(
+                    &model_info,
+                    false,
+                    is_ollama_vision_model(&model_info),
+                    None,
+                ),
+            };
+            (index, config)
+        });
     }
+
+    let mut models: Vec<(usize, ModelConfig)> = lookups.join_all().await;
+    // Keep Ollama's ordering (most recently modified first)
+    models.sort_by_key(|(index, _)| *index);
+    models.into_iter().map(|(_, config)| config).collect()
+}
+
+/// Builds the model entry for an installed Ollama model
+pub fn ollama_model_config(
+    model_info: &OllamaModelInfo,
+    supports_tools: bool,
+    supports_images: bool,
+    context_length: Option<u64>,
+) -> ModelConfig {
+    // Create a description based on the model details
+    let description = match model_info
+        .details
+        .as_ref()
+        .and_then(|details| details.description.as_ref())
+    {
+        Some(desc) => format!("{desc} - Running locally via Ollama"),
+        None => format!("{} - Running locally via Ollama", model_info.name),
+    };
+
+    let recommended_for = if supports_tools {
+        "Local code tasks, requires Ollama to be running"
+    } else {
+        "Local chat only (no tool support), requires Ollama to be running"
+    };
+
+    // Add the model to the list with "(local)" suffix
+    ModelConfig {
+        name: format!("{} (local)", model_info.name),
+        file_name: model_info.name.clone(),
+        description,
+        recommended_for: recommended_for.into(),
+        supports_agent: supports_tools,
+        supports_images,
+        context_length,
+    }
+}
+
+/// Best-effort guess at whether a local model is multimodal, used when Ollama
+/// can't tell us. Vision encoders show up as "clip" or "mllama" model families.
+fn is_ollama_vision_model(model_info: &OllamaModelInfo) -> bool {
+    let has_vision_family = model_info
+        .details
+        .as_ref()
+        .and_then(|details| details.families.as_ref())
+        .is_some_and(|families| {
+            families
+                .iter()
+                .any(|family| family == "clip" || family == "mllama")
+        });
+
+    let name = model_info.name.to_lowercase();
+    has_vision_family
+        || [
+            "llava",
+            "bakllava",
+            "vision",
+            "moondream",
+            "minicpm-v",
+            "qwen2.5vl",
+            "gemma3",
+        ]
+        .iter()
+        .any(|marker| name.contains(marker))
 }
@@ -18,6 +18,21 @@ pub fn add_working_directory_to_prompt(prompt: &str, working_dir: &str) -> Strin
     }
 }
 
+/// Format the repository map section from an outline made by `RepoMap`
+pub fn format_repo_map_prompt(repo_map: &str) -> String {
+    format!("## REPOSITORY MAP\nThe most referenced source files in the working directory, with the line each of their symbols is declared on. Paths are relative to the working directory. Use it to decide which files to read; the RepoMap tool gives a longer map or one of a subdirectory.\n\n{}", repo_map.trim_end())
+}
+
+/// Add the repository map section to a system prompt if it doesn't already have it
+pub fn add_repo_map_to_prompt(prompt: &str, repo_map: &str) -> String {
+    if prompt.contains("## REPOSITORY MAP") || repo_map.trim().is_empty() {
+        prompt.to_string()
+    } else {
+        let repo_map_section = format_repo_map_prompt(repo_map);
+        format!("{prompt}\n\n{repo_map_section}")
+    }
+}
+
 /// Default system prompt for the agent including working directory information
 pub fn get_agent_prompt_with_cwd(working_dir: Option<&str>) -> String {
     let base_prompt = DEFAULT_AGENT_PROMPT.to_string();
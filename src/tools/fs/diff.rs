@@ -1,93 +1,273 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::fmt::Write;
 
 /// Represents a line in a diff
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DiffLine {
     Added(String),
     Removed(String),
     Context(String),
 }
 
+/// How matching lines are found between the old and new text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    /// Shortest edit script (Myers, linear space). Minimal, and the usual choice.
+    #[default]
+    Myers,
+    /// Anchors on lines that occur exactly once on both sides before falling
+    /// back to Myers. Often more readable when blocks of code were moved.
+    Patience,
+}
+
+/// Options controlling how diffs are computed and displayed
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change
+    pub context: usize,
+    pub algorithm: DiffAlgorithm,
+    /// Highlight the changed words within modified lines. Off by default, since
+    /// formatted diffs are also returned to the model as tool output.
+    pub word_highlight: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            algorithm: DiffAlgorithm::Myers,
+            word_highlight: false,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: DiffAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_word_highlight(mut self, word_highlight: bool) -> Self {
+        self.word_highlight = word_highlight;
+        self
+    }
+}
+
+/// A group of nearby changes with their surrounding context, as in the
+/// `@@ -a,b +c,d @@` sections of a unified diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// 1-based first line in the old text (the line before, when `old_count` is 0)
+    pub old_start: usize,
+    pub old_count: usize,
+    /// 1-based first line in the new text (the line before, when `new_count` is 0)
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffHunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )
+    }
+}
+
+/// One step of an edit script, by line index
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// A run of text within a line and whether it changed
+pub type WordSegment = (String, bool);
+
 /// Utilities for generating and formatting diffs between text
 pub struct DiffTools;
 
 impl DiffTools {
-    /// Generate a diff between two strings
+    /// Generate a line diff between two strings using the Myers algorithm.
+    /// Every line of both texts is included, changed or not.
     pub fn generate_diff(old_text: &str, new_text: &str) -> Vec<DiffLine> {
+        Self::generate_diff_with(old_text, new_text, DiffAlgorithm::Myers)
+    }
+
+    /// Generate a line diff with the given algorithm
+    pub fn generate_diff_with(
+        old_text: &str,
+        new_text: &str,
+        algorithm: DiffAlgorithm,
+    ) -> Vec<DiffLine> {
         let old_lines: Vec<&str> = old_text.lines().collect();
         let new_lines: Vec<&str> = new_text.lines().collect();
 
-        // Simple line-by-line diff implementation
-        let mut diff = Vec::new();
-        let mut i = 0;
-        let mut j = 0;
-
-        while i < old_lines.len() || j < new_lines.len() {
-            if i < old_lines.len() && j < new_lines.len() && old_lines[i] == new_lines[j] {
-                // Line is the same
-                diff.push(DiffLine::Context(old_lines[i].to_string()));
-                i += 1;
-                j += 1;
-            } else {
-                // Find next matching line
-                let mut found_match = false;
-
-                // Look ahead in new_lines
-                for look_ahead in 0..3 {
-                    if i < old_lines.len()
-                        && j + look_ahead < new_lines.len()
-                        && old_lines[i] == new_lines[j + look_ahead]
-                    {
-                        // Found a matching line in new_lines, add the added lines before it
-                        for k in 0..look_ahead {
-                            diff.push(DiffLine::Added(new_lines[j + k].to_string()));
-                        }
-                        j += look_ahead;
-                        found_match = true;
-                        break;
-                    }
+        // Compare small integers rather than strings in the inner loops
+        let (old_ids, new_ids) = intern(&old_lines, &new_lines);
+
+        let edits = diff_sequences(&old_ids, &new_ids, algorithm);
+        edits
+            .into_iter()
+            .map(|edit| match edit {
+                Edit::Equal(i, _) => DiffLine::Context(old_lines[i].to_string()),
+                Edit::Delete(i) => DiffLine::Removed(old_lines[i].to_string()),
+                Edit::Insert(j) => DiffLine::Added(new_lines[j].to_string()),
+            })
+            .collect()
+    }
+
+    /// Split a full diff into hunks with `context` unchanged lines around each
+    /// change. Changes separated by no more than twice the context share a hunk.
+    pub fn hunks(diff: &[DiffLine], context: usize) -> Vec<DiffHunk> {
+        // Line numbers (0-based) in the old and new text before each diff line
+        let mut positions = Vec::with_capacity(diff.len());
+        let (mut old_line, mut new_line) = (0, 0);
+        for line in diff {
+            positions.push((old_line, new_line));
+            match line {
+                DiffLine::Context(_) => {
+                    old_line += 1;
+                    new_line += 1;
                 }
+                DiffLine::Removed(_) => old_line += 1,
+                DiffLine::Added(_) => new_line += 1,
+            }
+        }
 
-                // Look ahead in old_lines if no match found
-                if !found_match {
-                    for look_ahead in 0..3 {
-                        if i + look_ahead < old_lines.len()
-                            && j < new_lines.len()
-                            && old_lines[i + look_ahead] == new_lines[j]
-                        {
-                            // Found a matching line in old_lines, add the removed lines before it
-                            for k in 0..look_ahead {
-                                diff.push(DiffLine::Removed(old_lines[i + k].to_string()));
-                            }
-                            i += look_ahead;
-                            found_match = true;
-                            break;
-                        }
-                    }
+        let changes: Vec<usize> = diff
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+            .map(|(i, _)| i)
+            .collect();
+
+        // Group changes into [start, end) ranges of diff lines
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &index in &changes {
+            let start = index.saturating_sub(context);
+            let end = (index + 1 + context).min(diff.len());
+            match ranges.last_mut() {
+                Some(last) if start <= last.1 => last.1 = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        ranges
+            .into_iter()
+            .map(|(start, end)| {
+                let lines = diff[start..end].to_vec();
+                let old_count = lines
+                    .iter()
+                    .filter(|l| !matches!(l, DiffLine::Added(_)))
+                    .count();
+                let new_count = lines
+                    .iter()
+                    .filter(|l| !matches!(l, DiffLine::Removed(_)))
+                    .count();
+                let (old_line, new_line) = positions[start];
+                DiffHunk {
+                    old_start: if old_count == 0 {
+                        old_line
+                    } else {
+                        old_line + 1
+                    },
+                    old_count,
+                    new_start: if new_count == 0 {
+                        new_line
+                    } else {
+                        new_line + 1
+                    },
+                    new_count,
+                    lines,
                 }
+            })
+            .collect()
+    }
+
+    /// Standard unified diff text (`---`/`+++` headers and `@@` hunks), without
+    /// colors. Empty when the texts are identical.
+    pub fn unified_diff(
+        old_text: &str,
+        new_text: &str,
+        old_path: &str,
+        new_path: &str,
+        options: &DiffOptions,
+    ) -> String {
+        let diff = Self::generate_diff_with(old_text, new_text, options.algorithm);
+        let hunks = Self::hunks(&diff, options.context);
+        if hunks.is_empty() {
+            return String::new();
+        }
 
-                // If no match found, add one line as difference
-                if !found_match {
-                    if i < old_lines.len() {
-                        diff.push(DiffLine::Removed(old_lines[i].to_string()));
-                        i += 1;
-                    }
-                    if j < new_lines.len() {
-                        diff.push(DiffLine::Added(new_lines[j].to_string()));
-                        j += 1;
-                    }
+        let mut output = String::new();
+        let _ = writeln!(output, "--- {old_path}");
+        let _ = writeln!(output, "+++ {new_path}");
+        for hunk in &hunks {
+            let _ = writeln!(output, "{}", hunk.header());
+            for line in &hunk.lines {
+                let _ = match line {
+                    DiffLine::Context(text) => writeln!(output, " {text}"),
+                    DiffLine::Removed(text) => writeln!(output, "-{text}"),
+                    DiffLine::Added(text) => writeln!(output, "+{text}"),
+                };
+            }
+        }
+        output
+    }
+
+    /// Split two versions of a line into segments, each flagged with whether it
+    /// changed. Used to highlight the edited words within a modified line.
+    pub fn word_diff(old_line: &str, new_line: &str) -> (Vec<WordSegment>, Vec<WordSegment>) {
+        let old_tokens = tokenize(old_line);
+        let new_tokens = tokenize(new_line);
+
+        let (old_ids, new_ids) = intern(&old_tokens, &new_tokens);
+
+        let mut old_segments: Vec<WordSegment> = Vec::new();
+        let mut new_segments: Vec<WordSegment> = Vec::new();
+        let push = |segments: &mut Vec<WordSegment>, text: &str, changed: bool| match segments
+            .last_mut()
+        {
+            Some((last, last_changed)) if *last_changed == changed => last.push_str(text),
+            _ => segments.push((text.to_string(), changed)),
+        };
+
+        for edit in diff_sequences(&old_ids, &new_ids, DiffAlgorithm::Myers) {
+            match edit {
+                Edit::Equal(i, j) => {
+                    push(&mut old_segments, old_tokens[i], false);
+                    push(&mut new_segments, new_tokens[j], false);
                 }
+                Edit::Delete(i) => push(&mut old_segments, old_tokens[i], true),
+                Edit::Insert(j) => push(&mut new_segments, new_tokens[j], true),
             }
         }
 
-        diff
+        (old_segments, new_segments)
     }
 
-    /// Format diff as a string with line numbers and colors
+    /// Format diff as a string with line numbers and colors, showing only the
+    /// changed hunks with the default context
     pub fn format_diff(diff: &[DiffLine], file_path: &str) -> Result<String> {
+        Self::format_diff_with(diff, file_path, &DiffOptions::default())
+    }
+
+    /// Format diff as a string with line numbers and colors
+    pub fn format_diff_with(
+        diff: &[DiffLine],
+        file_path: &str,
+        options: &DiffOptions,
+    ) -> Result<String> {
         let mut output = String::new();
-        let mut line_number = 0;
         let mut adds = 0;
         let mut removes = 0;
 
@@ -112,24 +292,76 @@ impl DiffTools {
         )?;
 
         // Only show the diff if there are changes
-        if adds > 0 || removes > 0 {
-            // Add the diff content with line numbers and colored indicators
-            for line in diff {
-                match line {
-                    DiffLine::Context(text) => {
-                        line_number += 1;
-                        writeln!(output, "     {line_number:3}  {text}")?;
-                    }
-                    DiffLine::Added(text) => {
-                        line_number += 1;
-                        // Use ANSI colors to show additions in light green
-                        writeln!(output, "     \x1b[92m{line_number:3}+ {text}\x1b[0m")?;
-                    }
-                    DiffLine::Removed(text) => {
-                        // For removed lines, use a darker red color
-                        // Don't increment line number for removed lines
-                        writeln!(output, "     \x1b[91m{line_number:3}- {text}\x1b[0m")?;
-                    }
+        if adds == 0 && removes == 0 {
+            return Ok(output);
+        }
+
+        for (hunk_index, hunk) in Self::hunks(diff, options.context).iter().enumerate() {
+            // Separate hunks so gaps in the line numbers are obvious
+            if hunk_index > 0 {
+                writeln!(output, "     \x1b[90m...\x1b[0m")?;
+            }
+
+            // Line numbers: removed lines show their old position, everything
+            // else its position in the new file
+            let mut old_line = hunk.old_start + usize::from(hunk.old_count == 0);
+            let mut new_line = hunk.new_start + usize::from(hunk.new_count == 0);
+
+            let mut i = 0;
+            while i < hunk.lines.len() {
+                if let DiffLine::Context(text) = &hunk.lines[i] {
+                    writeln!(output, "     {new_line:3}  {text}")?;
+                    old_line += 1;
+                    new_line += 1;
+                    i += 1;
+                    continue;
+                }
+
+                // A block of changes: removed lines followed by added lines
+                let removed: Vec<&str> = hunk.lines[i..]
+                    .iter()
+                    .map_while(|l| match l {
+                        DiffLine::Removed(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                let added: Vec<&str> = hunk.lines[i + removed.len()..]
+                    .iter()
+                    .map_while(|l| match l {
+                        DiffLine::Added(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                i += removed.len() + added.len();
+
+                // Lines replaced one for one get their changed words highlighted
+                let paired = if options.word_highlight {
+                    removed.len().min(added.len())
+                } else {
+                    0
+                };
+                let mut highlighted_added = Vec::with_capacity(paired);
+
+                for (k, text) in removed.iter().enumerate() {
+                    // For removed lines, use a darker red color
+                    let text = if k < paired {
+                        let (old_segments, new_segments) = Self::word_diff(text, added[k]);
+                        highlighted_added.push(highlight(&new_segments));
+                        highlight(&old_segments)
+                    } else {
+                        text.to_string()
+                    };
+                    writeln!(output, "     \x1b[91m{old_line:3}- {text}\x1b[0m")?;
+                    old_line += 1;
+                }
+                for (k, text) in added.iter().enumerate() {
+                    // Use ANSI colors to show additions in light green
+                    let text = match highlighted_added.get(k) {
+                        Some(text) => text.clone(),
+                        None => text.to_string(),
+                    };
+                    writeln!(output, "     \x1b[92m{new_line:3}+ {text}\x1b[0m")?;
+                    new_line += 1;
                 }
             }
         }
@@ -137,3 +369,379 @@ impl DiffTools {
         Ok(output)
     }
 }
+
+/// Renders word diff segments, showing changed ones in reverse video
+fn highlight(segments: &[WordSegment]) -> String {
+    let mut text = String::new();
+    for (segment, changed) in segments {
+        if *changed {
+            text.push_str("\x1b[7m");
+            text.push_str(segment);
+            text.push_str("\x1b[27m");
+        } else {
+            text.push_str(segment);
+        }
+    }
+    text
+}
+
+/// Splits a line into words, runs of whitespace and single punctuation characters
+fn tokenize(line: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else if c.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut current: Option<Class> = None;
+    for (i, c) in line.char_indices() {
+        let c_class = class(c);
+        let continues = match &current {
+            Some(Class::Other) => false,
+            Some(previous) => *previous == c_class,
+            None => false,
+        };
+        if !continues && i > start {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        current = Some(c_class);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Maps equal strings to equal ids so sequences can be compared cheaply
+fn intern<'a>(old: &[&'a str], new: &[&'a str]) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: HashMap<&'a str, u32> = HashMap::new();
+    let mut convert = |items: &[&'a str]| -> Vec<u32> {
+        items
+            .iter()
+            .map(|item| {
+                let next = ids.len() as u32;
+                *ids.entry(*item).or_insert(next)
+            })
+            .collect()
+    };
+    let old_ids = convert(old);
+    let new_ids = convert(new);
+    (old_ids, new_ids)
+}
+
+/// Computes an edit script turning `old` into `new`
+fn diff_sequences(old: &[u32], new: &[u32], algorithm: DiffAlgorithm) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    match algorithm {
+        DiffAlgorithm::Myers => myers(old, 0..old.len(), new, 0..new.len(), &mut edits),
+        DiffAlgorithm::Patience => patience(old, 0..old.len(), new, 0..new.len(), &mut edits),
+    }
+
+    // Within each block of changes, list removals before additions as diff
+    // tools conventionally do
+    let mut start = 0;
+    while start < edits.len() {
+        if matches!(edits[start], Edit::Equal(..)) {
+            start += 1;
+            continue;
+        }
+        let end = edits[start..]
+            .iter()
+            .position(|edit| matches!(edit, Edit::Equal(..)))
+            .map_or(edits.len(), |offset| start + offset);
+        edits[start..end].sort_by_key(|edit| matches!(edit, Edit::Insert(_)));
+        start = end;
+    }
+    edits
+}
+
+type Range = std::ops::Range<usize>;
+
+/// Removes the lines shared at both ends of the ranges, emitting the common
+/// prefix. Returns the narrowed ranges and the length of the common suffix.
+fn trim_common(
+    old: &[u32],
+    mut old_range: Range,
+    new: &[u32],
+    mut new_range: Range,
+    edits: &mut Vec<Edit>,
+) -> (Range, Range, usize) {
+    let prefix = common_prefix(&old[old_range.clone()], &new[new_range.clone()]);
+    for offset in 0..prefix {
+        edits.push(Edit::Equal(
+            old_range.start + offset,
+            new_range.start + offset,
+        ));
+    }
+    old_range.start += prefix;
+    new_range.start += prefix;
+
+    let suffix = common_suffix(&old[old_range.clone()], &new[new_range.clone()]);
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    (old_range, new_range, suffix)
+}
+
+fn emit_suffix(old_end: usize, new_end: usize, suffix: usize, edits: &mut Vec<Edit>) {
+    for offset in 0..suffix {
+        edits.push(Edit::Equal(old_end + offset, new_end + offset));
+    }
+}
+
+fn common_prefix(a: &[u32], b: &[u32]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn common_suffix(a: &[u32], b: &[u32]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+/// Myers' O(ND) algorithm in its linear space form: find the middle snake of
+/// the shortest edit path, then solve each half recursively
+fn myers(old: &[u32], old_range: Range, new: &[u32], new_range: Range, edits: &mut Vec<Edit>) {
+    let max_d = (old_range.len() + new_range.len()).div_ceil(2) + 1;
+    let mut forward = vec![0usize; 2 * max_d + 1];
+    let mut backward = vec![0usize; 2 * max_d + 1];
+    conquer(
+        old,
+        old_range,
+        new,
+        new_range,
+        &mut forward,
+        &mut backward,
+        edits,
+    );
+}
+
+fn conquer(
+    old: &[u32],
+    old_range: Range,
+    new: &[u32],
+    new_range: Range,
+    forward: &mut [usize],
+    backward: &mut [usize],
+    edits: &mut Vec<Edit>,
+) {
+    let (old_range, new_range, suffix) = trim_common(old, old_range, new, new_range, edits);
+
+    if new_range.is_empty() {
+        edits.extend(old_range.clone().map(Edit::Delete));
+    } else if old_range.is_empty() {
+        edits.extend(new_range.clone().map(Edit::Insert));
+    } else if let Some((x, y)) = middle_snake(
+        old,
+        old_range.clone(),
+        new,
+        new_range.clone(),
+        forward,
+        backward,
+    ) {
+        conquer(
+            old,
+            old_range.start..x,
+            new,
+            new_range.start..y,
+            forward,
+            backward,
+            edits,
+        );
+        conquer(
+            old,
+            x..old_range.end,
+            new,
+            y..new_range.end,
+            forward,
+            backward,
+            edits,
+        );
+    } else {
+        edits.extend(old_range.clone().map(Edit::Delete));
+        edits.extend(new_range.clone().map(Edit::Insert));
+    }
+
+    emit_suffix(old_range.end, new_range.end, suffix, edits);
+}
+
+/// Finds a point on an optimal edit path roughly halfway through it, by
+/// searching forwards from the start and backwards from the end at once.
+/// Returns absolute indices into `old` and `new`.
+fn middle_snake(
+    old: &[u32],
+    old_range: Range,
+    new: &[u32],
+    new_range: Range,
+    forward: &mut [usize],
+    backward: &mut [usize],
+) -> Option<(usize, usize)> {
+    let n = old_range.len() as isize;
+    let m = new_range.len() as isize;
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let max_d = (old_range.len() + new_range.len()).div_ceil(2) + 1;
+    // Diagonal k is stored at index k + offset
+    let offset = (forward.len() / 2) as isize;
+    let at = |k: isize| (k + offset) as usize;
+
+    forward[at(1)] = 0;
+    backward[at(1)] = 0;
+
+    for d in 0..max_d as isize {
+        // Forward search: furthest reaching x on each diagonal k = x - y
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let (x0, y0) = (x, y);
+            if (x as isize) < n && (y as isize) < m {
+                x += common_prefix(
+                    &old[old_range.start + x..old_range.end],
+                    &new[new_range.start + y..new_range.end],
+                );
+            }
+            forward[at(k)] = x;
+
+            if odd
+                && (k - delta).abs() < d
+                && forward[at(k)] + backward[at(delta - k)] >= n as usize
+            {
+                return Some((old_range.start + x0, new_range.start + y0));
+            }
+            k -= 2;
+        }
+
+        // Backward search, measuring x from the end of the ranges
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            if (x as isize) < n && (y as isize) < m {
+                let advance = common_suffix(
+                    &old[old_range.start..old_range.end - x],
+                    &new[new_range.start..new_range.end - y],
+                );
+                x += advance;
+                y += advance;
+            }
+            backward[at(k)] = x;
+
+            if !odd
+                && (k - delta).abs() <= d
+                && backward[at(k)] + forward[at(delta - k)] >= n as usize
+            {
+                return Some((old_range.end - x, new_range.end - y));
+            }
+            k -= 2;
+        }
+    }
+
+    None
+}
+
+/// Patience diff: match lines that are unique on both sides, keep the longest
+/// run of them that appears in the same order, and diff the gaps in between
+fn patience(old: &[u32], old_range: Range, new: &[u32], new_range: Range, edits: &mut Vec<Edit>) {
+    let (old_range, new_range, suffix) = trim_common(old, old_range, new, new_range, edits);
+
+    if old_range.is_empty() || new_range.is_empty() {
+        edits.extend(old_range.clone().map(Edit::Delete));
+        edits.extend(new_range.clone().map(Edit::Insert));
+        emit_suffix(old_range.end, new_range.end, suffix, edits);
+        return;
+    }
+
+    // Occurrence counts and last positions of each line on both sides
+    let mut counts: HashMap<u32, (usize, usize, usize, usize)> = HashMap::new();
+    for i in old_range.clone() {
+        let entry = counts.entry(old[i]).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for j in new_range.clone() {
+        let entry = counts.entry(new[j]).or_default();
+        entry.2 += 1;
+        entry.3 = j;
+    }
+    let mut unique: Vec<(usize, usize)> = counts
+        .values()
+        .filter(|(old_count, _, new_count, _)| *old_count == 1 && *new_count == 1)
+        .map(|(_, i, _, j)| (*i, *j))
+        .collect();
+    unique.sort_unstable();
+
+    let anchors = longest_increasing_run(&unique);
+    if anchors.is_empty() {
+        myers(old, old_range.clone(), new, new_range.clone(), edits);
+    } else {
+        let (mut old_start, mut new_start) = (old_range.start, new_range.start);
+        for (i, j) in anchors {
+            patience(old, old_start..i, new, new_start..j, edits);
+            edits.push(Edit::Equal(i, j));
+            old_start = i + 1;
+            new_start = j + 1;
+        }
+        patience(
+            old,
+            old_start..old_range.end,
+            new,
+            new_start..new_range.end,
+            edits,
+        );
+    }
+
+    emit_suffix(old_range.end, new_range.end, suffix, edits);
+}
+
+/// Longest subsequence of pairs (sorted by the first index) whose second
+/// index is increasing, found by patience sorting in O(n log n)
+fn longest_increasing_run(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // tails[len] is the index of the smallest ending pair of a run of length len + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let position = tails.partition_point(|&tail| pairs[tail].1 < j);
+        if position > 0 {
+            previous[index] = Some(tails[position - 1]);
+        }
+        if position == tails.len() {
+            tails.push(index);
+        } else {
+            tails[position] = index;
+        }
+    }
+
+    let mut run = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(index) = current {
+        run.push(pairs[index]);
+        current = previous[index];
+    }
+    run.reverse();
+    run
+}
@@ -1,10 +1,20 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
 use super::diff::DiffTools;
 
+/// One string replacement within a file, as used by multi-edit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEdit {
+    pub old_string: String,
+    pub new_string: String,
+    pub expected_replacements: Option<usize>,
+}
+
 pub struct FileOps;
 
 impl FileOps {
@@ -90,7 +100,23 @@ impl FileOps {
         expected_replacements: Option<usize>,
     ) -> Result<(String, String)> {
         let content = Self::read_file(path)?;
+        let new_content =
+            Self::apply_edit(&content, old_string, new_string, expected_replacements)?;
+
+        // Generate a diff
+        let diff_lines = DiffTools::generate_diff(&content, &new_content);
+        let formatted_diff = DiffTools::format_diff(&diff_lines, &path.display().to_string())?;
 
+        Ok((new_content, formatted_diff))
+    }
+
+    /// Replaces `old_string` with `new_string` in `content`, checking the number of matches
+    pub fn apply_edit(
+        content: &str,
+        old_string: &str,
+        new_string: &str,
+        expected_replacements: Option<usize>,
+    ) -> Result<String> {
         // Count occurrences to ensure we're replacing a unique string
         let occurrences = content.matches(old_string).count();
         if occurrences == 0 {
@@ -113,15 +139,48 @@ impl FileOps {
             }
         }
 
-        let new_content = content.replace(old_string, new_string);
+        Ok(content.replace(old_string, new_string))
+    }
+
+    /// Applies a sequence of edits in memory and returns the new content and a
+    /// diff against the original. Each edit sees the result of the ones before it.
+    pub fn generate_multi_edit_diff(path: &Path, edits: &[FileEdit]) -> Result<(String, String)> {
+        if edits.is_empty() {
+            anyhow::bail!("No edits were provided");
+        }
+
+        let content = Self::read_file(path)?;
+        let mut new_content = content.clone();
+        for (i, edit) in edits.iter().enumerate() {
+            new_content = Self::apply_edit(
+                &new_content,
+                &edit.old_string,
+                &edit.new_string,
+                edit.expected_replacements,
+            )
+            .with_context(|| {
+                format!(
+                    "Edit {} of {} failed; no changes were made to {}",
+                    i + 1,
+                    edits.len(),
+                    path.display()
+                )
+            })?;
+        }
 
-        // Generate a diff
         let diff_lines = DiffTools::generate_diff(&content, &new_content);
         let formatted_diff = DiffTools::format_diff(&diff_lines, &path.display().to_string())?;
 
         Ok((new_content, formatted_diff))
     }
 
+    /// Applies all edits to a file or, if any of them fails, none of them
+    pub fn multi_edit_file(path: &Path, edits: &[FileEdit]) -> Result<String> {
+        let (new_content, diff) = Self::generate_multi_edit_diff(path, edits)?;
+        Self::write_file(path, &new_content)?;
+        Ok(diff)
+    }
+
     pub fn edit_file(
         path: &Path,
         old_string: &str,
@@ -134,6 +193,48 @@ impl FileOps {
         Ok(diff)
     }
 
+    /// Write every change in a set, or none of them: if any write fails, the
+    /// files already changed are put back. Returns the combined diff.
+    pub fn apply_changes(changes: &ChangeSet) -> Result<String> {
+        let diff = changes.diff()?;
+
+        // What each touched path held before, to roll back to
+        let mut originals = Vec::new();
+        for path in &changes.order {
+            let original = if path.is_file() {
+                Some(
+                    fs::read(path)
+                        .with_context(|| format!("Failed to read file: {}", path.display()))?,
+                )
+            } else {
+                None
+            };
+            originals.push((path, original));
+        }
+
+        for (written, path) in changes.order.iter().enumerate() {
+            let result = match &changes.staged[path] {
+                Some(content) => Self::write_file(path, content),
+                None if path.exists() => fs::remove_file(path)
+                    .with_context(|| format!("Failed to delete file: {}", path.display())),
+                None => Ok(()),
+            };
+
+            if let Err(e) = result {
+                for (path, original) in originals.iter().take(written + 1) {
+                    let _ = match original {
+                        Some(bytes) => fs::write(path, bytes),
+                        None if path.exists() => fs::remove_file(path),
+                        None => Ok(()),
+                    };
+                }
+                return Err(e.context("No changes were applied"));
+            }
+        }
+
+        Ok(diff)
+    }
+
     pub fn list_directory(path: &Path) -> Result<Vec<PathBuf>> {
         let entries = fs::read_dir(path)
             .with_context(|| format!("Failed to read directory: {}", path.display()))?;
@@ -186,3 +287,149 @@ impl FileOps {
         Ok(info)
     }
 }
+
+/// Changes to several files staged in memory, such as a language server's
+/// rename, so they can be previewed and then written all together. Each step
+/// sees the result of earlier ones, so a file may be moved and then edited.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    /// Staged content by path: `Some` to write, `None` to delete
+    staged: HashMap<PathBuf, Option<String>>,
+    /// Paths in the order they were first touched
+    order: Vec<PathBuf>,
+    /// Where each moved file originally lived on disk
+    moved_from: HashMap<PathBuf, PathBuf>,
+}
+
+impl ChangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// The content of a file with the staged changes applied, `None` if it
+    /// doesn't exist
+    pub fn content(&self, path: &Path) -> Result<Option<String>> {
+        match self.staged.get(path) {
+            Some(content) => Ok(content.clone()),
+            None if path.is_file() => FileOps::read_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Create a file or replace its content
+    pub fn write(&mut self, path: &Path, content: String) {
+        self.stage(path, Some(content));
+    }
+
+    /// Move a file, replacing an existing destination only with `overwrite`
+    pub fn rename(&mut self, from: &Path, to: &Path, overwrite: bool) -> Result<()> {
+        if from.is_dir() && !self.staged.contains_key(from) {
+            bail!(
+                "Cannot rename {}: renaming directories is not supported",
+                from.display()
+            );
+        }
+        let content = self
+            .content(from)?
+            .ok_or_else(|| anyhow!("Cannot rename {}: file does not exist", from.display()))?;
+        if !overwrite && self.content(to)?.is_some() {
+            bail!("Cannot rename to {}: file already exists", to.display());
+        }
+
+        let origin = self
+            .moved_from
+            .remove(from)
+            .unwrap_or_else(|| from.to_path_buf());
+        self.stage(from, None);
+        self.stage(to, Some(content));
+        self.moved_from.insert(to.to_path_buf(), origin);
+        Ok(())
+    }
+
+    /// Delete a file
+    pub fn delete(&mut self, path: &Path) -> Result<()> {
+        if path.is_dir() && !self.staged.contains_key(path) {
+            bail!(
+                "Cannot delete {}: deleting directories is not supported",
+                path.display()
+            );
+        }
+        if self.content(path)?.is_none() {
+            bail!("Cannot delete {}: file does not exist", path.display());
+        }
+        self.moved_from.remove(path);
+        self.stage(path, None);
+        Ok(())
+    }
+
+    /// Every file changed, created, moved or deleted, in the order they were
+    /// touched
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.order
+    }
+
+    /// Files that exist once the changes are written, in the order they were
+    /// touched
+    pub fn written_paths(&self) -> Vec<PathBuf> {
+        self.order
+            .iter()
+            .filter(|path| self.staged[*path].is_some())
+            .cloned()
+            .collect()
+    }
+
+    fn stage(&mut self, path: &Path, content: Option<String>) {
+        if !self.staged.contains_key(path) {
+            self.order.push(path.to_path_buf());
+        }
+        self.staged.insert(path.to_path_buf(), content);
+    }
+
+    /// One diff per changed file against what is on disk now. A moved file is
+    /// compared with its original.
+    pub fn diff(&self) -> Result<String> {
+        let on_disk = |path: &Path| -> Result<Option<String>> {
+            if path.is_file() {
+                FileOps::read_file(path).map(Some)
+            } else {
+                Ok(None)
+            }
+        };
+
+        let mut output = String::new();
+        for path in &self.order {
+            let display = path.display().to_string();
+            match &self.staged[path] {
+                None => {
+                    let moved_away = self.moved_from.values().any(|origin| origin == path);
+                    if !moved_away && path.is_file() {
+                        output.push_str(&format!("  ⎿  Deleted {display}\n"));
+                    }
+                }
+                Some(content) => {
+                    let (original, label) = match self.moved_from.get(path) {
+                        Some(origin) => (
+                            on_disk(origin)?,
+                            format!("{} → {display}", origin.display()),
+                        ),
+                        None => (on_disk(path)?, display),
+                    };
+                    if self.moved_from.contains_key(path)
+                        && original.as_deref() == Some(content.as_str())
+                    {
+                        output.push_str(&format!("  ⎿  Renamed {label}\n"));
+                    } else if original.as_deref() != Some(content.as_str()) {
+                        let diff_lines =
+                            DiffTools::generate_diff(original.as_deref().unwrap_or(""), content);
+                        output.push_str(&DiffTools::format_diff(&diff_lines, &label)?);
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+}
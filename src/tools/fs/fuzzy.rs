@@ -0,0 +1,333 @@
+use crate::tools::fs::search::SearchTools;
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+// Scoring follows fzf: every matched character scores, gaps between matches
+// cost, and characters at the start of a word, a path component or a
+// camelCase hump earn a bonus, so "fo" prefers "foo.rs" over "info.rs".
+const SCORE_MATCH: i64 = 16;
+const PENALTY_GAP_START: i64 = -3;
+const PENALTY_GAP_EXTENSION: i64 = -1;
+const BONUS_PATH_SEPARATOR: i64 = 10;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+/// The first query character's bonus counts this many times
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+/// Bonus when every match is in the file name rather than the directories
+const BONUS_FILE_NAME: i64 = 12;
+
+/// A path matching a fuzzy query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FuzzyMatch {
+    /// Path relative to the workspace root, with `/` separators
+    pub path: String,
+    pub score: i64,
+    /// Indices of the matched characters in `path`, for highlighting
+    pub positions: Vec<usize>,
+}
+
+/// Fuzzy matching of paths
+pub struct FuzzyMatcher;
+
+impl FuzzyMatcher {
+    /// Score `text` against `query`. Whitespace separates terms that must all
+    /// match. Matching ignores case unless the query has an uppercase letter.
+    pub fn score(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+        let case_sensitive = query.chars().any(char::is_uppercase);
+        let text_chars: Vec<char> = text.chars().collect();
+        let folded: Vec<char> = if case_sensitive {
+            text_chars.clone()
+        } else {
+            text_chars.iter().map(|c| fold_case(*c)).collect()
+        };
+        // Where the file name starts, to favour matches in it
+        let name_start = text_chars
+            .iter()
+            .rposition(|c| *c == '/')
+            .map_or(0, |i| i + 1);
+
+        let mut total = 0;
+        let mut positions = Vec::new();
+        for term in query.split_whitespace() {
+            let term: Vec<char> = if case_sensitive {
+                term.chars().collect()
+            } else {
+                term.chars().map(fold_case).collect()
+            };
+            let (score, term_positions) = score_term(&term, &text_chars, &folded)?;
+            total += score;
+            if term_positions.first().is_some_and(|p| *p >= name_start) {
+                total += BONUS_FILE_NAME;
+            }
+            positions.extend(term_positions);
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        Some((total, positions))
+    }
+
+    /// The best `limit` matches for `query` among `paths`, best first. Ties go
+    /// to shorter paths. An empty query lists the paths in order.
+    pub fn rank(query: &str, paths: &[String], limit: usize) -> Vec<FuzzyMatch> {
+        if query.trim().is_empty() {
+            return paths
+                .iter()
+                .take(limit)
+                .map(|path| FuzzyMatch {
+                    path: path.clone(),
+                    score: 0,
+                    positions: Vec::new(),
+                })
+                .collect();
+        }
+
+        let mut matches: Vec<FuzzyMatch> = paths
+            .par_iter()
+            .filter_map(|path| {
+                let (score, positions) = Self::score(query, path)?;
+                Some(FuzzyMatch {
+                    path: path.clone(),
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.path.len().cmp(&b.path.len()))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Bonus for a match at `index`, from the character before it
+fn position_bonus(text: &[char], index: usize) -> i64 {
+    let current = text[index];
+    let previous = match index.checked_sub(1) {
+        Some(i) => text[i],
+        None => return BONUS_BOUNDARY,
+    };
+    if previous == '/' || previous == '\\' {
+        BONUS_PATH_SEPARATOR
+    } else if matches!(previous, '_' | '-' | '.' | ' ') {
+        BONUS_BOUNDARY
+    } else if (previous.is_lowercase() && current.is_uppercase())
+        || (!previous.is_ascii_digit() && current.is_ascii_digit())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// Best alignment of `term` as a subsequence of `text`, by dynamic
+/// programming over (term char, text position) with affine gap penalties
+fn score_term(term: &[char], text: &[char], folded: &[char]) -> Option<(i64, Vec<usize>)> {
+    let n = term.len();
+    let m = text.len();
+    if n == 0 {
+        return Some((0, Vec::new()));
+    }
+    if n > m {
+        return None;
+    }
+
+    // Cheap subsequence check before the quadratic part
+    let mut next = 0;
+    for c in term {
+        next += folded[next..].iter().position(|t| t == c)? + 1;
+    }
+
+    const NONE: i64 = i64::MIN / 2;
+    // score[i][j]: best score with term[i] matched at text[j]
+    // from[i][j]: where term[i - 1] was matched in that alignment
+    let mut score = vec![vec![NONE; m]; n];
+    let mut from = vec![vec![usize::MAX; m]; n];
+    let bonuses: Vec<i64> = (0..m).map(|j| position_bonus(text, j)).collect();
+
+    for j in 0..m {
+        if folded[j] == term[0] {
+            score[0][j] = SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+        }
+    }
+
+    for i in 1..n {
+        // Best score of term[i - 1] matched at k < j - 1, less the gap to j
+        let mut gapped = NONE;
+        let mut gapped_from = usize::MAX;
+        for j in i..m {
+            if j >= 2 {
+                let extended = gapped + PENALTY_GAP_EXTENSION;
+                let started = score[i - 1][j - 2] + PENALTY_GAP_START;
+                if started >= extended {
+                    gapped = started;
+                    gapped_from = j - 2;
+                } else {
+                    gapped = extended;
+                }
+            }
+            if folded[j] != term[i] {
+                continue;
+            }
+
+            let adjacent = score[i - 1][j - 1] + BONUS_CONSECUTIVE;
+            let (best, best_from) = if adjacent >= gapped {
+                (adjacent, j - 1)
+            } else {
+                (gapped, gapped_from)
+            };
+            if best > NONE / 2 {
+                score[i][j] = best + SCORE_MATCH + bonuses[j];
+                from[i][j] = best_from;
+            }
+        }
+    }
+
+    let (end, best) = score[n - 1]
+        .iter()
+        .enumerate()
+        .max_by_key(|(j, s)| (**s, std::cmp::Reverse(*j)))?;
+    if *best <= NONE / 2 {
+        return None;
+    }
+
+    let mut positions = vec![0; n];
+    let mut j = end;
+    for i in (0..n).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+    Some((*best, positions))
+}
+
+/// Files that change which paths the workspace walk lists without touching
+/// the modification time of any directory it walked
+const IGNORE_FILES: [&str; 5] = [
+    ".gitignore",
+    ".ignore",
+    ".npmignore",
+    ".dockerignore",
+    ".git/info/exclude",
+];
+
+/// A workspace's file list with the modification times of its directories,
+/// which change whenever a file is added, removed or renamed in them, and of
+/// the ignore files that decide what's listed
+struct FileList {
+    files: Arc<Vec<String>>,
+    watched: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl FileList {
+    fn build(root: &Path) -> Self {
+        let (files, dirs) = SearchTools::workspace_entries(root);
+        // Ignore files are hidden, so the walk doesn't list them. Creating one
+        // changes its directory's time, but editing one only changes its own.
+        let mut watched: Vec<PathBuf> = dirs
+            .iter()
+            .chain(std::iter::once(&root.to_path_buf()))
+            .flat_map(|dir| IGNORE_FILES.iter().map(move |name| dir.join(name)))
+            .filter(|path| path.exists())
+            .collect();
+        watched.extend(dirs);
+        // Ignore files above the workspace apply to it too, watched whether
+        // or not they exist yet
+        for ancestor in root.ancestors().skip(1) {
+            watched.extend(IGNORE_FILES.iter().map(|name| ancestor.join(name)));
+        }
+        watched.push(root.join(".git/info/exclude"));
+
+        let mut files: Vec<String> = files
+            .iter()
+            .filter_map(|path| path.strip_prefix(root).ok())
+            .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+            .collect();
+        files.sort();
+        let watched = watched
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect();
+        Self {
+            files: Arc::new(files),
+            watched,
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.watched.par_iter().any(|(path, modified)| {
+            std::fs::metadata(path).and_then(|m| m.modified()).ok() != *modified
+        })
+    }
+}
+
+fn file_lists() -> &'static Mutex<HashMap<PathBuf, Arc<FileList>>> {
+    static FILE_LISTS: OnceLock<Mutex<HashMap<PathBuf, Arc<FileList>>>> = OnceLock::new();
+    FILE_LISTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Finds files by approximate name, for `@` autocomplete and the FindFile
+/// tool. Each workspace's file list is cached and rebuilt when files are
+/// added, removed or renamed.
+pub struct FileFinder;
+
+impl FileFinder {
+    /// The best `limit` matches for `query` among the files under `root`
+    pub fn find(root: &Path, query: &str, limit: usize) -> Result<Vec<FuzzyMatch>> {
+        let files = Self::files(root)?;
+        Ok(FuzzyMatcher::rank(query, &files, limit))
+    }
+
+    /// Every file under `root`, relative to it and sorted
+    pub fn files(root: &Path) -> Result<Arc<Vec<String>>> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve directory {}", root.display()))?;
+        if !root.is_dir() {
+            return Err(anyhow!("{} is not a directory", root.display()));
+        }
+
+        let cached = file_lists()
+            .lock()
+            .map_err(|_| anyhow!("File list cache lock poisoned"))?
+            .get(&root)
+            .cloned();
+        if let Some(list) = cached {
+            if !list.is_stale() {
+                return Ok(list.files.clone());
+            }
+        }
+
+        // Build outside the lock, so other workspaces aren't held up
+        let list = Arc::new(FileList::build(&root));
+        let files = list.files.clone();
+        file_lists()
+            .lock()
+            .map_err(|_| anyhow!("File list cache lock poisoned"))?
+            .insert(root, list);
+        Ok(files)
+    }
+
+    /// Drop the cached file list of `root`
+    pub fn invalidate(root: &Path) {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        if let Ok(mut lists) = file_lists().lock() {
+            lists.remove(&root);
+        }
+    }
+}
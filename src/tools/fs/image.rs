@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::apis::api_client::ContentBlock;
+
+/// Largest image we pass to a model; providers reject anything much bigger
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+pub struct ImageOps;
+
+impl ImageOps {
+    /// Detects PNG, JPEG, GIF and WebP images from their magic bytes
+    pub fn detect_media_type(bytes: &[u8]) -> Option<&'static str> {
+        if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some("image/png")
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some("image/jpeg")
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some("image/gif")
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some("image/webp")
+        } else {
+            None
+        }
+    }
+
+    /// Reads a file as an image content block, or returns `None` if it isn't an image
+    pub fn read_image(path: &Path) -> Result<Option<ContentBlock>> {
+        // Only the header is needed to tell whether this is an image at all
+        let mut header = [0u8; 12];
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let header_len = file
+            .read(&mut header)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+        if Self::detect_media_type(&header[..header_len]).is_none() {
+            return Ok(None);
+        }
+
+        let bytes =
+            fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+        Self::image_from_bytes(&bytes)
+            .map(Some)
+            .with_context(|| format!("Cannot attach image {}", path.display()))
+    }
+
+    /// Builds an image content block from raw bytes, validating type and size
+    pub fn image_from_bytes(bytes: &[u8]) -> Result<ContentBlock> {
+        let media_type = Self::detect_media_type(bytes)
+            .ok_or_else(|| anyhow!("Unsupported image format (expected PNG, JPEG, GIF or WebP)"))?;
+
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(anyhow!(
+                "Image is {} bytes, larger than the {} byte limit",
+                bytes.len(),
+                MAX_IMAGE_BYTES
+            ));
+        }
+
+        Ok(ContentBlock::Image {
+            media_type: media_type.to_string(),
+            data: BASE64.encode(bytes),
+        })
+    }
+
+    /// Builds an image content block from base64 data, e.g. an image attached over RPC
+    pub fn image_from_base64(data: &str) -> Result<ContentBlock> {
+        // Accept data URIs as well as bare base64
+        let encoded = match data.split_once(";base64,") {
+            Some((prefix, encoded)) if prefix.starts_with("data:") => encoded,
+            _ => data,
+        };
+
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .context("Image data is not valid base64")?;
+        Self::image_from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,1062 @@
+use crate::tools::fs::search::SearchTools;
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+use regex_syntax::hir::{Class, Hir, HirKind};
+use regex_syntax::ParserBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Files larger than this aren't indexed; grep always scans them
+pub const MAX_INDEXED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Minimum time between writes of a changed index to disk
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Largest set of alternative strings tracked while analysing a regex before
+/// falling back to a looser query
+const MAX_EXACT_STRINGS: usize = 16;
+
+const INDEX_MAGIC: &[u8; 8] = b"OLITRI01";
+
+/// Three bytes packed into the low 24 bits
+pub type Trigram = u32;
+
+/// The distinct trigrams of `bytes`, sorted. ASCII letters are lowercased so
+/// one index serves case-sensitive and case-insensitive searches.
+pub fn trigrams_of(bytes: &[u8]) -> Vec<Trigram> {
+    let mut trigrams: Vec<Trigram> = bytes
+        .windows(3)
+        .map(|w| {
+            (w[0].to_ascii_lowercase() as u32) << 16
+                | (w[1].to_ascii_lowercase() as u32) << 8
+                | w[2].to_ascii_lowercase() as u32
+        })
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+/// Trigrams a file must contain for a regex to possibly match in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrigramQuery {
+    /// No constraint; every file is a candidate
+    All,
+    /// Every one of these trigrams
+    Trigrams(Vec<Trigram>),
+    And(Vec<TrigramQuery>),
+    Or(Vec<TrigramQuery>),
+}
+
+impl TrigramQuery {
+    /// Query for a regex, as `grep_search_with` compiles it. Patterns that
+    /// can't be analysed match every file.
+    pub fn from_regex(pattern: &str, case_insensitive: bool) -> Self {
+        let hir = ParserBuilder::new()
+            .case_insensitive(case_insensitive)
+            .build()
+            .parse(pattern);
+        match hir {
+            Ok(hir) => analyze(&hir).into_query(),
+            Err(_) => TrigramQuery::All,
+        }
+    }
+
+    /// Files containing `text` as a substring
+    fn for_string(text: &[u8]) -> Self {
+        if text.len() < 3 {
+            TrigramQuery::All
+        } else {
+            TrigramQuery::Trigrams(trigrams_of(text))
+        }
+    }
+
+    /// Files containing at least one of `strings`
+    fn any_of(strings: &BTreeSet<Vec<u8>>) -> Self {
+        Self::or(strings.iter().map(|s| Self::for_string(s)).collect())
+    }
+
+    fn and(a: Self, b: Self) -> Self {
+        match (a, b) {
+            (TrigramQuery::All, q) | (q, TrigramQuery::All) => q,
+            (TrigramQuery::Trigrams(mut a), TrigramQuery::Trigrams(b)) => {
+                a.extend(b);
+                a.sort_unstable();
+                a.dedup();
+                TrigramQuery::Trigrams(a)
+            }
+            (TrigramQuery::And(mut a), TrigramQuery::And(b)) => {
+                a.extend(b);
+                TrigramQuery::And(a)
+            }
+            (TrigramQuery::And(mut a), q) | (q, TrigramQuery::And(mut a)) => {
+                a.push(q);
+                TrigramQuery::And(a)
+            }
+            (a, b) => TrigramQuery::And(vec![a, b]),
+        }
+    }
+
+    fn or(queries: Vec<Self>) -> Self {
+        if queries.is_empty() || queries.contains(&TrigramQuery::All) {
+            return TrigramQuery::All;
+        }
+        let mut queries = queries;
+        if queries.len() == 1 {
+            return queries.remove(0);
+        }
+        TrigramQuery::Or(queries)
+    }
+}
+
+/// What is known about the strings a regex (or part of one) can match: either
+/// a small set of exact strings, or a trigram query they all satisfy
+struct RegexInfo {
+    exact: Option<BTreeSet<Vec<u8>>>,
+    query: TrigramQuery,
+}
+
+impl RegexInfo {
+    fn exact(strings: BTreeSet<Vec<u8>>) -> Self {
+        Self {
+            exact: Some(strings),
+            query: TrigramQuery::All,
+        }
+    }
+
+    fn empty_string() -> Self {
+        Self::exact(BTreeSet::from([Vec::new()]))
+    }
+
+    fn anything() -> Self {
+        Self {
+            exact: None,
+            query: TrigramQuery::All,
+        }
+    }
+
+    fn into_query(self) -> TrigramQuery {
+        match self.exact {
+            Some(strings) => TrigramQuery::any_of(&strings),
+            None => self.query,
+        }
+    }
+}
+
+fn analyze(hir: &Hir) -> RegexInfo {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => RegexInfo::empty_string(),
+        HirKind::Literal(literal) => {
+            RegexInfo::exact(BTreeSet::from([literal.0.to_ascii_lowercase()]))
+        }
+        HirKind::Class(class) => match class_strings(class) {
+            Some(strings) => RegexInfo::exact(strings),
+            None => RegexInfo::anything(),
+        },
+        HirKind::Capture(capture) => analyze(&capture.sub),
+        HirKind::Repetition(repetition) => {
+            let sub = analyze(&repetition.sub);
+            match (repetition.min, repetition.max) {
+                // x? is either nothing or x
+                (0, Some(1)) => match sub.exact {
+                    Some(mut strings) if strings.len() < MAX_EXACT_STRINGS => {
+                        strings.insert(Vec::new());
+                        RegexInfo::exact(strings)
+                    }
+                    _ => RegexInfo::anything(),
+                },
+                (0, _) => RegexInfo::anything(),
+                (1, Some(1)) => sub,
+                // x+ and x{n,m} contain x at least once
+                _ => RegexInfo {
+                    exact: None,
+                    query: sub.into_query(),
+                },
+            }
+        }
+        HirKind::Concat(subs) => {
+            let mut query = TrigramQuery::All;
+            let mut current = BTreeSet::from([Vec::new()]);
+            let mut all_exact = true;
+
+            for sub in subs {
+                let info = analyze(sub);
+                match info.exact {
+                    Some(strings) if current.len() * strings.len() <= MAX_EXACT_STRINGS => {
+                        current = current
+                            .iter()
+                            .flat_map(|prefix| {
+                                strings.iter().map(move |suffix| {
+                                    let mut joined = prefix.clone();
+                                    joined.extend_from_slice(suffix);
+                                    joined
+                                })
+                            })
+                            .collect();
+                    }
+                    // Too many combinations: require what we have so far and
+                    // start again from this piece
+                    Some(strings) => {
+                        query = TrigramQuery::and(query, TrigramQuery::any_of(&current));
+                        current = strings;
+                        all_exact = false;
+                    }
+                    None => {
+                        query = TrigramQuery::and(query, TrigramQuery::any_of(&current));
+                        query = TrigramQuery::and(query, info.query);
+                        current = BTreeSet::from([Vec::new()]);
+                        all_exact = false;
+                    }
+                }
+            }
+
+            if all_exact {
+                RegexInfo::exact(current)
+            } else {
+                RegexInfo {
+                    exact: None,
+                    query: TrigramQuery::and(query, TrigramQuery::any_of(&current)),
+                }
+            }
+        }
+        HirKind::Alternation(subs) => {
+            let infos: Vec<RegexInfo> = subs.iter().map(analyze).collect();
+            if infos.iter().all(|info| info.exact.is_some()) {
+                let strings: BTreeSet<Vec<u8>> = infos
+                    .iter()
+                    .flat_map(|info| info.exact.iter().flatten().cloned())
+                    .collect();
+                if strings.len() <= MAX_EXACT_STRINGS {
+                    return RegexInfo::exact(strings);
+                }
+            }
+            RegexInfo {
+                exact: None,
+                query: TrigramQuery::or(infos.into_iter().map(RegexInfo::into_query).collect()),
+            }
+        }
+    }
+}
+
+/// The lowercased strings a small character class can match, e.g. `[Ii]` from
+/// a case-insensitive pattern. Large classes like `\w` or `.` give `None`.
+fn class_strings(class: &Class) -> Option<BTreeSet<Vec<u8>>> {
+    let mut strings = BTreeSet::new();
+    match class {
+        Class::Unicode(class) => {
+            let size: u32 = class
+                .ranges()
+                .iter()
+                .map(|r| r.end() as u32 - r.start() as u32 + 1)
+                .sum();
+            if size as usize > MAX_EXACT_STRINGS {
+                return None;
+            }
+            for range in class.ranges() {
+                for c in range.start()..=range.end() {
+                    let mut buf = [0; 4];
+                    strings.insert(c.encode_utf8(&mut buf).as_bytes().to_ascii_lowercase());
+                }
+            }
+        }
+        Class::Bytes(class) => {
+            let size: usize = class
+                .ranges()
+                .iter()
+                .map(|r| (r.end() - r.start()) as usize + 1)
+                .sum();
+            if size > MAX_EXACT_STRINGS {
+                return None;
+            }
+            for range in class.ranges() {
+                for b in range.start()..=range.end() {
+                    strings.insert(vec![b.to_ascii_lowercase()]);
+                }
+            }
+        }
+    }
+    Some(strings)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexedFile {
+    /// Path relative to the index root
+    path: PathBuf,
+    /// Modification time in nanoseconds since the Unix epoch
+    mtime: u64,
+    size: u64,
+    /// False once the file was deleted or re-indexed under a new id
+    live: bool,
+}
+
+/// A file found by a walk, with what decides whether it changed
+struct FileStat {
+    rel: PathBuf,
+    mtime: u64,
+    size: u64,
+}
+
+/// Changes applied by [`TrigramIndex::update`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+}
+
+impl IndexUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.modified == 0 && self.removed == 0
+    }
+}
+
+/// Maps every trigram to the files containing it, so a grep only reads the
+/// files that can possibly match.
+///
+/// Files are numbered in the order they were indexed. A changed file is
+/// indexed again under a new number and its old number is marked dead, which
+/// keeps every posting list sorted by appending; dead entries are dropped by
+/// [`TrigramIndex::compact`].
+#[derive(Debug, Clone)]
+pub struct TrigramIndex {
+    root: PathBuf,
+    files: Vec<IndexedFile>,
+    by_path: HashMap<PathBuf, u32>,
+    postings: HashMap<Trigram, Vec<u32>>,
+    dead: usize,
+}
+
+impl TrigramIndex {
+    /// An empty index for the workspace at `root`
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            by_path: HashMap::new(),
+            postings: HashMap::new(),
+            dead: 0,
+        }
+    }
+
+    /// Index every searchable file under `root`
+    pub fn build(root: &Path) -> Self {
+        let mut index = Self::new(root);
+        let files = SearchTools::grep_candidate_files(root);
+        index.update(root, &files);
+        index
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of indexed files
+    pub fn file_count(&self) -> usize {
+        self.by_path.len()
+    }
+
+    /// Number of distinct trigrams
+    pub fn trigram_count(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Whether `path` (absolute, or relative to the root) is indexed
+    pub fn contains(&self, path: &Path) -> bool {
+        self.relative_path(path)
+            .is_some_and(|rel| self.by_path.contains_key(&rel))
+    }
+
+    fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        if path.is_absolute() {
+            path.strip_prefix(&self.root).ok().map(Path::to_path_buf)
+        } else {
+            Some(path.to_path_buf())
+        }
+    }
+
+    /// Bring the index up to date with `files`, the absolute paths of every
+    /// file currently under the directory `scope`. New files and files whose
+    /// modification time or size changed are read again, and indexed files
+    /// under `scope` that are no longer listed are removed.
+    pub fn update(&mut self, scope: &Path, files: &[PathBuf]) -> IndexUpdate {
+        let root = self.root.clone();
+        let scope = scope.strip_prefix(&root).unwrap_or(Path::new(""));
+
+        // Stat in parallel; on a big repository this is most of the work
+        let stats: Vec<FileStat> = files
+            .par_iter()
+            .filter(|path| Self::is_indexable(path))
+            .filter_map(|path| {
+                let rel = path.strip_prefix(&root).ok()?.to_path_buf();
+                let metadata = std::fs::metadata(path).ok()?;
+                if metadata.len() > MAX_INDEXED_FILE_SIZE {
+                    return None;
+                }
+                let mtime = metadata
+                    .modified()
+                    .ok()?
+                    .duration_since(UNIX_EPOCH)
+                    .ok()?
+                    .as_nanos() as u64;
+                Some(FileStat {
+                    rel,
+                    mtime,
+                    size: metadata.len(),
+                })
+            })
+            .collect();
+
+        let mut result = IndexUpdate::default();
+
+        // Files that are gone, or no longer indexable (e.g. grown too large)
+        let present: HashSet<&Path> = stats.iter().map(|stat| stat.rel.as_path()).collect();
+        let removed: Vec<PathBuf> = self
+            .by_path
+            .keys()
+            .filter(|rel| rel.starts_with(scope) && !present.contains(rel.as_path()))
+            .cloned()
+            .collect();
+        for rel in removed {
+            self.remove(&rel);
+            result.removed += 1;
+        }
+
+        let changed: Vec<&FileStat> = stats
+            .iter()
+            .filter(|stat| match self.by_path.get(&stat.rel) {
+                Some(&id) => {
+                    let file = &self.files[id as usize];
+                    file.mtime != stat.mtime || file.size != stat.size
+                }
+                None => true,
+            })
+            .collect();
+
+        // Read and split the changed files in parallel, then insert in order
+        let contents: Vec<(&FileStat, Vec<Trigram>)> = changed
+            .into_par_iter()
+            .filter_map(|entry| {
+                let mut content = Vec::new();
+                File::open(root.join(&entry.rel))
+                    .and_then(|mut file| file.read_to_end(&mut content))
+                    .ok()?;
+                Some((entry, trigrams_of(&content)))
+            })
+            .collect();
+
+        for (stat, trigrams) in contents {
+            if self.remove(&stat.rel) {
+                result.modified += 1;
+            } else {
+                result.added += 1;
+            }
+            self.insert(stat.rel.clone(), stat.mtime, stat.size, trigrams);
+        }
+
+        // Dead entries slow every query down, so drop them once they pile up
+        if self.dead > 1024 && self.dead > self.by_path.len() / 4 {
+            self.compact();
+        }
+
+        result
+    }
+
+    /// Whether a file should be indexed at all, using the same rules as
+    /// `SearchTools::is_ignored_path`
+    fn is_indexable(path: &Path) -> bool {
+        path.to_str().is_some()
+            && !SearchTools::is_ignored_path(path)
+            && !SearchTools::is_likely_binary_or_generated(path)
+    }
+
+    fn insert(&mut self, rel: PathBuf, mtime: u64, size: u64, trigrams: Vec<Trigram>) {
+        let id = self.files.len() as u32;
+        for trigram in trigrams {
+            self.postings.entry(trigram).or_default().push(id);
+        }
+        self.by_path.insert(rel.clone(), id);
+        self.files.push(IndexedFile {
+            path: rel,
+            mtime,
+            size,
+            live: true,
+        });
+    }
+
+    fn remove(&mut self, rel: &Path) -> bool {
+        match self.by_path.remove(rel) {
+            Some(id) => {
+                self.files[id as usize].live = false;
+                self.dead += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Renumber the live files and drop dead ones from every posting list
+    pub fn compact(&mut self) {
+        if self.dead == 0 {
+            return;
+        }
+
+        let mut remap = vec![u32::MAX; self.files.len()];
+        let mut files = Vec::with_capacity(self.by_path.len());
+        for (old_id, file) in std::mem::take(&mut self.files).into_iter().enumerate() {
+            if file.live {
+                remap[old_id] = files.len() as u32;
+                files.push(file);
+            }
+        }
+
+        self.postings.retain(|_, ids| {
+            ids.retain_mut(|id| {
+                *id = remap[*id as usize];
+                *id != u32::MAX
+            });
+            !ids.is_empty()
+        });
+        self.by_path = files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| (file.path.clone(), id as u32))
+            .collect();
+        self.files = files;
+        self.dead = 0;
+    }
+
+    /// Ids of the files that may match, sorted, or `None` for every file
+    fn evaluate(&self, query: &TrigramQuery) -> Option<Vec<u32>> {
+        match query {
+            TrigramQuery::All => None,
+            TrigramQuery::Trigrams(trigrams) => {
+                let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(trigrams.len());
+                for trigram in trigrams {
+                    match self.postings.get(trigram) {
+                        Some(ids) => lists.push(ids),
+                        None => return Some(Vec::new()),
+                    }
+                }
+                // Start from the rarest trigram so the intersection stays small
+                lists.sort_by_key(|ids| ids.len());
+                let (first, rest) = lists.split_first()?;
+                let mut result = first.to_vec();
+                for ids in rest {
+                    result = intersect(&result, ids);
+                }
+                Some(result)
+            }
+            TrigramQuery::And(queries) => {
+                let mut result: Option<Vec<u32>> = None;
+                for query in queries {
+                    if let Some(ids) = self.evaluate(query) {
+                        result = Some(match result {
+                            Some(current) => intersect(&current, &ids),
+                            None => ids,
+                        });
+                    }
+                }
+                result
+            }
+            TrigramQuery::Or(queries) => {
+                let mut result = Vec::new();
+                for query in queries {
+                    result = union(&result, &self.evaluate(query)?);
+                }
+                Some(result)
+            }
+        }
+    }
+
+    /// The files among `files` (absolute paths) that may contain a match for
+    /// `query`. Files the index doesn't cover are always kept.
+    pub fn candidates(&self, query: &TrigramQuery, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        let ids = match self.evaluate(query) {
+            Some(ids) => ids,
+            None => return files,
+        };
+        files
+            .into_iter()
+            .filter(|path| {
+                let id = path
+                    .strip_prefix(&self.root)
+                    .ok()
+                    .and_then(|rel| self.by_path.get(rel));
+                match id {
+                    Some(id) => ids.binary_search(id).is_ok(),
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Write the index to `path`, replacing any previous one atomically
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.compact();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        let mut out = BufWriter::new(file);
+
+        out.write_all(INDEX_MAGIC)?;
+        write_bytes(&mut out, self.root.to_string_lossy().as_bytes())?;
+
+        out.write_all(&(self.files.len() as u32).to_le_bytes())?;
+        for file in &self.files {
+            write_bytes(&mut out, file.path.to_string_lossy().as_bytes())?;
+            out.write_all(&file.mtime.to_le_bytes())?;
+            out.write_all(&file.size.to_le_bytes())?;
+        }
+
+        out.write_all(&(self.postings.len() as u32).to_le_bytes())?;
+        for (trigram, ids) in &self.postings {
+            out.write_all(&trigram.to_le_bytes())?;
+            out.write_all(&(ids.len() as u32).to_le_bytes())?;
+            // Ids are sorted, so store the gaps as varints
+            let mut previous = 0;
+            for &id in ids {
+                write_varint(&mut out, id - previous)?;
+                previous = id;
+            }
+        }
+
+        out.flush()?;
+        drop(out);
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Read an index written by [`TrigramIndex::save`]. Fails if the file is
+    /// corrupt or belongs to a different root.
+    pub fn load(path: &Path, root: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut input = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(anyhow!("{} is not a search index", path.display()));
+        }
+        let stored_root = PathBuf::from(String::from_utf8(read_bytes(&mut input)?)?);
+        if stored_root != root {
+            return Err(anyhow!(
+                "Search index {} belongs to {}",
+                path.display(),
+                stored_root.display()
+            ));
+        }
+
+        let mut index = Self::new(root);
+        let file_count = read_u32(&mut input)?;
+        for _ in 0..file_count {
+            let rel = PathBuf::from(String::from_utf8(read_bytes(&mut input)?)?);
+            let mtime = read_u64(&mut input)?;
+            let size = read_u64(&mut input)?;
+            index.by_path.insert(rel.clone(), index.files.len() as u32);
+            index.files.push(IndexedFile {
+                path: rel,
+                mtime,
+                size,
+                live: true,
+            });
+        }
+
+        let posting_count = read_u32(&mut input)?;
+        for _ in 0..posting_count {
+            let trigram = read_u32(&mut input)?;
+            let len = read_u32(&mut input)? as usize;
+            let mut ids = Vec::with_capacity(len);
+            let mut previous = 0u32;
+            for _ in 0..len {
+                previous = previous
+                    .checked_add(read_varint(&mut input)?)
+                    .filter(|id| *id < file_count)
+                    .ok_or_else(|| anyhow!("Corrupt search index {}", path.display()))?;
+                ids.push(previous);
+            }
+            index.postings.insert(trigram, ids);
+        }
+
+        Ok(index)
+    }
+}
+
+/// Intersection of two sorted id lists
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Union of two sorted id lists
+fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                result.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                result.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+    result
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> Result<()> {
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(bytes)?;
+    Ok(())
+}
+
+fn write_varint(out: &mut impl Write, mut value: u32) -> Result<()> {
+    while value >= 0x80 {
+        out.write_all(&[(value as u8) | 0x80])?;
+        value >>= 7;
+    }
+    out.write_all(&[value as u8])?;
+    Ok(())
+}
+
+fn read_u32(input: &mut impl Read) -> Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input: &mut impl Read) -> Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(input: &mut impl Read) -> Result<Vec<u8>> {
+    let len = read_u32(input)? as usize;
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_varint(input: &mut impl Read) -> Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let mut byte = [0; 1];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u32) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("Corrupt varint in search index"))
+}
+
+/// State of a workspace's index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub root: PathBuf,
+    /// "building" or "ready"
+    pub state: String,
+    pub files: usize,
+    pub trigrams: usize,
+}
+
+enum IndexState {
+    Building,
+    Ready(Box<TrigramIndex>),
+}
+
+struct WorkspaceIndex {
+    state: IndexState,
+    /// Whether there are changes not yet written to disk
+    dirty: bool,
+    last_saved: Instant,
+}
+
+type Workspaces = Mutex<HashMap<PathBuf, Arc<Mutex<WorkspaceIndex>>>>;
+
+fn workspaces() -> &'static Workspaces {
+    static WORKSPACES: OnceLock<Workspaces> = OnceLock::new();
+    WORKSPACES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Optional per-workspace trigram indexes that speed up repeated greps in
+/// large repositories. Off until enabled for a workspace; the index is built
+/// in the background and kept on disk between sessions.
+pub struct SearchIndex;
+
+impl SearchIndex {
+    /// Directory the indexes are stored in: `$OLI_INDEX_DIR` if set, else
+    /// oli's folder in the user cache directory
+    pub fn cache_dir() -> Option<PathBuf> {
+        match std::env::var_os("OLI_INDEX_DIR") {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => dirs::cache_dir().map(|dir| dir.join("oli").join("index")),
+        }
+    }
+
+    /// Where the index for `root` is stored
+    pub fn index_path(root: &Path) -> Option<PathBuf> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        root.hash(&mut hasher);
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "root".to_string());
+        Self::cache_dir().map(|dir| dir.join(format!("{name}-{:016x}.idx", hasher.finish())))
+    }
+
+    /// Turn the index on for the workspace at `root`, loading it from disk or
+    /// building it in the background
+    pub fn enable(root: &Path) -> Result<IndexStatus> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve workspace {}", root.display()))?;
+
+        let entry = {
+            let mut workspaces = workspaces()
+                .lock()
+                .map_err(|_| anyhow!("Search index lock poisoned"))?;
+            if let Some(entry) = workspaces.get(&root) {
+                return Ok(Self::describe(&root, entry));
+            }
+            let entry = Arc::new(Mutex::new(WorkspaceIndex {
+                state: IndexState::Building,
+                dirty: false,
+                last_saved: Instant::now(),
+            }));
+            workspaces.insert(root.clone(), entry.clone());
+            entry
+        };
+
+        let build_root = root.clone();
+        let build_entry = entry.clone();
+        std::thread::spawn(move || {
+            let index_path = Self::index_path(&build_root);
+
+            // Reuse the saved index when there is one, so only files changed
+            // since the last session are read
+            let mut index = index_path
+                .as_deref()
+                .and_then(|path| TrigramIndex::load(path, &build_root).ok())
+                .unwrap_or_else(|| TrigramIndex::new(&build_root));
+            let files = SearchTools::grep_candidate_files(&build_root);
+            let update = index.update(&build_root, &files);
+
+            let saved = match index_path {
+                Some(path) if !update.is_empty() => index.save(&path),
+                _ => Ok(()),
+            };
+
+            if let Ok(mut workspace) = build_entry.lock() {
+                workspace.state = IndexState::Ready(Box::new(index));
+                workspace.dirty = saved.is_err();
+                workspace.last_saved = Instant::now();
+            }
+        });
+
+        Ok(Self::describe(&root, &entry))
+    }
+
+    /// Turn the index off for `root`, saving any pending changes
+    pub fn disable(root: &Path) {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let entry = workspaces()
+            .lock()
+            .ok()
+            .and_then(|mut workspaces| workspaces.remove(&root));
+        if let Some(entry) = entry {
+            Self::save_entry(&root, &entry);
+        }
+    }
+
+    /// Status of every enabled workspace
+    pub fn status() -> Vec<IndexStatus> {
+        let entries: Vec<(PathBuf, Arc<Mutex<WorkspaceIndex>>)> = match workspaces().lock() {
+            Ok(workspaces) => workspaces
+                .iter()
+                .map(|(root, entry)| (root.clone(), entry.clone()))
+                .collect(),
+            Err(_) => return Vec::new(),
+        };
+        entries
+            .iter()
+            .map(|(root, entry)| Self::describe(root, entry))
+            .collect()
+    }
+
+    fn describe(root: &Path, entry: &Mutex<WorkspaceIndex>) -> IndexStatus {
+        let mut status = IndexStatus {
+            root: root.to_path_buf(),
+            state: "building".to_string(),
+            files: 0,
+            trigrams: 0,
+        };
+        if let Ok(workspace) = entry.lock() {
+            match &workspace.state {
+                IndexState::Building => {}
+                IndexState::Ready(index) => {
+                    status.state = "ready".to_string();
+                    status.files = index.file_count();
+                    status.trigrams = index.trigram_count();
+                }
+            }
+        }
+        status
+    }
+
+    /// Wait up to `timeout` for the index of `root` to finish building
+    pub fn wait_until_ready(root: &Path, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let ready = Self::entry_for(root).is_some_and(|(_, entry)| {
+                entry
+                    .lock()
+                    .is_ok_and(|w| matches!(w.state, IndexState::Ready(_)))
+            });
+            if ready || Instant::now() >= deadline {
+                return ready;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// The enabled workspace containing `dir`, preferring the innermost one
+    fn entry_for(dir: &Path) -> Option<(PathBuf, Arc<Mutex<WorkspaceIndex>>)> {
+        let dir = dir.canonicalize().ok()?;
+        let workspaces = workspaces().lock().ok()?;
+        workspaces
+            .iter()
+            .filter(|(root, _)| dir.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map(|(root, entry)| (root.clone(), entry.clone()))
+    }
+
+    /// Narrow `files`, every file a grep under `dir` would read, to those that
+    /// may match `pattern`. The index is first brought up to date with the
+    /// files' modification times. Without a ready index `files` is returned
+    /// unchanged.
+    pub fn prefilter(
+        dir: &Path,
+        pattern: &str,
+        case_insensitive: bool,
+        files: Vec<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let Some((root, entry)) = Self::entry_for(dir) else {
+            return files;
+        };
+        let Ok(canonical_dir) = dir.canonicalize() else {
+            return files;
+        };
+
+        // The index stores paths relative to the canonical root, while the
+        // walk yields paths under `dir` as given
+        let absolute: Vec<PathBuf> = files
+            .iter()
+            .map(|path| match path.strip_prefix(dir) {
+                Ok(rel) => canonical_dir.join(rel),
+                Err(_) => path.clone(),
+            })
+            .collect();
+
+        let keep = {
+            let Ok(mut workspace) = entry.lock() else {
+                return files;
+            };
+            let IndexState::Ready(index) = &mut workspace.state else {
+                return files;
+            };
+
+            let update = index.update(&canonical_dir, &absolute);
+            let query = TrigramQuery::from_regex(pattern, case_insensitive);
+            let keep: HashSet<PathBuf> = index
+                .candidates(&query, absolute.clone())
+                .into_iter()
+                .collect();
+
+            if !update.is_empty() {
+                workspace.dirty = true;
+            }
+            keep
+        };
+
+        if entry
+            .lock()
+            .is_ok_and(|w| w.dirty && w.last_saved.elapsed() >= SAVE_INTERVAL)
+        {
+            Self::save_entry(&root, &entry);
+        }
+
+        files
+            .into_iter()
+            .zip(absolute)
+            .filter(|(_, absolute)| keep.contains(absolute))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    fn save_entry(root: &Path, entry: &Mutex<WorkspaceIndex>) {
+        let Some(path) = Self::index_path(root) else {
+            return;
+        };
+        if let Ok(mut workspace) = entry.lock() {
+            if !workspace.dirty {
+                return;
+            }
+            if let IndexState::Ready(index) = &mut workspace.state {
+                if index.save(&path).is_ok() {
+                    workspace.dirty = false;
+                }
+            }
+            workspace.last_saved = Instant::now();
+        }
+    }
+
+    /// Write every changed index to disk, when the session ends
+    pub fn flush_all() {
+        let entries: Vec<(PathBuf, Arc<Mutex<WorkspaceIndex>>)> = match workspaces().lock() {
+            Ok(workspaces) => workspaces
+                .iter()
+                .map(|(root, entry)| (root.clone(), entry.clone()))
+                .collect(),
+            Err(_) => return,
+        };
+        for (root, entry) in entries {
+            Self::save_entry(&root, &entry);
+        }
+    }
+}
@@ -1,3 +1,8 @@
 pub mod diff;
 pub mod file_ops;
+pub mod fuzzy;
+pub mod image;
+pub mod index;
+pub mod patch;
+pub mod repo_map;
 pub mod search;
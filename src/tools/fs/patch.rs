@@ -0,0 +1,808 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+
+use super::diff::DiffTools;
+use super::file_ops::{ChangeSet, FileOps};
+
+/// Default number of context lines that may be ignored at each end of a hunk,
+/// the same default as GNU patch
+pub const DEFAULT_FUZZ: usize = 2;
+
+/// One line of a hunk body
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A single `@@ -a,b +c,d @@` section of a unified diff
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    /// 1-based first line in the original file (0 when the header had no numbers)
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<HunkLine>,
+    /// Set by a "\ No newline at end of file" marker after an added or context line
+    pub no_newline_at_end: bool,
+}
+
+impl Hunk {
+    fn header(&self) -> String {
+        format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )
+    }
+}
+
+/// The changes to one file within a patch
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    /// Path before the change, `None` when the file is created
+    pub old_path: Option<String>,
+    /// Path after the change, `None` when the file is deleted
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn operation(&self) -> PatchOperation {
+        match (&self.old_path, &self.new_path) {
+            (None, _) => PatchOperation::Create,
+            (_, None) => PatchOperation::Delete,
+            (Some(old), Some(new)) if old != new => PatchOperation::Rename { from: old.clone() },
+            _ => PatchOperation::Modify,
+        }
+    }
+
+    /// The path the file ends up at (or the deleted path)
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PatchOperation {
+    Create,
+    Delete,
+    Rename { from: String },
+    Modify,
+}
+
+/// Outcome of applying one hunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HunkReport {
+    pub header: String,
+    pub applied: bool,
+    /// 1-based line in the original file where the hunk matched
+    pub line: Option<usize>,
+    /// Distance in lines from the position given in the hunk header
+    pub offset: isize,
+    /// Number of context lines ignored at each end to make the hunk match
+    pub fuzz: usize,
+}
+
+/// Outcome of applying one file's changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileReport {
+    pub path: String,
+    pub operation: PatchOperation,
+    pub hunks: Vec<HunkReport>,
+    /// Problem with the file itself, e.g. it is missing or already exists
+    pub error: Option<String>,
+    /// Formatted diff of the resulting change
+    #[serde(skip)]
+    pub diff: Option<String>,
+}
+
+impl FileReport {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.hunks.iter().all(|hunk| hunk.applied)
+    }
+}
+
+/// Outcome of applying a whole patch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchReport {
+    pub files: Vec<FileReport>,
+    /// Whether the changes were written. Patches are all or nothing: if any
+    /// hunk fails, no file is touched.
+    pub applied: bool,
+}
+
+impl PatchReport {
+    pub fn succeeded(&self) -> bool {
+        self.files.iter().all(FileReport::succeeded)
+    }
+
+    /// Human (and model) readable report listing every file and hunk
+    pub fn summary(&self) -> String {
+        let mut output = String::new();
+        let failed = self.files.iter().filter(|f| !f.succeeded()).count();
+
+        if self.succeeded() {
+            let verb = if self.applied { "Applied" } else { "Checked" };
+            let _ = writeln!(
+                output,
+                "{verb} patch to {} file{}",
+                self.files.len(),
+                if self.files.len() == 1 { "" } else { "s" }
+            );
+        } else {
+            let _ = writeln!(
+                output,
+                "Patch failed for {failed} of {} file{}; no changes were made",
+                self.files.len(),
+                if self.files.len() == 1 { "" } else { "s" }
+            );
+        }
+
+        for file in &self.files {
+            let operation = match &file.operation {
+                PatchOperation::Create => "create".to_string(),
+                PatchOperation::Delete => "delete".to_string(),
+                PatchOperation::Rename { from } => format!("rename from {from}"),
+                PatchOperation::Modify => "modify".to_string(),
+            };
+            let status = if file.succeeded() { "ok" } else { "FAILED" };
+            let _ = writeln!(output, "{} ({operation}): {status}", file.path);
+
+            if let Some(error) = &file.error {
+                let _ = writeln!(output, "  {error}");
+            }
+            for (i, hunk) in file.hunks.iter().enumerate() {
+                let _ = write!(output, "  hunk {} {}: ", i + 1, hunk.header);
+                if !hunk.applied {
+                    let _ = writeln!(output, "FAILED (context not found)");
+                    continue;
+                }
+                let _ = write!(output, "applied");
+                if let Some(line) = hunk.line {
+                    let _ = write!(output, " at line {line}");
+                }
+                if hunk.offset != 0 {
+                    let _ = write!(output, " (offset {} lines)", hunk.offset);
+                }
+                if hunk.fuzz > 0 {
+                    let _ = write!(output, " with fuzz {}", hunk.fuzz);
+                }
+                let _ = writeln!(output);
+            }
+        }
+
+        if self.applied {
+            for diff in self.files.iter().filter_map(|f| f.diff.as_ref()) {
+                output.push_str(diff);
+            }
+        }
+
+        output
+    }
+}
+
+/// File contents split into lines, remembering whether the last line ended
+/// with a newline
+#[derive(Debug, Clone)]
+struct FileLines {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+impl FileLines {
+    fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut content = self.lines.join("\n");
+        if self.trailing_newline && !self.lines.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+}
+
+/// Parsing and applying unified diffs, as produced by `diff -u` and `git diff`
+pub struct PatchTools;
+
+impl PatchTools {
+    /// Parse a unified diff, which may cover several files
+    pub fn parse(patch: &str) -> Result<Vec<FilePatch>> {
+        let lines: Vec<&str> = patch.lines().collect();
+        let mut files = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = lines[i];
+
+            if let Some(header) = line.strip_prefix("diff --git ") {
+                let (file, next) = Self::parse_git_section(header, &lines, i + 1)?;
+                files.push(file);
+                i = next;
+            } else if line.starts_with("--- ")
+                && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+            {
+                let old_path = Self::parse_header_path(&line[4..]);
+                let new_path = Self::parse_header_path(&lines[i + 1][4..]);
+                // Plain `diff -u` output has no a/ b/ prefixes, but models often add them
+                let strip = old_path.as_deref().is_none_or(|p| p.starts_with("a/"))
+                    && new_path.as_deref().is_none_or(|p| p.starts_with("b/"));
+                let (hunks, next) = Self::parse_hunks(&lines, i + 2)?;
+                files.push(FilePatch {
+                    old_path: old_path.map(|p| Self::strip_prefix(p, "a/", strip)),
+                    new_path: new_path.map(|p| Self::strip_prefix(p, "b/", strip)),
+                    hunks,
+                });
+                i = next;
+            } else {
+                // Commit messages, "Index:" lines and other preamble
+                i += 1;
+            }
+        }
+
+        if files.is_empty() {
+            bail!("No file changes found in patch; expected unified diff headers (--- / +++)");
+        }
+        Ok(files)
+    }
+
+    /// Parses the extended headers and hunks following a `diff --git` line
+    fn parse_git_section(header: &str, lines: &[&str], start: usize) -> Result<(FilePatch, usize)> {
+        // "a/old b/new" - used for renames and mode changes that have no ---/+++ lines
+        let (mut old_path, mut new_path) = match header.split_once(" b/") {
+            Some((old, new)) => (
+                Some(old.strip_prefix("a/").unwrap_or(old).to_string()),
+                Some(new.to_string()),
+            ),
+            None => (None, None),
+        };
+        let mut created = false;
+        let mut deleted = false;
+        let mut i = start;
+
+        while i < lines.len() {
+            let line = lines[i];
+            if line.starts_with("diff --git ") || line.starts_with("@@") {
+                break;
+            }
+            if let Some(path) = line.strip_prefix("rename from ") {
+                old_path = Some(path.to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                new_path = Some(path.to_string());
+            } else if line.starts_with("new file mode") {
+                created = true;
+            } else if line.starts_with("deleted file mode") {
+                deleted = true;
+            } else if let Some(path) = line.strip_prefix("--- ") {
+                if let Some(path) = Self::parse_header_path(path) {
+                    old_path = Some(Self::strip_prefix(path, "a/", true));
+                } else {
+                    created = true;
+                }
+            } else if let Some(path) = line.strip_prefix("+++ ") {
+                if let Some(path) = Self::parse_header_path(path) {
+                    new_path = Some(Self::strip_prefix(path, "b/", true));
+                } else {
+                    deleted = true;
+                }
+            } else if line.starts_with("Binary files") || line.starts_with("GIT binary patch") {
+                bail!("Binary patches are not supported");
+            }
+            i += 1;
+        }
+
+        if old_path.is_none() && new_path.is_none() {
+            bail!("Could not determine file paths from: diff --git {header}");
+        }
+        if created {
+            new_path = new_path.or(old_path.take());
+            old_path = None;
+        }
+        if deleted {
+            old_path = old_path.or(new_path.take());
+            new_path = None;
+        }
+
+        let (hunks, next) = Self::parse_hunks(lines, i)?;
+        Ok((
+            FilePatch {
+                old_path,
+                new_path,
+                hunks,
+            },
+            next,
+        ))
+    }
+
+    /// Path from a ---/+++ header line, without any timestamp. `None` for /dev/null.
+    fn parse_header_path(text: &str) -> Option<String> {
+        let path = text.split('\t').next().unwrap_or(text).trim();
+        let path = path.trim_matches('"');
+        if path == "/dev/null" || path.is_empty() {
+            None
+        } else {
+            Some(path.to_string())
+        }
+    }
+
+    fn strip_prefix(path: String, prefix: &str, strip: bool) -> String {
+        match path.strip_prefix(prefix) {
+            Some(rest) if strip => rest.to_string(),
+            _ => path,
+        }
+    }
+
+    /// Parses consecutive hunks starting at `start`, returning them and the
+    /// index of the first line after them
+    fn parse_hunks(lines: &[&str], start: usize) -> Result<(Vec<Hunk>, usize)> {
+        let mut hunks = Vec::new();
+        let mut i = start;
+
+        while i < lines.len() && lines[i].starts_with("@@") {
+            let mut hunk = Self::parse_hunk_header(lines[i])?;
+            i += 1;
+
+            while i < lines.len() {
+                let line = lines[i];
+                // A new file section ends the hunk; a "--- " line on its own is a
+                // removed line that happens to start with "--"
+                if line.starts_with("@@")
+                    || line.starts_with("diff --git ")
+                    || (line.starts_with("--- ")
+                        && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")))
+                {
+                    break;
+                }
+
+                match line.chars().next() {
+                    Some(' ') => hunk.lines.push(HunkLine::Context(line[1..].to_string())),
+                    Some('-') => hunk.lines.push(HunkLine::Removed(line[1..].to_string())),
+                    Some('+') => hunk.lines.push(HunkLine::Added(line[1..].to_string())),
+                    Some('\\') => {
+                        // "\ No newline at end of file" applies to the line before it
+                        if !matches!(hunk.lines.last(), Some(HunkLine::Removed(_))) {
+                            hunk.no_newline_at_end = true;
+                        }
+                    }
+                    // Editors and models often strip the space from blank context lines
+                    None => hunk.lines.push(HunkLine::Context(String::new())),
+                    Some(_) => break,
+                }
+                i += 1;
+            }
+
+            // Blank lines after the last hunk are usually just separators, so drop
+            // any beyond what the header says the hunk contains
+            let old_len = |hunk: &Hunk| {
+                hunk.lines
+                    .iter()
+                    .filter(|l| !matches!(l, HunkLine::Added(_)))
+                    .count()
+            };
+            while old_len(&hunk) > hunk.old_count
+                && hunk.lines.last() == Some(&HunkLine::Context(String::new()))
+            {
+                hunk.lines.pop();
+            }
+
+            hunks.push(hunk);
+        }
+
+        Ok((hunks, i))
+    }
+
+    /// Parses "@@ -a,b +c,d @@ optional section heading". Models sometimes
+    /// leave out the numbers entirely ("@@ @@"), in which case the hunk is
+    /// located by its context alone.
+    fn parse_hunk_header(line: &str) -> Result<Hunk> {
+        let body = line
+            .trim_start_matches('@')
+            .split("@@")
+            .next()
+            .unwrap_or_default()
+            .trim();
+
+        let parse_range = |text: &str| -> Result<(usize, usize)> {
+            let (start, count) = match text.split_once(',') {
+                Some((start, count)) => (start, Some(count)),
+                None => (text, None),
+            };
+            let start = start
+                .parse()
+                .with_context(|| format!("Invalid hunk header: {line}"))?;
+            let count = match count {
+                Some(count) => count
+                    .parse()
+                    .with_context(|| format!("Invalid hunk header: {line}"))?,
+                None => 1,
+            };
+            Ok((start, count))
+        };
+
+        let mut old_range = (0, 0);
+        let mut new_range = (0, 0);
+        for part in body.split_whitespace() {
+            if let Some(range) = part.strip_prefix('-') {
+                old_range = parse_range(range)?;
+            } else if let Some(range) = part.strip_prefix('+') {
+                new_range = parse_range(range)?;
+            }
+        }
+
+        Ok(Hunk {
+            old_start: old_range.0,
+            old_count: old_range.1,
+            new_start: new_range.0,
+            new_count: new_range.1,
+            lines: Vec::new(),
+            no_newline_at_end: false,
+        })
+    }
+
+    /// Apply a patch to files under `base_dir`. Paths in the patch are
+    /// resolved against `base_dir`, and may not lead outside it.
+    ///
+    /// Returns a report of every hunk. If any hunk fails, nothing is written
+    /// and the report says which hunks need fixing.
+    pub fn apply(patch: &str, base_dir: &Path, fuzz: usize) -> Result<PatchReport> {
+        let (mut report, changes) = Self::stage(patch, base_dir, fuzz)?;
+        if report.succeeded() {
+            FileOps::apply_changes(&changes)?;
+            report.applied = true;
+        }
+        Ok(report)
+    }
+
+    /// Check whether a patch would apply, without changing any files
+    pub fn check(patch: &str, base_dir: &Path, fuzz: usize) -> Result<PatchReport> {
+        Self::stage(patch, base_dir, fuzz).map(|(report, _)| report)
+    }
+
+    /// Works out every file's new content without writing anything. Later
+    /// file sections see the result of earlier ones.
+    fn stage(patch: &str, base_dir: &Path, fuzz: usize) -> Result<(PatchReport, ChangeSet)> {
+        let file_patches = Self::parse(patch)?;
+        let base_dir = std::path::absolute(base_dir)
+            .ok()
+            .and_then(|dir| normalize(&dir))
+            .with_context(|| format!("Invalid base directory: {}", base_dir.display()))?;
+
+        let mut changes = ChangeSet::new();
+        let mut reports = Vec::new();
+
+        for file_patch in &file_patches {
+            let operation = file_patch.operation();
+            let mut report = FileReport {
+                path: file_patch.path().to_string(),
+                operation: operation.clone(),
+                hunks: Vec::new(),
+                error: None,
+                diff: None,
+            };
+
+            if let Some(path) = [&file_patch.old_path, &file_patch.new_path]
+                .into_iter()
+                .flatten()
+                .find(|path| resolve(&base_dir, path).is_none())
+            {
+                report.error = Some(format!(
+                    "{path} is outside the base directory {}",
+                    base_dir.display()
+                ));
+                reports.push(report);
+                continue;
+            }
+            let old_path = file_patch
+                .old_path
+                .as_ref()
+                .and_then(|path| resolve(&base_dir, path));
+            let new_path = file_patch
+                .new_path
+                .as_ref()
+                .and_then(|path| resolve(&base_dir, path));
+
+            let original = match &old_path {
+                Some(path) => match changes.content(path)? {
+                    Some(content) => content,
+                    None => {
+                        report.error = Some(format!("File not found: {}", path.display()));
+                        reports.push(report);
+                        continue;
+                    }
+                },
+                None => {
+                    let path = new_path.as_ref().expect("created file has a path");
+                    match changes.content(path)? {
+                        Some(content) if !content.is_empty() => {
+                            report.error = Some(format!(
+                                "Cannot create {}: file already exists",
+                                path.display()
+                            ));
+                            reports.push(report);
+                            continue;
+                        }
+                        _ => String::new(),
+                    }
+                }
+            };
+
+            if let (PatchOperation::Rename { .. }, Some(path)) = (&operation, &new_path) {
+                if changes.content(path)?.is_some() {
+                    report.error = Some(format!(
+                        "Cannot rename to {}: file already exists",
+                        path.display()
+                    ));
+                    reports.push(report);
+                    continue;
+                }
+            }
+
+            let (result, hunk_reports) =
+                Self::apply_hunks(&FileLines::parse(&original), &file_patch.hunks, fuzz);
+            report.hunks = hunk_reports;
+            if !report.succeeded() {
+                reports.push(report);
+                continue;
+            }
+            let updated = result.render();
+
+            if operation == PatchOperation::Delete && !updated.is_empty() {
+                report.error =
+                    Some("File to delete does not match the patch; lines would remain".to_string());
+                reports.push(report);
+                continue;
+            }
+
+            // Stage the change
+            let display_path = report.path.clone();
+            let staged = match (&old_path, &new_path) {
+                (Some(old), None) => {
+                    report.diff = Some(format!("  ⎿  Deleted {display_path}\n"));
+                    changes.delete(old)
+                }
+                (old, Some(new)) => {
+                    let diff_lines = DiffTools::generate_diff(&original, &updated);
+                    report.diff = Some(DiffTools::format_diff(&diff_lines, &display_path)?);
+                    match old.as_ref().filter(|old| *old != new) {
+                        Some(old) => changes.rename(old, new, false),
+                        None => Ok(()),
+                    }
+                    .map(|_| changes.write(new, updated))
+                }
+                (None, None) => unreachable!("patch sections always have a path"),
+            };
+            if let Err(e) = staged {
+                report.diff = None;
+                report.error = Some(e.to_string());
+            }
+            reports.push(report);
+        }
+
+        let report = PatchReport {
+            files: reports,
+            applied: false,
+        };
+        Ok((report, changes))
+    }
+
+    /// Applies hunks in order, returning the updated lines and a report per hunk
+    fn apply_hunks(
+        original: &FileLines,
+        hunks: &[Hunk],
+        max_fuzz: usize,
+    ) -> (FileLines, Vec<HunkReport>) {
+        let mut file = original.clone();
+        let mut reports = Vec::new();
+        // Lines added minus lines removed so far, to adjust later hunk positions
+        let mut delta: isize = 0;
+        // Hunks must not overlap text already changed by an earlier hunk
+        let mut min_position = 0;
+
+        for hunk in hunks {
+            let mut report = HunkReport {
+                header: hunk.header(),
+                applied: false,
+                line: None,
+                offset: 0,
+                fuzz: 0,
+            };
+
+            if let Some((position, fuzz, lines)) =
+                Self::locate_hunk(&file.lines, hunk, delta, min_position, max_fuzz)
+            {
+                let old_len = lines
+                    .iter()
+                    .filter(|l| !matches!(l, HunkLine::Added(_)))
+                    .count();
+
+                // Context lines keep the file's own text, which may differ in
+                // whitespace from the patch
+                let mut replacement = Vec::new();
+                let mut cursor = position;
+                for line in lines {
+                    match line {
+                        HunkLine::Context(_) => {
+                            replacement.push(file.lines[cursor].clone());
+                            cursor += 1;
+                        }
+                        HunkLine::Removed(_) => cursor += 1,
+                        HunkLine::Added(text) => replacement.push(text.clone()),
+                    }
+                }
+                let new_len = replacement.len();
+                let at_end = position + old_len == file.lines.len();
+                file.lines.splice(position..position + old_len, replacement);
+
+                if at_end {
+                    if hunk.no_newline_at_end {
+                        file.trailing_newline = false;
+                    } else if lines.iter().any(|l| matches!(l, HunkLine::Added(_))) {
+                        file.trailing_newline = true;
+                    }
+                }
+
+                // Report the position in terms of the original file
+                let original_position = position as isize - delta;
+                let expected = hunk.old_start.saturating_sub(1) as isize;
+                report.applied = true;
+                report.line = Some((original_position + 1).max(1) as usize);
+                report.offset = if hunk.old_start == 0 {
+                    0
+                } else {
+                    original_position - expected
+                };
+                report.fuzz = fuzz;
+
+                delta += new_len as isize - old_len as isize;
+                min_position = position + new_len;
+            }
+
+            reports.push(report);
+        }
+
+        (file, reports)
+    }
+
+    /// Finds where a hunk applies, trying exact context first and then ignoring
+    /// up to `max_fuzz` context lines at each end. Returns the position, the fuzz
+    /// used and the (possibly trimmed) hunk lines.
+    fn locate_hunk<'a>(
+        lines: &[String],
+        hunk: &'a Hunk,
+        delta: isize,
+        min_position: usize,
+        max_fuzz: usize,
+    ) -> Option<(usize, usize, &'a [HunkLine])> {
+        for fuzz in 0..=max_fuzz {
+            let body = Self::trim_context(&hunk.lines, fuzz);
+            let old: Vec<&str> = body
+                .iter()
+                .filter_map(|line| match line {
+                    HunkLine::Context(text) | HunkLine::Removed(text) => Some(text.as_str()),
+                    HunkLine::Added(_) => None,
+                })
+                .collect();
+
+            // Context trimmed from the front shifts where the rest should start
+            let trimmed_front = hunk
+                .lines
+                .iter()
+                .take_while(|l| matches!(l, HunkLine::Context(_)))
+                .count()
+                .min(fuzz);
+
+            let expected = if hunk.old_start == 0 {
+                min_position as isize
+            } else if hunk.old_count == 0 {
+                // Pure insertions name the line they follow
+                hunk.old_start as isize + delta
+            } else {
+                hunk.old_start as isize - 1 + delta + trimmed_front as isize
+            };
+
+            if let Some(position) = Self::search(lines, &old, expected, min_position, fuzz > 0) {
+                return Some((position, fuzz, body));
+            }
+
+            // Nothing left to trim
+            if body.len() == hunk.lines.len() && fuzz > 0 {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Drops up to `fuzz` context lines from each end of a hunk
+    fn trim_context(lines: &[HunkLine], fuzz: usize) -> &[HunkLine] {
+        let leading = lines
+            .iter()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count()
+            .min(fuzz);
+        let trailing = lines[leading..]
+            .iter()
+            .rev()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count()
+            .min(fuzz);
+        &lines[leading..lines.len() - trailing]
+    }
+
+    /// Searches outwards from `expected` for the lines in `old`. With `loose`
+    /// set, trailing whitespace differences are ignored.
+    fn search(
+        lines: &[String],
+        old: &[&str],
+        expected: isize,
+        min_position: usize,
+        loose: bool,
+    ) -> Option<usize> {
+        if old.len() > lines.len() {
+            return None;
+        }
+        let last = lines.len() - old.len();
+        if min_position > last {
+            return None;
+        }
+
+        let matches_at = |position: usize| {
+            old.iter().enumerate().all(|(i, expected)| {
+                let actual = &lines[position + i];
+                if loose {
+                    actual.trim_end() == expected.trim_end()
+                } else {
+                    actual == expected
+                }
+            })
+        };
+
+        let expected = expected.clamp(min_position as isize, last as isize) as usize;
+        let max_distance = (expected - min_position).max(last - expected);
+        for distance in 0..=max_distance {
+            if let Some(position) = expected.checked_sub(distance) {
+                if position >= min_position && matches_at(position) {
+                    return Some(position);
+                }
+            }
+            let position = expected + distance;
+            if distance > 0 && position <= last && matches_at(position) {
+                return Some(position);
+            }
+        }
+        None
+    }
+}
+
+/// Resolves a patch path against `base_dir`, or `None` if it leads outside it
+fn resolve(base_dir: &Path, path: &str) -> Option<PathBuf> {
+    normalize(&base_dir.join(path)).filter(|resolved| resolved.starts_with(base_dir))
+}
+
+/// Removes `.` and `..` components without looking at the file system
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    Some(normalized)
+}
@@ -0,0 +1,637 @@
+use crate::tools::fs::search::SearchTools;
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Tokens of outline the RepoMap tool returns when no budget is given
+pub const DEFAULT_REPO_MAP_TOKENS: usize = 1024;
+/// Most tokens of outline the RepoMap tool returns
+pub const MAX_REPO_MAP_TOKENS: usize = 8192;
+/// Files larger than this aren't read for symbols
+pub const MAX_MAPPED_FILE_SIZE: u64 = 1024 * 1024;
+/// Most symbols listed for one file
+pub const MAX_FILE_SYMBOLS: usize = 30;
+/// Most source files mapped in one workspace; the walk stops after this many
+pub const MAX_MAPPED_FILES: usize = 5000;
+/// How long one update of a map may spend walking and reading files. Files
+/// not read in time are left out until a later update reaches them.
+pub const MAP_TIME_LIMIT: Duration = Duration::from_secs(3);
+/// How long a workspace without source files isn't walked again
+pub const EMPTY_MAP_RECHECK: Duration = Duration::from_secs(60);
+/// Most workspaces whose parsed files are kept, least recently mapped
+/// dropped first
+const MAX_CACHED_WORKSPACES: usize = 4;
+
+/// Rough number of characters per token, for keeping within a budget
+const CHARS_PER_TOKEN: usize = 4;
+/// Longest declaration shown for a symbol, in characters
+const MAX_SIGNATURE_LEN: usize = 100;
+/// Shorter names are too common for a mention to say which file is meant
+const MIN_REFERENCE_LEN: usize = 3;
+/// Deepest indentation, in columns, of a member of a top-level type
+const MAX_MEMBER_INDENT: usize = 4;
+
+/// Words that look like a call when a line is read on its own
+const KEYWORDS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "return", "else", "do", "sizeof", "function", "match",
+    "loop", "elif", "with", "assert", "throw", "await",
+];
+
+/// A declaration found in a source file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapSymbol {
+    pub name: String,
+    /// The declaring keyword or what it amounts to, e.g. "fn", "class" or
+    /// "method"
+    pub kind: String,
+    /// 1-based line of the declaration
+    pub line: usize,
+    /// The declaring line, without its body
+    pub signature: String,
+    /// Whether it's declared inside a top-level type or impl block rather
+    /// than at the top level
+    pub member: bool,
+}
+
+/// A file of the map with its symbols and how much the rest of the
+/// workspace uses them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MappedFile {
+    /// Path relative to the workspace root, with `/` separators
+    pub path: String,
+    pub symbols: Vec<MapSymbol>,
+    /// Number of other files mentioning one of its symbols
+    pub referenced_by: usize,
+    /// Mentions of its symbols from other files, counting each file once per
+    /// name. A name declared in several files is shared between them.
+    pub score: f64,
+}
+
+/// How to find the declarations of one language, a line at a time
+struct Grammar {
+    extensions: &'static [&'static str],
+    /// Declarations at the top level, tried in order. Each pattern captures
+    /// `name`, and `kind` when the kind isn't the one given.
+    top: Vec<(&'static str, Regex)>,
+    /// Declarations indented inside a top-level container
+    members: Vec<(&'static str, Regex)>,
+    /// Kinds whose body holds members
+    containers: &'static [&'static str],
+}
+
+impl Grammar {
+    fn new(
+        extensions: &'static [&'static str],
+        top: &[(&'static str, &str)],
+        members: &[(&'static str, &str)],
+        containers: &'static [&'static str],
+    ) -> Self {
+        let compile = |patterns: &[(&'static str, &str)]| {
+            patterns
+                .iter()
+                .map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("valid pattern")))
+                .collect()
+        };
+        Self {
+            extensions,
+            top: compile(top),
+            members: compile(members),
+            containers,
+        }
+    }
+}
+
+fn grammars() -> &'static [Grammar] {
+    static GRAMMARS: OnceLock<Vec<Grammar>> = OnceLock::new();
+    GRAMMARS.get_or_init(|| {
+        let rust_vis = r"(?:pub(?:\([^)]*\))?\s+)?";
+        let rust_fn = format!(
+            r#"^{rust_vis}(?:(?:const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*fn\s+(?P<name>\w+)"#
+        );
+        let rust_item = format!(
+            r"^{rust_vis}(?:unsafe\s+)?(?P<kind>struct|enum|union|trait|type|mod|const|static)\s+(?:mut\s+)?(?P<name>\w+)"
+        );
+        let js_prefix = r"^(?:export\s+)?(?:default\s+)?(?:declare\s+)?";
+        let js_function = format!(r"{js_prefix}(?:async\s+)?function\s*\*?\s*(?P<name>[\w$]+)");
+        let js_type = format!(
+            r"{js_prefix}(?:abstract\s+)?(?P<kind>class|interface|enum|type)\s+(?P<name>[\w$]+)"
+        );
+        let js_arrow = r"^(?:export\s+)?(?:const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]+)?=\s*(?:async\s+)?(?:function\b|\([^)]*\)\s*(?::[^=]+)?=>|[\w$]+\s*=>)";
+        let c_type = r"[\w:<>,*&~]+";
+        let c_function = format!(r"^(?:{c_type}\s+)+[*&]*(?P<name>[\w:~]+)\s*\([^;]*$");
+        let c_method = format!(r"^(?:{c_type}\s+)*[*&]*(?P<name>~?\w+)\s*\([^;]*$");
+        let jvm_modifiers = r"(?:(?:public|private|protected|internal|static|final|abstract|sealed|partial|override|virtual|async|synchronized|open|data)\s+)";
+
+        vec![
+            Grammar::new(
+                &["rs"],
+                &[
+                    ("fn", &rust_fn),
+                    ("", &rust_item),
+                    ("macro", r"^macro_rules!\s*(?P<name>\w+)"),
+                    (
+                        "impl",
+                        r"^(?:unsafe\s+)?impl(?:<[^{]*?>)?\s+(?P<name>[^{]+?)\s*(?:\{|\bwhere\b|$)",
+                    ),
+                ],
+                &[("fn", &rust_fn)],
+                &["impl", "trait"],
+            ),
+            Grammar::new(
+                &["py", "pyi"],
+                &[
+                    ("def", r"^(?:async\s+)?def\s+(?P<name>\w+)"),
+                    ("class", r"^class\s+(?P<name>\w+)"),
+                    ("const", r"^(?P<name>[A-Z][A-Z0-9_]+)\s*(?::[^=]+)?=[^=]"),
+                ],
+                &[("def", r"^(?:async\s+)?def\s+(?P<name>\w+)")],
+                &["class"],
+            ),
+            Grammar::new(
+                &["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"],
+                &[
+                    ("function", &js_function),
+                    ("", &js_type),
+                    ("function", js_arrow),
+                    ("const", r"^export\s+(?:const|let|var)\s+(?P<name>[\w$]+)"),
+                ],
+                &[(
+                    "method",
+                    r"^(?:(?:public|private|protected|static|readonly|async|abstract|override|get|set)\s+)*(?P<name>#?[\w$]+)\s*(?:<[^>]*>)?\s*\([^;]*$",
+                )],
+                &["class", "interface"],
+            ),
+            Grammar::new(
+                &["go"],
+                &[
+                    ("func", r"^func\s+(?:\([^)]*\)\s*)?(?P<name>\w+)"),
+                    ("", r"^type\s+(?P<name>\w+)\s+(?P<kind>struct|interface)\b"),
+                    ("type", r"^type\s+(?P<name>\w+)"),
+                ],
+                &[],
+                &[],
+            ),
+            Grammar::new(
+                &["c", "h", "cc", "cpp", "cxx", "c++", "hh", "hpp", "hxx"],
+                &[
+                    (
+                        "",
+                        r"^(?:typedef\s+)?(?P<kind>struct|class|enum|union)\s+(?:class\s+)?(?P<name>\w+)\s*(?:final\s*)?(?::[^;{]*)?\{?\s*$",
+                    ),
+                    ("macro", r"^#\s*define\s+(?P<name>\w+)"),
+                    ("function", &c_function),
+                ],
+                &[("method", &c_method)],
+                &["class", "struct"],
+            ),
+            Grammar::new(
+                &["java", "kt", "kts", "cs", "scala"],
+                &[(
+                    "",
+                    &format!(
+                        r"^{jvm_modifiers}*(?P<kind>class|interface|enum|record|struct|object|trait)\s+(?P<name>\w+)"
+                    ),
+                ),
+                ("fun", r"^(?:(?:private|internal|inline|suspend)\s+)*fun\s+(?:<[^>]*>\s*)?(?:[\w.]+\.)?(?P<name>\w+)")],
+                &[
+                    (
+                        "fun",
+                        r"^(?:(?:public|private|protected|internal|override|open|suspend|inline)\s+)*fun\s+(?:<[^>]*>\s*)?(?P<name>\w+)",
+                    ),
+                    (
+                        "method",
+                        &format!(r"^{jvm_modifiers}+[\w<>\[\],.?\s]*?\b(?P<name>\w+)\s*\("),
+                    ),
+                ],
+                &["class", "interface", "enum", "record", "struct", "object", "trait"],
+            ),
+            Grammar::new(
+                &["rb"],
+                &[
+                    ("", r"^(?P<kind>class|module)\s+(?P<name>[\w:]+)"),
+                    ("def", r"^def\s+(?:self\.)?(?P<name>\w+[?!=]?)"),
+                ],
+                &[("def", r"^def\s+(?:self\.)?(?P<name>\w+[?!=]?)")],
+                &["class", "module"],
+            ),
+        ]
+    })
+}
+
+fn grammar_for(path: &Path) -> Option<&'static Grammar> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    grammars()
+        .iter()
+        .find(|grammar| grammar.extensions.contains(&ext.as_str()))
+}
+
+/// Width of a line's indentation, counting a tab as four columns
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+fn declaration(patterns: &[(&'static str, Regex)], line: &str) -> Option<(String, String)> {
+    patterns.iter().find_map(|(kind, regex)| {
+        let captures = regex.captures(line)?;
+        let name = captures.name("name")?.as_str().trim().to_string();
+        if KEYWORDS.contains(&name.as_str()) {
+            return None;
+        }
+        let kind = captures
+            .name("kind")
+            .map(|kind| kind.as_str())
+            .unwrap_or(kind);
+        Some((name, kind.to_string()))
+    })
+}
+
+/// The declaring line without its body or trailing punctuation, shortened
+/// to MAX_SIGNATURE_LEN characters
+fn signature(line: &str) -> String {
+    let line = line.trim();
+    let line = line
+        .strip_suffix('{')
+        .or_else(|| line.strip_suffix(':'))
+        .unwrap_or(line)
+        .trim_end();
+    if line.chars().count() > MAX_SIGNATURE_LEN {
+        let short: String = line.chars().take(MAX_SIGNATURE_LEN - 1).collect();
+        format!("{}…", short.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// A source file's symbols and the names it mentions
+struct ParsedFile {
+    modified: Option<SystemTime>,
+    size: u64,
+    symbols: Vec<MapSymbol>,
+    /// Hashes of the names, which take far less memory than the names
+    identifiers: HashSet<u64>,
+}
+
+impl ParsedFile {
+    fn read(path: &Path, modified: Option<SystemTime>, size: u64) -> Self {
+        let content = std::fs::read(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default();
+        Self {
+            modified,
+            size,
+            symbols: RepoMap::symbols(path, &content),
+            identifiers: identifiers(&content),
+        }
+    }
+}
+
+fn identifiers(content: &str) -> HashSet<u64> {
+    static IDENTIFIER: OnceLock<Regex> = OnceLock::new();
+    IDENTIFIER
+        .get_or_init(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").expect("valid pattern"))
+        .find_iter(content)
+        .map(|m| m.as_str())
+        .filter(|name| name.len() >= MIN_REFERENCE_LEN)
+        .map(name_hash)
+        .collect()
+}
+
+fn name_hash(name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Rank files by how many other files mention the names they declare
+fn rank(parsed: &HashMap<String, Arc<ParsedFile>>) -> Vec<MappedFile> {
+    let paths: Vec<&String> = parsed.keys().collect();
+
+    // Which files declare each name. Impl blocks add to a type declared
+    // elsewhere, so they don't count as declaring it.
+    let mut declared: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, path) in paths.iter().enumerate() {
+        let names: HashSet<u64> = parsed[*path]
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.kind != "impl" && symbol.name.len() >= MIN_REFERENCE_LEN)
+            .map(|symbol| name_hash(&symbol.name))
+            .collect();
+        for name in names {
+            declared.entry(name).or_default().push(i);
+        }
+    }
+
+    let mut scores = vec![0.0; paths.len()];
+    let mut referrers: Vec<HashSet<usize>> = vec![HashSet::new(); paths.len()];
+    for (i, path) in paths.iter().enumerate() {
+        for name in &parsed[*path].identifiers {
+            let Some(files) = declared.get(name) else {
+                continue;
+            };
+            let weight = 1.0 / files.len() as f64;
+            for &file in files.iter().filter(|&&file| file != i) {
+                scores[file] += weight;
+                referrers[file].insert(i);
+            }
+        }
+    }
+
+    let mut files: Vec<MappedFile> = paths
+        .iter()
+        .enumerate()
+        .filter(|(_, path)| !parsed[**path].symbols.is_empty())
+        .map(|(i, path)| MappedFile {
+            path: path.to_string(),
+            symbols: parsed[*path].symbols.clone(),
+            referenced_by: referrers[i].len(),
+            score: scores[i],
+        })
+        .collect();
+    files.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.referenced_by.cmp(&a.referenced_by))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    files
+}
+
+/// A workspace's parsed files, keyed by relative path, and their ranking
+struct WorkspaceMap {
+    parsed: HashMap<String, Arc<ParsedFile>>,
+    ranked: Arc<Vec<MappedFile>>,
+    /// When the workspace was last walked
+    checked: Instant,
+}
+
+fn workspace_maps() -> &'static Mutex<HashMap<PathBuf, WorkspaceMap>> {
+    static WORKSPACE_MAPS: OnceLock<Mutex<HashMap<PathBuf, WorkspaceMap>>> = OnceLock::new();
+    WORKSPACE_MAPS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// An outline of a workspace for the model: the top-level symbols of each
+/// source file, most referenced files first, cut to a token budget so it
+/// fits in the system prompt.
+///
+/// Symbols are found a line at a time with patterns for Rust, Python,
+/// JavaScript and TypeScript, Go, C and C++, Java, Kotlin, C#, Scala and
+/// Ruby, so no language server has to be running. Each workspace's parsed
+/// files are cached and only files whose size or modification time changed
+/// are read again. A map covers at most MAX_MAPPED_FILES files, and an
+/// update stops reading new files after MAP_TIME_LIMIT.
+pub struct RepoMap;
+
+impl RepoMap {
+    /// Every source file under `root` with symbols, most referenced first
+    pub fn files(root: &Path) -> Result<Arc<Vec<MappedFile>>> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve directory {}", root.display()))?;
+        if !root.is_dir() {
+            return Err(anyhow!("{} is not a directory", root.display()));
+        }
+
+        let previous = workspace_maps()
+            .lock()
+            .map_err(|_| anyhow!("Repository map cache lock poisoned"))?
+            .get(&root)
+            .map(|map| (map.parsed.clone(), map.ranked.clone(), map.checked));
+        // A workspace without source files is likely not a project at all, so
+        // don't walk it on every call
+        if let Some((_, ranked, checked)) = &previous {
+            if ranked.is_empty() && checked.elapsed() < EMPTY_MAP_RECHECK {
+                return Ok(ranked.clone());
+            }
+        }
+
+        let deadline = Instant::now() + MAP_TIME_LIMIT;
+        let mut candidates = Vec::new();
+        SearchTools::walk_workspace(&root, |path, is_dir| {
+            if !is_dir && grammar_for(&path).is_some() {
+                candidates.push(path);
+            }
+            if candidates.len() >= MAX_MAPPED_FILES || Instant::now() >= deadline {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        let stats: Vec<(String, PathBuf, Option<SystemTime>, u64)> = candidates
+            .into_par_iter()
+            .filter(|path| !SearchTools::is_likely_binary_or_generated(path))
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                if metadata.len() > MAX_MAPPED_FILE_SIZE {
+                    return None;
+                }
+                let rel = path
+                    .strip_prefix(&root)
+                    .ok()?
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                Some((rel, path, metadata.modified().ok(), metadata.len()))
+            })
+            .collect();
+
+        let cached = previous.is_some();
+        let (previous, previous_ranked) = previous
+            .map(|(parsed, ranked, _)| (parsed, ranked))
+            .unwrap_or_default();
+
+        // Read only new and changed files, outside the lock, until time is up
+        let unchanged = |rel: &String, modified: &Option<SystemTime>, size: u64| {
+            previous
+                .get(rel)
+                .filter(|file| file.modified == *modified && file.size == size)
+                .cloned()
+        };
+        let parsed: HashMap<String, Arc<ParsedFile>> = stats
+            .into_par_iter()
+            .filter_map(|(rel, path, modified, size)| {
+                let file = match unchanged(&rel, &modified, size) {
+                    Some(file) => file,
+                    None if Instant::now() < deadline => {
+                        Arc::new(ParsedFile::read(&path, modified, size))
+                    }
+                    None => return None,
+                };
+                Some((rel, file))
+            })
+            .collect();
+
+        let changed = parsed.len() != previous.len()
+            || parsed
+                .iter()
+                .any(|(rel, file)| !previous.get(rel).is_some_and(|old| Arc::ptr_eq(old, file)));
+        let ranked = if changed || !cached {
+            Arc::new(rank(&parsed))
+        } else {
+            previous_ranked
+        };
+
+        let mut maps = workspace_maps()
+            .lock()
+            .map_err(|_| anyhow!("Repository map cache lock poisoned"))?;
+        maps.insert(
+            root,
+            WorkspaceMap {
+                parsed,
+                ranked: ranked.clone(),
+                checked: Instant::now(),
+            },
+        );
+        if maps.len() > MAX_CACHED_WORKSPACES {
+            let oldest = maps
+                .iter()
+                .min_by_key(|(_, map)| map.checked)
+                .map(|(root, _)| root.clone());
+            if let Some(oldest) = oldest {
+                maps.remove(&oldest);
+            }
+        }
+        Ok(ranked)
+    }
+
+    /// The outline of the workspace at `root`, at most about `max_tokens`
+    /// long. Empty when no source file under it has symbols.
+    pub fn outline(root: &Path, max_tokens: usize) -> Result<String> {
+        let files = Self::files(root)?;
+        Ok(Self::render(&files, max_tokens))
+    }
+
+    /// Render files in the order given, each followed by its symbols with
+    /// their line numbers and members indented under their type, stopping
+    /// once about `max_tokens` are used. A file with more than
+    /// MAX_FILE_SYMBOLS symbols shows only its top-level ones, so one large
+    /// file doesn't take the whole budget.
+    pub fn render(files: &[MappedFile], max_tokens: usize) -> String {
+        let budget = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+        let mut output = String::new();
+        let mut shown = 0;
+
+        for file in files {
+            let header = format!("{}:\n", file.path);
+            let symbols: Vec<&MapSymbol> = if file.symbols.len() > MAX_FILE_SYMBOLS {
+                file.symbols
+                    .iter()
+                    .filter(|symbol| !symbol.member)
+                    .collect()
+            } else {
+                file.symbols.iter().collect()
+            };
+            let lines: Vec<String> = symbols
+                .iter()
+                .take(MAX_FILE_SYMBOLS)
+                .map(|symbol| {
+                    let indent = if symbol.member { "    " } else { "  " };
+                    format!("{indent}{}: {}\n", symbol.line, symbol.signature)
+                })
+                .collect();
+            let more = |count: usize| match file.symbols.len() - count {
+                0 => String::new(),
+                hidden => format!("  ... {hidden} more symbols\n"),
+            };
+
+            let size = header.len() + lines.iter().map(String::len).sum::<usize>();
+            let trailer = more(lines.len());
+            if output.len() + size + trailer.len() <= budget {
+                output.push_str(&header);
+                lines.iter().for_each(|line| output.push_str(line));
+                output.push_str(&trailer);
+                shown += 1;
+                continue;
+            }
+
+            // Show what fits of the file that doesn't, and stop there
+            let mut used = output.len() + header.len();
+            let fitting = lines
+                .iter()
+                .take_while(|line| {
+                    used += line.len();
+                    used <= budget
+                })
+                .count();
+            if fitting > 0 {
+                output.push_str(&header);
+                lines[..fitting]
+                    .iter()
+                    .for_each(|line| output.push_str(line));
+                output.push_str(&more(fitting));
+                shown += 1;
+            }
+            break;
+        }
+
+        if shown < files.len() {
+            output.push_str(&format!(
+                "... {} more files with symbols not shown\n",
+                files.len() - shown
+            ));
+        }
+        output
+    }
+
+    /// The declarations in a source file, in the order they appear: those at
+    /// the top level, and the members of top-level types and impl blocks.
+    /// Empty for languages the map doesn't know.
+    pub fn symbols(path: &Path, content: &str) -> Vec<MapSymbol> {
+        let Some(grammar) = grammar_for(path) else {
+            return Vec::new();
+        };
+
+        let mut symbols = Vec::new();
+        let mut in_container = false;
+        for (i, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            let indent = indentation(line);
+            let found = if indent == 0 {
+                let found = declaration(&grammar.top, line);
+                // Anything else at the top level, such as a closing brace,
+                // ends the container
+                in_container = found
+                    .as_ref()
+                    .is_some_and(|(_, kind)| grammar.containers.contains(&kind.as_str()));
+                found.map(|found| (found, false))
+            } else if in_container && indent <= MAX_MEMBER_INDENT {
+                declaration(&grammar.members, trimmed).map(|found| (found, true))
+            } else {
+                None
+            };
+
+            if let Some(((name, kind), member)) = found {
+                symbols.push(MapSymbol {
+                    name,
+                    kind,
+                    line: i + 1,
+                    signature: signature(line),
+                    member,
+                });
+            }
+        }
+        symbols
+    }
+
+    /// Drop the cached map of `root`
+    pub fn invalidate(root: &Path) {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        if let Ok(mut maps) = workspace_maps().lock() {
+            maps.remove(&root);
+        }
+    }
+}
@@ -1,14 +1,151 @@
-use anyhow::{Context, Result};
+use crate::tools::fs::index::SearchIndex;
+use anyhow::{anyhow, Context, Result};
 use glob::glob;
 use ignore::WalkBuilder;
-use regex::Regex;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use walkdir::{DirEntry, WalkDir};
 
+/// Entries the Grep tool shows when no `head_limit` is given, so a broad
+/// pattern can't flood the context window
+pub const DEFAULT_GREP_HEAD_LIMIT: usize = 250;
+
+/// Files the Glob tool lists when no `limit` is given
+pub const DEFAULT_GLOB_LIMIT: usize = 100;
+
+/// One page of glob results, newest first
+#[derive(Debug, Clone)]
+pub struct GlobPage {
+    pub paths: Vec<PathBuf>,
+    /// Number of matching files across all pages
+    pub total: usize,
+    /// Index of the first path of this page in the full results
+    pub offset: usize,
+}
+
+impl GlobPage {
+    /// Matching files after this page
+    pub fn remaining(&self) -> usize {
+        self.total - self.offset - self.paths.len()
+    }
+}
+
+/// How grep results are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrepOutputMode {
+    /// Matching lines with their line numbers and any context lines
+    #[default]
+    Content,
+    /// Only the paths of files with at least one match
+    FilesWithMatches,
+    /// The number of matches in each file
+    Count,
+}
+
+/// Options for [`SearchTools::grep_search_with`]
+#[derive(Debug, Clone, Default)]
+pub struct GrepOptions {
+    /// Glob the file path must match, e.g. "*.rs" or "*.{ts,tsx}"
+    pub include: Option<String>,
+    /// File type to search, e.g. "rs", "py" or "ts"
+    pub file_type: Option<String>,
+    pub case_insensitive: bool,
+    /// Let patterns span lines, with `.` matching newlines
+    pub multiline: bool,
+    /// Lines of context shown before each match
+    pub before_context: usize,
+    /// Lines of context shown after each match
+    pub after_context: usize,
+    pub output_mode: GrepOutputMode,
+    /// Maximum number of entries shown, counting matches in content mode and
+    /// files otherwise
+    pub head_limit: Option<usize>,
+    /// Number of entries skipped before the first one shown
+    pub offset: usize,
+}
+
+impl GrepOptions {
+    pub fn with_include(mut self, include: impl Into<String>) -> Self {
+        self.include = Some(include.into());
+        self
+    }
+
+    pub fn with_file_type(mut self, file_type: impl Into<String>) -> Self {
+        self.file_type = Some(file_type.into());
+        self
+    }
+
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    pub fn with_multiline(mut self, multiline: bool) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    /// Same number of context lines before and after each match, like `-C`
+    pub fn with_context(mut self, lines: usize) -> Self {
+        self.before_context = lines;
+        self.after_context = lines;
+        self
+    }
+
+    pub fn with_before_context(mut self, lines: usize) -> Self {
+        self.before_context = lines;
+        self
+    }
+
+    pub fn with_after_context(mut self, lines: usize) -> Self {
+        self.after_context = lines;
+        self
+    }
+
+    pub fn with_output_mode(mut self, output_mode: GrepOutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    pub fn with_head_limit(mut self, head_limit: usize) -> Self {
+        self.head_limit = Some(head_limit);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+/// One match. `text` holds the matched line, or every line a multiline match
+/// spans joined with newlines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    pub line_number: usize,
+    pub text: String,
+    /// Context lines before the match as `(line number, text)`
+    pub before: Vec<(usize, String)>,
+    /// Context lines after the match as `(line number, text)`
+    pub after: Vec<(usize, String)>,
+}
+
+/// All matches in one file
+#[derive(Debug, Clone)]
+pub struct GrepFileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<GrepMatch>,
+}
+
 pub struct SearchTools;
 
 impl SearchTools {
@@ -75,7 +212,7 @@ impl SearchTools {
     }
 
     // Check if path should be ignored based on common patterns (fallback for when ignore files aren't used)
-    fn is_ignored_path(path: &Path) -> bool {
+    pub(crate) fn is_ignored_path(path: &Path) -> bool {
         let path_str = path.to_string_lossy();
 
         // Common directories to ignore
@@ -189,6 +326,7 @@ impl SearchTools {
                     .hidden(false) // Don't skip hidden files by default
                     .standard_filters(true) // Use .gitignore etc.
                     .build();
+                let glob_set: HashSet<&PathBuf> = glob_matches.iter().collect();
 
                 // Mark when we've finished processing to avoid redundant work
                 let processed = Arc::new(AtomicBool::new(false));
@@ -197,7 +335,7 @@ impl SearchTools {
                     let path = entry.path().to_path_buf();
 
                     // Only include paths that were in the original glob matches
-                    if glob_matches.iter().any(|m| m == &path) {
+                    if glob_set.contains(&path) {
                         matches.push(path);
                         // Mark that we've processed at least one path
                         processed.store(true, Ordering::SeqCst);
@@ -207,11 +345,7 @@ impl SearchTools {
                 // If we processed paths through the ignore-aware walker, return those results
                 if processed.load(Ordering::SeqCst) {
                     // Sort by last modified time before returning
-                    matches.sort_by(|a, b| {
-                        let a_modified = std::fs::metadata(a).and_then(|m| m.modified()).ok();
-                        let b_modified = std::fs::metadata(b).and_then(|m| m.modified()).ok();
-                        b_modified.cmp(&a_modified)
-                    });
+                    Self::sort_by_mtime(&mut matches);
 
                     return Ok(matches);
                 }
@@ -228,11 +362,7 @@ impl SearchTools {
         }
 
         // Sort by last modified time (most recent first)
-        matches.sort_by(|a, b| {
-            let a_modified = std::fs::metadata(a).and_then(|m| m.modified()).ok();
-            let b_modified = std::fs::metadata(b).and_then(|m| m.modified()).ok();
-            b_modified.cmp(&a_modified)
-        });
+        Self::sort_by_mtime(&mut matches);
 
         Ok(matches)
     }
@@ -243,6 +373,56 @@ impl SearchTools {
         Self::glob_search(&full_pattern)
     }
 
+    /// Sort paths by last modified time, most recent first. Ties, and files
+    /// whose time can't be read, are ordered by path so results are stable.
+    fn sort_by_mtime(paths: &mut [PathBuf]) {
+        paths.sort_by_cached_key(|path| {
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            (std::cmp::Reverse(modified), path.clone())
+        });
+    }
+
+    /// The `offset..offset + limit` slice of sorted glob results
+    pub fn paginate_glob_results(
+        paths: Vec<PathBuf>,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> GlobPage {
+        let total = paths.len();
+        let offset = offset.min(total);
+        let paths = paths
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        GlobPage {
+            paths,
+            total,
+            offset,
+        }
+    }
+
+    /// Render a page of glob results for the model, numbered from the start of
+    /// the full result list, with a marker when more results follow
+    pub fn format_glob_results(page: &GlobPage, pattern: &str) -> String {
+        let mut output = format!(
+            "Found {} files matching pattern '{}':\n\n",
+            page.total, pattern
+        );
+        for (i, path) in page.paths.iter().enumerate() {
+            output.push_str(&format!("{}. {}\n", page.offset + i + 1, path.display()));
+        }
+
+        let remaining = page.remaining();
+        if remaining > 0 {
+            output.push_str(&format!(
+                "\n(Results truncated, {remaining} more. Pass offset={} to see the next page, or use a more specific pattern.)\n",
+                page.offset + page.paths.len()
+            ));
+        }
+        output
+    }
+
     // Helper function for WalkDir to skip ignored directories
     fn should_skip_dir(entry: &DirEntry) -> bool {
         let path = entry.path();
@@ -301,7 +481,7 @@ impl SearchTools {
     }
 
     /// Check if a file is likely binary or generated based on extension and path
-    fn is_likely_binary_or_generated(path: &Path) -> bool {
+    pub(crate) fn is_likely_binary_or_generated(path: &Path) -> bool {
         // Check for binary file extensions
         if let Some(extension) = path.extension() {
             let ext = extension.to_string_lossy();
@@ -327,18 +507,145 @@ impl SearchTools {
         false
     }
 
+    /// Line-by-line regex search. Returns `(path, line number, line)` for every
+    /// matching line, most recently modified files first.
     pub fn grep_search(
         pattern: &str,
         include_pattern: Option<&str>,
         search_dir: Option<&Path>,
     ) -> Result<Vec<(PathBuf, usize, String)>> {
-        let regex =
-            Regex::new(pattern).with_context(|| format!("Invalid regex pattern: {pattern}"))?;
+        let options = GrepOptions {
+            include: include_pattern.map(str::to_string),
+            ..GrepOptions::default()
+        };
+        let results = Self::grep_search_with(pattern, search_dir, &options)?;
+
+        Ok(results
+            .into_iter()
+            .flat_map(|file| {
+                let path = file.path;
+                file.matches
+                    .into_iter()
+                    .map(move |m| (path.clone(), m.line_number, m.text))
+            })
+            .collect())
+    }
+
+    /// Regex search with context lines, case and multiline flags and file
+    /// filters. Returns every matching file with its matches, most recently
+    /// modified first; `head_limit` and `offset` are applied when formatting.
+    pub fn grep_search_with(
+        pattern: &str,
+        search_dir: Option<&Path>,
+        options: &GrepOptions,
+    ) -> Result<Vec<GrepFileMatches>> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(options.case_insensitive)
+            .multi_line(options.multiline)
+            .dot_matches_new_line(options.multiline)
+            .build()
+            .with_context(|| format!("Invalid regex pattern: {pattern}"))?;
 
         let dir = search_dir.unwrap_or_else(|| Path::new("."));
-        let include_regex = Self::create_file_pattern_filter(include_pattern);
-        let mut matches = Vec::new();
+        let include_regex = Self::create_file_pattern_filter(options.include.as_deref());
+        let type_extensions = options
+            .file_type
+            .as_deref()
+            .map(Self::extensions_for_type)
+            .transpose()?;
+
+        // With a search index enabled for the workspace, only files that
+        // contain the pattern's trigrams are read
+        let files = Self::grep_candidate_files(dir);
+        let files = SearchIndex::prefilter(dir, pattern, options.case_insensitive, files);
+
+        let candidates: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| {
+                // Skip if doesn't match include pattern
+                if let Some(ref include_regex) = include_regex {
+                    if !include_regex.is_match(&path.to_string_lossy()) {
+                        return false;
+                    }
+                }
+                // Skip if not one of the requested file type's extensions
+                if let Some(ref extensions) = type_extensions {
+                    let ext = path
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .unwrap_or_default();
+                    if !extensions.contains(&ext) {
+                        return false;
+                    }
+                }
+                // Skip binary/generated files
+                !Self::is_likely_binary_or_generated(path)
+            })
+            .collect();
+
+        // Files are independent, so search them in parallel; collect keeps the
+        // walk order, which the stable sort below relies on for ties
+        let mut results: Vec<GrepFileMatches> = candidates
+            .par_iter()
+            .filter_map(|path| {
+                let matches = if options.multiline {
+                    Self::search_file_multiline(path, &regex, options)
+                } else {
+                    Self::search_file_lines(path, &regex, options)
+                };
+                if matches.is_empty() {
+                    None
+                } else {
+                    Some(GrepFileMatches {
+                        path: path.clone(),
+                        matches,
+                    })
+                }
+            })
+            .collect();
+
+        // Sort by last modified time (most recent first)
+        results.sort_by_cached_key(|file| {
+            std::cmp::Reverse(
+                std::fs::metadata(&file.path)
+                    .and_then(|m| m.modified())
+                    .ok(),
+            )
+        });
 
+        Ok(results)
+    }
+
+    /// Every file under `dir` that is a candidate for grep, respecting the
+    /// repository's ignore files when it has them
+    pub(crate) fn grep_candidate_files(dir: &Path) -> Vec<PathBuf> {
+        Self::workspace_entries(dir).0
+    }
+
+    /// Every file and directory under `dir` that searches should see, as
+    /// `(files, directories)`. Respects the repository's ignore files when it
+    /// has them, and never descends into `.git`: the standard filters skip
+    /// hidden entries and the fallback walk skips `.git` by name.
+    pub(crate) fn workspace_entries(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        Self::walk_workspace(dir, |path, is_dir| {
+            if is_dir {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+            ControlFlow::Continue(())
+        });
+        (files, dirs)
+    }
+
+    /// Calls `visit` with each entry `workspace_entries` would return and
+    /// whether it's a directory, until it breaks
+    pub(crate) fn walk_workspace<F>(dir: &Path, mut visit: F)
+    where
+        F: FnMut(PathBuf, bool) -> ControlFlow<()>,
+    {
         // Check if we should use repository ignore files
         let project_root = Self::find_project_root(dir);
         let use_repo_ignore = project_root
@@ -352,37 +659,14 @@ impl SearchTools {
                 .hidden(false)
                 .standard_filters(true) // Respect .gitignore, .ignore, etc.
                 .build();
-
             for entry in walker.flatten() {
-                let path = entry.path();
-
-                // Skip non-files
-                if !entry.file_type().is_some_and(|ft| ft.is_file()) {
-                    continue;
-                }
-
-                // Skip if doesn't match include pattern
-                if let Some(ref include_regex) = include_regex {
-                    if !include_regex.is_match(&path.to_string_lossy()) {
-                        continue;
-                    }
-                }
-
-                // Skip binary/generated files
-                if Self::is_likely_binary_or_generated(path) {
-                    continue;
-                }
-
-                // Try to search within the file
-                if let Ok(file) = File::open(path) {
-                    let reader = BufReader::new(file);
-                    for (line_num, line_result) in reader.lines().enumerate() {
-                        if let Ok(line) = line_result {
-                            if regex.is_match(&line) {
-                                matches.push((path.to_path_buf(), line_num + 1, line.clone()));
-                            }
-                        }
-                    }
+                let flow = match entry.file_type() {
+                    Some(ft) if ft.is_file() => visit(entry.into_path(), false),
+                    Some(ft) if ft.is_dir() => visit(entry.into_path(), true),
+                    _ => ControlFlow::Continue(()),
+                };
+                if flow.is_break() {
+                    return;
                 }
             }
         } else {
@@ -392,44 +676,264 @@ impl SearchTools {
                 .into_iter()
                 .filter_entry(|e| !Self::should_skip_dir(e))
                 .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_file())
             {
-                let path = entry.path();
-
-                // Skip if doesn't match include pattern
-                if let Some(ref include_regex) = include_regex {
-                    if !include_regex.is_match(&path.to_string_lossy()) {
-                        continue;
-                    }
+                let flow = if entry.file_type().is_file() {
+                    visit(entry.into_path(), false)
+                } else if entry.file_type().is_dir() {
+                    visit(entry.into_path(), true)
+                } else {
+                    ControlFlow::Continue(())
+                };
+                if flow.is_break() {
+                    return;
                 }
+            }
+        }
+    }
 
-                // Skip binary files and generated files
-                if Self::is_likely_binary_or_generated(path) {
-                    continue;
-                }
+    /// File extensions searched for a `type` filter such as "rs" or "py".
+    /// Unknown types are treated as a bare extension.
+    fn extensions_for_type(file_type: &str) -> Result<Vec<String>> {
+        let file_type = file_type.trim().trim_start_matches('.').to_lowercase();
+        if file_type.is_empty() {
+            return Err(anyhow!("File type filter must not be empty"));
+        }
+
+        let extensions: &[&str] = match file_type.as_str() {
+            "rs" | "rust" => &["rs"],
+            "py" | "python" => &["py", "pyi"],
+            "ts" | "typescript" => &["ts", "tsx", "mts", "cts"],
+            "js" | "javascript" => &["js", "jsx", "mjs", "cjs"],
+            "go" => &["go"],
+            "java" => &["java"],
+            "kt" | "kotlin" => &["kt", "kts"],
+            "c" => &["c", "h"],
+            "cpp" | "c++" => &["cpp", "cc", "cxx", "hpp", "hh", "hxx", "h"],
+            "cs" | "csharp" => &["cs"],
+            "rb" | "ruby" => &["rb"],
+            "php" => &["php"],
+            "swift" => &["swift"],
+            "sh" | "shell" => &["sh", "bash", "zsh"],
+            "md" | "markdown" => &["md", "markdown"],
+            "json" => &["json"],
+            "toml" => &["toml"],
+            "yaml" | "yml" => &["yaml", "yml"],
+            "html" => &["html", "htm"],
+            "css" => &["css", "scss", "sass", "less"],
+            _ => return Ok(vec![file_type]),
+        };
+        Ok(extensions.iter().map(|ext| ext.to_string()).collect())
+    }
+
+    /// Matches a single line at a time, collecting context lines around each
+    fn search_file_lines(path: &Path, regex: &Regex, options: &GrepOptions) -> Vec<GrepMatch> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Vec::new(),
+        };
+        let reader = BufReader::new(file);
+
+        // Context needs the lines after a match, so without it we can stream
+        if options.before_context == 0 && options.after_context == 0 {
+            return reader
+                .lines()
+                .enumerate()
+                .filter_map(|(line_num, line)| {
+                    let line = line.ok()?;
+                    regex.is_match(&line).then(|| GrepMatch {
+                        line_number: line_num + 1,
+                        text: line,
+                        before: Vec::new(),
+                        after: Vec::new(),
+                    })
+                })
+                .collect();
+        }
+
+        let lines: Vec<String> = match reader.lines().collect::<std::io::Result<_>>() {
+            Ok(lines) => lines,
+            Err(_) => return Vec::new(),
+        };
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line))
+            .map(|(index, line)| Self::match_with_context(&lines, index, index, line, options))
+            .collect()
+    }
+
+    /// Matches against the whole file, so a pattern can span lines. Each match
+    /// is reported at its first line with every line it touches.
+    fn search_file_multiline(path: &Path, regex: &Regex, options: &GrepOptions) -> Vec<GrepMatch> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return Vec::new(),
+        };
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+        // Byte offset where each line starts, to map matches back to lines
+        let mut line_starts = vec![0];
+        line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+        let line_of = |offset: usize| match line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+
+        let mut matches: Vec<GrepMatch> = Vec::new();
+        if lines.is_empty() {
+            return matches;
+        }
+        // Last line of the previous match, as several matches on the same
+        // lines are reported once
+        let mut previous_last: Option<usize> = None;
+        for found in regex.find_iter(&content) {
+            let first = line_of(found.start()).min(lines.len().saturating_sub(1));
+            // A match ending with a newline doesn't touch the following line
+            let end = if found.end() > found.start() {
+                found.end() - 1
+            } else {
+                found.end()
+            };
+            let last = line_of(end).clamp(first, lines.len().saturating_sub(1));
+
+            if previous_last.is_some_and(|previous| first <= previous) {
+                continue;
+            }
+            previous_last = Some(last);
+            let text = lines[first..=last].join("\n");
+            matches.push(Self::match_with_context(
+                &lines, first, last, &text, options,
+            ));
+        }
+        matches
+    }
+
+    fn match_with_context(
+        lines: &[String],
+        first: usize,
+        last: usize,
+        text: &str,
+        options: &GrepOptions,
+    ) -> GrepMatch {
+        let before_start = first.saturating_sub(options.before_context);
+        let after_end = (last + 1 + options.after_context).min(lines.len());
+        GrepMatch {
+            line_number: first + 1,
+            text: text.to_string(),
+            before: (before_start..first)
+                .map(|i| (i + 1, lines[i].clone()))
+                .collect(),
+            after: (last + 1..after_end)
+                .map(|i| (i + 1, lines[i].clone()))
+                .collect(),
+        }
+    }
 
-                // Try to open file
-                if let Ok(file) = File::open(path) {
-                    let reader = BufReader::new(file);
-                    for (line_num, line_result) in reader.lines().enumerate() {
-                        if let Ok(line) = line_result {
-                            if regex.is_match(&line) {
-                                matches.push((path.to_path_buf(), line_num + 1, line.clone()));
-                            }
+    /// Render grep results for the model in the requested output mode, showing
+    /// only the `offset..offset + head_limit` slice of entries. Entries are
+    /// matches in content mode and files in the other modes.
+    pub fn format_grep_results(
+        results: &[GrepFileMatches],
+        pattern: &str,
+        options: &GrepOptions,
+    ) -> String {
+        let total = match options.output_mode {
+            GrepOutputMode::Content => results.iter().map(|f| f.matches.len()).sum(),
+            GrepOutputMode::FilesWithMatches | GrepOutputMode::Count => results.len(),
+        };
+        if total == 0 {
+            return format!("No matches found for pattern '{pattern}'");
+        }
+
+        let start = options.offset.min(total);
+        let end = match options.head_limit {
+            Some(limit) => start.saturating_add(limit).min(total),
+            None => total,
+        };
+
+        let mut output = match options.output_mode {
+            GrepOutputMode::Content => {
+                format!("Found {total} matches for pattern '{pattern}':\n\n")
+            }
+            GrepOutputMode::FilesWithMatches => {
+                format!("Found {total} files with matches for pattern '{pattern}':\n\n")
+            }
+            GrepOutputMode::Count => {
+                let matches: usize = results.iter().map(|f| f.matches.len()).sum();
+                format!("Found {matches} matches in {total} files for pattern '{pattern}':\n\n")
+            }
+        };
+
+        match options.output_mode {
+            GrepOutputMode::Content => {
+                let has_context = options.before_context > 0 || options.after_context > 0;
+                let shown: Vec<(&PathBuf, &GrepMatch)> = results
+                    .iter()
+                    .flat_map(|file| file.matches.iter().map(move |m| (&file.path, m)))
+                    .skip(start)
+                    .take(end - start)
+                    .collect();
+
+                // Collect each file's lines first, so overlapping context is
+                // printed once and a context line that is itself a match is
+                // marked as one. Separate groups get a "--" between them like
+                // grep does.
+                let mut first_group = true;
+                for file_matches in shown.chunk_by(|a, b| a.0 == b.0) {
+                    let path_str = file_matches[0].0.display();
+                    let mut lines: BTreeMap<usize, (bool, &str)> = BTreeMap::new();
+                    for (_, m) in file_matches {
+                        for (line, text) in m.before.iter().chain(&m.after) {
+                            lines.entry(*line).or_insert((false, text));
+                        }
+                        // A multiline match prints each of its lines as a match
+                        for (i, text) in m.text.split('\n').enumerate() {
+                            lines.insert(m.line_number + i, (true, text));
+                        }
+                    }
+
+                    let mut previous: Option<usize> = None;
+                    for (line, (is_match, text)) in lines {
+                        let gap = previous.map_or(!first_group, |p| line > p + 1);
+                        if has_context && gap {
+                            output.push_str("--\n");
                         }
+                        let separator = if is_match { ':' } else { '-' };
+                        output.push_str(&format!("{path_str}{separator}{line}{separator}{text}\n"));
+                        previous = Some(line);
                     }
+                    first_group = false;
+                }
+            }
+            GrepOutputMode::FilesWithMatches => {
+                for file in &results[start..end] {
+                    output.push_str(&format!("{}\n", file.path.display()));
+                }
+            }
+            GrepOutputMode::Count => {
+                for file in &results[start..end] {
+                    output.push_str(&format!("{}:{}\n", file.path.display(), file.matches.len()));
                 }
             }
         }
 
-        // Sort by last modified time (most recent first)
-        matches.sort_by(|a, b| {
-            let a_modified = std::fs::metadata(&a.0).and_then(|m| m.modified()).ok();
-            let b_modified = std::fs::metadata(&b.0).and_then(|m| m.modified()).ok();
-            b_modified.cmp(&a_modified)
-        });
+        if start > 0 || end < total {
+            let noun = match options.output_mode {
+                GrepOutputMode::Content => "matches",
+                _ => "files",
+            };
+            output.push_str(&format!(
+                "\n[Showing {noun} {}-{end} of {total}; {} more.",
+                start + 1,
+                total - end
+            ));
+            if end < total {
+                output.push_str(&format!(" Pass offset={end} to see the next page"));
+            }
+            output.push_str("]\n");
+        }
 
-        Ok(matches)
+        output
     }
 }
 
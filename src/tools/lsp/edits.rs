@@ -0,0 +1,212 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::models::{Position, Range, TextEdit};
+use super::protocol::uri_to_path;
+use crate::tools::fs::file_ops::ChangeSet;
+
+/// Turns the edits language servers send back into file changes
+pub struct WorkspaceEdits;
+
+impl WorkspaceEdits {
+    /// Apply text edits to a document. Positions count UTF-16 code units, as
+    /// LSP does by default. Edits may not overlap; ones starting at the same
+    /// place are applied in the order given.
+    pub fn apply_text_edits(content: &str, edits: &[TextEdit]) -> Result<String> {
+        let line_starts = line_starts(content);
+        let mut spans = Vec::with_capacity(edits.len());
+        for edit in edits {
+            let start = byte_offset(content, &line_starts, &edit.range.start);
+            let end = byte_offset(content, &line_starts, &edit.range.end);
+            if end < start {
+                bail!(
+                    "Edit range ends before it starts at {}:{}",
+                    edit.range.start.line + 1,
+                    edit.range.start.character + 1
+                );
+            }
+            spans.push((start, end, edit.new_text.as_str()));
+        }
+        // Stable, so insertions at one place keep their order
+        spans.sort_by_key(|(start, end, _)| (*start, *end));
+
+        let mut result = String::with_capacity(content.len());
+        let mut cursor = 0;
+        for (start, end, text) in spans {
+            if start < cursor {
+                bail!("Edits overlap");
+            }
+            result.push_str(&content[cursor..start]);
+            result.push_str(text);
+            cursor = end;
+        }
+        result.push_str(&content[cursor..]);
+        Ok(result)
+    }
+
+    /// The single edit that turns `old` into `new`: everything between their
+    /// common start and common end, for sending only what changed
+    pub fn diff_edit(old: &str, new: &str) -> TextEdit {
+        let mut prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        // Never between the two halves of a character or of a "\r\n"
+        while !old.is_char_boundary(prefix)
+            || !new.is_char_boundary(prefix)
+            || (prefix > 0 && old.as_bytes()[prefix - 1] == b'\r')
+        {
+            prefix -= 1;
+        }
+
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while suffix > 0
+            && (!old.is_char_boundary(old.len() - suffix)
+                || !new.is_char_boundary(new.len() - suffix)
+                || old[..old.len() - suffix].ends_with('\r'))
+        {
+            suffix -= 1;
+        }
+
+        TextEdit {
+            range: Range {
+                start: position_of(old, prefix),
+                end: position_of(old, old.len() - suffix),
+            },
+            new_text: new[prefix..new.len() - suffix].to_string(),
+        }
+    }
+
+    /// Stage a `WorkspaceEdit`, from either its `documentChanges` (text edits
+    /// and file creates, renames and deletes, in order) or its `changes` map
+    pub fn to_change_set(edit: &Value) -> Result<ChangeSet> {
+        let mut changes = ChangeSet::new();
+
+        if let Some(document_changes) = edit.get("documentChanges").and_then(Value::as_array) {
+            for change in document_changes {
+                let options = change.get("options");
+                let option = |name: &str| {
+                    options
+                        .and_then(|options| options.get(name))
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false)
+                };
+
+                match change.get("kind").and_then(Value::as_str) {
+                    Some("create") => {
+                        let path = path_of(change, "uri")?;
+                        if changes.content(&path)?.is_some() && !option("overwrite") {
+                            if option("ignoreIfExists") {
+                                continue;
+                            }
+                            bail!("Cannot create {}: file already exists", path.display());
+                        }
+                        changes.write(&path, String::new());
+                    }
+                    Some("rename") => {
+                        let from = path_of(change, "oldUri")?;
+                        let to = path_of(change, "newUri")?;
+                        let overwrite = option("overwrite");
+                        if !overwrite && option("ignoreIfExists") && changes.content(&to)?.is_some()
+                        {
+                            continue;
+                        }
+                        changes.rename(&from, &to, overwrite)?;
+                    }
+                    Some("delete") => {
+                        let path = path_of(change, "uri")?;
+                        if option("ignoreIfNotExists") && changes.content(&path)?.is_none() {
+                            continue;
+                        }
+                        changes.delete(&path)?;
+                    }
+                    Some(kind) => bail!("Unsupported workspace edit operation: {kind}"),
+                    None => {
+                        let document = change
+                            .get("textDocument")
+                            .ok_or_else(|| anyhow!("Document change without a textDocument"))?;
+                        let path = path_of(document, "uri")?;
+                        Self::stage_text_edits(&mut changes, &path, change.get("edits"))?;
+                    }
+                }
+            }
+        } else if let Some(changes_by_uri) = edit.get("changes").and_then(Value::as_object) {
+            for (uri, edits) in changes_by_uri {
+                Self::stage_text_edits(&mut changes, &uri_to_path(uri), Some(edits))?;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn stage_text_edits(changes: &mut ChangeSet, path: &Path, edits: Option<&Value>) -> Result<()> {
+        let edits: Vec<TextEdit> = serde_json::from_value(edits.cloned().unwrap_or_default())
+            .with_context(|| format!("Invalid text edits for {}", path.display()))?;
+        let content = changes
+            .content(path)?
+            .ok_or_else(|| anyhow!("Cannot edit {}: file does not exist", path.display()))?;
+
+        let updated = Self::apply_text_edits(&content, &edits)
+            .with_context(|| format!("Failed to apply edits to {}", path.display()))?;
+        if updated != content {
+            changes.write(path, updated);
+        }
+        Ok(())
+    }
+}
+
+fn path_of(value: &Value, field: &str) -> Result<PathBuf> {
+    value
+        .get(field)
+        .and_then(Value::as_str)
+        .map(uri_to_path)
+        .ok_or_else(|| anyhow!("Workspace edit is missing `{field}`"))
+}
+
+/// Byte offset of the start of each line
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Byte offset of an LSP position. Positions past the end of a line clamp to
+/// it, and ones past the last line to the end of the document.
+fn byte_offset(content: &str, line_starts: &[usize], position: &Position) -> usize {
+    let Some(&start) = line_starts.get(position.line as usize) else {
+        return content.len();
+    };
+    let end = line_starts
+        .get(position.line as usize + 1)
+        .copied()
+        .unwrap_or(content.len());
+    let line = content[start..end].trim_end_matches(['\n', '\r']);
+
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    start + line.len()
+}
+
+/// LSP position of a byte offset, counting UTF-16 code units within the line
+fn position_of(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
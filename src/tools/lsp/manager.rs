@@ -1,23 +1,158 @@
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once, OnceLock};
+use std::time::{Duration, Instant};
 
-use super::servers::LspServer;
+use super::edits::WorkspaceEdits;
+use super::registry::{ServerRegistry, ServerSpec};
+use super::servers::{LspServer, INDEXING_WAIT};
+use crate::app::logger::{format_log_with_color, LogLevel};
+use crate::tools::fs::file_ops::{ChangeSet, FileOps};
 use crate::tools::lsp::models::{
-    CodeLens, DocumentSymbol, Location, LspServerType, Position, Range, SemanticTokens,
+    hover_text, symbol_kind_name, CallDirection, CodeAction, CodeLens, Command, Diagnostic,
+    DocumentSymbol, DocumentSymbolResponse, FileDiagnostics, HierarchyItem, HierarchyNode,
+    Location, Position, Range, SemanticTokens, TextEdit, TypeDirection, WorkspaceSymbol,
 };
+use crate::tools::lsp::protocol::{path_to_uri, uri_to_path};
+
+/// How long the Diagnostics tool waits for a file to be checked
+pub const DIAGNOSTICS_WAIT: Duration = Duration::from_secs(15);
+/// How long an edit waits for the diagnostics of the files it changed
+pub const EDIT_DIAGNOSTICS_WAIT: Duration = Duration::from_secs(3);
+/// Servers of the global manager unused for this long are stopped
+pub const IDLE_SHUTDOWN: Duration = Duration::from_secs(10 * 60);
+/// How often the global manager looks for idle servers
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Restarts of a server that keeps exiting before giving up on it
+pub const MAX_RESTARTS: u32 = 3;
+/// Items a call or type hierarchy lists at most, as each one expanded costs
+/// a request
+pub const MAX_HIERARCHY_NODES: usize = 100;
+
+/// A file synced to the server that handles it
+struct OpenDocument {
+    path: PathBuf,
+    uri: String,
+    server_key: String,
+    /// Whether the server was sent new content for it
+    changed: bool,
+}
+
+/// A code action offered by a server, kept by id until it's applied or the
+/// next listing replaces it
+#[derive(Clone)]
+struct ListedCodeAction {
+    server_key: String,
+    /// The `CodeAction` or `Command` exactly as the server sent it
+    action: serde_json::Value,
+}
+
+/// A listed code action ready to apply: resolved, with its edit staged but
+/// not yet written
+#[derive(Debug)]
+pub struct PreparedCodeAction {
+    pub id: u32,
+    pub title: String,
+    /// The action's own edit. Edits its command asks for are only known once
+    /// the command runs.
+    pub changes: ChangeSet,
+    /// The command run after the edit is written
+    pub command: Option<Command>,
+    server_key: String,
+}
+
+/// What applying a code action did
+#[derive(Debug, Clone)]
+pub struct AppliedCodeAction {
+    pub title: String,
+    /// Diff of the files changed, empty if none were
+    pub diff: String,
+    /// Files written or deleted
+    pub paths: Vec<PathBuf>,
+}
+
+/// A running server behind its own lock, which a request holds until it's
+/// answered. What the status RPC reports is kept beside it, so reading it
+/// never waits on a request.
+struct ManagedServer {
+    name: String,
+    root: PathBuf,
+    pid: u32,
+    server: Mutex<LspServer>,
+    /// Open documents and work in progress as last seen while it was free
+    last_seen: Mutex<(usize, Vec<String>)>,
+}
+
+impl ManagedServer {
+    fn new(server: LspServer) -> Self {
+        Self {
+            name: server.spec().name.clone(),
+            root: server.root_path().to_path_buf(),
+            pid: server.pid(),
+            last_seen: Mutex::new((server.open_document_count(), server.active_progress())),
+            server: Mutex::new(server),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, LspServer>> {
+        self.server
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock the {} server mutex", self.name))
+    }
+
+    fn remember(&self, server: &LspServer) {
+        if let Ok(mut last_seen) = self.last_seen.lock() {
+            *last_seen = (server.open_document_count(), server.active_progress());
+        }
+    }
+}
 
 /// Manager for LSP servers
 pub struct LspServerManager {
-    servers: Mutex<HashMap<String, LspServer>>,
+    /// Locked only to look servers up, add or remove them
+    servers: Mutex<HashMap<String, Arc<ManagedServer>>>,
+    /// How often the server under each key was restarted after exiting
+    restarts: Mutex<HashMap<String, u32>>,
+    /// The servers that may be started, the global registry if not set
+    registry: Option<ServerRegistry>,
+    /// Code actions from the last listing, by id
+    code_actions: Mutex<HashMap<u32, ListedCodeAction>>,
+    /// Ids keep counting across listings, so a stale one is never reused
+    next_code_action_id: AtomicU32,
+}
+
+/// What the status RPC reports about a running language server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub name: String,
+    pub root: PathBuf,
+    /// "running", "busy" while it handles a request or reports work in
+    /// progress, or "exited"
+    pub state: String,
+    pub pid: u32,
+    /// Resident memory in bytes, where the platform reports it
+    pub memory_bytes: Option<u64>,
+    pub open_documents: usize,
+    /// Seconds since the server last handled a request or a document change
+    pub idle_secs: u64,
+    /// Times the server was restarted after exiting
+    pub restarts: u32,
+    /// Titles of the work in progress, such as indexing
+    pub progress: Vec<String>,
 }
 
 impl Default for LspServerManager {
     fn default() -> Self {
         Self {
             servers: Mutex::new(HashMap::new()),
+            restarts: Mutex::new(HashMap::new()),
+            registry: None,
+            code_actions: Mutex::new(HashMap::new()),
+            next_code_action_id: AtomicU32::new(1),
         }
     }
 }
@@ -27,37 +162,142 @@ impl LspServerManager {
         Self::default()
     }
 
-    /// Get or create an LSP server for a specific language and workspace
-    pub fn get_server(&self, server_type: &LspServerType, workspace_path: &Path) -> Result<String> {
+    /// Start servers from `registry` instead of the global one
+    pub fn with_registry(mut self, registry: ServerRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    fn registry(&self) -> Result<&ServerRegistry> {
+        match &self.registry {
+            Some(registry) => Ok(registry),
+            None => ServerRegistry::global(),
+        }
+    }
+
+    /// The manager shared by the agent's tools, so servers and the
+    /// documents opened on them outlive a single tool call. Servers it
+    /// started are stopped once idle for [`IDLE_SHUTDOWN`].
+    pub fn global() -> &'static LspServerManager {
+        static MANAGER: OnceLock<LspServerManager> = OnceLock::new();
+        static REAPER: Once = Once::new();
+
+        let manager = MANAGER.get_or_init(LspServerManager::new);
+        REAPER.call_once(|| {
+            std::thread::spawn(move || loop {
+                std::thread::sleep(IDLE_CHECK_INTERVAL);
+                manager.stop_idle(IDLE_SHUTDOWN);
+            });
+        });
+        manager
+    }
+
+    fn server_key(spec: &ServerSpec, workspace_path: &Path) -> String {
+        format!("{}-{}", spec.name, workspace_path.display())
+    }
+
+    /// Get or create a language server for a workspace
+    pub fn get_server(&self, spec: &ServerSpec, workspace_path: &Path) -> Result<String> {
+        // Create a unique key for this server combination
+        let server_key = Self::server_key(spec, workspace_path);
+
+        // A server that exited is replaced, unless it keeps exiting
+        if let Ok(managed) = self.server(&server_key) {
+            let mut server = managed.lock()?;
+            if !server.has_exited() {
+                return Ok(server_key);
+            }
+            let stderr = server.stderr_hint();
+            drop(server);
+            self.forget_server(&server_key, &managed)?;
+
+            let mut restarts = self
+                .restarts
+                .lock()
+                .map_err(|_| anyhow!("Failed to lock restarts mutex"))?;
+            let count = restarts.entry(server_key.clone()).or_insert(0);
+            if *count >= MAX_RESTARTS {
+                return Err(anyhow!(
+                    "Language server {} for {} exited {} times; not restarting it{}",
+                    spec.name,
+                    workspace_path.display(),
+                    *count + 1,
+                    stderr
+                ));
+            }
+            *count += 1;
+            eprintln!(
+                "{}",
+                format_log_with_color(
+                    LogLevel::Warning,
+                    &format!(
+                        "Language server {} for {} exited; restarting it",
+                        spec.name,
+                        workspace_path.display()
+                    )
+                )
+            );
+        }
+
+        // Start and initialize a new server without the map locked, so the
+        // servers already running stay usable meanwhile
+        let mut server = LspServer::start(spec, workspace_path)?;
+        server.initialize()?;
+        let managed = Arc::new(ManagedServer::new(server));
+
         let mut servers = self
             .servers
             .lock()
             .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
+        // If another request started one first, that one is kept and this
+        // one shut down once the map is unlocked
+        if !servers.contains_key(&server_key) {
+            servers.insert(server_key.clone(), managed);
+        }
+        Ok(server_key)
+    }
 
-        // Create a unique key for this server combination
-        let server_key = format!("{:?}-{}", server_type, workspace_path.display());
+    /// The server registered under `server_key`
+    fn server(&self, server_key: &str) -> Result<Arc<ManagedServer>> {
+        self.servers
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock servers mutex"))?
+            .get(server_key)
+            .cloned()
+            .ok_or_else(|| anyhow!("Server not found: {}", server_key))
+    }
 
-        if !servers.contains_key(&server_key) {
-            // Start a new server
-            let mut server = match server_type {
-                LspServerType::Python => LspServer::start_python_server(workspace_path)?,
-                LspServerType::Rust => LspServer::start_rust_server(workspace_path)?,
-            };
+    /// Every running server, so they can be used without the map locked
+    fn running_servers(&self) -> Vec<(String, Arc<ManagedServer>)> {
+        self.servers
+            .lock()
+            .map(|servers| {
+                servers
+                    .iter()
+                    .map(|(key, managed)| (key.clone(), managed.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-            // Initialize the server
-            server.initialize()?;
-            servers.insert(server_key.clone(), server);
+    /// Drop `managed` from the map, unless it was replaced already
+    fn forget_server(&self, server_key: &str, managed: &Arc<ManagedServer>) -> Result<()> {
+        let mut servers = self
+            .servers
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
+        if servers
+            .get(server_key)
+            .is_some_and(|current| Arc::ptr_eq(current, managed))
+        {
+            servers.remove(server_key);
         }
-
-        Ok(server_key)
+        Ok(())
     }
 
-    /// Get document symbols for a file
-    pub fn document_symbol(
-        &self,
-        file_path: &str,
-        server_type: &LspServerType,
-    ) -> Result<Vec<DocumentSymbol>> {
+    /// Resolve `file_path`, start the server for its workspace if needed and
+    /// sync the file's current content to it
+    fn open_document(&self, file_path: &str, server_type: Option<&str>) -> Result<OpenDocument> {
         // Normalize the path - convert relative to absolute
         let path = if Path::new(file_path).is_relative() {
             let current_dir = std::env::current_dir()?;
@@ -70,255 +310,96 @@ impl LspServerManager {
             return Err(anyhow!("File does not exist: {}", path.display()));
         }
 
-        eprintln!("Processing file: {}", path.display());
-
-        // Use find_workspace_root with the Path
-        let workspace_path = self.find_workspace_root(&path)?;
-        let server_key = self.get_server(server_type, &workspace_path)?;
-
-        // Create a proper URI with file:// scheme
-        let uri = format!("file://{}", path.to_string_lossy().replace('\\', "/"));
-        eprintln!("Using URI: {uri}");
+        let spec = self.registry()?.resolve(&path, server_type)?.clone();
+        let workspace_path = spec.workspace_root(&path)?;
+        let server_key = self.get_server(&spec, &workspace_path)?;
 
+        let uri = path_to_uri(&path);
         let file_content = fs::read_to_string(&path)?;
-        let language_id = match server_type {
-            LspServerType::Python => "python",
-            LspServerType::Rust => "rust",
-        };
 
-        let mut servers = self
-            .servers
-            .lock()
-            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
-        let server = servers
-            .get_mut(&server_key)
-            .ok_or_else(|| anyhow!("Server not found: {}", server_key))?;
+        let managed = self.server(&server_key)?;
+        let mut server = managed.lock()?;
 
         // Notify the server about the file
-        server.did_open_text_document(&uri, language_id, 1, &file_content)?;
-
-        // Get document symbols
-        let result = server.document_symbol(&uri)?;
-
-        // We now know from the test logs that pyright returns the SymbolInformation format
-        // Let's try to parse that directly first
-        eprintln!("Response: {result:?}");
-
-        if let Some(array) = result.as_array() {
-            if !array.is_empty() {
-                // Check if this is a SymbolInformation format (has location field)
-                if array[0].get("location").is_some() {
-                    let symbols: Vec<super::models::SymbolInformation> =
-                        serde_json::from_value(result.clone())?;
-
-                    // Convert SymbolInformation to DocumentSymbol
-                    let converted_symbols = symbols
-                        .into_iter()
-                        .map(|si| DocumentSymbol {
-                            name: si.name,
-                            detail: Some(si.container_name.unwrap_or_default()),
-                            kind: si.kind,
-                            range: si.location.range.clone(),
-                            selection_range: si.location.range,
-                            children: None,
-                        })
-                        .collect();
-
-                    return Ok(converted_symbols);
-                }
-            }
-        }
+        let changed = server.sync_document(&uri, &spec.language_id_for(&path), &file_content)?;
+        managed.remember(&server);
 
-        // Otherwise try the normal DocumentSymbolResponse parsing
-        match serde_json::from_value::<super::models::DocumentSymbolResponse>(result.clone()) {
-            Ok(response) => {
-                match response {
-                    super::models::DocumentSymbolResponse::HierarchicalSymbols(symbols) => {
-                        Ok(symbols)
-                    }
-                    super::models::DocumentSymbolResponse::FlatSymbols(symbols) => {
-                        // Convert SymbolInformation to DocumentSymbol
-                        let converted_symbols = symbols
-                            .into_iter()
-                            .map(|si| DocumentSymbol {
-                                name: si.name,
-                                detail: Some(si.container_name.unwrap_or_default()),
-                                kind: si.kind,
-                                range: si.location.range.clone(),
-                                selection_range: si.location.range,
-                                children: None,
-                            })
-                            .collect();
-                        Ok(converted_symbols)
-                    }
-                }
+        Ok(OpenDocument {
+            path,
+            uri,
+            server_key,
+            changed,
+        })
+    }
+
+    /// Run `f` on the server registered under `server_key`
+    fn with_server<T>(
+        &self,
+        server_key: &str,
+        f: impl FnOnce(&mut LspServer) -> Result<T>,
+    ) -> Result<T> {
+        let managed = self.server(server_key)?;
+        let mut server = managed.lock()?;
+        let result = f(&mut server).map_err(|e| {
+            if server.has_exited() {
+                anyhow!(
+                    "{e}\nThe language server exited; it will be restarted on the next request."
+                )
+            } else {
+                e
             }
-            Err(e) => {
-                eprintln!("Error parsing DocumentSymbolResponse: {e}");
-
-                // Fallback for when the server returns a null or other unexpected response
-                // Create a synthetic document symbol
-                if result.is_null() || result.as_array().is_none_or(|a| a.is_empty()) {
-                    // The actual response from pyright is coming in the logs (see the test output)
-                    // Let's create symbols for the most common Python features from the test file
-                    let symbols = vec![
-                        DocumentSymbol {
-                            name: "MyClass".to_string(),
-                            detail: None,
-                            kind: 5, // Class
-                            range: Range {
-                                start: Position {
-                                    line: 1,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 9,
-                                    character: 0,
-                                },
-                            },
-                            selection_range: Range {
-                                start: Position {
-                                    line: 1,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 9,
-                                    character: 0,
-                                },
-                            },
-                            children: None,
-                        },
-                        DocumentSymbol {
-                            name: "greet".to_string(),
-                            detail: Some("MyClass".to_string()),
-                            kind: 6, // Method
-                            range: Range {
-                                start: Position {
-                                    line: 7,
-                                    character: 4,
-                                },
-                                end: Position {
-                                    line: 9,
-                                    character: 0,
-                                },
-                            },
-                            selection_range: Range {
-                                start: Position {
-                                    line: 7,
-                                    character: 4,
-                                },
-                                end: Position {
-                                    line: 9,
-                                    character: 0,
-                                },
-                            },
-                            children: None,
-                        },
-                        DocumentSymbol {
-                            name: "add".to_string(),
-                            detail: None,
-                            kind: 12, // Function
-                            range: Range {
-                                start: Position {
-                                    line: 11,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 13,
-                                    character: 0,
-                                },
-                            },
-                            selection_range: Range {
-                                start: Position {
-                                    line: 11,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 13,
-                                    character: 0,
-                                },
-                            },
-                            children: None,
-                        },
-                        DocumentSymbol {
-                            name: "CONSTANT".to_string(),
-                            detail: None,
-                            kind: 14, // Constant
-                            range: Range {
-                                start: Position {
-                                    line: 15,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 15,
-                                    character: 0,
-                                },
-                            },
-                            selection_range: Range {
-                                start: Position {
-                                    line: 15,
-                                    character: 0,
-                                },
-                                end: Position {
-                                    line: 15,
-                                    character: 0,
-                                },
-                            },
-                            children: None,
-                        },
-                    ];
-
-                    Ok(symbols)
+        });
+        managed.remember(&server);
+        result
+    }
+
+    /// Get document symbols for a file
+    pub fn document_symbol(
+        &self,
+        file_path: &str,
+        server_type: Option<&str>,
+    ) -> Result<Vec<DocumentSymbol>> {
+        let document = self.open_document(file_path, server_type)?;
+        let path = &document.path;
+
+        self.with_server(&document.server_key, |server| {
+            // Get document symbols
+            let result = server.document_symbol(&document.uri)?;
+
+            let response = DocumentSymbolResponse::from_value(result)
+                .map_err(|e| anyhow!("Failed to parse document symbols: {}", e))?;
+            if response.is_empty() {
+                let busy = server.active_progress();
+                return Err(if busy.is_empty() {
+                    anyhow!(
+                        "The language server reported no symbols for {}",
+                        path.display()
+                    )
                 } else {
-                    Err(anyhow!("Failed to parse document symbols: {}", e))
-                }
+                    anyhow!(
+                        "No symbols available for {} yet: the language server is still busy ({})",
+                        path.display(),
+                        busy.join(", ")
+                    )
+                });
             }
-        }
+
+            Ok(response.into_document_symbols())
+        })
     }
 
     /// Get semantic tokens for a file
     pub fn semantic_tokens(
         &self,
         file_path: &str,
-        server_type: &LspServerType,
+        server_type: Option<&str>,
     ) -> Result<SemanticTokens> {
-        // Normalize the path - convert relative to absolute
-        let path = if Path::new(file_path).is_relative() {
-            let current_dir = std::env::current_dir()?;
-            current_dir.join(file_path).canonicalize()?
-        } else {
-            PathBuf::from(file_path).canonicalize()?
-        };
-
-        if !path.exists() {
-            return Err(anyhow!("File does not exist: {}", path.display()));
-        }
-
-        // Use find_workspace_root with the Path
-        let workspace_path = self.find_workspace_root(&path)?;
-        let server_key = self.get_server(server_type, &workspace_path)?;
-
-        // Create a proper URI with file:// scheme
-        let uri = format!("file://{}", path.to_string_lossy().replace('\\', "/"));
-        let file_content = fs::read_to_string(&path)?;
-        let language_id = match server_type {
-            LspServerType::Python => "python",
-            LspServerType::Rust => "rust",
-        };
-
-        let mut servers = self
-            .servers
-            .lock()
-            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
-        let server = servers
-            .get_mut(&server_key)
-            .ok_or_else(|| anyhow!("Server not found: {}", server_key))?;
-
-        // Notify the server about the file
-        server.did_open_text_document(&uri, language_id, 1, &file_content)?;
+        let document = self.open_document(file_path, server_type)?;
 
         // Get semantic tokens
-        let result = server.semantic_tokens(&uri)?;
+        let result = self.with_server(&document.server_key, |server| {
+            server.semantic_tokens(&document.uri)
+        })?;
 
         // Parse the result
         let tokens: SemanticTokens = serde_json::from_value(result)?;
@@ -326,44 +407,13 @@ impl LspServerManager {
     }
 
     /// Get code lenses for a file
-    pub fn code_lens(&self, file_path: &str, server_type: &LspServerType) -> Result<Vec<CodeLens>> {
-        // Normalize the path - convert relative to absolute
-        let path = if Path::new(file_path).is_relative() {
-            let current_dir = std::env::current_dir()?;
-            current_dir.join(file_path).canonicalize()?
-        } else {
-            PathBuf::from(file_path).canonicalize()?
-        };
-
-        if !path.exists() {
-            return Err(anyhow!("File does not exist: {}", path.display()));
-        }
-
-        // Use find_workspace_root with the Path
-        let workspace_path = self.find_workspace_root(&path)?;
-        let server_key = self.get_server(server_type, &workspace_path)?;
-
-        // Create a proper URI with file:// scheme
-        let uri = format!("file://{}", path.to_string_lossy().replace('\\', "/"));
-        let file_content = fs::read_to_string(&path)?;
-        let language_id = match server_type {
-            LspServerType::Python => "python",
-            LspServerType::Rust => "rust",
-        };
-
-        let mut servers = self
-            .servers
-            .lock()
-            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
-        let server = servers
-            .get_mut(&server_key)
-            .ok_or_else(|| anyhow!("Server not found: {}", server_key))?;
-
-        // Notify the server about the file
-        server.did_open_text_document(&uri, language_id, 1, &file_content)?;
+    pub fn code_lens(&self, file_path: &str, server_type: Option<&str>) -> Result<Vec<CodeLens>> {
+        let document = self.open_document(file_path, server_type)?;
 
         // Get code lenses
-        let result = server.code_lens(&uri)?;
+        let result = self.with_server(&document.server_key, |server| {
+            server.code_lens(&document.uri)
+        })?;
 
         // Parse the result
         let lenses: Vec<CodeLens> = serde_json::from_value(result)?;
@@ -375,118 +425,951 @@ impl LspServerManager {
         &self,
         file_path: &str,
         position: &Position,
-        server_type: &LspServerType,
+        server_type: Option<&str>,
     ) -> Result<Vec<Location>> {
-        // Normalize the path - convert relative to absolute
-        let path = if Path::new(file_path).is_relative() {
-            let current_dir = std::env::current_dir()?;
-            current_dir.join(file_path).canonicalize()?
+        let document = self.open_document(file_path, server_type)?;
+
+        // Get definition
+        let result = self.with_server(&document.server_key, |server| {
+            server.definition(&document.uri, position.line, position.character)
+        })?;
+
+        // Parse the result
+        Ok(Location::from_response(result))
+    }
+
+    /// Find every reference to the symbol at a position
+    pub fn references(
+        &self,
+        file_path: &str,
+        position: &Position,
+        server_type: Option<&str>,
+        include_declaration: bool,
+    ) -> Result<Vec<Location>> {
+        let document = self.open_document(file_path, server_type)?;
+
+        let result = self.with_server(&document.server_key, |server| {
+            server.references(
+                &document.uri,
+                position.line,
+                position.character,
+                include_declaration,
+            )
+        })?;
+
+        let mut locations = Location::from_response(result);
+        locations.sort_by(|a, b| {
+            a.uri
+                .cmp(&b.uri)
+                .then(a.range.start.line.cmp(&b.range.start.line))
+                .then(a.range.start.character.cmp(&b.range.start.character))
+        });
+        Ok(locations)
+    }
+
+    /// Type information and documentation for the symbol at a position, or
+    /// `None` if the server has nothing to show there
+    pub fn hover(
+        &self,
+        file_path: &str,
+        position: &Position,
+        server_type: Option<&str>,
+    ) -> Result<Option<String>> {
+        let document = self.open_document(file_path, server_type)?;
+
+        let result = self.with_server(&document.server_key, |server| {
+            server.hover(&document.uri, position.line, position.character)
+        })?;
+
+        Ok(hover_text(&result))
+    }
+
+    /// The changes that rename the symbol at a position everywhere it is
+    /// used, staged but not yet written
+    pub fn rename(
+        &self,
+        file_path: &str,
+        position: &Position,
+        new_name: &str,
+        server_type: Option<&str>,
+    ) -> Result<ChangeSet> {
+        let document = self.open_document(file_path, server_type)?;
+
+        let edit = self.with_server(&document.server_key, |server| {
+            let prepared =
+                server.prepare_rename(&document.uri, position.line, position.character)?;
+            if prepared.is_null() {
+                return Err(anyhow!(
+                    "There is no symbol that can be renamed at {}:{} in {}",
+                    position.line,
+                    position.character,
+                    file_path
+                ));
+            }
+            server.rename(&document.uri, position.line, position.character, new_name)
+        })?;
+
+        let changes = WorkspaceEdits::to_change_set(&edit)?;
+        if changes.is_empty() {
+            return Err(anyhow!(
+                "The language server returned no changes for renaming to '{}'",
+                new_name
+            ));
+        }
+        Ok(changes)
+    }
+
+    /// The callers or callees of the function at a position, and theirs,
+    /// down to `depth` levels
+    pub fn call_hierarchy(
+        &self,
+        file_path: &str,
+        position: &Position,
+        direction: CallDirection,
+        depth: u32,
+        server_type: Option<&str>,
+    ) -> Result<Vec<HierarchyNode>> {
+        let document = self.open_document(file_path, server_type)?;
+
+        self.with_server(&document.server_key, |server| {
+            let prepared =
+                server.prepare_call_hierarchy(&document.uri, position.line, position.character)?;
+            let roots = HierarchyItem::from_response(prepared);
+            if roots.is_empty() {
+                return Err(anyhow!(
+                    "There is no function at {}:{} in {}",
+                    position.line,
+                    position.character,
+                    file_path
+                ));
+            }
+            expand_hierarchy(roots, depth, |item| {
+                Ok(match direction {
+                    CallDirection::Incoming => {
+                        HierarchyNode::from_calls(server.incoming_calls(item)?, "from")
+                    }
+                    CallDirection::Outgoing => {
+                        HierarchyNode::from_calls(server.outgoing_calls(item)?, "to")
+                    }
+                })
+            })
+        })
+    }
+
+    /// The supertypes or subtypes of the type at a position, and theirs,
+    /// down to `depth` levels
+    pub fn type_hierarchy(
+        &self,
+        file_path: &str,
+        position: &Position,
+        direction: TypeDirection,
+        depth: u32,
+        server_type: Option<&str>,
+    ) -> Result<Vec<HierarchyNode>> {
+        let document = self.open_document(file_path, server_type)?;
+
+        self.with_server(&document.server_key, |server| {
+            let prepared =
+                server.prepare_type_hierarchy(&document.uri, position.line, position.character)?;
+            let roots = HierarchyItem::from_response(prepared);
+            if roots.is_empty() {
+                return Err(anyhow!(
+                    "There is no type at {}:{} in {}",
+                    position.line,
+                    position.character,
+                    file_path
+                ));
+            }
+            expand_hierarchy(roots, depth, |item| {
+                let related = match direction {
+                    TypeDirection::Supertypes => server.supertypes(item)?,
+                    TypeDirection::Subtypes => server.subtypes(item)?,
+                };
+                Ok(HierarchyItem::from_response(related)
+                    .into_iter()
+                    .map(HierarchyNode::new)
+                    .collect())
+            })
+        })
+    }
+
+    /// A call or type hierarchy as an indented tree, one item per line with
+    /// its `path:line:col`. Each level is marked with `marker`, e.g. `←` for
+    /// callers; call sites follow as `at line:col`.
+    pub fn format_hierarchy(roots: &[HierarchyNode], marker: &str) -> String {
+        fn write(
+            node: &HierarchyNode,
+            marker: &str,
+            level: usize,
+            cwd: &Path,
+            output: &mut Vec<String>,
+        ) {
+            let item = &node.item;
+            let path = uri_to_path(&item.uri);
+            let prefix = match level {
+                0 => String::new(),
+                _ => format!("{}{marker} ", "  ".repeat(level - 1)),
+            };
+            let mut line = format!(
+                "{prefix}{} ({}) {}:{}:{}",
+                item.name,
+                symbol_kind_name(item.kind),
+                path.strip_prefix(cwd).unwrap_or(&path).display(),
+                item.selection_range.start.line + 1,
+                item.selection_range.start.character + 1
+            );
+            if !node.call_sites.is_empty() {
+                let sites: Vec<String> = node
+                    .call_sites
+                    .iter()
+                    .map(|site| format!("{}:{}", site.start.line + 1, site.start.character + 1))
+                    .collect();
+                line.push_str(&format!(" at {}", sites.join(", ")));
+            }
+            if node.repeated {
+                line.push_str(" (see above)");
+            }
+            output.push(line);
+            for child in &node.children {
+                write(child, marker, level + 1, cwd, output);
+            }
+        }
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut output = Vec::new();
+        for root in roots {
+            write(root, marker, 0, &cwd, &mut output);
+        }
+        output.join("\n")
+    }
+
+    /// The changes that format a whole file, staged but not yet written.
+    /// Empty when the file is already formatted.
+    pub fn format(&self, file_path: &str, server_type: Option<&str>) -> Result<ChangeSet> {
+        let document = self.open_document(file_path, server_type)?;
+        let content = fs::read_to_string(&document.path)?;
+        let (tab_size, insert_spaces) = indentation_of(&content);
+
+        let result = self.with_server(&document.server_key, |server| {
+            server.formatting(&document.uri, tab_size, insert_spaces)
+        })?;
+        let edits: Vec<TextEdit> = if result.is_null() {
+            Vec::new()
         } else {
-            PathBuf::from(file_path).canonicalize()?
+            serde_json::from_value(result)
+                .map_err(|e| anyhow!("Failed to parse formatting edits: {}", e))?
         };
 
-        if !path.exists() {
-            return Err(anyhow!("File does not exist: {}", path.display()));
+        let formatted = WorkspaceEdits::apply_text_edits(&content, &edits)?;
+        let mut changes = ChangeSet::new();
+        if formatted != content {
+            changes.write(&document.path, formatted);
         }
+        Ok(changes)
+    }
 
-        // Use find_workspace_root with the Path
-        let workspace_path = self.find_workspace_root(&path)?;
-        let server_key = self.get_server(server_type, &workspace_path)?;
+    /// The fixes and refactorings available for a range of a file, given
+    /// the diagnostics there. They replace the ones listed before, and can
+    /// be applied by id with [`LspServerManager::apply_code_action`].
+    pub fn code_actions(
+        &self,
+        file_path: &str,
+        range: &Range,
+        server_type: Option<&str>,
+    ) -> Result<Vec<CodeAction>> {
+        let generation = self.generation_for(file_path, server_type);
+        let document = self.open_document(file_path, server_type)?;
 
-        // Create a proper URI with file:// scheme
-        let uri = format!("file://{}", path.to_string_lossy().replace('\\', "/"));
-        let file_content = fs::read_to_string(&path)?;
-        let language_id = match server_type {
-            LspServerType::Python => "python",
-            LspServerType::Rust => "rust",
+        let result = self.with_server(&document.server_key, |server| {
+            // Quick fixes are only offered for the diagnostics sent along
+            let diagnostics = match server.diagnostics(&document.path) {
+                Some(diagnostics) if !document.changed => diagnostics,
+                _ => server
+                    .wait_for_diagnostics(&document.path, generation, EDIT_DIAGNOSTICS_WAIT)
+                    .unwrap_or_default(),
+            };
+            let diagnostics: Vec<Diagnostic> = diagnostics
+                .into_iter()
+                .filter(|diagnostic| diagnostic.range.intersects(range))
+                .collect();
+            server.code_action(&document.uri, range, &diagnostics)
+        })?;
+
+        let mut listed = self
+            .code_actions
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock code actions mutex"))?;
+        listed.clear();
+        let entries = match result {
+            serde_json::Value::Array(entries) => entries,
+            _ => Vec::new(),
         };
+        let mut actions = Vec::with_capacity(entries.len());
+        for action in entries {
+            let id = self.next_code_action_id.fetch_add(1, Ordering::SeqCst);
+            if let Some(summary) = CodeAction::from_value(id, &action) {
+                actions.push(summary);
+                listed.insert(
+                    id,
+                    ListedCodeAction {
+                        server_key: document.server_key.clone(),
+                        action,
+                    },
+                );
+            }
+        }
+        Ok(actions)
+    }
 
-        let mut servers = self
-            .servers
+    /// Apply a listed code action: write its edit, then run its command,
+    /// applying any edits the server asks for while the command runs
+    pub fn apply_code_action(&self, id: u32) -> Result<AppliedCodeAction> {
+        let prepared = self.prepare_code_action(id)?;
+        self.apply_prepared_code_action(prepared)
+    }
+
+    /// Resolve a listed code action and stage its edit without writing
+    /// anything, so it can be previewed before it's applied
+    pub fn prepare_code_action(&self, id: u32) -> Result<PreparedCodeAction> {
+        let listed = self
+            .code_actions
             .lock()
-            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
-        let server = servers
-            .get_mut(&server_key)
-            .ok_or_else(|| anyhow!("Server not found: {}", server_key))?;
+            .map_err(|_| anyhow!("Failed to lock code actions mutex"))?
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown code action {}. List the available ones with CodeActions first.",
+                    id
+                )
+            })?;
+        let server_key = listed.server_key;
+        let action = listed.action;
+        let title = action["title"].as_str().unwrap_or_default().to_string();
 
-        // Notify the server about the file
-        server.did_open_text_document(&uri, language_id, 1, &file_content)?;
+        // A bare `Command` has the command name as its `command`
+        let (edit, command) = if action["command"].is_string() {
+            (None, Some(action))
+        } else {
+            if let Some(reason) = action.pointer("/disabled/reason").and_then(|r| r.as_str()) {
+                return Err(anyhow!("Code action '{}' is disabled: {}", title, reason));
+            }
+            let action = match action.get("edit") {
+                Some(_) => action,
+                None => {
+                    self.with_server(&server_key, |server| server.resolve_code_action(&action))?
+                }
+            };
+            (action.get("edit").cloned(), action.get("command").cloned())
+        };
 
-        // Get definition
-        let result = server.definition(&uri, position.line, position.character)?;
+        let changes = match edit {
+            Some(edit) => WorkspaceEdits::to_change_set(&edit)?,
+            None => ChangeSet::new(),
+        };
+        let command = command
+            .map(|command| {
+                serde_json::from_value::<Command>(command)
+                    .map_err(|e| anyhow!("Invalid command in code action '{}': {}", title, e))
+            })
+            .transpose()?;
 
-        // Parse the result
-        let locations: Vec<Location> = serde_json::from_value(result)?;
-        Ok(locations)
+        Ok(PreparedCodeAction {
+            id,
+            title,
+            changes,
+            command,
+            server_key,
+        })
     }
 
-    /// Find the root directory of a workspace
-    fn find_workspace_root(&self, file_path: &Path) -> Result<PathBuf> {
-        let parent_dir = file_path
-            .parent()
-            .ok_or_else(|| anyhow!("Cannot determine parent directory"))?;
+    /// Write a prepared code action's edit, then run its command. The action
+    /// stays listed until this succeeds, so a failed one can be tried again.
+    pub fn apply_prepared_code_action(
+        &self,
+        prepared: PreparedCodeAction,
+    ) -> Result<AppliedCodeAction> {
+        let PreparedCodeAction {
+            id,
+            title,
+            changes,
+            command,
+            server_key,
+        } = prepared;
 
-        let mut current_dir = parent_dir.to_path_buf();
+        let (mut diff, mut paths) = self.apply_change_set(&server_key, &changes)?;
+        if let Some(command) = command {
+            let edits = self.with_server(&server_key, |server| {
+                server.execute_command(
+                    &command.command,
+                    command.arguments.as_deref().unwrap_or_default(),
+                )
+            })?;
+            for edit in edits {
+                let changes = WorkspaceEdits::to_change_set(&edit)?;
+                let (edit_diff, edit_paths) = self.apply_change_set(&server_key, &changes)?;
+                diff.push_str(&edit_diff);
+                paths.extend(edit_paths);
+            }
+        }
 
-        // For Python standalone files, check the file extension
-        if let Some(ext) = file_path.extension() {
-            if ext == "py" {
-                // For standalone .py files without a project structure,
-                // we'll use the file's parent directory directly
-                eprintln!("Python file detected: {}", file_path.to_string_lossy());
-                return Ok(current_dir);
+        // The action is used up, and once files changed the other actions
+        // listed were computed for the old content
+        if let Ok(mut listed) = self.code_actions.lock() {
+            if paths.is_empty() {
+                listed.remove(&id);
+            } else {
+                listed.clear();
             }
         }
+        Ok(AppliedCodeAction { title, diff, paths })
+    }
+
+    /// Write staged changes to disk and send the new content to the server,
+    /// so a command that runs next sees it. Returns the diff and the files
+    /// changed.
+    fn apply_change_set(
+        &self,
+        server_key: &str,
+        changes: &ChangeSet,
+    ) -> Result<(String, Vec<PathBuf>)> {
+        if changes.is_empty() {
+            return Ok((String::new(), Vec::new()));
+        }
+        let diff = FileOps::apply_changes(changes)?;
+
+        // The files are written either way; a server that missed them is
+        // sent them again with the diagnostics after the edit
+        self.with_server(server_key, |server| {
+            for path in changes.paths() {
+                let uri = path_to_uri(path);
+                match fs::read_to_string(path) {
+                    Ok(content) if server.spec().handles(path) => {
+                        let language_id = server.spec().language_id_for(path);
+                        server.sync_document(&uri, &language_id, &content).ok();
+                    }
+                    Ok(_) => {}
+                    Err(_) => {
+                        server.did_close(&uri).ok();
+                    }
+                }
+            }
+            Ok(())
+        })
+        .ok();
+        Ok((diff, changes.paths().to_vec()))
+    }
 
-        // Look for common project indicators
-        loop {
-            // Check for Rust project
-            if current_dir.join("Cargo.toml").exists() {
-                return Ok(current_dir);
+    /// One line per code action, for the CodeActions tool
+    pub fn format_code_actions(actions: &[CodeAction]) -> String {
+        actions
+            .iter()
+            .map(CodeAction::format)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Search the symbols of a whole workspace by name
+    pub fn workspace_symbol(
+        &self,
+        query: &str,
+        workspace: &Path,
+        server_type: Option<&str>,
+    ) -> Result<Vec<WorkspaceSymbol>> {
+        let workspace = workspace
+            .canonicalize()
+            .map_err(|e| anyhow!("Cannot resolve {}: {}", workspace.display(), e))?;
+        let server_key = match server_type {
+            Some(name) => {
+                let registry = self.registry()?;
+                let spec = registry
+                    .find(name)
+                    .ok_or_else(|| anyhow!("Unknown language server '{}'", name))?;
+                self.get_server(spec, &workspace)?
             }
+            None => match self.running_server_for(&workspace) {
+                Some(server_key) => server_key,
+                None => {
+                    let spec = self.registry()?.for_workspace(&workspace).ok_or_else(|| {
+                        anyhow!(
+                            "No language server found for {}. Pass server_type to choose one.",
+                            workspace.display()
+                        )
+                    })?;
+                    self.get_server(spec, &workspace)?
+                }
+            },
+        };
 
-            // Check for Python project
-            if current_dir.join("pyproject.toml").exists()
-                || current_dir.join("setup.py").exists()
-                || current_dir.join("requirements.txt").exists()
-            {
-                return Ok(current_dir);
+        let result = self.with_server(&server_key, |server| {
+            // An index still being built gives partial results
+            server.wait_until_idle(INDEXING_WAIT);
+            server.workspace_symbol(query)
+        })?;
+
+        Ok(WorkspaceSymbol::from_response(result))
+    }
+
+    /// A running server whose project contains `workspace` or lies within it
+    fn running_server_for(&self, workspace: &Path) -> Option<String> {
+        let mut keys: Vec<String> = self
+            .running_servers()
+            .into_iter()
+            .filter(|(_, managed)| {
+                workspace.starts_with(&managed.root) || managed.root.starts_with(workspace)
+            })
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        keys.into_iter().next()
+    }
+
+    /// Diagnostics for a file, starting its language server if needed and
+    /// waiting for the server to check it
+    pub fn diagnostics(
+        &self,
+        file_path: &str,
+        server_type: Option<&str>,
+    ) -> Result<Vec<Diagnostic>> {
+        let generation = self.generation_for(file_path, server_type);
+        let document = self.open_document(file_path, server_type)?;
+
+        self.with_server(&document.server_key, |server| {
+            // The server already checked this content
+            if !document.changed {
+                if let Some(diagnostics) = server.diagnostics(&document.path) {
+                    return Ok(diagnostics);
+                }
+            }
+            Ok(server
+                .wait_for_diagnostics(&document.path, generation, DIAGNOSTICS_WAIT)
+                .unwrap_or_default())
+        })
+    }
+
+    /// The diagnostics generation of the server that would handle
+    /// `file_path`, or 0 if none is running yet. Taken before the file is
+    /// synced, so a publication triggered by opening it counts as new.
+    fn generation_for(&self, file_path: &str, server_type: Option<&str>) -> u64 {
+        let Ok(path) = std::env::current_dir().map(|cwd| cwd.join(file_path)) else {
+            return 0;
+        };
+        let Ok(path) = path.canonicalize() else {
+            return 0;
+        };
+        let Ok(spec) = self.registry().and_then(|r| r.resolve(&path, server_type)) else {
+            return 0;
+        };
+        let Ok(workspace_path) = spec.workspace_root(&path) else {
+            return 0;
+        };
+        let server_key = Self::server_key(spec, &workspace_path);
+        self.server(&server_key)
+            .ok()
+            .and_then(|managed| {
+                managed
+                    .lock()
+                    .ok()
+                    .map(|server| server.diagnostics_generation())
+            })
+            .unwrap_or(0)
+    }
+
+    /// Every non-empty set of diagnostics published for files under
+    /// `workspace`. If no server is running there yet, one is started from
+    /// the project markers and given time to finish indexing.
+    pub fn workspace_diagnostics(&self, workspace: &Path) -> Result<Vec<FileDiagnostics>> {
+        let workspace = workspace
+            .canonicalize()
+            .map_err(|e| anyhow!("Cannot resolve {}: {}", workspace.display(), e))?;
+
+        let running = self.collect_workspace_diagnostics(&workspace)?;
+        if let Some(files) = running {
+            return Ok(files);
+        }
+
+        let Some(spec) = self.registry()?.for_workspace(&workspace) else {
+            return Err(anyhow!(
+                "No language server is running for {}. Check a file first to start one.",
+                workspace.display()
+            ));
+        };
+        let server_key = self.get_server(spec, &workspace)?;
+        self.with_server(&server_key, |server| {
+            server.wait_until_idle(INDEXING_WAIT);
+            Ok(())
+        })?;
+
+        Ok(self
+            .collect_workspace_diagnostics(&workspace)?
+            .unwrap_or_default())
+    }
+
+    /// Diagnostics under `workspace` from the servers running there, or
+    /// `None` if there are none
+    fn collect_workspace_diagnostics(
+        &self,
+        workspace: &Path,
+    ) -> Result<Option<Vec<FileDiagnostics>>> {
+        let mut found_server = false;
+        let mut files = Vec::new();
+        for (_, managed) in self.running_servers() {
+            let root = &managed.root;
+            if !root.starts_with(workspace) && !workspace.starts_with(root) {
+                continue;
             }
+            found_server = true;
+            let server = managed.lock()?;
+            files.extend(
+                server
+                    .diagnostics_since(0)
+                    .into_iter()
+                    .filter(|(path, diagnostics)| {
+                        path.starts_with(workspace) && !diagnostics.is_empty()
+                    }),
+            );
+        }
 
-            // Check for git repository
-            if current_dir.join(".git").exists() {
-                return Ok(current_dir);
+        if !found_server {
+            return Ok(None);
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Some(files))
+    }
+
+    /// After a tool changed `paths` on disk, send the new content of each to
+    /// the running servers that handle it and collect fresh diagnostics.
+    /// Servers aren't started for this: files no server is running for are
+    /// left out. Every file is sent before any is waited for, and all the
+    /// waiting shares one [`EDIT_DIAGNOSTICS_WAIT`].
+    pub fn diagnostics_after_edit(&self, paths: &[PathBuf]) -> Vec<FileDiagnostics> {
+        let servers = self.running_servers();
+        if servers.is_empty() {
+            return Vec::new();
+        }
+
+        // The generation each server was at before it was sent anything, and
+        // the files it was sent
+        let mut sent: Vec<Option<(u64, Vec<PathBuf>)>> = vec![None; servers.len()];
+        for path in paths {
+            let Some(path) = absolute_path(path) else {
+                continue;
+            };
+            let uri = path_to_uri(&path);
+            let Ok(content) = fs::read_to_string(&path) else {
+                // Deleted or moved away, so servers should forget it
+                for (_, managed) in &servers {
+                    if let Ok(mut server) = managed.lock() {
+                        server.did_close(&uri).ok();
+                    }
+                }
+                continue;
+            };
+
+            for ((_, managed), sent) in servers.iter().zip(sent.iter_mut()) {
+                let Ok(mut server) = managed.lock() else {
+                    continue;
+                };
+                if !server.spec().handles(&path) || !path.starts_with(server.root_path()) {
+                    continue;
+                }
+                let language_id = server.spec().language_id_for(&path);
+
+                let generation = server.diagnostics_generation();
+                let synced = server
+                    .sync_document(&uri, &language_id, &content)
+                    .and_then(|_| server.did_save(&uri, &content));
+                if let Err(e) = synced {
+                    eprintln!(
+                        "{}",
+                        format_log_with_color(
+                            LogLevel::Warning,
+                            &format!(
+                                "Failed to send {} to the language server: {}",
+                                path.display(),
+                                e
+                            )
+                        )
+                    );
+                    continue;
+                }
+                sent.get_or_insert_with(|| (generation, Vec::new()))
+                    .1
+                    .push(path.clone());
             }
+        }
 
-            // Go up one directory
-            if let Some(parent) = current_dir.parent() {
-                current_dir = parent.to_path_buf();
-            } else {
-                // If we reached the root, use the file's directory
-                return Ok(file_path
-                    .parent()
-                    .ok_or_else(|| anyhow!("Cannot determine parent directory"))?
-                    .to_path_buf());
+        let deadline = Instant::now() + EDIT_DIAGNOSTICS_WAIT;
+        let mut files = Vec::new();
+        for ((_, managed), sent) in servers.iter().zip(sent) {
+            let Some((generation, paths)) = sent else {
+                continue;
+            };
+            if let Ok(server) = managed.lock() {
+                files.extend(server.wait_for_all_diagnostics(&paths, generation, deadline));
             }
         }
+        files
+    }
+
+    /// `path:line:col: source line` for each location, counted from 1 as
+    /// editors show them, so results can be read and grepped for directly
+    pub fn format_locations(locations: &[Location]) -> String {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        let mut output = Vec::with_capacity(locations.len());
+        for location in locations {
+            let path = uri_to_path(&location.uri);
+            let lines = files.entry(path.clone()).or_insert_with(|| {
+                fs::read_to_string(&path)
+                    .map(|content| content.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            });
+            let snippet = lines
+                .get(location.range.start.line as usize)
+                .map(|line| line.trim())
+                .unwrap_or_default();
+            let display = path
+                .strip_prefix(&cwd)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            output.push(format!(
+                "{}:{}:{}: {}",
+                display,
+                location.range.start.line + 1,
+                location.range.start.character + 1,
+                snippet
+            ));
+        }
+        output.join("\n")
+    }
+
+    /// One line per diagnostic, errors first, at most `limit` lines. Paths
+    /// under the working directory are shown relative to it.
+    pub fn format_diagnostics(files: &[FileDiagnostics], limit: usize) -> String {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let mut lines: Vec<(u32, String)> = Vec::new();
+        for (path, diagnostics) in files {
+            let display = path
+                .strip_prefix(&cwd)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            let mut diagnostics: Vec<&Diagnostic> = diagnostics.iter().collect();
+            diagnostics.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+            for diagnostic in diagnostics {
+                lines.push((
+                    diagnostic.severity.unwrap_or(1),
+                    diagnostic.format(&display),
+                ));
+            }
+        }
+        // Stable, so each severity keeps file and line order
+        lines.sort_by_key(|(severity, _)| *severity);
+
+        let total = lines.len();
+        let mut output: Vec<String> = lines
+            .into_iter()
+            .take(limit)
+            .map(|(_, line)| line)
+            .collect();
+        if total > limit {
+            output.push(format!("... and {} more", total - limit));
+        }
+        output.join("\n")
     }
 
     /// Stop all servers
     pub fn stop_all(&self) -> Result<()> {
-        let mut servers = self
+        let servers: Vec<Arc<ManagedServer>> = self
             .servers
             .lock()
-            .map_err(|_| anyhow!("Failed to lock servers mutex"))?;
+            .map_err(|_| anyhow!("Failed to lock servers mutex"))?
+            .drain()
+            .map(|(_, managed)| managed)
+            .collect();
 
-        for (_, server) in servers.iter_mut() {
-            if server.shutdown().is_err() {
+        for managed in servers {
+            if managed
+                .lock()
+                .and_then(|mut server| server.shutdown())
+                .is_err()
+            {
                 eprintln!("Error shutting down LSP server");
             }
         }
+        Ok(())
+    }
+
+    /// Stop servers that haven't been used for `max_idle`. Returns the names
+    /// of the servers stopped.
+    pub fn stop_idle(&self, max_idle: Duration) -> Vec<String> {
+        let idle: Vec<Arc<ManagedServer>> = {
+            let Ok(mut servers) = self.servers.lock() else {
+                return Vec::new();
+            };
+            // A server handling a request isn't idle
+            let keys: Vec<String> = servers
+                .iter()
+                .filter(|(_, managed)| {
+                    managed
+                        .server
+                        .try_lock()
+                        .is_ok_and(|server| server.idle_for() >= max_idle)
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.iter().filter_map(|key| servers.remove(key)).collect()
+        };
 
-        servers.clear();
+        let mut stopped = Vec::new();
+        for managed in idle {
+            eprintln!(
+                "{}",
+                format_log_with_color(
+                    LogLevel::Info,
+                    &format!(
+                        "Stopping idle language server {} for {}",
+                        managed.name,
+                        managed.root.display()
+                    )
+                )
+            );
+            if let Ok(mut server) = managed.lock() {
+                server.shutdown().ok();
+            }
+            stopped.push(managed.name.clone());
+        }
+        stopped
+    }
+
+    /// The servers this manager runs, sorted by name and project. Never
+    /// waits for a request: a server handling one is reported busy, with
+    /// what was last seen of it.
+    pub fn status(&self) -> Vec<ServerStatus> {
+        let restarts = self
+            .restarts
+            .lock()
+            .map(|restarts| restarts.clone())
+            .unwrap_or_default();
+
+        let mut status: Vec<ServerStatus> = self
+            .running_servers()
+            .into_iter()
+            .map(|(key, managed)| {
+                let (state, open_documents, idle_secs, progress) = match managed.server.try_lock() {
+                    Ok(mut server) => {
+                        let progress = server.active_progress();
+                        let state = if server.has_exited() {
+                            "exited"
+                        } else if !progress.is_empty() {
+                            "busy"
+                        } else {
+                            "running"
+                        };
+                        let idle_secs = server.idle_for().as_secs();
+                        (state, server.open_document_count(), idle_secs, progress)
+                    }
+                    Err(_) => {
+                        let (open_documents, progress) = managed
+                            .last_seen
+                            .lock()
+                            .map(|last_seen| last_seen.clone())
+                            .unwrap_or_default();
+                        ("busy", open_documents, 0, progress)
+                    }
+                };
+                ServerStatus {
+                    name: managed.name.clone(),
+                    root: managed.root.clone(),
+                    state: state.to_string(),
+                    pid: managed.pid,
+                    memory_bytes: resident_memory(managed.pid),
+                    open_documents,
+                    idle_secs,
+                    restarts: restarts.get(&key).copied().unwrap_or(0),
+                    progress,
+                }
+            })
+            .collect();
+        status.sort_by(|a, b| a.name.cmp(&b.name).then(a.root.cmp(&b.root)));
+        status
+    }
+}
+
+/// Build a hierarchy from `roots` down to `depth` levels, asking
+/// `children_of` for each item's children. An item already listed is
+/// listed again but not expanded, which also stops recursion; past
+/// [`MAX_HIERARCHY_NODES`] items nothing more is expanded.
+fn expand_hierarchy(
+    roots: Vec<HierarchyItem>,
+    depth: u32,
+    mut children_of: impl FnMut(&HierarchyItem) -> Result<Vec<HierarchyNode>>,
+) -> Result<Vec<HierarchyNode>> {
+    fn expand(
+        node: &mut HierarchyNode,
+        depth: u32,
+        seen: &mut HashSet<(String, u32, u32)>,
+        children_of: &mut impl FnMut(&HierarchyItem) -> Result<Vec<HierarchyNode>>,
+    ) -> Result<()> {
+        if depth == 0 || seen.len() >= MAX_HIERARCHY_NODES {
+            return Ok(());
+        }
+        let mut children = children_of(&node.item)?;
+        for child in &mut children {
+            if seen.insert(child.item.key()) {
+                expand(child, depth - 1, seen, children_of)?;
+            } else {
+                child.repeated = true;
+            }
+        }
+        node.children = children;
         Ok(())
     }
+
+    let mut seen: HashSet<(String, u32, u32)> = roots.iter().map(HierarchyItem::key).collect();
+    let mut nodes: Vec<HierarchyNode> = roots.into_iter().map(HierarchyNode::new).collect();
+    for node in &mut nodes {
+        expand(node, depth, &mut seen, &mut children_of)?;
+    }
+    Ok(nodes)
+}
+
+/// An absolute, symlink-free form of `path`, which may no longer exist
+fn absolute_path(path: &Path) -> Option<PathBuf> {
+    let path = match path.is_relative() {
+        true => std::env::current_dir().ok()?.join(path),
+        false => path.to_path_buf(),
+    };
+    path.canonicalize().ok().or_else(|| {
+        let parent = path.parent()?.canonicalize().ok()?;
+        Some(parent.join(path.file_name()?))
+    })
+}
+
+/// Resident memory of a process in bytes, read from `/proc` on Linux
+fn resident_memory(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Indentation to format a file with: tabs if any line is indented with
+/// one, else the smallest indent of at least two spaces, 4 by default
+fn indentation_of(content: &str) -> (u32, bool) {
+    if content.lines().any(|line| line.starts_with('\t')) {
+        return (4, false);
+    }
+    let tab_size = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .filter(|indent| *indent >= 2)
+        .min()
+        .unwrap_or(4);
+    (tab_size.min(8) as u32, true)
 }
 
 impl Drop for LspServerManager {
@@ -1,12 +1,30 @@
+mod edits;
 mod manager;
 mod models;
 mod protocol;
+mod registry;
 mod servers;
+mod transport;
 
-pub use manager::LspServerManager;
+pub use edits::WorkspaceEdits;
+pub use manager::{
+    AppliedCodeAction, LspServerManager, PreparedCodeAction, ServerStatus, DIAGNOSTICS_WAIT,
+    EDIT_DIAGNOSTICS_WAIT, IDLE_SHUTDOWN, MAX_HIERARCHY_NODES, MAX_RESTARTS,
+};
 pub use models::{
-    CodeLens, CodeLensParams as ModelsCodeLensParams, DefinitionParams, DocumentSymbol,
-    DocumentSymbolParams as ModelsDocumentSymbolParams, Location, LspServerType, Position, Range,
-    SemanticTokens, SemanticTokensParams as ModelsSemanticTokensParams, SymbolKind,
+    hover_text, symbol_kind_name, ApplyCodeActionParams, CallDirection, CallHierarchyParams,
+    CodeAction, CodeActionsParams, CodeLens, CodeLensParams as ModelsCodeLensParams,
+    DefinitionParams, Diagnostic, DiagnosticsParams, DocumentSymbol,
+    DocumentSymbolParams as ModelsDocumentSymbolParams, DocumentSymbolResponse, FileDiagnostics,
+    FormatParams, HierarchyItem, HierarchyNode, HoverParams, Location, Position, Range,
+    ReferencesParams, RenameParams, SemanticTokens,
+    SemanticTokensParams as ModelsSemanticTokensParams, SymbolKind, TextEdit, TypeDirection,
+    TypeHierarchyParams, WorkspaceSymbol, WorkspaceSymbolParams, DEFAULT_HIERARCHY_DEPTH,
+    MAX_HIERARCHY_DEPTH,
+};
+pub use protocol::{
+    path_to_uri, uri_to_path, CodeLensParams, DocumentSymbolParams, NotificationMessage,
+    ResponseError, ResponseMessage, SemanticTokensParams, WorkspaceFolder,
 };
-pub use protocol::{CodeLensParams, DocumentSymbolParams, SemanticTokensParams};
+pub use registry::{LspConfig, ServerRegistry, ServerSpec};
+pub use transport::{LspTransport, MAX_BUFFERED_NOTIFICATIONS};
@@ -1,34 +1,171 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-pub enum LspServerType {
-    Python,
-    Rust,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSymbolParams {
     pub file_path: String,
-    pub server_type: LspServerType,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SemanticTokensParams {
     pub file_path: String,
-    pub server_type: LspServerType,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeLensParams {
     pub file_path: String,
-    pub server_type: LspServerType,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefinitionParams {
     pub file_path: String,
     pub position: Position,
-    pub server_type: LspServerType,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferencesParams {
+    pub file_path: String,
+    pub position: Position,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+    /// Whether to list the declaration itself too, true by default
+    #[serde(default)]
+    pub include_declaration: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoverParams {
+    pub file_path: String,
+    pub position: Position,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceSymbolParams {
+    pub query: String,
+    /// A language server's name or language; picked by the project's root
+    /// markers when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+    /// The project to search, the current directory by default
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameParams {
+    pub file_path: String,
+    pub position: Position,
+    pub new_name: String,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatParams {
+    pub file_path: String,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeActionsParams {
+    pub file_path: String,
+    pub range: Range,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyCodeActionParams {
+    /// An id listed by the CodeActions tool
+    pub id: u32,
+}
+
+/// Which way to follow calls from a function
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallDirection {
+    /// The functions that call it
+    #[default]
+    Incoming,
+    /// The functions it calls
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallHierarchyParams {
+    pub file_path: String,
+    pub position: Position,
+    #[serde(default)]
+    pub direction: CallDirection,
+    /// Levels of calls to follow, [`DEFAULT_HIERARCHY_DEPTH`] if not given
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+/// Which way to follow inheritance from a type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeDirection {
+    /// The types it extends or implements
+    #[default]
+    Supertypes,
+    /// The types that extend or implement it
+    Subtypes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypeHierarchyParams {
+    pub file_path: String,
+    pub position: Position,
+    #[serde(default)]
+    pub direction: TypeDirection,
+    /// Levels of types to follow, [`DEFAULT_HIERARCHY_DEPTH`] if not given
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// A language server's name or language; picked by the file extension
+    /// when not given
+    #[serde(default)]
+    pub server_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsParams {
+    /// A file to check; its diagnostics are waited for
+    pub file_path: Option<String>,
+    /// A workspace directory whose published diagnostics are all reported
+    pub workspace: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,18 +174,156 @@ pub struct Position {
     pub character: u32,
 }
 
+impl Position {
+    /// For ordering positions within a document
+    fn key(&self) -> (u32, u32) {
+        (self.line, self.character)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Range {
     pub start: Position,
     pub end: Position,
 }
 
+impl Range {
+    /// Whether `other` lies within this range, ends included
+    pub fn contains(&self, other: &Range) -> bool {
+        self.start.key() <= other.start.key() && other.end.key() <= self.end.key()
+    }
+
+    /// Whether the two ranges share any position, ends included
+    pub fn intersects(&self, other: &Range) -> bool {
+        self.start.key() <= other.end.key() && other.start.key() <= self.end.key()
+    }
+}
+
+/// A replacement of a range of a document; `AnnotatedTextEdit`s parse as
+/// these too
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub range: Range,
+    #[serde(rename = "newText")]
+    pub new_text: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Location {
     pub uri: String,
     pub range: Range,
 }
 
+impl Location {
+    /// Parse a definition or references result, which may be `null`, one
+    /// `Location`, or an array of `Location`s or `LocationLink`s. Links point
+    /// at their target's name.
+    pub fn from_response(value: serde_json::Value) -> Vec<Location> {
+        let entries = match value {
+            serde_json::Value::Array(entries) => entries,
+            serde_json::Value::Null => Vec::new(),
+            single => vec![single],
+        };
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                if let Some(uri) = entry.get("targetUri").and_then(|u| u.as_str()) {
+                    let range = entry
+                        .get("targetSelectionRange")
+                        .or_else(|| entry.get("targetRange"))?;
+                    return Some(Location {
+                        uri: uri.to_string(),
+                        range: serde_json::from_value(range.clone()).ok()?,
+                    });
+                }
+                serde_json::from_value(entry).ok()
+            })
+            .collect()
+    }
+}
+
+/// A `workspace/symbol` result entry. Newer servers may leave out the range
+/// until the symbol is resolved, in which case it points at the file's start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceSymbol {
+    pub name: String,
+    pub kind: u32,
+    pub container_name: Option<String>,
+    pub location: Location,
+}
+
+impl WorkspaceSymbol {
+    pub fn from_response(value: serde_json::Value) -> Vec<WorkspaceSymbol> {
+        let serde_json::Value::Array(entries) = value else {
+            return Vec::new();
+        };
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                let location = entry.get("location")?;
+                let range = match location.get("range") {
+                    Some(range) => serde_json::from_value(range.clone()).ok()?,
+                    None => Range {
+                        start: Position {
+                            line: 0,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: 0,
+                            character: 0,
+                        },
+                    },
+                };
+                Some(WorkspaceSymbol {
+                    name: entry.get("name")?.as_str()?.to_string(),
+                    kind: entry.get("kind")?.as_u64()? as u32,
+                    container_name: entry
+                        .get("containerName")
+                        .and_then(|c| c.as_str())
+                        .filter(|c| !c.is_empty())
+                        .map(str::to_string),
+                    location: Location {
+                        uri: location.get("uri")?.as_str()?.to_string(),
+                        range,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// The text of a `textDocument/hover` result, or `None` if there's nothing
+/// to show. Contents may be `MarkupContent`, a `MarkedString` or an array of
+/// them; code blocks keep their fences so the language is visible.
+pub fn hover_text(value: &serde_json::Value) -> Option<String> {
+    fn render(contents: &serde_json::Value) -> String {
+        match contents {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(render)
+                .filter(|text| !text.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            serde_json::Value::Object(object) => {
+                let text = object
+                    .get("value")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                match object.get("language").and_then(|l| l.as_str()) {
+                    Some(language) => format!("```{language}\n{text}\n```"),
+                    None => text.to_string(),
+                }
+            }
+            _ => String::new(),
+        }
+    }
+
+    let text = render(value.get("contents")?);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum DocumentSymbolResponse {
@@ -56,6 +331,109 @@ pub enum DocumentSymbolResponse {
     FlatSymbols(Vec<SymbolInformation>),
 }
 
+impl DocumentSymbolResponse {
+    /// Parse a `textDocument/documentSymbol` result. Servers answer with
+    /// `null`, a `DocumentSymbol[]` tree or a flat `SymbolInformation[]`; the
+    /// shape is taken from the first entry, and entries that don't parse are
+    /// skipped rather than failing the whole response.
+    pub fn from_value(value: serde_json::Value) -> Result<Self, String> {
+        let entries = match value {
+            serde_json::Value::Null => return Ok(Self::HierarchicalSymbols(Vec::new())),
+            serde_json::Value::Array(entries) => entries,
+            other => return Err(format!("expected an array of symbols, got {other}")),
+        };
+        let flat = entries
+            .first()
+            .is_some_and(|entry| entry.get("location").is_some());
+
+        let total = entries.len();
+        let response = if flat {
+            Self::FlatSymbols(
+                entries
+                    .into_iter()
+                    .filter_map(|entry| serde_json::from_value(entry).ok())
+                    .collect(),
+            )
+        } else {
+            Self::HierarchicalSymbols(
+                entries
+                    .into_iter()
+                    .filter_map(|entry| serde_json::from_value(entry).ok())
+                    .collect(),
+            )
+        };
+        if total > 0 && response.is_empty() {
+            return Err(format!("none of the {total} symbols could be parsed"));
+        }
+        Ok(response)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::HierarchicalSymbols(symbols) => symbols.is_empty(),
+            Self::FlatSymbols(symbols) => symbols.is_empty(),
+        }
+    }
+
+    /// The symbols as a tree. Flat symbols are nested by range: each goes
+    /// under the smallest symbol enclosing it, so methods end up under their
+    /// class as they would in a `DocumentSymbol` response.
+    pub fn into_document_symbols(self) -> Vec<DocumentSymbol> {
+        let symbols = match self {
+            Self::HierarchicalSymbols(symbols) => return symbols,
+            Self::FlatSymbols(symbols) => symbols,
+        };
+
+        let mut flat: Vec<DocumentSymbol> = symbols
+            .into_iter()
+            .map(|si| DocumentSymbol {
+                name: si.name,
+                detail: None,
+                kind: si.kind,
+                range: si.location.range.clone(),
+                selection_range: si.location.range,
+                children: None,
+            })
+            .collect();
+        // Outer symbols first: by start, then the longest range
+        flat.sort_by(|a, b| {
+            a.range
+                .start
+                .key()
+                .cmp(&b.range.start.key())
+                .then(b.range.end.key().cmp(&a.range.end.key()))
+        });
+
+        fn attach(
+            stack: &mut [DocumentSymbol],
+            roots: &mut Vec<DocumentSymbol>,
+            symbol: DocumentSymbol,
+        ) {
+            match stack.last_mut() {
+                Some(parent) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+                None => roots.push(symbol),
+            }
+        }
+
+        let mut roots = Vec::new();
+        let mut stack: Vec<DocumentSymbol> = Vec::new();
+        for symbol in flat {
+            while stack
+                .last()
+                .is_some_and(|open| !open.range.contains(&symbol.range))
+            {
+                let done = stack.pop().expect("stack is not empty");
+                attach(&mut stack, &mut roots, done);
+            }
+            stack.push(symbol);
+        }
+        while let Some(done) = stack.pop() {
+            attach(&mut stack, &mut roots, done);
+        }
+        roots
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSymbol {
     pub name: String,
@@ -144,35 +522,40 @@ impl SymbolKind {
 impl DocumentSymbol {
     // Get a human-readable representation of the symbol kind
     pub fn kind_to_string(&self) -> String {
-        match self.kind {
-            1 => "File".to_string(),
-            2 => "Module".to_string(),
-            3 => "Namespace".to_string(),
-            4 => "Package".to_string(),
-            5 => "Class".to_string(),
-            6 => "Method".to_string(),
-            7 => "Property".to_string(),
-            8 => "Field".to_string(),
-            9 => "Constructor".to_string(),
-            10 => "Enum".to_string(),
-            11 => "Interface".to_string(),
-            12 => "Function".to_string(),
-            13 => "Variable".to_string(),
-            14 => "Constant".to_string(),
-            15 => "String".to_string(),
-            16 => "Number".to_string(),
-            17 => "Boolean".to_string(),
-            18 => "Array".to_string(),
-            19 => "Object".to_string(),
-            20 => "Key".to_string(),
-            21 => "Null".to_string(),
-            22 => "EnumMember".to_string(),
-            23 => "Struct".to_string(),
-            24 => "Event".to_string(),
-            25 => "Operator".to_string(),
-            26 => "TypeParameter".to_string(),
-            _ => format!("Unknown ({})", self.kind),
-        }
+        symbol_kind_name(self.kind)
+    }
+}
+
+/// Human-readable name of an LSP `SymbolKind` number
+pub fn symbol_kind_name(kind: u32) -> String {
+    match kind {
+        1 => "File".to_string(),
+        2 => "Module".to_string(),
+        3 => "Namespace".to_string(),
+        4 => "Package".to_string(),
+        5 => "Class".to_string(),
+        6 => "Method".to_string(),
+        7 => "Property".to_string(),
+        8 => "Field".to_string(),
+        9 => "Constructor".to_string(),
+        10 => "Enum".to_string(),
+        11 => "Interface".to_string(),
+        12 => "Function".to_string(),
+        13 => "Variable".to_string(),
+        14 => "Constant".to_string(),
+        15 => "String".to_string(),
+        16 => "Number".to_string(),
+        17 => "Boolean".to_string(),
+        18 => "Array".to_string(),
+        19 => "Object".to_string(),
+        20 => "Key".to_string(),
+        21 => "Null".to_string(),
+        22 => "EnumMember".to_string(),
+        23 => "Struct".to_string(),
+        24 => "Event".to_string(),
+        25 => "Operator".to_string(),
+        26 => "TypeParameter".to_string(),
+        _ => format!("Unknown ({kind})"),
     }
 }
 
@@ -194,3 +577,209 @@ pub struct Command {
     pub command: String,
     pub arguments: Option<Vec<serde_json::Value>>,
 }
+
+/// Levels a call or type hierarchy follows when no depth is given
+pub const DEFAULT_HIERARCHY_DEPTH: u32 = 2;
+/// The most levels a call or type hierarchy follows
+pub const MAX_HIERARCHY_DEPTH: u32 = 5;
+
+/// A function or type in a call or type hierarchy, as the server describes
+/// it. Sent back as is to ask for its callers, callees, supertypes or
+/// subtypes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HierarchyItem {
+    pub name: String,
+    pub kind: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: String,
+    pub range: Range,
+    pub selection_range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl HierarchyItem {
+    /// Parse a prepare or supertypes/subtypes result, an array of items or
+    /// `null`
+    pub fn from_response(value: serde_json::Value) -> Vec<HierarchyItem> {
+        match value {
+            serde_json::Value::Array(items) => items
+                .into_iter()
+                .filter_map(|item| serde_json::from_value(item).ok())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Identifies the item when the same function or type turns up again
+    pub(crate) fn key(&self) -> (String, u32, u32) {
+        (
+            self.uri.clone(),
+            self.selection_range.start.line,
+            self.selection_range.start.character,
+        )
+    }
+}
+
+/// An item in a call or type hierarchy with what was found below it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HierarchyNode {
+    pub item: HierarchyItem,
+    /// Where the calls between this item and its parent are: in this item
+    /// for callers, in the parent for callees. Empty in type hierarchies.
+    pub call_sites: Vec<Range>,
+    pub children: Vec<HierarchyNode>,
+    /// Already shown above, so its children aren't listed again
+    pub repeated: bool,
+}
+
+impl HierarchyNode {
+    pub fn new(item: HierarchyItem) -> Self {
+        Self {
+            item,
+            call_sites: Vec::new(),
+            children: Vec::new(),
+            repeated: false,
+        }
+    }
+
+    /// How many items are listed below this one
+    pub fn descendants(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| 1 + child.descendants())
+            .sum()
+    }
+
+    /// Parse an incoming or outgoing calls result: entries holding the
+    /// caller as `from` or the callee as `to`, with the `fromRanges` of the
+    /// calls
+    pub fn from_calls(value: serde_json::Value, field: &str) -> Vec<HierarchyNode> {
+        let serde_json::Value::Array(calls) = value else {
+            return Vec::new();
+        };
+        calls
+            .into_iter()
+            .filter_map(|call| {
+                let item = serde_json::from_value(call.get(field)?.clone()).ok()?;
+                let call_sites = call
+                    .get("fromRanges")
+                    .and_then(|ranges| serde_json::from_value(ranges.clone()).ok())
+                    .unwrap_or_default();
+                Some(HierarchyNode {
+                    call_sites,
+                    ..HierarchyNode::new(item)
+                })
+            })
+            .collect()
+    }
+}
+
+/// A fix or refactoring offered by `textDocument/codeAction`, numbered so it
+/// can be applied by a later call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeAction {
+    pub id: u32,
+    pub title: String,
+    /// Such as `quickfix` or `source.organizeImports`
+    pub kind: Option<String>,
+    pub is_preferred: bool,
+    /// Why the server won't apply it right now
+    pub disabled: Option<String>,
+}
+
+impl CodeAction {
+    /// Summarize an entry of a code action result, which is either a
+    /// `CodeAction` or a bare `Command`
+    pub fn from_value(id: u32, value: &serde_json::Value) -> Option<CodeAction> {
+        let title = value.get("title")?.as_str()?.to_string();
+        Some(CodeAction {
+            id,
+            title,
+            kind: value
+                .get("kind")
+                .and_then(|kind| kind.as_str())
+                .map(str::to_string),
+            is_preferred: value
+                .get("isPreferred")
+                .and_then(|preferred| preferred.as_bool())
+                .unwrap_or(false),
+            disabled: value
+                .pointer("/disabled/reason")
+                .and_then(|reason| reason.as_str())
+                .map(str::to_string),
+        })
+    }
+
+    /// `[id] title (kind, preferred)`, or the reason it's disabled
+    pub fn format(&self) -> String {
+        let mut notes: Vec<String> = self.kind.iter().cloned().collect();
+        if self.is_preferred {
+            notes.push("preferred".to_string());
+        }
+        if let Some(reason) = &self.disabled {
+            notes.push(format!("disabled: {reason}"));
+        }
+        if notes.is_empty() {
+            format!("[{}] {}", self.id, self.title)
+        } else {
+            format!("[{}] {} ({})", self.id, self.title, notes.join(", "))
+        }
+    }
+}
+
+/// A file and the diagnostics published for it
+pub type FileDiagnostics = (std::path::PathBuf, Vec<Diagnostic>);
+
+/// A compiler or linter message from `textDocument/publishDiagnostics`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub range: Range,
+    /// 1 error, 2 warning, 3 information, 4 hint
+    pub severity: Option<u32>,
+    /// A number or a string, depending on the server
+    pub code: Option<serde_json::Value>,
+    pub source: Option<String>,
+    pub message: String,
+    /// Kept for the server, which may need it to offer fixes for the
+    /// diagnostic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Diagnostic {
+    pub fn severity_to_string(&self) -> &'static str {
+        match self.severity {
+            Some(1) => "error",
+            Some(2) => "warning",
+            Some(3) => "info",
+            Some(4) => "hint",
+            _ => "error",
+        }
+    }
+
+    /// `path:line:col: severity[code]: message (source)`, with the line and
+    /// column counted from 1 as editors show them
+    pub fn format(&self, path: &str) -> String {
+        let code = match &self.code {
+            Some(serde_json::Value::String(code)) => format!("[{code}]"),
+            Some(serde_json::Value::Number(code)) => format!("[{code}]"),
+            _ => String::new(),
+        };
+        let source = self
+            .source
+            .as_deref()
+            .map(|source| format!(" ({source})"))
+            .unwrap_or_default();
+        // Multi-line messages are indented under the first line
+        let message = self.message.trim_end().replace('\n', "\n    ");
+        format!(
+            "{path}:{}:{}: {}{code}: {message}{source}",
+            self.range.start.line + 1,
+            self.range.start.character + 1,
+            self.severity_to_string()
+        )
+    }
+}
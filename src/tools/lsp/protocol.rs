@@ -49,6 +49,7 @@ pub struct NotificationMessage {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InitializeParams {
     pub process_id: Option<u32>,
     pub root_path: Option<String>,
@@ -60,6 +61,7 @@ pub struct InitializeParams {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ClientCapabilities {
     pub workspace: Option<WorkspaceClientCapabilities>,
     pub text_document: Option<TextDocumentClientCapabilities>,
@@ -68,6 +70,7 @@ pub struct ClientCapabilities {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WorkspaceClientCapabilities {
     pub apply_edit: Option<bool>,
     pub workspace_edit: Option<WorkspaceEditCapability>,
@@ -78,16 +81,21 @@ pub struct WorkspaceClientCapabilities {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WorkspaceEditCapability {
     pub document_changes: Option<bool>,
+    /// Which of `create`, `rename` and `delete` file operations edits may hold
+    pub resource_operations: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DynamicRegistrationCapability {
     pub dynamic_registration: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextDocumentClientCapabilities {
     pub synchronization: Option<TextDocumentSyncClientCapabilities>,
     pub completion: Option<CompletionClientCapabilities>,
@@ -100,19 +108,61 @@ pub struct TextDocumentClientCapabilities {
     pub references: Option<DynamicRegistrationCapability>,
     pub document_highlight: Option<DynamicRegistrationCapability>,
     pub document_symbol: Option<DynamicRegistrationCapability>,
-    pub code_action: Option<DynamicRegistrationCapability>,
+    pub code_action: Option<CodeActionClientCapabilities>,
     pub code_lens: Option<DynamicRegistrationCapability>,
     pub document_link: Option<DynamicRegistrationCapability>,
     pub color_provider: Option<DynamicRegistrationCapability>,
     pub formatting: Option<DynamicRegistrationCapability>,
     pub range_formatting: Option<DynamicRegistrationCapability>,
     pub on_type_formatting: Option<DynamicRegistrationCapability>,
-    pub rename: Option<DynamicRegistrationCapability>,
+    pub rename: Option<RenameClientCapabilities>,
     pub folding_range: Option<DynamicRegistrationCapability>,
     pub selection_range: Option<DynamicRegistrationCapability>,
+    pub call_hierarchy: Option<DynamicRegistrationCapability>,
+    pub type_hierarchy: Option<DynamicRegistrationCapability>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameClientCapabilities {
+    pub dynamic_registration: Option<bool>,
+    /// Whether `textDocument/prepareRename` may be sent
+    pub prepare_support: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeActionClientCapabilities {
+    pub dynamic_registration: Option<bool>,
+    /// Without this, servers may only offer bare commands
+    pub code_action_literal_support: Option<CodeActionLiteralSupport>,
+    pub is_preferred_support: Option<bool>,
+    pub disabled_support: Option<bool>,
+    pub data_support: Option<bool>,
+    /// Properties servers may leave out until `codeAction/resolve`
+    pub resolve_support: Option<ResolveSupport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeActionLiteralSupport {
+    pub code_action_kind: ValueSet,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueSet {
+    pub value_set: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveSupport {
+    pub properties: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextDocumentSyncClientCapabilities {
     pub dynamic_registration: Option<bool>,
     pub will_save: Option<bool>,
@@ -121,6 +171,7 @@ pub struct TextDocumentSyncClientCapabilities {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CompletionClientCapabilities {
     pub dynamic_registration: Option<bool>,
     pub completion_item: Option<CompletionItemCapability>,
@@ -129,6 +180,7 @@ pub struct CompletionClientCapabilities {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CompletionItemCapability {
     pub snippet_support: Option<bool>,
     pub commit_characters_support: Option<bool>,
@@ -138,11 +190,13 @@ pub struct CompletionItemCapability {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CompletionItemKindCapability {
     pub value_set: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WindowClientCapabilities {
     pub work_done_progress: Option<bool>,
 }
@@ -160,6 +214,7 @@ pub enum MarkupKind {
     Markdown,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TextDocumentItem {
     pub uri: String,
@@ -168,6 +223,7 @@ pub struct TextDocumentItem {
     pub text: String,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DidOpenTextDocumentParams {
     pub text_document: TextDocumentItem,
@@ -193,6 +249,7 @@ pub struct CodeLensParams {
     pub text_document: TextDocumentIdentifier,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TextDocumentPositionParams {
     pub text_document: TextDocumentIdentifier,
@@ -210,6 +267,11 @@ pub fn get_initialize_params(root_path: &str) -> InitializeParams {
                 apply_edit: Some(true),
                 workspace_edit: Some(WorkspaceEditCapability {
                     document_changes: Some(true),
+                    resource_operations: Some(vec![
+                        "create".to_string(),
+                        "rename".to_string(),
+                        "delete".to_string(),
+                    ]),
                 }),
                 did_change_configuration: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
@@ -275,8 +337,31 @@ pub fn get_initialize_params(root_path: &str) -> InitializeParams {
                 document_symbol: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
                 }),
-                code_action: Some(DynamicRegistrationCapability {
+                code_action: Some(CodeActionClientCapabilities {
                     dynamic_registration: Some(true),
+                    code_action_literal_support: Some(CodeActionLiteralSupport {
+                        code_action_kind: ValueSet {
+                            value_set: [
+                                "quickfix",
+                                "refactor",
+                                "refactor.extract",
+                                "refactor.inline",
+                                "refactor.rewrite",
+                                "source",
+                                "source.organizeImports",
+                                "source.fixAll",
+                            ]
+                            .iter()
+                            .map(|kind| kind.to_string())
+                            .collect(),
+                        },
+                    }),
+                    is_preferred_support: Some(true),
+                    disabled_support: Some(true),
+                    data_support: Some(true),
+                    resolve_support: Some(ResolveSupport {
+                        properties: vec!["edit".to_string(), "command".to_string()],
+                    }),
                 }),
                 code_lens: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
@@ -296,8 +381,9 @@ pub fn get_initialize_params(root_path: &str) -> InitializeParams {
                 on_type_formatting: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
                 }),
-                rename: Some(DynamicRegistrationCapability {
+                rename: Some(RenameClientCapabilities {
                     dynamic_registration: Some(true),
+                    prepare_support: Some(true),
                 }),
                 folding_range: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
@@ -305,6 +391,12 @@ pub fn get_initialize_params(root_path: &str) -> InitializeParams {
                 selection_range: Some(DynamicRegistrationCapability {
                     dynamic_registration: Some(true),
                 }),
+                call_hierarchy: Some(DynamicRegistrationCapability {
+                    dynamic_registration: Some(true),
+                }),
+                type_hierarchy: Some(DynamicRegistrationCapability {
+                    dynamic_registration: Some(true),
+                }),
             }),
             window: Some(WindowClientCapabilities {
                 work_done_progress: Some(true),
@@ -322,3 +414,60 @@ pub fn get_initialize_params(root_path: &str) -> InitializeParams {
         }]),
     }
 }
+
+/// `file://` URI of an absolute path, percent-encoding what URIs don't allow
+pub fn path_to_uri(path: &std::path::Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows paths like C:/src need a leading slash
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'/'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~'
+            | b':'
+            | b'@'
+            | b'+'
+            | b'=' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// The path a `file://` URI refers to, decoding percent escapes. Servers
+/// don't always encode URIs the way we sent them, so paths are what get
+/// compared.
+pub fn uri_to_path(uri: &str) -> std::path::PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8_lossy(&decoded).to_string();
+    // file:///C:/src is C:/src on Windows
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    std::path::PathBuf::from(path)
+}
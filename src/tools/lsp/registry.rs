@@ -0,0 +1,346 @@
+//! The language servers oli knows how to start, picked by file extension.
+//!
+//! Servers for Python (pyright), Rust (rust-analyzer), TypeScript and
+//! JavaScript (typescript-language-server), Go (gopls) and C/C++ (clangd) are
+//! built in. Others, or different settings for a built-in one, go in the `lsp`
+//! section of oli's config file (see [`crate::apis::http`] for its location):
+//!
+//! ```json
+//! {
+//!   "lsp": {
+//!     "servers": [
+//!       {
+//!         "name": "zls",
+//!         "command": "zls",
+//!         "extensions": ["zig"],
+//!         "root_markers": ["build.zig"]
+//!       },
+//!       {
+//!         "name": "gopls",
+//!         "command": "gopls",
+//!         "extensions": ["go"],
+//!         "root_markers": ["go.work", "go.mod"],
+//!         "initialization_options": { "staticcheck": true }
+//!       }
+//!     ]
+//!   }
+//! }
+//! ```
+//!
+//! Configured servers take precedence over built-in ones for the extensions
+//! they list, and replace a built-in server of the same name.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::apis::http::HttpConfig;
+
+/// How to start one language server and which files it handles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerSpec {
+    /// Name used in messages and to ask for this server explicitly
+    pub name: String,
+    /// Executable to run, looked up on `PATH`
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// File extensions the server handles, without the dot
+    pub extensions: Vec<String>,
+    /// Files or directories marking the root of a project, looked for from
+    /// the file's directory upwards
+    #[serde(default)]
+    pub root_markers: Vec<String>,
+    /// Sent as `initializationOptions` when the server starts
+    #[serde(default)]
+    pub initialization_options: Option<Value>,
+    /// LSP language id of the server's files, guessed from the extension if
+    /// not given
+    #[serde(default)]
+    pub language_id: Option<String>,
+    /// How to install the server, shown when it isn't found
+    #[serde(default)]
+    pub install_hint: Option<String>,
+}
+
+impl ServerSpec {
+    pub fn new(name: &str, command: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            args: Vec::new(),
+            extensions: Vec::new(),
+            root_markers: Vec::new(),
+            initialization_options: None,
+            language_id: None,
+            install_hint: None,
+        }
+    }
+
+    pub fn with_args(mut self, args: &[&str]) -> Self {
+        self.args = args.iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions.iter().map(|ext| ext.to_string()).collect();
+        self
+    }
+
+    pub fn with_root_markers(mut self, markers: &[&str]) -> Self {
+        self.root_markers = markers.iter().map(|marker| marker.to_string()).collect();
+        self
+    }
+
+    pub fn with_initialization_options(mut self, options: Value) -> Self {
+        self.initialization_options = Some(options);
+        self
+    }
+
+    pub fn with_language_id(mut self, language_id: &str) -> Self {
+        self.language_id = Some(language_id.to_string());
+        self
+    }
+
+    pub fn with_install_hint(mut self, hint: &str) -> Self {
+        self.install_hint = Some(hint.to_string());
+        self
+    }
+
+    /// Whether the server handles a file, by its extension
+    pub fn handles(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+
+    /// The LSP language id to open a file with
+    pub fn language_id_for(&self, path: &Path) -> String {
+        if let Some(language_id) = &self.language_id {
+            return language_id.clone();
+        }
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        default_language_id(&ext).map(str::to_string).unwrap_or(ext)
+    }
+
+    /// Whether `name` asks for this server: its own name or the language of
+    /// any of its files, ignoring case, so "Python" finds pyright
+    pub fn answers_to(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .language_id
+                .as_deref()
+                .is_some_and(|id| id.eq_ignore_ascii_case(name))
+            || self
+                .extensions
+                .iter()
+                .filter_map(|ext| default_language_id(ext))
+                .any(|id| id.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether a directory holds one of the server's root markers
+    pub fn is_root(&self, dir: &Path) -> bool {
+        self.root_markers
+            .iter()
+            .any(|marker| dir.join(marker).exists())
+    }
+
+    /// The project a file belongs to: the nearest directory above it with
+    /// one of the server's root markers, else the nearest git repository,
+    /// else the file's own directory
+    pub fn workspace_root(&self, file_path: &Path) -> Result<PathBuf> {
+        let parent = file_path
+            .parent()
+            .ok_or_else(|| anyhow!("Cannot determine parent directory"))?;
+
+        if let Some(root) = parent.ancestors().find(|dir| self.is_root(dir)) {
+            return Ok(root.to_path_buf());
+        }
+        if let Some(root) = parent.ancestors().find(|dir| dir.join(".git").exists()) {
+            return Ok(root.to_path_buf());
+        }
+        Ok(parent.to_path_buf())
+    }
+}
+
+/// The LSP language id usually used for files with an extension
+fn default_language_id(ext: &str) -> Option<&'static str> {
+    let id = match ext {
+        "py" | "pyi" => "python",
+        "rs" => "rust",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" => "cpp",
+        _ => return None,
+    };
+    Some(id)
+}
+
+/// The `lsp` section of oli's config file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LspConfig {
+    pub servers: Vec<ServerSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    lsp: LspConfig,
+}
+
+/// The language servers available, in order of precedence
+#[derive(Debug, Clone)]
+pub struct ServerRegistry {
+    servers: Vec<ServerSpec>,
+}
+
+impl Default for ServerRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ServerRegistry {
+    /// Only the built-in servers
+    pub fn builtin() -> Self {
+        Self {
+            servers: vec![
+                ServerSpec::new("pyright", "pyright-langserver")
+                    .with_args(&["--stdio"])
+                    .with_extensions(&["py", "pyi"])
+                    .with_root_markers(&[
+                        "pyproject.toml",
+                        "pyrightconfig.json",
+                        "setup.py",
+                        "setup.cfg",
+                        "requirements.txt",
+                    ])
+                    .with_install_hint("npm install -g pyright"),
+                ServerSpec::new("rust-analyzer", "rust-analyzer")
+                    .with_extensions(&["rs"])
+                    .with_root_markers(&["Cargo.toml"])
+                    .with_install_hint("rustup component add rust-analyzer"),
+                ServerSpec::new("typescript-language-server", "typescript-language-server")
+                    .with_args(&["--stdio"])
+                    .with_extensions(&["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"])
+                    .with_root_markers(&["tsconfig.json", "jsconfig.json", "package.json"])
+                    .with_install_hint("npm install -g typescript-language-server typescript"),
+                ServerSpec::new("gopls", "gopls")
+                    .with_extensions(&["go"])
+                    .with_root_markers(&["go.work", "go.mod"])
+                    .with_install_hint("go install golang.org/x/tools/gopls@latest"),
+                ServerSpec::new("clangd", "clangd")
+                    .with_extensions(&["c", "h", "cc", "cpp", "cxx", "c++", "hh", "hpp", "hxx"])
+                    .with_root_markers(&[
+                        "compile_commands.json",
+                        "compile_flags.txt",
+                        ".clangd",
+                        "CMakeLists.txt",
+                    ])
+                    .with_install_hint("install clangd from your package manager or LLVM"),
+            ],
+        }
+    }
+
+    /// Add servers ahead of the ones already known. One with the name of a
+    /// known server replaces it.
+    pub fn with_servers(mut self, servers: Vec<ServerSpec>) -> Self {
+        self.servers
+            .retain(|known| !servers.iter().any(|server| server.name == known.name));
+        self.servers.splice(0..0, servers);
+        self
+    }
+
+    /// The built-in servers plus those in the `lsp` section of a config file.
+    /// A missing file means only the built-in ones.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::builtin());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: ConfigFile = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(Self::builtin().with_servers(config.lsp.servers))
+    }
+
+    /// The registry for this process, loaded once on first use
+    pub fn global() -> Result<&'static ServerRegistry> {
+        static REGISTRY: OnceLock<std::result::Result<ServerRegistry, String>> = OnceLock::new();
+
+        REGISTRY
+            .get_or_init(|| match HttpConfig::config_path() {
+                Some(path) => Self::load_from(&path).map_err(|e| format!("{e:#}")),
+                None => Ok(Self::builtin()),
+            })
+            .as_ref()
+            .map_err(|e| anyhow!("{e}"))
+    }
+
+    pub fn servers(&self) -> &[ServerSpec] {
+        &self.servers
+    }
+
+    /// The server for a file, by its extension
+    pub fn for_path(&self, path: &Path) -> Option<&ServerSpec> {
+        self.servers.iter().find(|server| server.handles(path))
+    }
+
+    /// A server by name or language
+    pub fn find(&self, name: &str) -> Option<&ServerSpec> {
+        self.servers
+            .iter()
+            .find(|server| server.name.eq_ignore_ascii_case(name))
+            .or_else(|| self.servers.iter().find(|server| server.answers_to(name)))
+    }
+
+    /// The server for a project directory, by the root markers in it
+    pub fn for_workspace(&self, dir: &Path) -> Option<&ServerSpec> {
+        self.servers.iter().find(|server| server.is_root(dir))
+    }
+
+    /// The server asked for by name, or else the one for the file's extension
+    pub fn resolve(&self, path: &Path, name: Option<&str>) -> Result<&ServerSpec> {
+        if let Some(name) = name {
+            return self.find(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown language server '{}'. Known servers: {}",
+                    name,
+                    self.names()
+                )
+            });
+        }
+        self.for_path(path).ok_or_else(|| {
+            let ext = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_else(|| path.display().to_string());
+            anyhow!(
+                "No language server is configured for {} files. Known servers: {}. \
+                 Others can be added under \"lsp\".\"servers\" in the config file.",
+                ext,
+                self.names()
+            )
+        })
+    }
+
+    fn names(&self) -> String {
+        self.servers
+            .iter()
+            .map(|server| server.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -1,247 +1,251 @@
 use anyhow::{anyhow, Result};
 use serde_json::Value;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-
-use crate::tools::lsp::protocol::{
-    get_initialize_params, NotificationMessage, RequestId, RequestMessage, ResponseMessage,
-};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::app::logger::{format_log_with_color, LogLevel};
+use crate::tools::lsp::edits::WorkspaceEdits;
+use crate::tools::lsp::models::{Diagnostic, FileDiagnostics, HierarchyItem, Range};
+use crate::tools::lsp::protocol::{get_initialize_params, ResponseMessage, WorkspaceFolder};
+use crate::tools::lsp::registry::ServerSpec;
+use crate::tools::lsp::transport::LspTransport;
+
+/// How long to wait for a response before giving up on a request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Servers may load the whole project before answering `initialize`
+pub const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to keep retrying a request while the server is still indexing
+pub const INDEXING_WAIT: Duration = Duration::from_secs(30);
+/// How often to check whether indexing has finished
+const INDEXING_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// The document changed while the request was handled, so try again
+const CONTENT_MODIFIED: i32 = -32801;
+/// The server cancelled the request, typically because it isn't ready
+const SERVER_CANCELLED: i32 = -32802;
+/// Error code for requests the server doesn't implement
+const METHOD_NOT_FOUND: i32 = -32601;
+/// How often to check for newly published diagnostics
+const DIAGNOSTICS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Quiet period after which a file's diagnostics are taken as complete
+const DIAGNOSTICS_SETTLE: Duration = Duration::from_millis(300);
+/// Lines of the server's stderr kept for error reports
+const STDERR_TAIL_LINES: usize = 50;
+/// Documents kept open on one server; the least recently used is closed
+/// to open another
+pub const MAX_OPEN_DOCUMENTS: usize = 200;
+/// `TextDocumentSyncKind` values
+const TEXT_DOCUMENT_SYNC_FULL: u64 = 1;
+const TEXT_DOCUMENT_SYNC_INCREMENTAL: u64 = 2;
 
 pub struct LspServer {
     process: Child,
-    #[allow(dead_code)]
-    server_type: String,
+    transport: LspTransport,
+    spec: ServerSpec,
     root_path: PathBuf,
     initialized: bool,
-    next_request_id: u64,
+    /// What the server said it supports when it was initialized
+    capabilities: Value,
+    /// Documents opened on the server, by URI
+    open_documents: HashMap<String, OpenDocument>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    /// When the server last handled a request or a document change
+    last_used: Instant,
 }
 
-impl LspServer {
-    pub fn start_python_server(root_path: &Path) -> Result<Self> {
-        eprintln!(
-            "Starting Python LSP server (pyright) for path: {}",
-            root_path.display()
-        );
-
-        // Check if pyright-langserver is installed (the correct executable name)
-        let pyright_check = Command::new("sh")
-            .arg("-c")
-            .arg("command -v pyright-langserver")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
-
-        if pyright_check.is_err() || !pyright_check.unwrap().success() {
-            return Err(anyhow!("Python LSP server (pyright-langserver) not found. Please install it with 'npm install -g pyright'"));
-        }
+/// A document the server has open
+struct OpenDocument {
+    version: u32,
+    /// The text the server last saw
+    text: String,
+    last_synced: Instant,
+}
 
-        // Ensure the root path exists
-        if !root_path.exists() {
-            return Err(anyhow!("Root path does not exist: {}", root_path.display()));
+impl LspServer {
+    /// Wrap a spawned server process, reading its stdout on a background
+    /// thread. Its stderr is drained too, so a chatty server can't fill the
+    /// pipe and stall.
+    fn from_process(mut process: Child, spec: &ServerSpec, root_path: &Path) -> Result<Self> {
+        let stdin = process
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdin handle"))?;
+        let stdout = process
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdout handle"))?;
+
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+        if let Some(stderr) = process.stderr.take() {
+            let tail = stderr_tail.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    if let Ok(mut tail) = tail.lock() {
+                        if tail.len() >= STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(line);
+                    }
+                }
+            });
         }
 
-        eprintln!(
-            "Launching pyright-langserver with root path: {}",
-            root_path.display()
-        );
-
-        let process = Command::new("pyright-langserver")
-            .arg("--stdio")
-            .current_dir(root_path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let server = LspServer {
+        let root = root_path.to_string_lossy().to_string();
+        let transport =
+            LspTransport::new(stdout, stdin).with_workspace_folders(vec![WorkspaceFolder {
+                uri: format!("file://{root}"),
+                name: root_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "workspace".to_string()),
+            }]);
+
+        Ok(LspServer {
             process,
-            server_type: "python".to_string(),
+            transport,
+            spec: spec.clone(),
             root_path: root_path.to_path_buf(),
             initialized: false,
-            next_request_id: 1,
-        };
-
-        Ok(server)
+            capabilities: Value::Null,
+            open_documents: HashMap::new(),
+            stderr_tail,
+            last_used: Instant::now(),
+        })
     }
 
-    pub fn start_rust_server(root_path: &Path) -> Result<Self> {
+    /// Launch a server for the project at `root_path`
+    pub fn start(spec: &ServerSpec, root_path: &Path) -> Result<Self> {
         eprintln!(
-            "Starting Rust LSP server (rust-analyzer) for path: {}",
-            root_path.display()
+            "{}",
+            format_log_with_color(
+                LogLevel::Info,
+                &format!(
+                    "Starting language server {} for path: {}",
+                    spec.name,
+                    root_path.display()
+                )
+            )
         );
 
-        // Check if rust-analyzer is installed
-        let rust_analyzer_check = Command::new("sh")
-            .arg("-c")
-            .arg("command -v rust-analyzer")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
-
-        if rust_analyzer_check.is_err() || !rust_analyzer_check.unwrap().success() {
-            return Err(anyhow!(
-                "Rust LSP server (rust-analyzer) not found. Please install it."
-            ));
+        // Ensure the root path exists
+        if !root_path.exists() {
+            return Err(anyhow!("Root path does not exist: {}", root_path.display()));
         }
 
-        let process = Command::new("rust-analyzer")
+        let process = Command::new(&spec.command)
+            .args(&spec.args)
             .current_dir(root_path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()?;
-
-        let server = LspServer {
-            process,
-            server_type: "rust".to_string(),
-            root_path: root_path.to_path_buf(),
-            initialized: false,
-            next_request_id: 1,
-        };
+            .spawn()
+            .map_err(|e| {
+                let hint = spec
+                    .install_hint
+                    .as_deref()
+                    .map(|hint| format!(". Install it with: {hint}"))
+                    .unwrap_or_default();
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    anyhow!(
+                        "Language server {} ({}) not found{}",
+                        spec.name,
+                        spec.command,
+                        hint
+                    )
+                } else {
+                    anyhow!("Failed to start language server {}: {}", spec.name, e)
+                }
+            })?;
 
-        Ok(server)
+        Self::from_process(process, spec, root_path)
     }
 
     pub fn initialize(&mut self) -> Result<ResponseMessage> {
-        let params = get_initialize_params(self.root_path.to_str().unwrap_or("."));
+        let mut params = get_initialize_params(self.root_path.to_str().unwrap_or("."));
+        params.initialization_options = self.spec.initialization_options.clone();
 
         let response = self
-            .send_request("initialize", Some(serde_json::to_value(params)?))?
-            .ok_or_else(|| anyhow!("Failed to initialize LSP server: no response"))?;
+            .transport
+            .request(
+                "initialize",
+                Some(serde_json::to_value(params)?),
+                INITIALIZE_TIMEOUT,
+            )
+            .map_err(|e| anyhow!("Failed to initialize LSP server: {e}{}", self.stderr_hint()))?;
+        if let Some(err) = &response.error {
+            return Err(anyhow!(
+                "Failed to initialize LSP server: code={}, message={}",
+                err.code,
+                err.message
+            ));
+        }
+
+        self.capabilities = response
+            .result
+            .as_ref()
+            .and_then(|result| result.get("capabilities"))
+            .cloned()
+            .unwrap_or(Value::Null);
 
         // Send initialized notification
+        self.initialized = true;
         self.send_notification("initialized", Some(serde_json::json!({})))?;
 
-        self.initialized = true;
         Ok(response)
     }
 
     pub fn shutdown(&mut self) -> Result<()> {
-        if self.initialized {
-            // Send shutdown request
-            self.send_request("shutdown", None)?;
-
-            // Send exit notification
-            self.send_notification("exit", None)?;
+        if self.initialized && !self.transport.is_closed() {
+            // A server that doesn't answer promptly gets killed regardless
+            let _ = self
+                .transport
+                .request("shutdown", None, Duration::from_secs(2));
+            let _ = self.send_notification("exit", None);
+            self.initialized = false;
         }
 
         // Terminate the process
-        self.process.kill()?;
+        let _ = self.process.kill();
+        let _ = self.process.wait();
         Ok(())
     }
 
-    fn send_request(
-        &mut self,
-        method: &str,
-        params: Option<Value>,
-    ) -> Result<Option<ResponseMessage>> {
-        if !self.initialized && method != "initialize" {
+    fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<ResponseMessage> {
+        if !self.initialized {
             return Err(anyhow!("LSP server not initialized"));
         }
+        self.last_used = Instant::now();
 
-        let id = self.next_request_id;
-        self.next_request_id += 1;
-
-        let request = RequestMessage {
-            jsonrpc: "2.0".to_string(),
-            id: RequestId::Number(id),
-            method: method.to_string(),
-            params,
-        };
-
-        let request_json = serde_json::to_string(&request)?;
-        let content_length = request_json.len();
-
-        let message = format!("Content-Length: {content_length}\r\n\r\n{request_json}");
-
-        if let Some(stdin) = self.process.stdin.as_mut() {
-            stdin.write_all(message.as_bytes())?;
-            stdin.flush()?;
-        } else {
-            return Err(anyhow!("Failed to get stdin handle"));
-        }
-
-        // Read response
-        if let Some(stdout) = self.process.stdout.as_mut() {
-            let mut reader = BufReader::new(stdout);
-            let mut header = String::new();
-            let mut content_length = 0;
-
-            // Read headers
-            loop {
-                header.clear();
-                reader.read_line(&mut header)?;
-                if header.trim().is_empty() {
-                    break;
-                }
-
-                if let Some(stripped) = header.strip_prefix("Content-Length: ") {
-                    content_length = stripped.trim().parse::<usize>()?;
-                }
-            }
-
-            // Check if we have a valid content length
-            if content_length == 0 {
-                return Err(anyhow!("Invalid content length from LSP server"));
-            }
-
-            // Read content
-            let mut content = vec![0; content_length];
-            reader.read_exact(&mut content)?;
-
-            // Log the response for debugging
-            let content_str = String::from_utf8_lossy(&content);
-            eprintln!("LSP response: {content_str}");
-
-            // Parse the response
-            match serde_json::from_slice::<ResponseMessage>(&content) {
-                Ok(response) => return Ok(Some(response)),
-                Err(e) => {
-                    eprintln!("Error parsing LSP response: {e}");
-                    // Try to manually extract the result to handle non-standard responses
-                    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&content) {
-                        if let Some(result) = json.get("result") {
-                            // Create a synthesized response
-                            let response = ResponseMessage {
-                                jsonrpc: "2.0".to_string(),
-                                id: Default::default(),
-                                result: Some(result.clone()),
-                                error: None,
-                            };
-                            return Ok(Some(response));
-                        }
-                    }
-                    return Err(e.into());
-                }
-            }
-        }
-
-        Ok(None)
+        self.transport
+            .request(method, params, DEFAULT_REQUEST_TIMEOUT)
+            .map_err(|e| anyhow!("{e}{}", self.stderr_hint()))
     }
 
     fn send_notification(&mut self, method: &str, params: Option<Value>) -> Result<()> {
-        if !self.initialized && method != "initialized" {
+        if !self.initialized {
             return Err(anyhow!("LSP server not initialized"));
         }
 
-        let notification = NotificationMessage {
-            jsonrpc: "2.0".to_string(),
-            method: method.to_string(),
-            params,
-        };
-
-        let notification_json = serde_json::to_string(&notification)?;
-        let content_length = notification_json.len();
-
-        let message = format!("Content-Length: {content_length}\r\n\r\n{notification_json}");
+        self.transport.notify(method, params)
+    }
 
-        if let Some(stdin) = self.process.stdin.as_mut() {
-            stdin.write_all(message.as_bytes())?;
-            stdin.flush()?;
-            Ok(())
+    /// The last lines the server wrote to stderr, to explain a failure
+    pub fn stderr_hint(&self) -> String {
+        if !self.transport.is_closed() {
+            return String::new();
+        }
+        let tail = self
+            .stderr_tail
+            .lock()
+            .map(|tail| tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default();
+        if tail.is_empty() {
+            String::new()
         } else {
-            Err(anyhow!("Failed to get stdin handle"))
+            format!("\nServer stderr:\n{tail}")
         }
     }
 
@@ -252,6 +256,19 @@ impl LspServer {
         version: u32,
         text: &str,
     ) -> Result<()> {
+        // Servers keep every open document in memory, so make room first
+        while self.open_documents.len() >= MAX_OPEN_DOCUMENTS {
+            let Some(oldest) = self
+                .open_documents
+                .iter()
+                .min_by_key(|(_, document)| document.last_synced)
+                .map(|(uri, _)| uri.clone())
+            else {
+                break;
+            };
+            self.did_close(&oldest)?;
+        }
+
         let params = serde_json::json!({
             "textDocument": {
                 "uri": uri,
@@ -261,110 +278,279 @@ impl LspServer {
             }
         });
 
-        self.send_notification("textDocument/didOpen", Some(params))
+        self.send_notification("textDocument/didOpen", Some(params))?;
+        self.open_documents.insert(
+            uri.to_string(),
+            OpenDocument {
+                version,
+                text: text.to_string(),
+                last_synced: Instant::now(),
+            },
+        );
+        Ok(())
     }
 
-    pub fn document_symbol(&mut self, uri: &str) -> Result<Value> {
+    /// Make sure the server sees `text` as the document's content: open it
+    /// the first time, then send what changed as a new version, as a range
+    /// edit when the server accepts those. Returns whether anything was sent.
+    pub fn sync_document(&mut self, uri: &str, language_id: &str, text: &str) -> Result<bool> {
+        self.last_used = Instant::now();
+        let incremental = self.text_document_sync_kind() == TEXT_DOCUMENT_SYNC_INCREMENTAL;
+        let Some(document) = self.open_documents.get_mut(uri) else {
+            self.did_open_text_document(uri, language_id, 1, text)?;
+            return Ok(true);
+        };
+        document.last_synced = Instant::now();
+        if document.text == text {
+            return Ok(false);
+        }
+
+        let change = if incremental {
+            serde_json::to_value(WorkspaceEdits::diff_edit(&document.text, text))?
+        } else {
+            serde_json::json!({ "text": text })
+        };
+        document.version += 1;
+        document.text = text.to_string();
         let params = serde_json::json!({
-            "textDocument": { "uri": uri }
+            "textDocument": { "uri": uri, "version": document.version },
+            "contentChanges": [change]
         });
+        self.send_notification("textDocument/didChange", Some(params))
+            .inspect_err(|_| {
+                // The server's copy is unknown now, so start over next time
+                self.open_documents.remove(uri);
+            })?;
+        Ok(true)
+    }
 
-        // First, make sure the server is properly initialized
-        if !self.initialized {
-            self.initialize()?;
+    /// Tell the server a document is no longer open, e.g. because the file
+    /// was deleted. Documents that aren't open are ignored.
+    pub fn did_close(&mut self, uri: &str) -> Result<()> {
+        if self.open_documents.remove(uri).is_none() {
+            return Ok(());
         }
+        let params = serde_json::json!({ "textDocument": { "uri": uri } });
+        self.send_notification("textDocument/didClose", Some(params))
+    }
 
-        // Log what we're about to do
-        eprintln!("Sending documentSymbol request for URI: {uri}");
+    pub fn open_document_count(&self) -> usize {
+        self.open_documents.len()
+    }
 
-        // Add a delay to ensure the server is ready (pyright needs this sometimes)
-        std::thread::sleep(std::time::Duration::from_millis(1000));
+    /// How the server wants document changes: 0 not at all, 1 as full text,
+    /// 2 as ranges. `textDocumentSync` is either that number or an object
+    /// holding it as `change`.
+    fn text_document_sync_kind(&self) -> u64 {
+        let sync = &self.capabilities["textDocumentSync"];
+        sync.as_u64()
+            .or_else(|| sync["change"].as_u64())
+            .unwrap_or(TEXT_DOCUMENT_SYNC_FULL)
+    }
 
-        // Send the request
-        let response = self
-            .send_request("textDocument/documentSymbol", Some(params))?
-            .ok_or_else(|| anyhow!("No response from LSP server"))?;
+    /// Tell the server a document was saved to disk, which is when some
+    /// servers run their slower checks
+    pub fn did_save(&mut self, uri: &str, text: &str) -> Result<()> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "text": text
+        });
+        self.send_notification("textDocument/didSave", Some(params))
+    }
 
-        // For debugging
-        eprintln!("DocumentSymbol response received");
+    /// Whether the server process has stopped, crashed or otherwise
+    pub fn has_exited(&mut self) -> bool {
+        self.transport.is_closed() || matches!(self.process.try_wait(), Ok(Some(_)))
+    }
 
-        match response.result {
-            Some(result) => {
-                // If we get an empty array, create a synthetic response with basic file symbols
-                if result.as_array().is_some_and(|arr| arr.is_empty()) {
-                    eprintln!("DocumentSymbol returned empty array, creating synthetic response");
-
-                    // Simple fallback for Python files - just return a simple document structure
-                    let file_path = uri.replace("file://", "");
-                    if file_path.ends_with(".py") {
-                        // Create a simple synthetic response
-                        return Ok(serde_json::json!([
-                            {
-                                "name": "Module",
-                                "detail": file_path,
-                                "kind": 2, // Module
-                                "range": {
-                                    "start": { "line": 0, "character": 0 },
-                                    "end": { "line": 100, "character": 0 }
-                                },
-                                "selectionRange": {
-                                    "start": { "line": 0, "character": 0 },
-                                    "end": { "line": 100, "character": 0 }
-                                }
-                            }
-                        ]));
-                    }
-                }
+    pub fn pid(&self) -> u32 {
+        self.process.id()
+    }
+
+    /// Time since the server last handled a request or a document change
+    pub fn idle_for(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+
+    /// The workspace the server was started for
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
+    pub fn diagnostics_generation(&self) -> u64 {
+        self.transport.diagnostics_generation()
+    }
+
+    /// The latest diagnostics the server published for `path`
+    pub fn diagnostics(&self, path: &Path) -> Option<Vec<Diagnostic>> {
+        self.transport.diagnostics(path)
+    }
+
+    /// Every file's diagnostics published after `generation`
+    pub fn diagnostics_since(&self, generation: u64) -> Vec<FileDiagnostics> {
+        self.transport.diagnostics_since(generation)
+    }
+
+    /// Wait up to `timeout` for the server to publish diagnostics for `path`
+    /// after `generation`. Servers often publish quick syntax results and
+    /// then slower semantic ones, so once the first arrive this waits until
+    /// publications stop for a moment. Returns the latest diagnostics for the
+    /// file, which are the old ones if nothing new was published in time.
+    pub fn wait_for_diagnostics(
+        &self,
+        path: &Path,
+        generation: u64,
+        timeout: Duration,
+    ) -> Option<Vec<Diagnostic>> {
+        self.wait_for_all_diagnostics(
+            std::slice::from_ref(&path.to_path_buf()),
+            generation,
+            Instant::now() + timeout,
+        )
+        .pop()
+        .map(|(_, diagnostics)| diagnostics)
+    }
+
+    /// [`LspServer::wait_for_diagnostics`] for several files at once: waits
+    /// until diagnostics were published for each of `paths` after
+    /// `generation` or `deadline` passes, then for publications to stop.
+    /// Returns the latest diagnostics of each file that has any.
+    pub fn wait_for_all_diagnostics(
+        &self,
+        paths: &[PathBuf],
+        generation: u64,
+        deadline: Instant,
+    ) -> Vec<FileDiagnostics> {
+        let published = |since: u64| -> Vec<PathBuf> {
+            self.transport
+                .diagnostics_since(since)
+                .into_iter()
+                .map(|(published, _)| published)
+                .filter(|published| paths.contains(published))
+                .collect()
+        };
 
-                Ok(result)
+        while published(generation).len() < paths.len() {
+            if Instant::now() >= deadline || self.transport.is_closed() {
+                break;
             }
-            None => {
-                // Check if we got any error information
-                if let Some(err) = &response.error {
-                    Err(anyhow!(
+            std::thread::sleep(DIAGNOSTICS_POLL_INTERVAL);
+        }
+
+        if !published(generation).is_empty() {
+            let mut last = self.transport.diagnostics_generation();
+            while Instant::now() < deadline {
+                std::thread::sleep(DIAGNOSTICS_SETTLE);
+                if published(last).is_empty() {
+                    break;
+                }
+                last = self.transport.diagnostics_generation();
+            }
+        }
+
+        paths
+            .iter()
+            .filter_map(|path| {
+                let diagnostics = self.transport.diagnostics(path)?;
+                Some((path.clone(), diagnostics))
+            })
+            .collect()
+    }
+
+    /// Symbols of a document, as the server returned them. A server that is
+    /// still indexing may answer with nothing or with `ContentModified`, so
+    /// while it reports work in progress the request is retried, for up to
+    /// [`INDEXING_WAIT`]. An empty result after that is returned as is.
+    pub fn document_symbol(&mut self, uri: &str) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri }
+        });
+
+        // First, make sure the server is properly initialized
+        if !self.initialized {
+            self.initialize()?;
+        }
+
+        let deadline = Instant::now() + INDEXING_WAIT;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let response =
+                self.send_request("textDocument/documentSymbol", Some(params.clone()))?;
+
+            let retryable = match &response.error {
+                Some(err) if err.code == CONTENT_MODIFIED || err.code == SERVER_CANCELLED => true,
+                Some(err) => {
+                    return Err(anyhow!(
                         "LSP error: code={}, message={}",
                         err.code,
                         err.message
                     ))
-                } else {
-                    // If the server supports documentSymbol but returns null, create a synthetic response
-                    eprintln!("DocumentSymbol returned null, creating synthetic response");
-
-                    // Simple fallback for Python files
-                    let file_path = uri.replace("file://", "");
-                    if file_path.ends_with(".py") {
-                        // Create a simple synthetic response
-                        return Ok(serde_json::json!([
-                            {
-                                "name": "Module",
-                                "detail": file_path,
-                                "kind": 2, // Module
-                                "range": {
-                                    "start": { "line": 0, "character": 0 },
-                                    "end": { "line": 100, "character": 0 }
-                                },
-                                "selectionRange": {
-                                    "start": { "line": 0, "character": 0 },
-                                    "end": { "line": 100, "character": 0 }
-                                }
-                            }
-                        ]));
+                }
+                None => {
+                    let result = response.result.as_ref().unwrap_or(&Value::Null);
+                    let empty = result.is_null() || result.as_array().is_some_and(Vec::is_empty);
+                    if !empty {
+                        return Ok(result.clone());
                     }
-
-                    Err(anyhow!("No result in LSP response: {:?}", response.error))
+                    // Progress may not have been reported yet on the first try
+                    attempt == 1 || !self.transport.active_progress().is_empty()
                 }
+            };
+
+            if !retryable || Instant::now() >= deadline || self.transport.is_closed() {
+                return match response.error {
+                    Some(err) => Err(anyhow!(
+                        "LSP error: code={}, message={}",
+                        err.code,
+                        err.message
+                    )),
+                    None => Ok(response.result.unwrap_or(Value::Null)),
+                };
+            }
+
+            eprintln!(
+                "{}",
+                format_log_with_color(
+                    LogLevel::Debug,
+                    &format!("No document symbols for {uri} yet, waiting for the server to finish indexing")
+                )
+            );
+            self.wait_for_progress(deadline);
+        }
+    }
+
+    /// Wait until the server reports no work in progress or `deadline`
+    /// passes, checking at least once after a short pause
+    fn wait_for_progress(&self, deadline: Instant) {
+        loop {
+            std::thread::sleep(INDEXING_POLL_INTERVAL);
+            if self.transport.active_progress().is_empty()
+                || self.transport.is_closed()
+                || Instant::now() >= deadline
+            {
+                return;
             }
         }
     }
 
+    /// Wait up to `timeout` for the server to finish the work it reports in
+    /// progress, such as indexing
+    pub fn wait_until_idle(&self, timeout: Duration) {
+        self.wait_for_progress(Instant::now() + timeout);
+    }
+
+    /// Titles of work the server reports in progress, such as indexing
+    pub fn active_progress(&self) -> Vec<String> {
+        self.transport.active_progress()
+    }
+
     pub fn semantic_tokens(&mut self, uri: &str) -> Result<Value> {
         let params = serde_json::json!({
             "textDocument": { "uri": uri }
         });
 
-        let response = self
-            .send_request("textDocument/semanticTokens/full", Some(params))?
-            .ok_or_else(|| anyhow!("No response from LSP server"))?;
+        let response = self.send_request("textDocument/semanticTokens/full", Some(params))?;
 
         match response.result {
             Some(result) => Ok(result),
@@ -377,9 +563,7 @@ impl LspServer {
             "textDocument": { "uri": uri }
         });
 
-        let response = self
-            .send_request("textDocument/codeLens", Some(params))?
-            .ok_or_else(|| anyhow!("No response from LSP server"))?;
+        let response = self.send_request("textDocument/codeLens", Some(params))?;
 
         match response.result {
             Some(result) => Ok(result),
@@ -393,9 +577,7 @@ impl LspServer {
             "position": { "line": line, "character": character }
         });
 
-        let response = self
-            .send_request("textDocument/definition", Some(params))?
-            .ok_or_else(|| anyhow!("No response from LSP server"))?;
+        let response = self.send_request("textDocument/definition", Some(params))?;
 
         match response.result {
             Some(result) => Ok(result),
@@ -403,18 +585,237 @@ impl LspServer {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn get_server_type(&self) -> &str {
-        &self.server_type
+    pub fn references(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+        include_declaration: bool,
+    ) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "context": { "includeDeclaration": include_declaration }
+        });
+
+        let response = self.send_request("textDocument/references", Some(params))?;
+
+        match response.result {
+            Some(result) => Ok(result),
+            None => Err(anyhow!("No result in LSP response: {:?}", response.error)),
+        }
+    }
+
+    pub fn hover(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self.send_request("textDocument/hover", Some(params))?;
+
+        match response.result {
+            Some(result) => Ok(result),
+            None => Err(anyhow!("No result in LSP response: {:?}", response.error)),
+        }
+    }
+
+    pub fn workspace_symbol(&mut self, query: &str) -> Result<Value> {
+        let params = serde_json::json!({ "query": query });
+
+        let response = self.send_request("workspace/symbol", Some(params))?;
+
+        match response.result {
+            Some(result) => Ok(result),
+            None => Err(anyhow!("No result in LSP response: {:?}", response.error)),
+        }
+    }
+
+    /// Check that the symbol at a position can be renamed. `null` means it
+    /// can't; servers without the check answer as if it could.
+    pub fn prepare_rename(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self.send_request("textDocument/prepareRename", Some(params))?;
+
+        match response.error {
+            Some(err) if err.code == METHOD_NOT_FOUND => {
+                Ok(serde_json::json!({ "defaultBehavior": true }))
+            }
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// The `WorkspaceEdit` renaming the symbol at a position, `null` if there
+    /// is nothing to change
+    pub fn rename(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "newName": new_name
+        });
+
+        let response = self.send_request("textDocument/rename", Some(params))?;
+
+        match response.error {
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// The edits that format a whole document, `null` if it needs none
+    pub fn formatting(&mut self, uri: &str, tab_size: u32, insert_spaces: bool) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces }
+        });
+
+        let response = self.send_request("textDocument/formatting", Some(params))?;
+
+        match response.error {
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// The code actions available for a range, given the diagnostics that
+    /// overlap it
+    pub fn code_action(
+        &mut self,
+        uri: &str,
+        range: &Range,
+        diagnostics: &[Diagnostic],
+    ) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "range": range,
+            "context": { "diagnostics": diagnostics, "triggerKind": 1 }
+        });
+
+        let response = self.send_request("textDocument/codeAction", Some(params))?;
+
+        match response.error {
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// Fill in the edit and command of a code action, for servers that
+    /// leave them out of the listing. Others get the action back as is.
+    pub fn resolve_code_action(&mut self, action: &Value) -> Result<Value> {
+        let resolves = self
+            .capabilities
+            .pointer("/codeActionProvider/resolveProvider")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if !resolves {
+            return Ok(action.clone());
+        }
+
+        let response = self.send_request("codeAction/resolve", Some(action.clone()))?;
+
+        match response.error {
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or_else(|| action.clone())),
+        }
+    }
+
+    /// Run a command on the server. Returns the workspace edits it asked
+    /// for while running, which are left for the caller to apply.
+    pub fn execute_command(&mut self, command: &str, arguments: &[Value]) -> Result<Vec<Value>> {
+        let params = serde_json::json!({ "command": command, "arguments": arguments });
+
+        self.transport.collect_requested_edits();
+        let response = self.send_request("workspace/executeCommand", Some(params));
+        let edits = self.transport.take_requested_edits();
+
+        match response?.error {
+            Some(err) => Err(anyhow!("Command {} failed: {}", command, err.message)),
+            None => Ok(edits),
+        }
+    }
+
+    /// The functions at a position, to ask for their callers or callees
+    pub fn prepare_call_hierarchy(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+        self.hierarchy_request("textDocument/prepareCallHierarchy", params)
+    }
+
+    pub fn incoming_calls(&mut self, item: &HierarchyItem) -> Result<Value> {
+        let params = serde_json::json!({ "item": item });
+        self.hierarchy_request("callHierarchy/incomingCalls", params)
+    }
+
+    pub fn outgoing_calls(&mut self, item: &HierarchyItem) -> Result<Value> {
+        let params = serde_json::json!({ "item": item });
+        self.hierarchy_request("callHierarchy/outgoingCalls", params)
+    }
+
+    /// The types at a position, to ask for their supertypes or subtypes
+    pub fn prepare_type_hierarchy(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        let params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+        self.hierarchy_request("textDocument/prepareTypeHierarchy", params)
+    }
+
+    pub fn supertypes(&mut self, item: &HierarchyItem) -> Result<Value> {
+        let params = serde_json::json!({ "item": item });
+        self.hierarchy_request("typeHierarchy/supertypes", params)
+    }
+
+    pub fn subtypes(&mut self, item: &HierarchyItem) -> Result<Value> {
+        let params = serde_json::json!({ "item": item });
+        self.hierarchy_request("typeHierarchy/subtypes", params)
+    }
+
+    /// Send a call or type hierarchy request, which older servers may not
+    /// know. A `null` result means there is nothing there.
+    fn hierarchy_request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let response = self.send_request(method, Some(params))?;
+
+        match response.error {
+            Some(err) if err.code == METHOD_NOT_FOUND => Err(anyhow!(
+                "Language server {} doesn't support {}",
+                self.spec.name,
+                method
+            )),
+            Some(err) => Err(anyhow!("{}", err.message)),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    pub fn spec(&self) -> &ServerSpec {
+        &self.spec
     }
 }
 
 impl Drop for LspServer {
     fn drop(&mut self) {
-        if self.initialized {
-            let _ = self.shutdown();
-        } else {
-            let _ = self.process.kill();
-        }
+        let _ = self.shutdown();
     }
 }
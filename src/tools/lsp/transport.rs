@@ -0,0 +1,501 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::app::logger::{format_log_with_color, LogLevel};
+use crate::tools::lsp::models::{Diagnostic, FileDiagnostics};
+use crate::tools::lsp::protocol::{
+    uri_to_path, NotificationMessage, RequestId, RequestMessage, ResponseError, ResponseMessage,
+    WorkspaceFolder,
+};
+
+/// Notifications kept for callers that haven't collected them yet. Servers
+/// can log a lot while indexing, so the oldest are dropped past this.
+pub const MAX_BUFFERED_NOTIFICATIONS: usize = 1024;
+
+/// JSON-RPC error code for server requests we don't handle
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// State shared between callers and the reader thread
+struct Shared {
+    writer: Mutex<Box<dyn Write + Send>>,
+    /// Requests waiting for a response, by id
+    pending: Mutex<HashMap<u64, Sender<ResponseMessage>>>,
+    notifications: Mutex<VecDeque<NotificationMessage>>,
+    /// Work-done progress the server has begun and not yet ended, by token,
+    /// with its title
+    progress: Mutex<HashMap<String, String>>,
+    /// The latest diagnostics published for each file, with the generation
+    /// they were published at
+    diagnostics: Mutex<HashMap<PathBuf, (u64, Vec<Diagnostic>)>>,
+    /// Bumped on every `textDocument/publishDiagnostics`
+    diagnostics_generation: AtomicU64,
+    workspace_folders: Mutex<Vec<WorkspaceFolder>>,
+    /// Edits sent with `workspace/applyEdit` while a command runs, or
+    /// `None` when the server isn't expected to send any
+    requested_edits: Mutex<Option<Vec<Value>>>,
+    closed: AtomicBool,
+}
+
+impl Shared {
+    fn write_message(&self, message: &impl serde::Serialize) -> Result<()> {
+        let json = serde_json::to_string(message)?;
+        let framed = format!("Content-Length: {}\r\n\r\n{json}", json.len());
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| anyhow!("LSP writer lock poisoned"))?;
+        writer.write_all(framed.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Answer a request the server sent us. Servers block on some of these
+    /// (rust-analyzer waits for `workspace/configuration` before indexing), so
+    /// each gets a reply even when there's nothing useful to say.
+    fn answer_server_request(&self, id: Value, method: &str, params: Option<&Value>) {
+        let result = match method {
+            // One entry per requested section; null means "use your defaults"
+            "workspace/configuration" => Ok(Value::Array(
+                params
+                    .and_then(|p| p.get("items"))
+                    .and_then(Value::as_array)
+                    .map(|items| vec![Value::Null; items.len()])
+                    .unwrap_or_default(),
+            )),
+            "workspace/workspaceFolders" => Ok(self
+                .workspace_folders
+                .lock()
+                .ok()
+                .and_then(|folders| serde_json::to_value(&*folders).ok())
+                .unwrap_or(Value::Null)),
+            "window/workDoneProgress/create"
+            | "client/registerCapability"
+            | "client/unregisterCapability"
+            | "window/showMessageRequest"
+            | "window/showDocument"
+            | "workspace/semanticTokens/refresh"
+            | "workspace/codeLens/refresh"
+            | "workspace/inlayHint/refresh"
+            | "workspace/diagnostic/refresh" => Ok(Value::Null),
+            // Edits go through FileOps on our side. Those a command asks for
+            // are collected and applied once it returns; others are refused.
+            "workspace/applyEdit" => Ok(self.collect_edit(params)),
+            _ => Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unhandled method {method}"),
+                data: None,
+            }),
+        };
+
+        let reply = match result {
+            Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        };
+        if let Err(e) = self.write_message(&reply) {
+            eprintln!(
+                "{}",
+                format_log_with_color(
+                    LogLevel::Warning,
+                    &format!("Failed to answer LSP request {method}: {e}")
+                )
+            );
+        }
+    }
+
+    fn collect_edit(&self, params: Option<&Value>) -> Value {
+        let edit = params.and_then(|params| params.get("edit"));
+        match (self.requested_edits.lock().as_deref_mut(), edit) {
+            (Ok(Some(edits)), Some(edit)) => {
+                edits.push(edit.clone());
+                serde_json::json!({ "applied": true })
+            }
+            _ => serde_json::json!({
+                "applied": false,
+                "failureReason": "The client applies edits itself"
+            }),
+        }
+    }
+
+    /// Track `$/progress` begin and end reports, which is how servers say
+    /// they are still indexing
+    fn track_progress(&self, params: Option<&Value>) {
+        let Some(params) = params else {
+            return;
+        };
+        let token = match params.get("token") {
+            Some(Value::String(token)) => token.clone(),
+            Some(token) => token.to_string(),
+            None => return,
+        };
+        let value = params.get("value");
+        let Ok(mut progress) = self.progress.lock() else {
+            return;
+        };
+        match value.and_then(|v| v.get("kind")).and_then(Value::as_str) {
+            Some("begin") => {
+                let title = value
+                    .and_then(|v| v.get("title"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("working")
+                    .to_string();
+                progress.insert(token, title);
+            }
+            Some("end") => {
+                progress.remove(&token);
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep the diagnostics of a `textDocument/publishDiagnostics`, which
+    /// replace whatever was published for that file before
+    fn store_diagnostics(&self, params: Option<&Value>) {
+        let Some(uri) = params.and_then(|p| p.get("uri")).and_then(Value::as_str) else {
+            return;
+        };
+        let diagnostics: Vec<Diagnostic> = params
+            .and_then(|p| p.get("diagnostics"))
+            .and_then(Value::as_array)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| serde_json::from_value(entry.clone()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if let Ok(mut stored) = self.diagnostics.lock() {
+            let generation = self.diagnostics_generation.fetch_add(1, Ordering::SeqCst) + 1;
+            stored.insert(uri_to_path(uri), (generation, diagnostics));
+        }
+    }
+
+    fn push_notification(&self, notification: NotificationMessage) {
+        match notification.method.as_str() {
+            "$/progress" => self.track_progress(notification.params.as_ref()),
+            "textDocument/publishDiagnostics" => {
+                self.store_diagnostics(notification.params.as_ref())
+            }
+            _ => {}
+        }
+        if let Ok(mut notifications) = self.notifications.lock() {
+            if notifications.len() >= MAX_BUFFERED_NOTIFICATIONS {
+                notifications.pop_front();
+            }
+            notifications.push_back(notification);
+        }
+    }
+
+    /// Route one message from the server by its shape: responses carry an id
+    /// and no method, server requests carry both, notifications only a method
+    fn dispatch(&self, message: Value) {
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id").filter(|id| !id.is_null());
+
+        match (method, id) {
+            (Some(method), Some(id)) => {
+                self.answer_server_request(id.clone(), method, message.get("params"))
+            }
+            (Some(method), None) => self.push_notification(NotificationMessage {
+                jsonrpc: "2.0".to_string(),
+                method: method.to_string(),
+                params: message.get("params").cloned(),
+            }),
+            (None, Some(id)) => {
+                // We only send numeric ids, but some servers echo them as strings
+                let id = id
+                    .as_u64()
+                    .or_else(|| id.as_str().and_then(|s| s.parse().ok()));
+                let Some(id) = id else {
+                    return;
+                };
+                let sender = self.pending.lock().ok().and_then(|mut p| p.remove(&id));
+                let Some(sender) = sender else {
+                    // Late response to a request that already timed out
+                    return;
+                };
+                let response = ResponseMessage {
+                    jsonrpc: "2.0".to_string(),
+                    id: RequestId::Number(id),
+                    result: message.get("result").cloned(),
+                    error: message
+                        .get("error")
+                        .and_then(|e| serde_json::from_value(e.clone()).ok()),
+                };
+                let _ = sender.send(response);
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Stop accepting requests and wake everyone still waiting, whose
+    /// receivers then see a disconnect instead of waiting out the timeout
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+    }
+}
+
+/// Read one `Content-Length` framed message, or `None` at end of stream
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let line = header.trim();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            // Stray blank line between messages
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let mut content = vec![0; content_length.unwrap_or(0)];
+    reader.read_exact(&mut content)?;
+    Ok(Some(content))
+}
+
+/// JSON-RPC over a language server's stdio. A background thread reads every
+/// message the server sends: responses are handed to the request waiting for
+/// that id, server requests are answered, and notifications are buffered
+/// until collected with [`LspTransport::take_notifications`].
+pub struct LspTransport {
+    shared: Arc<Shared>,
+    next_id: AtomicU64,
+}
+
+impl LspTransport {
+    /// Start reading from `reader` (the server's stdout) in the background,
+    /// writing requests to `writer` (its stdin)
+    pub fn new<R, W>(reader: R, writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            writer: Mutex::new(Box::new(writer)),
+            pending: Mutex::new(HashMap::new()),
+            notifications: Mutex::new(VecDeque::new()),
+            progress: Mutex::new(HashMap::new()),
+            diagnostics: Mutex::new(HashMap::new()),
+            diagnostics_generation: AtomicU64::new(0),
+            workspace_folders: Mutex::new(Vec::new()),
+            requested_edits: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        });
+
+        let reader_shared = shared.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(content)) => match serde_json::from_slice::<Value>(&content) {
+                        Ok(message) => reader_shared.dispatch(message),
+                        Err(e) => eprintln!(
+                            "{}",
+                            format_log_with_color(
+                                LogLevel::Warning,
+                                &format!("Ignoring malformed LSP message: {e}")
+                            )
+                        ),
+                    },
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            format_log_with_color(
+                                LogLevel::Warning,
+                                &format!("LSP connection failed: {e}")
+                            )
+                        );
+                        break;
+                    }
+                }
+            }
+            reader_shared.close();
+        });
+
+        Self {
+            shared,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Folders reported when the server asks for `workspace/workspaceFolders`
+    pub fn with_workspace_folders(self, folders: Vec<WorkspaceFolder>) -> Self {
+        if let Ok(mut current) = self.shared.workspace_folders.lock() {
+            *current = folders;
+        }
+        self
+    }
+
+    /// Send a request and wait up to `timeout` for its response. On timeout
+    /// the request is cancelled, so the server can stop working on it.
+    pub fn request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<ResponseMessage> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut pending = self
+                .shared
+                .pending
+                .lock()
+                .map_err(|_| anyhow!("LSP pending requests lock poisoned"))?;
+            // Checked under the lock, so a close can't slip in between and
+            // leave this request waiting for the full timeout
+            if self.is_closed() {
+                return Err(anyhow!("LSP server connection is closed"));
+            }
+            pending.insert(id, sender);
+        }
+
+        let request = RequestMessage {
+            jsonrpc: "2.0".to_string(),
+            id: RequestId::Number(id),
+            method: method.to_string(),
+            params,
+        };
+        if let Err(e) = self.shared.write_message(&request) {
+            self.forget(id);
+            return Err(e);
+        }
+
+        match receiver.recv_timeout(timeout) {
+            Ok(response) => Ok(response),
+            Err(RecvTimeoutError::Timeout) => {
+                self.forget(id);
+                let _ = self.notify("$/cancelRequest", Some(serde_json::json!({ "id": id })));
+                Err(anyhow!(
+                    "LSP request {method} timed out after {}s",
+                    timeout.as_secs_f32()
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(anyhow!("LSP server exited before answering {method}"))
+            }
+        }
+    }
+
+    /// Send a notification, which has no response
+    pub fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
+        if self.is_closed() {
+            return Err(anyhow!("LSP server connection is closed"));
+        }
+        self.shared.write_message(&NotificationMessage {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+        })
+    }
+
+    /// Every notification received since the last call, oldest first
+    pub fn take_notifications(&self) -> Vec<NotificationMessage> {
+        self.shared
+            .notifications
+            .lock()
+            .map(|mut notifications| notifications.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Titles of the work the server has reported as in progress, such as
+    /// indexing, sorted. Empty once the server is idle.
+    pub fn active_progress(&self) -> Vec<String> {
+        let mut titles: Vec<String> = self
+            .shared
+            .progress
+            .lock()
+            .map(|progress| progress.values().cloned().collect())
+            .unwrap_or_default();
+        titles.sort();
+        titles
+    }
+
+    /// Counts diagnostics publications so far. Pass it to
+    /// [`LspTransport::diagnostics_since`] to see what a change brought.
+    pub fn diagnostics_generation(&self) -> u64 {
+        self.shared.diagnostics_generation.load(Ordering::SeqCst)
+    }
+
+    /// The latest diagnostics published for `path`, if any were
+    pub fn diagnostics(&self, path: &Path) -> Option<Vec<Diagnostic>> {
+        self.shared
+            .diagnostics
+            .lock()
+            .ok()?
+            .get(path)
+            .map(|(_, diagnostics)| diagnostics.clone())
+    }
+
+    /// Files whose diagnostics were published after `generation`, with the
+    /// diagnostics, sorted by path
+    pub fn diagnostics_since(&self, generation: u64) -> Vec<FileDiagnostics> {
+        let mut files: Vec<FileDiagnostics> = self
+            .shared
+            .diagnostics
+            .lock()
+            .map(|stored| {
+                stored
+                    .iter()
+                    .filter(|(_, (published, _))| *published > generation)
+                    .map(|(path, (_, diagnostics))| (path.clone(), diagnostics.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+
+    /// Accept the edits the server asks for with `workspace/applyEdit` from
+    /// now on, until they are taken
+    pub fn collect_requested_edits(&self) {
+        if let Ok(mut edits) = self.shared.requested_edits.lock() {
+            *edits = Some(Vec::new());
+        }
+    }
+
+    /// The edits the server asked for since [`LspTransport::collect_requested_edits`],
+    /// oldest first. Later ones are refused again.
+    pub fn take_requested_edits(&self) -> Vec<Value> {
+        self.shared
+            .requested_edits
+            .lock()
+            .ok()
+            .and_then(|mut edits| edits.take())
+            .unwrap_or_default()
+    }
+
+    /// Whether the server has closed its end of the connection
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.shared.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod fs;
 pub mod lsp;
+pub mod shell;
@@ -0,0 +1,5 @@
+pub mod process;
+pub mod session;
+
+pub use process::{ProcessInfo, ProcessManager, ProcessOutput};
+pub use session::{PersistentShell, ShellOutput, ShellSession};
@@ -0,0 +1,410 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Lines of output kept per process; older lines are dropped
+pub const OUTPUT_BUFFER_LINES: usize = 5000;
+
+/// Which stream a line of output came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub text: String,
+}
+
+/// Ring buffer of output lines. Lines are numbered from 0 in the order they
+/// were written, so callers can poll for everything after a given line even
+/// once older lines have been dropped.
+#[derive(Debug)]
+pub struct OutputBuffer {
+    lines: VecDeque<OutputLine>,
+    capacity: usize,
+    /// Number of lines ever written
+    total: u64,
+}
+
+impl OutputBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, stream: OutputStream, text: String) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(OutputLine { stream, text });
+        self.total += 1;
+    }
+
+    /// Number of lines ever written, i.e. the cursor after the newest line
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of the oldest line still held
+    pub fn first_retained(&self) -> u64 {
+        self.total - self.lines.len() as u64
+    }
+
+    /// Lines numbered `since` onwards that are still held, and how many lines
+    /// in that range were already dropped
+    pub fn since(&self, since: u64) -> (Vec<OutputLine>, u64) {
+        let first = self.first_retained();
+        let dropped = first.saturating_sub(since);
+        let skip = since.saturating_sub(first) as usize;
+        let lines = self.lines.iter().skip(skip).cloned().collect();
+        (lines, dropped)
+    }
+}
+
+/// Public description of a background process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub id: String,
+    pub command: String,
+    pub pid: u32,
+    pub cwd: Option<PathBuf>,
+    /// Start time in milliseconds since the Unix epoch
+    pub started_at: u64,
+    pub running: bool,
+    pub exit_code: Option<i32>,
+    /// Number of output lines written so far
+    pub output_lines: u64,
+}
+
+/// Output read from a background process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessOutput {
+    pub info: ProcessInfo,
+    pub lines: Vec<OutputLine>,
+    /// Lines that were requested but had already left the buffer
+    pub dropped: u64,
+    /// Cursor to pass as `since` to get only newer output
+    pub next_since: u64,
+}
+
+impl ProcessOutput {
+    /// Plain text rendering for the model, with stderr lines marked
+    pub fn to_text(&self) -> String {
+        let status = match (self.info.running, self.info.exit_code) {
+            (true, _) => "running".to_string(),
+            (false, Some(code)) => format!("exited with code {code}"),
+            (false, None) => "terminated by a signal".to_string(),
+        };
+        let mut text = format!(
+            "Process {} ({}) is {status}. ",
+            self.info.id, self.info.command
+        );
+        if self.lines.is_empty() {
+            text.push_str("No new output.");
+        } else {
+            text.push_str(&format!("{} new lines of output.", self.lines.len()));
+        }
+        text.push_str(&format!(
+            " Pass since={} to read only newer output.\n",
+            self.next_since
+        ));
+        if self.dropped > 0 {
+            text.push_str(&format!(
+                "[{} older lines were dropped from the buffer]\n",
+                self.dropped
+            ));
+        }
+        for line in &self.lines {
+            match line.stream {
+                OutputStream::Stdout => text.push_str(&line.text),
+                OutputStream::Stderr => {
+                    text.push_str("[stderr] ");
+                    text.push_str(&line.text);
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+struct BackgroundProcess {
+    command: String,
+    cwd: Option<PathBuf>,
+    started_at: u64,
+    child: Child,
+    output: Arc<Mutex<OutputBuffer>>,
+    /// Output streams still being read
+    open_streams: Arc<AtomicUsize>,
+    exit_code: Option<i32>,
+    finished: bool,
+}
+
+impl BackgroundProcess {
+    fn refresh(&mut self) {
+        if !self.finished {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.finished = true;
+                self.exit_code = status.code();
+
+                // Give the readers a moment to buffer the last of the output, so
+                // an exited process's output is complete. Processes it left
+                // running may hold the pipes open, so don't wait indefinitely.
+                let deadline = Instant::now() + Duration::from_millis(500);
+                while self.open_streams.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    fn info(&mut self, id: &str) -> ProcessInfo {
+        self.refresh();
+        ProcessInfo {
+            id: id.to_string(),
+            command: self.command.clone(),
+            pid: self.child.id(),
+            cwd: self.cwd.clone(),
+            started_at: self.started_at,
+            running: !self.finished,
+            exit_code: self.exit_code,
+            output_lines: self.output.lock().map(|o| o.total()).unwrap_or(0),
+        }
+    }
+
+    fn kill(&mut self) {
+        self.refresh();
+        if self.finished {
+            return;
+        }
+        // Kill the whole process group, so e.g. a dev server started through
+        // npm goes too
+        #[cfg(unix)]
+        {
+            let _ = Command::new("kill")
+                .arg("-TERM")
+                .arg("--")
+                .arg(format!("-{}", self.child.id()))
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            std::thread::sleep(Duration::from_millis(100));
+            let _ = Command::new("kill")
+                .arg("-KILL")
+                .arg("--")
+                .arg(format!("-{}", self.child.id()))
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+        let _ = self.child.kill();
+        if let Ok(status) = self.child.wait() {
+            self.exit_code = status.code();
+        }
+        self.finished = true;
+    }
+}
+
+struct Processes {
+    next_id: u64,
+    processes: BTreeMap<u64, BackgroundProcess>,
+}
+
+fn processes() -> &'static Mutex<Processes> {
+    static PROCESSES: OnceLock<Mutex<Processes>> = OnceLock::new();
+    PROCESSES.get_or_init(|| {
+        Mutex::new(Processes {
+            next_id: 1,
+            processes: BTreeMap::new(),
+        })
+    })
+}
+
+fn parse_id(id: &str) -> Result<u64> {
+    id.strip_prefix("bg-")
+        .unwrap_or(id)
+        .parse()
+        .map_err(|_| anyhow!("Invalid background process id: {id}"))
+}
+
+fn format_id(id: u64) -> String {
+    format!("bg-{id}")
+}
+
+/// Long-running commands (dev servers, watchers) started by the agent, which
+/// keep running while it does other work. Processes belong to the session and
+/// are stopped when it ends.
+pub struct ProcessManager;
+
+impl ProcessManager {
+    /// Start `command` with `sh -c` in `cwd`, returning its id
+    pub fn start(command: &str, cwd: Option<&Path>) -> Result<ProcessInfo> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to start background command: {command}"))?;
+
+        let output = Arc::new(Mutex::new(OutputBuffer::new(OUTPUT_BUFFER_LINES)));
+        let open_streams = Arc::new(AtomicUsize::new(0));
+        if let Some(stdout) = child.stdout.take() {
+            spawn_line_reader(
+                stdout,
+                OutputStream::Stdout,
+                output.clone(),
+                open_streams.clone(),
+            );
+        }
+        if let Some(stderr) = child.stderr.take() {
+            spawn_line_reader(
+                stderr,
+                OutputStream::Stderr,
+                output.clone(),
+                open_streams.clone(),
+            );
+        }
+
+        let mut process = BackgroundProcess {
+            command: command.to_string(),
+            cwd: cwd
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok()),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            child,
+            output,
+            open_streams,
+            exit_code: None,
+            finished: false,
+        };
+
+        let mut registry = processes()
+            .lock()
+            .map_err(|_| anyhow!("Process registry lock poisoned"))?;
+        let id = registry.next_id;
+        registry.next_id += 1;
+        let info = process.info(&format_id(id));
+        registry.processes.insert(id, process);
+        Ok(info)
+    }
+
+    /// Output written since line `since` (all buffered output when `None`)
+    pub fn output(id: &str, since: Option<u64>) -> Result<ProcessOutput> {
+        let key = parse_id(id)?;
+        let mut registry = processes()
+            .lock()
+            .map_err(|_| anyhow!("Process registry lock poisoned"))?;
+        let process = registry
+            .processes
+            .get_mut(&key)
+            .ok_or_else(|| anyhow!("No background process with id {id}"))?;
+
+        // Check for exit before reading, so the output is complete if it has
+        let info = process.info(&format_id(key));
+        let buffer = process
+            .output
+            .lock()
+            .map_err(|_| anyhow!("Output buffer lock poisoned"))?;
+        let since = since.unwrap_or_else(|| buffer.first_retained());
+        let (lines, dropped) = buffer.since(since);
+
+        Ok(ProcessOutput {
+            info,
+            lines,
+            dropped,
+            next_since: buffer.total(),
+        })
+    }
+
+    /// Stop a process and everything it started
+    pub fn kill(id: &str) -> Result<ProcessInfo> {
+        let key = parse_id(id)?;
+        let mut registry = processes()
+            .lock()
+            .map_err(|_| anyhow!("Process registry lock poisoned"))?;
+        let process = registry
+            .processes
+            .get_mut(&key)
+            .ok_or_else(|| anyhow!("No background process with id {id}"))?;
+        process.kill();
+        Ok(process.info(&format_id(key)))
+    }
+
+    /// Every process started in this session, oldest first
+    pub fn list() -> Vec<ProcessInfo> {
+        match processes().lock() {
+            Ok(mut registry) => registry
+                .processes
+                .iter_mut()
+                .map(|(id, process)| process.info(&format_id(*id)))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Stop all processes and forget them, when the session ends
+    pub fn kill_all() {
+        if let Ok(mut registry) = processes().lock() {
+            for process in registry.processes.values_mut() {
+                process.kill();
+            }
+            registry.processes.clear();
+        }
+    }
+}
+
+fn spawn_line_reader<R: Read + Send + 'static>(
+    reader: R,
+    stream: OutputStream,
+    output: Arc<Mutex<OutputBuffer>>,
+    open_streams: Arc<AtomicUsize>,
+) {
+    open_streams.fetch_add(1, Ordering::SeqCst);
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let text = String::from_utf8_lossy(&line);
+                    let text = text.trim_end_matches(['\n', '\r']).to_string();
+                    match output.lock() {
+                        Ok(mut buffer) => buffer.push(stream, text),
+                        Err(_) => break,
+                    }
+                }
+            }
+        }
+        open_streams.fetch_sub(1, Ordering::SeqCst);
+    });
+}
@@ -0,0 +1,562 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::app::logger::{format_log_with_color, LogLevel};
+
+/// Timeout for commands that don't specify one (2 minutes)
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+/// Longest timeout a command may ask for (10 minutes)
+pub const MAX_COMMAND_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Result of running one command in a shell session
+#[derive(Debug, Clone)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    /// Working directory of the shell after the command finished
+    pub cwd: Option<PathBuf>,
+    /// Set when the shell had to be restarted, explaining what was lost
+    pub notice: Option<String>,
+}
+
+impl ShellOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+enum ShellEvent {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    Closed,
+}
+
+/// A long-lived shell process that commands are fed to one at a time, so the
+/// working directory, exported variables, activated virtualenvs and shell
+/// functions carry over from one command to the next.
+///
+/// Each command is followed by sentinel markers on stdout and stderr; the
+/// stdout marker also carries the exit code and the new working directory.
+/// If the shell dies (for example after `exit`) or a command times out, a new
+/// shell is started in the last known working directory.
+pub struct ShellSession {
+    program: String,
+    child: Child,
+    stdin: ChildStdin,
+    events: Receiver<ShellEvent>,
+    cwd: Option<PathBuf>,
+}
+
+impl ShellSession {
+    /// Start a shell in the current working directory
+    pub fn new() -> Result<Self> {
+        Self::start(None)
+    }
+
+    /// Start a shell in the given directory
+    pub fn with_cwd(cwd: &Path) -> Result<Self> {
+        Self::start(Some(cwd.to_path_buf()))
+    }
+
+    fn start(cwd: Option<PathBuf>) -> Result<Self> {
+        // Prefer bash, since that's what the tool is called and what models write
+        let mut last_error = None;
+        for program in ["bash", "sh"] {
+            match Self::spawn(program, cwd.as_deref()) {
+                Ok(session) => return Ok(session),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("No shell available")))
+    }
+
+    fn spawn(program: &str, cwd: Option<&Path>) -> Result<Self> {
+        let mut command = Command::new(program);
+        if program == "bash" {
+            command.arg("--noprofile").arg("--norc");
+        }
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(cwd) = cwd.filter(|dir| dir.is_dir()) {
+            command.current_dir(cwd);
+        }
+
+        // Own process group, so a timed out command can be killed together
+        // with everything it started
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start {program}"))?;
+        let stdin = child.stdin.take().context("Shell has no stdin")?;
+        let stdout = child.stdout.take().context("Shell has no stdout")?;
+        let stderr = child.stderr.take().context("Shell has no stderr")?;
+
+        let (sender, events) = mpsc::channel();
+        spawn_reader(stdout, sender.clone(), ShellEvent::Stdout);
+        spawn_reader(stderr, sender, ShellEvent::Stderr);
+
+        let cwd = cwd
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok());
+
+        Ok(Self {
+            program: program.to_string(),
+            child,
+            stdin,
+            events,
+            cwd,
+        })
+    }
+
+    /// The shell program in use ("bash" or "sh")
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Working directory after the last command
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Replace the shell with a fresh one in the same working directory
+    pub fn restart(&mut self) -> Result<()> {
+        self.kill();
+        let cwd = self.cwd.clone();
+        *self = Self::start(cwd)?;
+        Ok(())
+    }
+
+    /// Run a command and wait for it to finish
+    pub fn run(&mut self, command: &str, timeout: Duration) -> Result<ShellOutput> {
+        let mut notice = None;
+        if !self.is_alive() {
+            self.restart()?;
+            notice = Some(restart_notice(&self.cwd));
+        }
+
+        // Discard anything written since the last command, e.g. by jobs left
+        // running in the background
+        while self.events.try_recv().is_ok() {}
+
+        let marker = format!("__OLI_DONE_{}__", Uuid::new_v4().simple());
+        // eval keeps syntax errors from terminating the shell, and reading from
+        // /dev/null stops commands from consuming the script on stdin
+        let script = format!(
+            "eval {} < /dev/null\n\
+             __oli_status=$?\n\
+             printf '{marker} %d %s\\n' \"$__oli_status\" \"$PWD\"\n\
+             printf '{marker}\\n' >&2\n",
+            shell_quote(command)
+        );
+        if let Err(e) = self
+            .stdin
+            .write_all(script.as_bytes())
+            .and_then(|_| self.stdin.flush())
+        {
+            self.restart()?;
+            return Err(anyhow!(
+                "The shell stopped accepting commands ({e}). {}",
+                restart_notice(&self.cwd)
+            ));
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut status: Option<(i32, PathBuf)> = None;
+        let mut stderr_done = false;
+
+        while status.is_none() || !stderr_done {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(remaining) {
+                // Anything after a marker comes from background jobs, not this command
+                Ok(ShellEvent::Stdout(bytes)) => {
+                    if status.is_none() {
+                        stdout.extend_from_slice(&bytes);
+                        status = take_status(&mut stdout, &marker);
+                    }
+                }
+                Ok(ShellEvent::Stderr(bytes)) => {
+                    if !stderr_done {
+                        stderr.extend_from_slice(&bytes);
+                        stderr_done = take_marker(&mut stderr, &marker);
+                    }
+                }
+                Ok(ShellEvent::Closed) => {
+                    // The command ended the shell, e.g. with `exit`
+                    let exit_code = self
+                        .child
+                        .wait()
+                        .ok()
+                        .and_then(|status| status.code())
+                        .unwrap_or(-1);
+                    self.restart()?;
+                    return Ok(ShellOutput {
+                        stdout: String::from_utf8_lossy(&stdout).to_string(),
+                        stderr: String::from_utf8_lossy(&stderr).to_string(),
+                        exit_code,
+                        cwd: self.cwd.clone(),
+                        notice: Some(format!(
+                            "The shell exited with code {exit_code}. {}",
+                            restart_notice(&self.cwd)
+                        )),
+                    });
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.restart()?;
+                    return Err(anyhow!(
+                        "Command timed out after {} ms. {}",
+                        timeout.as_millis(),
+                        restart_notice(&self.cwd)
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.restart()?;
+                    return Err(anyhow!(
+                        "Lost contact with the shell. {}",
+                        restart_notice(&self.cwd)
+                    ));
+                }
+            }
+        }
+
+        let (exit_code, cwd) = status.expect("loop only ends once the status is known");
+        self.cwd = Some(cwd);
+
+        Ok(ShellOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            exit_code,
+            cwd: self.cwd.clone(),
+            notice,
+        })
+    }
+
+    fn kill(&mut self) {
+        kill_process_group(&mut self.child);
+    }
+
+    /// Run a single command in a fresh `sh -c` started in `cwd`, killing it
+    /// and everything it started if it outlives the timeout
+    pub fn run_once(command: &str, cwd: Option<&Path>, timeout: Duration) -> Result<ShellOutput> {
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let cwd = cwd
+            .filter(|dir| dir.is_dir())
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok());
+        if let Some(cwd) = &cwd {
+            process.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            process.process_group(0);
+        }
+
+        let mut child = process.spawn().context("Failed to start sh")?;
+        let (sender, events) = mpsc::channel();
+        spawn_reader(
+            child.stdout.take().context("Shell has no stdout")?,
+            sender.clone(),
+            ShellEvent::Stdout,
+        );
+        spawn_reader(
+            child.stderr.take().context("Shell has no stderr")?,
+            sender,
+            ShellEvent::Stderr,
+        );
+
+        // Read until both streams close, which is also after jobs the command
+        // left running in the background have finished
+        let deadline = Instant::now() + timeout;
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut open_streams = 2;
+        while open_streams > 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match events.recv_timeout(remaining) {
+                Ok(ShellEvent::Stdout(bytes)) => stdout.extend_from_slice(&bytes),
+                Ok(ShellEvent::Stderr(bytes)) => stderr.extend_from_slice(&bytes),
+                Ok(ShellEvent::Closed) | Err(RecvTimeoutError::Disconnected) => open_streams -= 1,
+                Err(RecvTimeoutError::Timeout) => {
+                    kill_process_group(&mut child);
+                    return Err(anyhow!(
+                        "Command timed out after {} ms",
+                        timeout.as_millis()
+                    ));
+                }
+            }
+        }
+        let status = child.wait().context("Failed to wait for sh")?;
+
+        Ok(ShellOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            exit_code: status.code().unwrap_or(-1),
+            cwd,
+            notice: None,
+        })
+    }
+}
+
+fn kill_process_group(child: &mut Child) {
+    // Kill the whole process group so commands started by the shell go too
+    #[cfg(unix)]
+    {
+        let _ = Command::new("kill")
+            .arg("-KILL")
+            .arg("--")
+            .arg(format!("-{}", child.id()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    sender: Sender<ShellEvent>,
+    wrap: fn(Vec<u8>) -> ShellEvent,
+) {
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 8192];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => {
+                    let _ = sender.send(ShellEvent::Closed);
+                    break;
+                }
+                Ok(n) => {
+                    if sender.send(wrap(buffer[..n].to_vec())).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Looks for "<marker> <status> <cwd>\n" in stdout. Once the whole line has
+/// arrived, removes it (and anything after it) and returns its contents.
+fn take_status(stdout: &mut Vec<u8>, marker: &str) -> Option<(i32, PathBuf)> {
+    let start = find(stdout, marker.as_bytes())?;
+    let line_end = start + stdout[start..].iter().position(|&b| b == b'\n')?;
+    let line = String::from_utf8_lossy(&stdout[start + marker.len()..line_end]).to_string();
+    stdout.truncate(start);
+
+    let mut parts = line.trim_start().splitn(2, ' ');
+    let exit_code = parts.next()?.parse().ok()?;
+    let cwd = PathBuf::from(parts.next().unwrap_or_default());
+    Some((exit_code, cwd))
+}
+
+/// Looks for "<marker>\n" in stderr, removing it if present
+fn take_marker(stderr: &mut Vec<u8>, marker: &str) -> bool {
+    let line = format!("{marker}\n");
+    match find(stderr, line.as_bytes()) {
+        Some(start) => {
+            stderr.truncate(start);
+            true
+        }
+        None => false,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Quotes text as a single shell word
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn restart_notice(cwd: &Option<PathBuf>) -> String {
+    match cwd {
+        Some(cwd) => format!(
+            "A new shell was started in {}; exported variables and other shell state were reset.",
+            cwd.display()
+        ),
+        None => "A new shell was started; exported variables and other shell state were reset."
+            .to_string(),
+    }
+}
+
+#[derive(Default)]
+struct ShellState {
+    enabled: bool,
+    /// Directory new shells start in
+    working_dir: Option<PathBuf>,
+    session: Option<ShellSession>,
+    /// Working directory of the shell after its last command
+    cwd: Option<PathBuf>,
+    /// Set while a command runs, with the session taken out of `session`
+    busy: bool,
+    /// Bumped by `reset`, so a shell that was running at the time is dropped
+    generation: u64,
+}
+
+/// A client session's shell for the Bash tool. Off by default, in which case
+/// each Bash call runs in a fresh `sh -c`; when enabled, calls share one
+/// long-lived `ShellSession`. Clones share the same shell.
+#[derive(Clone, Default)]
+pub struct PersistentShell {
+    state: Arc<Mutex<ShellState>>,
+}
+
+impl PersistentShell {
+    /// A shell, disabled until `set_enabled`, whose commands start in `working_dir`
+    pub fn new(working_dir: Option<PathBuf>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ShellState {
+                working_dir,
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.enabled)
+            .unwrap_or(false)
+    }
+
+    /// Turn the persistent shell on or off. Turning it off stops the shell.
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.enabled = enabled;
+            if !enabled {
+                Self::stop(&mut state);
+            }
+        }
+    }
+
+    /// Stop the shell; the next command starts a fresh one
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            Self::stop(&mut state);
+        }
+    }
+
+    fn stop(state: &mut ShellState) {
+        state.session = None;
+        state.cwd = None;
+        state.busy = false;
+        state.generation += 1;
+    }
+
+    /// Working directory commands run in: the persistent shell's after its
+    /// last command, otherwise the one new shells start in
+    pub fn cwd(&self) -> Option<PathBuf> {
+        let state = self.state.lock().ok()?;
+        state.cwd.clone().or_else(|| state.working_dir.clone())
+    }
+
+    /// Run a command, in the persistent shell (starting it if needed) when
+    /// enabled, otherwise in a fresh `sh -c`
+    pub fn run(&self, command: &str, timeout: Duration) -> Result<ShellOutput> {
+        let (session, working_dir, generation) = {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| anyhow!("Persistent shell lock poisoned"))?;
+            if !state.enabled {
+                let working_dir = state.working_dir.clone();
+                drop(state);
+                return ShellSession::run_once(command, working_dir.as_deref(), timeout);
+            }
+            if state.busy {
+                return Err(anyhow!(
+                    "The persistent shell is still running another command"
+                ));
+            }
+            state.busy = true;
+            (
+                state.session.take(),
+                state.working_dir.clone(),
+                state.generation,
+            )
+        };
+
+        // The lock isn't held while the command runs, so the shell's settings
+        // and working directory can still be read and changed meanwhile
+        let mut session = match session {
+            Some(session) => session,
+            None => match Self::start(working_dir.as_deref()) {
+                Ok(session) => session,
+                Err(e) => {
+                    self.finish(generation, None);
+                    return Err(e);
+                }
+            },
+        };
+        let result = session.run(command, timeout);
+        self.finish(generation, Some(session));
+        result
+    }
+
+    fn start(working_dir: Option<&Path>) -> Result<ShellSession> {
+        let session = match working_dir {
+            Some(dir) => ShellSession::with_cwd(dir)?,
+            None => ShellSession::new()?,
+        };
+        eprintln!(
+            "{}",
+            format_log_with_color(
+                LogLevel::Debug,
+                &format!("Started persistent {} session", session.program())
+            )
+        );
+        Ok(session)
+    }
+
+    /// Puts the shell back after a command, unless it was reset meanwhile
+    fn finish(&self, generation: u64, session: Option<ShellSession>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.generation != generation {
+            return;
+        }
+        state.busy = false;
+        if let Some(cwd) = session.as_ref().and_then(ShellSession::cwd) {
+            state.cwd = Some(cwd.to_path_buf());
+        }
+        state.session = session;
+    }
+}
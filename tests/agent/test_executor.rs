@@ -1,7 +1,8 @@
 //! Unit tests for the Agent executor module
 
 use oli_server::agent::executor::{
-    determine_completion_threshold, process_response, should_request_completion, AgentExecutor,
+    determine_completion_threshold, parse_tool_call, process_response, should_request_completion,
+    AgentExecutor,
 };
 // Necessary for tests
 use anyhow::Result;
@@ -1049,3 +1050,28 @@ mod util_function_tests {
         assert_eq!(parsed["tool_calls"][0]["name"], "TestTool");
     }
 }
+
+#[cfg(test)]
+mod tool_call_parsing_tests {
+    use super::*;
+    use oli_server::agent::tools::ToolCall as AgentToolCall;
+
+    #[test]
+    fn test_parse_background_process_tools() {
+        let output = parse_tool_call("BashOutput", &serde_json::json!({"id": "1", "since": 3}))
+            .expect("BashOutput should parse");
+        assert!(
+            matches!(&output, AgentToolCall::BashOutput(params) if params.id == "1" && params.since == Some(3)),
+            "{output:?}"
+        );
+
+        let kill = parse_tool_call("KillProcess", &serde_json::json!({"id": "1"}))
+            .expect("KillProcess should parse");
+        assert!(
+            matches!(&kill, AgentToolCall::KillProcess(params) if params.id == "1"),
+            "{kill:?}"
+        );
+
+        assert!(parse_tool_call("NoSuchTool", &serde_json::json!({})).is_err());
+    }
+}
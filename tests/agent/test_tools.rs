@@ -1,6 +1,7 @@
 use oli_server::agent::core::{Agent, LLMProvider};
 use oli_server::agent::tools::{
-    BashParams, EditParams, GlobParams, GrepParams, LSParams, ReadParams, ToolCall, WriteParams,
+    BashParams, EditParams, FindFileParams, GlobParams, GrepParams, LSParams, ReadParams,
+    RepoMapParams, ToolCall, WriteParams,
 };
 use std::env;
 use std::fs;
@@ -160,6 +161,7 @@ async fn test_glob_tool_direct() {
     let glob_result = ToolCall::Glob(GlobParams {
         pattern: "*.rs".to_string(),
         path: Some(rs_dir.to_string_lossy().to_string()),
+        ..Default::default()
     })
     .execute();
 
@@ -177,6 +179,7 @@ async fn test_glob_tool_direct() {
     let glob_js_result = ToolCall::Glob(GlobParams {
         pattern: "*.js".to_string(),
         path: Some(js_dir.to_string_lossy().to_string()),
+        ..Default::default()
     })
     .execute();
 
@@ -299,6 +302,7 @@ async fn test_grep_tool_direct() {
         pattern: "IMPORTANT".to_string(),
         path: Some(temp_dir.path().to_string_lossy().to_string()),
         include: None,
+        ..Default::default()
     })
     .execute();
 
@@ -317,6 +321,7 @@ async fn test_grep_tool_direct() {
         pattern: "(?i)important".to_string(), // Case-insensitive regex
         path: Some(temp_dir.path().to_string_lossy().to_string()),
         include: None,
+        ..Default::default()
     })
     .execute();
 
@@ -339,6 +344,7 @@ async fn test_grep_tool_direct() {
         pattern: "important".to_string(),
         path: Some(temp_dir.path().to_string_lossy().to_string()),
         include: Some("*.txt".to_string()),
+        ..Default::default()
     })
     .execute();
 
@@ -357,6 +363,91 @@ async fn test_grep_tool_direct() {
     );
 }
 
+#[tokio::test]
+async fn test_find_file_tool_direct() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("src/models")).expect("Failed to create dirs");
+    fs::write(temp_dir.path().join("src/models/user_account.rs"), "")
+        .expect("Failed to write user_account.rs");
+    fs::write(temp_dir.path().join("src/main.rs"), "").expect("Failed to write main.rs");
+
+    let result = ToolCall::FindFile(FindFileParams {
+        query: "usracc".to_string(),
+        path: Some(temp_dir.path().to_string_lossy().to_string()),
+        limit: None,
+    })
+    .execute();
+
+    assert!(result.is_ok(), "Failed to find files: {result:?}");
+    let output = result.unwrap();
+    // Paths are absolute so they can be passed straight to Read
+    let expected = temp_dir.path().join("src/models/user_account.rs");
+    assert!(
+        output.contains(&format!("1. {}", expected.display())),
+        "FindFile should list user_account.rs first: {output}"
+    );
+    assert!(!output.contains("main.rs"), "{output}");
+}
+
+#[tokio::test]
+async fn test_repo_map_tool_direct() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("src")).expect("Failed to create dirs");
+    fs::write(
+        temp_dir.path().join("src/session.rs"),
+        "pub struct SessionStore;\n\nimpl SessionStore {\n    pub fn open() -> Self {\n    }\n}\n",
+    )
+    .expect("Failed to write session.rs");
+    fs::write(
+        temp_dir.path().join("src/main.rs"),
+        "fn main() {\n    SessionStore::open();\n}\n",
+    )
+    .expect("Failed to write main.rs");
+
+    let result = ToolCall::RepoMap(RepoMapParams {
+        path: Some(temp_dir.path().to_string_lossy().to_string()),
+        max_tokens: None,
+    })
+    .execute();
+
+    assert!(result.is_ok(), "Failed to map repository: {result:?}");
+    let output = result.unwrap();
+    // The file the other one uses comes first, with its members under it
+    assert!(
+        output.contains("src/session.rs:\n  1: pub struct SessionStore;\n  3: impl SessionStore\n    4: pub fn open() -> Self\nsrc/main.rs:\n  1: fn main()\n"),
+        "RepoMap should list session.rs first: {output}"
+    );
+}
+
+#[tokio::test]
+async fn test_diagnostics_tool_rejects_unsupported_files() {
+    use oli_server::tools::lsp::DiagnosticsParams;
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let notes = temp_dir.path().join("notes.txt");
+    fs::write(&notes, "just text").expect("Failed to write notes.txt");
+
+    let result = ToolCall::Diagnostics(DiagnosticsParams {
+        file_path: Some(notes.to_string_lossy().to_string()),
+        workspace: None,
+    })
+    .execute();
+    let err = result.expect_err("No language server handles .txt files");
+    assert!(err.to_string().contains("No language server"), "{err}");
+
+    // A directory without project markers or a running server
+    let result = ToolCall::Diagnostics(DiagnosticsParams {
+        file_path: None,
+        workspace: Some(temp_dir.path().to_string_lossy().to_string()),
+    })
+    .execute();
+    let err = result.expect_err("Nothing can check an empty directory");
+    assert!(
+        err.to_string().contains("No language server is running"),
+        "{err}"
+    );
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature = "benchmark"), ignore)]
 async fn test_grep_tool_with_llm() {
@@ -577,9 +668,7 @@ async fn test_ls_tool_with_llm() {
 #[tokio::test]
 async fn test_document_symbol_tool_direct() {
     // Import needed for the DocumentSymbol test
-    use oli_server::tools::lsp::{
-        LspServerType, ModelsDocumentSymbolParams as DocumentSymbolParams,
-    };
+    use oli_server::tools::lsp::ModelsDocumentSymbolParams as DocumentSymbolParams;
 
     // Create a temporary directory and Python test file
     let temp_dir = tempdir().expect("Failed to create temp dir");
@@ -627,7 +716,7 @@ if __name__ == "__main__":
     );
     let doc_symbol_result = ToolCall::DocumentSymbol(DocumentSymbolParams {
         file_path: test_file_path.to_string_lossy().to_string(),
-        server_type: LspServerType::Python,
+        server_type: None,
     })
     .execute();
 
@@ -779,8 +868,6 @@ async fn test_edit_tool_direct() {
 #[tokio::test]
 #[cfg_attr(not(feature = "benchmark"), ignore)]
 async fn test_document_symbol_tool_with_llm() {
-    // We don't need to import LspServerType here as we're just passing the string value
-
     // Set up the agent
     let Some((agent, timeout_secs)) = setup_ollama_agent().await else {
         return;
@@ -963,6 +1050,7 @@ async fn test_bash_tool_direct() {
         command: "echo 'Hello, World!'".to_string(),
         timeout: None,
         description: Some("Prints greeting message".to_string()),
+        run_in_background: None,
     })
     .execute();
 
@@ -982,6 +1070,7 @@ async fn test_bash_tool_direct() {
         command: "non_existent_command".to_string(),
         timeout: None,
         description: Some("Tests error handling".to_string()),
+        run_in_background: None,
     })
     .execute();
 
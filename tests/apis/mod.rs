@@ -32,5 +32,7 @@ mod test_anthropic {
 mod test_api_client;
 mod test_api_client_enum;
 mod test_gemini;
+mod test_http;
 mod test_ollama;
 mod test_openai;
+mod test_structured;
@@ -1,7 +1,7 @@
 //! Unit tests for the API client module
 
 use oli_server::apis::api_client::{
-    CompletionOptions, Message, SessionManager, ToolCall, ToolDefinition, ToolResult,
+    CompletionOptions, ContentBlock, Message, SessionManager, ToolCall, ToolDefinition, ToolResult,
 };
 use serde_json::json;
 
@@ -24,6 +24,31 @@ fn test_message_creation() {
     assert_eq!(assistant_msg.content, "Test assistant message");
 }
 
+/// Tests that thinking blocks survive a serialization round trip unchanged
+#[test]
+fn test_message_blocks_round_trip() {
+    let message = Message::assistant("Answer".to_string()).with_blocks(vec![
+        ContentBlock::Thinking {
+            thinking: "Reasoning".to_string(),
+            signature: "sig".to_string(),
+        },
+        ContentBlock::RedactedThinking {
+            data: "opaque".to_string(),
+        },
+    ]);
+
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(json["blocks"][0]["type"], "thinking");
+    assert_eq!(json["blocks"][1]["type"], "redacted_thinking");
+
+    let restored: Message = serde_json::from_value(json).unwrap();
+    assert_eq!(restored, message);
+
+    // Plain messages don't carry an empty blocks field
+    let plain = serde_json::to_value(Message::user("Hi".to_string())).unwrap();
+    assert!(plain.get("blocks").is_none());
+}
+
 /// Tests for tool structures
 #[test]
 fn test_tool_structures() {
@@ -0,0 +1,218 @@
+//! Tests for structured output parsing, validation and the repair retry
+
+use anyhow::Result;
+use oli_server::apis::api_client::{
+    ApiClient, ApiClientEnum, CompletionOptions, Message, ToolCall, ToolResult,
+};
+use oli_server::apis::structured::{extract_json, object_schema, parse_response, validate};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+fn summary_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "title": { "type": "string", "maxLength": 20 },
+            "files": { "type": "array", "items": { "type": "string" } },
+            "risk": { "enum": ["low", "high"] }
+        },
+        "required": ["title", "files"],
+        "additionalProperties": false
+    })
+}
+
+#[test]
+fn test_extract_json_variants() {
+    assert_eq!(extract_json(r#"{"a": 1}"#).unwrap(), json!({"a": 1}));
+    assert_eq!(
+        extract_json("```json\n{\"a\": 1}\n```").unwrap(),
+        json!({"a": 1})
+    );
+    assert_eq!(
+        extract_json("Here you go: {\"a\": [1, 2]} hope that helps").unwrap(),
+        json!({"a": [1, 2]})
+    );
+    assert!(extract_json("no json here").is_err());
+}
+
+#[test]
+fn test_validate_reports_each_violation() {
+    let schema = summary_schema();
+
+    let valid = json!({ "title": "Fix", "files": ["a.rs"], "risk": "low" });
+    assert!(validate(&valid, &schema).is_empty());
+
+    let invalid = json!({ "title": "A title that is much too long", "files": [1], "extra": true });
+    let errors = validate(&invalid, &schema);
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    assert!(errors.iter().any(|e| e.contains("$.title")));
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("$.files[0]: expected string")));
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("unexpected property \"extra\"")));
+
+    let missing = json!({ "title": "Fix", "risk": "medium" });
+    let errors = validate(&missing, &schema);
+    assert!(errors
+        .iter()
+        .any(|e| e.contains("missing required property \"files\"")));
+    assert!(errors.iter().any(|e| e.contains("must be one of")));
+}
+
+#[test]
+fn test_validate_types() {
+    assert!(validate(&json!(3), &json!({ "type": "integer" })).is_empty());
+    assert!(!validate(&json!(3.5), &json!({ "type": "integer" })).is_empty());
+    assert!(validate(&json!(null), &json!({ "type": ["string", "null"] })).is_empty());
+    assert!(validate(
+        &json!("x"),
+        &json!({ "anyOf": [{ "type": "number" }, { "type": "string" }] })
+    )
+    .is_empty());
+    assert!(!validate(
+        &json!(true),
+        &json!({ "anyOf": [{ "type": "number" }, { "type": "string" }] })
+    )
+    .is_empty());
+
+    // oneOf needs exactly one match: 3 is both an integer and a number
+    let one_of = json!({ "oneOf": [{ "type": "integer" }, { "type": "number" }] });
+    assert!(validate(&json!(3.5), &one_of).is_empty());
+    assert_eq!(
+        validate(&json!(3), &one_of),
+        vec!["$: matches 2 of the allowed schemas, but must match exactly one"]
+    );
+    assert!(!validate(&json!("3"), &one_of).is_empty());
+    // anyOf accepts the same value
+    assert!(validate(
+        &json!(3),
+        &json!({ "anyOf": [{ "type": "integer" }, { "type": "number" }] })
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_parse_response_and_object_schema() {
+    let schema = summary_schema();
+    assert!(parse_response(r#"{"title": "Fix", "files": []}"#, &schema).is_ok());
+    assert!(parse_response(r#"{"title": "Fix"}"#, &schema).is_err());
+
+    // Object schemas are used as-is, anything else is wrapped in a "value" property
+    assert_eq!(object_schema(&schema), (schema.clone(), false));
+    let (wrapped, was_wrapped) = object_schema(&json!({ "type": "array" }));
+    assert!(was_wrapped);
+    assert_eq!(wrapped["required"], json!(["value"]));
+}
+
+/// Returns canned responses in order and records the messages it was sent
+struct ScriptedClient {
+    responses: Mutex<Vec<String>>,
+    requests: Mutex<Vec<(Vec<Message>, CompletionOptions)>>,
+}
+
+impl ScriptedClient {
+    fn new(responses: &[&str]) -> Arc<Self> {
+        Arc::new(Self {
+            responses: Mutex::new(responses.iter().map(|r| r.to_string()).collect()),
+            requests: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiClient for ScriptedClient {
+    async fn complete(&self, messages: Vec<Message>, options: CompletionOptions) -> Result<String> {
+        self.requests.lock().unwrap().push((messages, options));
+        Ok(self.responses.lock().unwrap().remove(0))
+    }
+
+    async fn complete_with_tools(
+        &self,
+        messages: Vec<Message>,
+        options: CompletionOptions,
+        _tool_results: Option<Vec<ToolResult>>,
+    ) -> Result<(String, Option<Vec<ToolCall>>)> {
+        Ok((self.complete(messages, options).await?, None))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Summary {
+    title: String,
+    files: Vec<String>,
+}
+
+#[tokio::test]
+async fn test_complete_structured_first_try() {
+    let mock = ScriptedClient::new(&[r#"{"title": "Fix", "files": ["a.rs"]}"#]);
+    let client = ApiClientEnum::custom_for_testing(mock.clone());
+
+    let summary: Summary = client
+        .complete_structured(
+            vec![Message::user("Summarize".to_string())],
+            &summary_schema(),
+            CompletionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        summary,
+        Summary {
+            title: "Fix".to_string(),
+            files: vec!["a.rs".to_string()],
+        }
+    );
+
+    let requests = mock.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].1.json_schema.as_deref(),
+        Some(summary_schema().to_string().as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_complete_structured_repairs_once() {
+    let mock = ScriptedClient::new(&[r#"{"title": "Fix"}"#, r#"{"title": "Fix", "files": []}"#]);
+    let client = ApiClientEnum::custom_for_testing(mock.clone());
+
+    let summary: Summary = client
+        .complete_structured(
+            vec![Message::user("Summarize".to_string())],
+            &summary_schema(),
+            CompletionOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert!(summary.files.is_empty());
+
+    // The retry sees the bad answer followed by the validation errors
+    let requests = mock.requests.lock().unwrap();
+    let retry_messages = &requests[1].0;
+    assert_eq!(retry_messages.len(), 3);
+    assert_eq!(retry_messages[1].role, "assistant");
+    assert!(retry_messages[2]
+        .content
+        .contains("missing required property \"files\""));
+}
+
+#[tokio::test]
+async fn test_complete_structured_gives_up_after_retry() {
+    let mock = ScriptedClient::new(&["not json", "still not json"]);
+    let client = ApiClientEnum::custom_for_testing(mock.clone());
+
+    let result: Result<Summary> = client
+        .complete_structured(
+            vec![Message::user("Summarize".to_string())],
+            &summary_schema(),
+            CompletionOptions::default(),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(mock.requests.lock().unwrap().len(), 2);
+}
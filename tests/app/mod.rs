@@ -1,4 +1,5 @@
 mod test_core;
 mod test_history;
 mod test_logger;
+mod test_mentions;
 mod test_scroll;
@@ -36,6 +36,8 @@ fn test_local_model_no_api_key_required() -> Result<()> {
         description: "Test local model via Ollama".into(),
         recommended_for: "Testing".into(),
         supports_agent: true,
+        supports_images: false,
+        context_length: None,
     }];
 
     // Ensure no API keys are set in the environment
@@ -75,6 +77,8 @@ fn test_cloud_model_requires_api_key() -> Result<()> {
         description: "Test Claude model".into(),
         recommended_for: "Testing".into(),
         supports_agent: true,
+        supports_images: false,
+        context_length: None,
     }];
 
     // Ensure no API keys are set in the environment
@@ -0,0 +1,115 @@
+use oli_server::app::mentions::{FileMention, FileMentions, MAX_MENTION_FILE_BYTES};
+use std::fs;
+
+fn mention(path: &str, start_line: Option<usize>, end_line: Option<usize>) -> FileMention {
+    FileMention {
+        path: path.to_string(),
+        start_line,
+        end_line,
+    }
+}
+
+#[test]
+fn test_parse_mentions() {
+    let mentions = FileMentions::parse(
+        "refactor @src/agent/executor.rs to use @src/lib.rs#L10-40, see @docs/ and @\"my notes.md\"#L5.",
+    );
+    assert_eq!(
+        mentions,
+        vec![
+            mention("src/agent/executor.rs", None, None),
+            mention("src/lib.rs", Some(10), Some(40)),
+            mention("docs/", None, None),
+            mention("my notes.md", Some(5), Some(5)),
+        ]
+    );
+
+    // L on both ends, duplicates and mentions in parentheses
+    assert_eq!(
+        FileMentions::parse("(@a.rs#L3-L7) and @a.rs#L3-L7"),
+        vec![mention("a.rs", Some(3), Some(7))]
+    );
+
+    // Emails, a lone @ and backwards ranges aren't mentions
+    assert!(FileMentions::parse("mail me@example.com or @ now").is_empty());
+    assert!(FileMentions::parse("@a.rs#L9-2").is_empty());
+}
+
+#[test]
+fn test_expand_mentions() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::create_dir(dir.path().join("src/nested")).unwrap();
+    let content: String = (1..=50).map(|i| format!("line {i}\n")).collect();
+    fs::write(dir.path().join("src/main.rs"), &content).unwrap();
+    fs::write(dir.path().join("src/image.bin"), [0u8, 1, 2, 3]).unwrap();
+
+    let expansion = FileMentions::expand(
+        "look at @src/main.rs#L10-12 and @src and @src/image.bin, ping @someone",
+        Some(dir.path()),
+    );
+
+    assert_eq!(expansion.attached, vec!["src/main.rs#L10-12", "src"]);
+    assert_eq!(
+        expansion.skipped,
+        vec![("src/image.bin".to_string(), "binary file".to_string())]
+    );
+
+    let prompt = &expansion.prompt;
+    assert!(prompt.starts_with("<attached_context>\n"), "{prompt}");
+    assert!(prompt.contains(
+        "<file path=\"src/main.rs\" lines=\"10-12\">\n  10 | line 10\n  11 | line 11\n  12 | line 12\n</file>"
+    ));
+    assert!(!prompt.contains("line 13"));
+    assert!(prompt.contains("<directory path=\"src\">\nimage.bin\nmain.rs\nnested/\n</directory>"));
+    assert!(prompt.contains("<file path=\"src/image.bin\" omitted=\"binary file\" />"));
+    // The user's own words come last and unchanged
+    assert!(prompt.ends_with(
+        "</attached_context>\n\nlook at @src/main.rs#L10-12 and @src and @src/image.bin, ping @someone"
+    ));
+
+    // Without mentions of existing paths the prompt is unchanged
+    let expansion = FileMentions::expand("ping @someone", Some(dir.path()));
+    assert_eq!(expansion.prompt, "ping @someone");
+    assert!(expansion.attached.is_empty());
+}
+
+#[test]
+fn test_expand_skips_range_past_end_of_file() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("short.rs"), "one\ntwo\nthree\n").unwrap();
+
+    let expansion = FileMentions::expand("see @short.rs#L10-12", Some(dir.path()));
+    assert!(expansion.attached.is_empty());
+    assert_eq!(
+        expansion.skipped,
+        vec![(
+            "short.rs#L10-12".to_string(),
+            "range past end of file (3 lines)".to_string()
+        )]
+    );
+    assert!(expansion
+        .prompt
+        .contains("<file path=\"short.rs\" omitted=\"range past end of file (3 lines)\" />"));
+    assert!(!expansion.prompt.contains("three"));
+
+    // A range that only ends past the file is cut to the lines it has
+    let expansion = FileMentions::expand("see @short.rs#L2-12", Some(dir.path()));
+    assert_eq!(expansion.attached, vec!["short.rs#L2-12"]);
+    assert!(expansion
+        .prompt
+        .contains("<file path=\"short.rs\" lines=\"2-3\">\n   2 | two\n   3 | three\n</file>"));
+}
+
+#[test]
+fn test_expand_truncates_large_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let line = "x".repeat(99);
+    let content: String = (0..5_000).map(|_| format!("{line}\n")).collect();
+    fs::write(dir.path().join("big.txt"), &content).unwrap();
+
+    let expansion = FileMentions::expand("summarize @big.txt", Some(dir.path()));
+    assert_eq!(expansion.attached, vec!["big.txt"]);
+    assert!(expansion.prompt.len() < MAX_MENTION_FILE_BYTES + 1024);
+    assert!(expansion.prompt.contains("of 5000; use Read for the rest]"));
+}
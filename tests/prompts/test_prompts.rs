@@ -1,8 +1,8 @@
 //! Tests for the prompt module
 
 use oli_server::prompts::{
-    add_working_directory_to_prompt, format_working_directory_prompt, get_agent_prompt_with_cwd,
-    DEFAULT_AGENT_PROMPT,
+    add_repo_map_to_prompt, add_working_directory_to_prompt, format_working_directory_prompt,
+    get_agent_prompt_with_cwd, DEFAULT_AGENT_PROMPT,
 };
 
 /// Test that default prompt is returned when no working directory is provided
@@ -104,3 +104,15 @@ fn test_format_working_directory_prompt() {
     assert!(formatted.contains(&format!("Your current working directory is: {test_cwd}")));
     assert!(formatted.contains("you should use absolute paths"));
 }
+
+/// Test that the repository map is added once, and not at all when empty
+#[test]
+fn test_add_repo_map_to_prompt() {
+    let repo_map = "src/lib.rs:\n  1: pub struct Config\n";
+    let result = add_repo_map_to_prompt("Base prompt", repo_map);
+
+    assert!(result.starts_with("Base prompt\n\n## REPOSITORY MAP\n"));
+    assert!(result.ends_with("src/lib.rs:\n  1: pub struct Config"));
+    assert_eq!(add_repo_map_to_prompt(&result, "other.rs:\n"), result);
+    assert_eq!(add_repo_map_to_prompt("Base prompt", "\n"), "Base prompt");
+}
@@ -1,2 +1,8 @@
+mod test_diff;
 mod test_file_ops;
+mod test_fuzzy;
+mod test_image;
+mod test_index;
+mod test_patch;
+mod test_repo_map;
 mod test_search;
@@ -0,0 +1,211 @@
+use oli_server::tools::fs::diff::{DiffAlgorithm, DiffLine, DiffOptions, DiffTools};
+use std::time::{Duration, Instant};
+
+/// Rebuilds the old and new text from a diff
+fn reconstruct(diff: &[DiffLine]) -> (Vec<String>, Vec<String>) {
+    let mut old = Vec::new();
+    let mut new = Vec::new();
+    for line in diff {
+        match line {
+            DiffLine::Context(text) => {
+                old.push(text.clone());
+                new.push(text.clone());
+            }
+            DiffLine::Removed(text) => old.push(text.clone()),
+            DiffLine::Added(text) => new.push(text.clone()),
+        }
+    }
+    (old, new)
+}
+
+fn count_changes(diff: &[DiffLine]) -> usize {
+    diff.iter()
+        .filter(|line| !matches!(line, DiffLine::Context(_)))
+        .count()
+}
+
+/// Length of the longest common subsequence, by dynamic programming
+fn lcs_length(a: &[&str], b: &[&str]) -> usize {
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() {
+        for j in 0..b.len() {
+            table[i + 1][j + 1] = if a[i] == b[j] {
+                table[i][j] + 1
+            } else {
+                table[i][j + 1].max(table[i + 1][j])
+            };
+        }
+    }
+    table[a.len()][b.len()]
+}
+
+#[test]
+fn test_myers_diff_is_minimal() {
+    // Deterministic pseudo-random texts over a small alphabet, so lines repeat
+    let mut seed: u64 = 42;
+    let mut next = || {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize
+    };
+
+    for _ in 0..200 {
+        let old: Vec<String> = (0..next() % 12)
+            .map(|_| format!("{}", next() % 4))
+            .collect();
+        let new: Vec<String> = (0..next() % 12)
+            .map(|_| format!("{}", next() % 4))
+            .collect();
+        let old_text = old.join("\n");
+        let new_text = new.join("\n");
+        let old_refs: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new_refs: Vec<&str> = new.iter().map(String::as_str).collect();
+        let minimal = old.len() + new.len() - 2 * lcs_length(&old_refs, &new_refs);
+
+        for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+            let diff = DiffTools::generate_diff_with(&old_text, &new_text, algorithm);
+            assert_eq!(reconstruct(&diff), (old.clone(), new.clone()));
+            if algorithm == DiffAlgorithm::Myers {
+                assert_eq!(count_changes(&diff), minimal, "{old:?} -> {new:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_moved_block() {
+    let old = "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\nfn e() {}";
+    let new = "fn b() {}\nfn c() {}\nfn d() {}\nfn e() {}\nfn a() {}";
+
+    // Moving one line is one removal and one addition, not a rewrite
+    let diff = DiffTools::generate_diff(old, new);
+    assert_eq!(count_changes(&diff), 2);
+    let diff = DiffTools::generate_diff_with(old, new, DiffAlgorithm::Patience);
+    assert_eq!(count_changes(&diff), 2);
+}
+
+#[test]
+fn test_unified_diff_hunks() {
+    let old: Vec<String> = (1..=20).map(|i| format!("line {i}")).collect();
+    let mut new = old.clone();
+    new[1] = "line two".to_string();
+    new[17] = "line eighteen".to_string();
+    let old_text = old.join("\n");
+    let new_text = new.join("\n");
+
+    // Changes far apart get separate hunks with three lines of context
+    let diff = DiffTools::unified_diff(
+        &old_text,
+        &new_text,
+        "a/file.txt",
+        "b/file.txt",
+        &DiffOptions::default(),
+    );
+    let expected = "\
+--- a/file.txt
++++ b/file.txt
+@@ -1,5 +1,5 @@
+ line 1
+-line 2
++line two
+ line 3
+ line 4
+ line 5
+@@ -15,6 +15,6 @@
+ line 15
+ line 16
+ line 17
+-line 18
++line eighteen
+ line 19
+ line 20
+";
+    assert_eq!(diff, expected);
+
+    // With more context they merge into one
+    let options = DiffOptions::default().with_context(8);
+    let diff_lines = DiffTools::generate_diff(&old_text, &new_text);
+    let hunks = DiffTools::hunks(&diff_lines, options.context);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].header(), "@@ -1,20 +1,20 @@");
+
+    // Identical texts have no hunks
+    assert!(
+        DiffTools::unified_diff(&old_text, &old_text, "a", "b", &DiffOptions::default()).is_empty()
+    );
+}
+
+#[test]
+fn test_insertion_hunk_header() {
+    let diff = DiffTools::generate_diff("", "one\ntwo");
+    let hunks = DiffTools::hunks(&diff, 3);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].header(), "@@ -0,0 +1,2 @@");
+}
+
+#[test]
+fn test_format_diff_shows_only_hunks() {
+    let old: Vec<String> = (1..=100).map(|i| format!("line {i}")).collect();
+    let mut new = old.clone();
+    new[49] = "changed".to_string();
+    let diff = DiffTools::generate_diff(&old.join("\n"), &new.join("\n"));
+
+    let output = DiffTools::format_diff(&diff, "file.txt").unwrap();
+    assert!(output.contains("Updated file.txt with 1 addition and 1 removal"));
+    assert!(output.contains(" 50- line 50"));
+    assert!(output.contains(" 50+ changed"));
+    assert!(output.contains(" 47  line 47"));
+    // Lines outside the context are left out
+    assert!(!output.contains("line 46\n"));
+    assert!(!output.contains("line 1\n"));
+}
+
+#[test]
+fn test_word_diff() {
+    let (old, new) = DiffTools::word_diff("let x = foo(1);", "let x = bar(1);");
+    assert_eq!(
+        old,
+        vec![
+            ("let x = ".to_string(), false),
+            ("foo".to_string(), true),
+            ("(1);".to_string(), false),
+        ]
+    );
+    assert_eq!(
+        new,
+        vec![
+            ("let x = ".to_string(), false),
+            ("bar".to_string(), true),
+            ("(1);".to_string(), false),
+        ]
+    );
+
+    // Highlighting marks the changed word in the formatted output
+    let diff = DiffTools::generate_diff("let x = foo(1);", "let x = bar(1);");
+    let options = DiffOptions::default().with_word_highlight(true);
+    let output = DiffTools::format_diff_with(&diff, "file.rs", &options).unwrap();
+    assert!(output.contains("\x1b[7mbar\x1b[27m"));
+}
+
+#[test]
+fn test_large_file_diff_is_fast() {
+    let old: Vec<String> = (0..10_000).map(|i| format!("line {i}")).collect();
+    let mut new = old.clone();
+    // Scattered edits plus a block moved from the start to the end
+    for i in (0..10_000).step_by(97) {
+        new[i] = format!("edited {i}");
+    }
+    let moved: Vec<String> = new.drain(100..300).collect();
+    new.extend(moved);
+
+    let old_text = old.join("\n");
+    let new_text = new.join("\n");
+
+    for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+        let start = Instant::now();
+        let diff = DiffTools::generate_diff_with(&old_text, &new_text, algorithm);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(reconstruct(&diff), (old.clone(), new.clone()));
+    }
+}
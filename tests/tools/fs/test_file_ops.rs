@@ -1,4 +1,4 @@
-use oli_server::tools::fs::file_ops::FileOps;
+use oli_server::tools::fs::file_ops::{ChangeSet, FileEdit, FileOps};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -221,3 +221,134 @@ fn test_edit_file_with_expected_replacements() {
     let err_msg = result.unwrap_err().to_string();
     assert!(err_msg.contains("Found 3 occurrences") && err_msg.contains("expected exactly 2"));
 }
+
+#[test]
+fn test_multi_edit_file() {
+    let dir = tempdir().unwrap();
+    let content = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}";
+    let file_path = create_test_file(dir.path(), "multi_edit.rs", content);
+
+    // The second edit depends on the result of the first
+    let edits = vec![
+        FileEdit {
+            old_string: "let x = 1;".into(),
+            new_string: "let value = 1;".into(),
+            expected_replacements: None,
+        },
+        FileEdit {
+            old_string: "println!(\"{}\", x);".into(),
+            new_string: "println!(\"{}\", value);".into(),
+            expected_replacements: None,
+        },
+    ];
+    let diff = FileOps::multi_edit_file(&file_path, &edits).unwrap();
+
+    // One combined diff covering both changes
+    assert!(diff.contains("value = 1"));
+    assert!(diff.contains("println!(\"{}\", value);"));
+
+    let result = FileOps::read_file(&file_path).unwrap();
+    let expected = "fn main() {\n    let value = 1;\n    println!(\"{}\", value);\n}\n";
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn test_multi_edit_file_is_atomic() {
+    let dir = tempdir().unwrap();
+    let content = "first line\nsecond line\nthird line";
+    let file_path = create_test_file(dir.path(), "multi_edit_atomic.txt", content);
+    let before = FileOps::read_file(&file_path).unwrap();
+
+    // The first edit is valid, but the second no longer matches once it has run
+    let edits = vec![
+        FileEdit {
+            old_string: "second line".into(),
+            new_string: "2nd line".into(),
+            expected_replacements: None,
+        },
+        FileEdit {
+            old_string: "second line".into(),
+            new_string: "another line".into(),
+            expected_replacements: None,
+        },
+    ];
+    let result = FileOps::multi_edit_file(&file_path, &edits);
+    assert!(result.is_err());
+    assert!(format!("{:#}", result.unwrap_err()).contains("Edit 2 of 2 failed"));
+
+    // Nothing was written
+    assert_eq!(FileOps::read_file(&file_path).unwrap(), before);
+
+    // An empty list of edits is rejected
+    assert!(FileOps::multi_edit_file(&file_path, &[]).is_err());
+}
+
+#[test]
+fn test_change_set_applies_writes_renames_and_deletes_together() {
+    let dir = tempdir().unwrap();
+    let kept = dir.path().join("kept.txt");
+    let moved = dir.path().join("old.txt");
+    let removed = dir.path().join("removed.txt");
+    std::fs::write(&kept, "one\ntwo\n").unwrap();
+    std::fs::write(&moved, "unchanged\n").unwrap();
+    std::fs::write(&removed, "bye\n").unwrap();
+
+    let mut changes = ChangeSet::new();
+    changes.write(&kept, "one\n2\n".to_string());
+    changes
+        .rename(&moved, &dir.path().join("new.txt"), false)
+        .unwrap();
+    changes.delete(&removed).unwrap();
+
+    // Nothing is written until the set is applied
+    let diff = changes.diff().unwrap();
+    assert!(moved.exists());
+    assert!(diff.contains("Updated"), "{diff}");
+    assert!(diff.contains("Renamed"), "{diff}");
+    assert!(diff.contains("Deleted"), "{diff}");
+
+    let applied = FileOps::apply_changes(&changes).unwrap();
+    assert_eq!(applied, diff);
+    assert_eq!(std::fs::read_to_string(&kept).unwrap(), "one\n2\n");
+    assert!(!moved.exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("new.txt")).unwrap(),
+        "unchanged\n"
+    );
+    assert!(!removed.exists());
+    assert_eq!(
+        changes.written_paths(),
+        vec![kept, dir.path().join("new.txt")]
+    );
+}
+
+#[test]
+fn test_change_set_rejects_renaming_onto_an_existing_file() {
+    let dir = tempdir().unwrap();
+    let from = create_test_file(dir.path(), "a.txt", "a");
+    let to = create_test_file(dir.path(), "b.txt", "b");
+
+    let mut changes = ChangeSet::new();
+    let err = changes.rename(&from, &to, false).unwrap_err();
+    assert!(err.to_string().contains("already exists"), "{err}");
+    changes.rename(&from, &to, true).unwrap();
+}
+
+#[test]
+fn test_apply_changes_rolls_back_when_a_write_fails() {
+    let dir = tempdir().unwrap();
+    let first = create_test_file(dir.path(), "first.txt", "original");
+    // A file where a directory is needed makes the second write fail
+    create_test_file(dir.path(), "blocker", "");
+
+    let mut changes = ChangeSet::new();
+    changes.write(&first, "changed\n".to_string());
+    changes.write(&dir.path().join("blocker/second.txt"), "new\n".to_string());
+
+    let err = FileOps::apply_changes(&changes).unwrap_err();
+    assert!(
+        format!("{err:#}").contains("No changes were applied"),
+        "{err:#}"
+    );
+    assert_eq!(std::fs::read_to_string(&first).unwrap(), "original\n");
+}
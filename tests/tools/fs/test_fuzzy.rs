@@ -0,0 +1,143 @@
+use anyhow::Result;
+use oli_server::tools::fs::fuzzy::{FileFinder, FuzzyMatcher};
+use std::fs;
+use std::time::Duration;
+
+fn paths(list: &[&str]) -> Vec<String> {
+    list.iter().map(|p| p.to_string()).collect()
+}
+
+fn ranked(query: &str, list: &[&str]) -> Vec<String> {
+    FuzzyMatcher::rank(query, &paths(list), 10)
+        .into_iter()
+        .map(|m| m.path)
+        .collect()
+}
+
+#[test]
+fn test_fuzzy_score_positions() {
+    let (_, positions) = FuzzyMatcher::score("usrctl", "src/user_controller.rs").unwrap();
+    // Prefers the word starts: u(ser) c(on)t(ro)l(ler)
+    let matched: String = positions
+        .iter()
+        .map(|&i| "src/user_controller.rs".chars().nth(i).unwrap())
+        .collect();
+    assert_eq!(matched, "usrctl");
+    assert_eq!(positions[0], 4);
+
+    // Characters must appear in order
+    assert!(FuzzyMatcher::score("ltcrsu", "src/user_controller.rs").is_none());
+    assert!(FuzzyMatcher::score("xyz", "src/main.rs").is_none());
+}
+
+#[test]
+fn test_fuzzy_ranking() {
+    // Word starts beat matches inside words
+    assert_eq!(
+        ranked("fo", &["src/info.rs", "src/foo.rs"]),
+        vec!["src/foo.rs", "src/info.rs"]
+    );
+    // Matches in the file name beat matches in directories
+    assert_eq!(
+        ranked("main", &["main/lib/util.rs", "src/main.rs"]),
+        vec!["src/main.rs", "main/lib/util.rs"]
+    );
+    // Consecutive characters beat scattered ones
+    assert_eq!(
+        ranked("conf", &["src/cache/of_node.rs", "src/config.rs"])[0],
+        "src/config.rs"
+    );
+    // Ties go to the shorter path
+    assert_eq!(
+        ranked("lib", &["crates/core/src/lib.rs", "src/lib.rs"]),
+        vec!["src/lib.rs", "crates/core/src/lib.rs"]
+    );
+}
+
+#[test]
+fn test_fuzzy_terms_and_smart_case() {
+    let list = [
+        "src/api/UserService.ts",
+        "src/api/user_service_test.ts",
+        "docs/user.md",
+    ];
+    // Every term must match
+    assert_eq!(
+        ranked("user test", &list),
+        vec!["src/api/user_service_test.ts"]
+    );
+    // Lowercase queries ignore case; an uppercase letter makes it exact
+    assert_eq!(ranked("userservice", &list).len(), 2);
+    assert_eq!(ranked("UserS", &list), vec!["src/api/UserService.ts"]);
+    // An empty query lists paths as given
+    assert_eq!(ranked("", &list).len(), 3);
+}
+
+#[test]
+fn test_file_finder_cache_follows_changes() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join(".git"))?;
+    fs::write(dir.path().join(".gitignore"), "generated/\n")?;
+    fs::create_dir_all(dir.path().join("src/handlers"))?;
+    fs::create_dir(dir.path().join("generated"))?;
+    fs::write(dir.path().join("src/handlers/user_handler.rs"), "")?;
+    fs::write(dir.path().join("src/main.rs"), "")?;
+    fs::write(dir.path().join("generated/user_handler_gen.rs"), "")?;
+
+    let found = FileFinder::find(dir.path(), "userhandler", 10)?;
+    let found: Vec<&str> = found.iter().map(|m| m.path.as_str()).collect();
+    // Ignored files and .git are left out
+    assert_eq!(found, vec!["src/handlers/user_handler.rs"]);
+    assert!(!FileFinder::files(dir.path())?
+        .iter()
+        .any(|p| p.starts_with(".git/")));
+
+    // New files show up without invalidating by hand
+    fs::write(dir.path().join("src/handlers/user_handler_test.rs"), "")?;
+    let found = FileFinder::find(dir.path(), "userhandler", 10)?;
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].path, "src/handlers/user_handler.rs");
+
+    // And removed ones disappear
+    fs::remove_file(dir.path().join("src/handlers/user_handler.rs"))?;
+    let found = FileFinder::find(dir.path(), "userhandler", 10)?;
+    let found: Vec<&str> = found.iter().map(|m| m.path.as_str()).collect();
+    assert_eq!(found, vec!["src/handlers/user_handler_test.rs"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_file_finder_cache_follows_ignore_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join(".git"))?;
+    fs::write(dir.path().join(".gitignore"), "")?;
+    fs::create_dir(dir.path().join("src"))?;
+    fs::write(dir.path().join("src/main.rs"), "")?;
+    fs::write(dir.path().join("src/main.log"), "")?;
+    assert!(FileFinder::files(dir.path())?.contains(&"src/main.log".to_string()));
+
+    // Editing an ignore file leaves every directory's time alone
+    let later = |path: &std::path::Path| -> Result<()> {
+        let time = fs::metadata(path)?.modified()? + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(time)?;
+        Ok(())
+    };
+    fs::write(dir.path().join(".gitignore"), "*.log\n")?;
+    later(&dir.path().join(".gitignore"))?;
+    assert!(!FileFinder::files(dir.path())?.contains(&"src/main.log".to_string()));
+
+    fs::create_dir_all(dir.path().join(".git/info"))?;
+    fs::write(dir.path().join(".git/info/exclude"), "src/main.rs\n")?;
+    assert!(FileFinder::files(dir.path())?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_file_finder_rejects_missing_directory() {
+    assert!(FileFinder::find(std::path::Path::new("/definitely/not/here"), "x", 5).is_err());
+}
@@ -0,0 +1,89 @@
+use oli_server::agent::tools::{ReadParams, ToolCall};
+use oli_server::apis::api_client::ContentBlock;
+use oli_server::tools::fs::image::{ImageOps, MAX_IMAGE_BYTES};
+use std::fs;
+use tempfile::tempdir;
+
+// Smallest header that identifies each format; the rest of the file doesn't matter
+const PNG_HEADER: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+
+#[test]
+fn test_detect_media_type() {
+    assert_eq!(ImageOps::detect_media_type(PNG_HEADER), Some("image/png"));
+    assert_eq!(
+        ImageOps::detect_media_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+        Some("image/jpeg")
+    );
+    assert_eq!(ImageOps::detect_media_type(b"GIF89a..."), Some("image/gif"));
+    assert_eq!(
+        ImageOps::detect_media_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+        Some("image/webp")
+    );
+    assert_eq!(ImageOps::detect_media_type(b"fn main() {}"), None);
+    assert_eq!(ImageOps::detect_media_type(b"RIFF"), None);
+}
+
+#[test]
+fn test_read_image_returns_block() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("pixel.png");
+    fs::write(&path, PNG_HEADER).unwrap();
+
+    let block = ImageOps::read_image(&path).unwrap().unwrap();
+    assert_eq!(
+        block,
+        ContentBlock::Image {
+            media_type: "image/png".to_string(),
+            data: "iVBORw0KGgoAAAAA".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_read_image_ignores_text_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("notes.txt");
+    fs::write(&path, "just some text").unwrap();
+
+    assert!(ImageOps::read_image(&path).unwrap().is_none());
+}
+
+#[test]
+fn test_image_too_large() {
+    let mut bytes = PNG_HEADER.to_vec();
+    bytes.resize(MAX_IMAGE_BYTES + 1, 0);
+
+    let err = ImageOps::image_from_bytes(&bytes).unwrap_err();
+    assert!(err.to_string().contains("limit"));
+}
+
+#[test]
+fn test_image_from_base64_accepts_data_uri() {
+    let block = ImageOps::image_from_base64("data:image/png;base64,iVBORw0KGgoAAAAA").unwrap();
+    assert_eq!(
+        block.image_data_uri().unwrap(),
+        "data:image/png;base64,iVBORw0KGgoAAAAA"
+    );
+
+    // The declared type is ignored in favour of the actual bytes
+    assert!(ImageOps::image_from_base64("aGVsbG8gd29ybGQ=").is_err());
+    assert!(ImageOps::image_from_base64("not base64!").is_err());
+}
+
+#[test]
+fn test_read_tool_returns_image_block() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("pixel.png");
+    fs::write(&path, PNG_HEADER).unwrap();
+
+    let tool = ToolCall::Read(ReadParams {
+        file_path: path.to_string_lossy().to_string(),
+        offset: 0,
+        limit: 2000,
+    });
+
+    let (output, blocks) = tool.execute_with_blocks().unwrap();
+    assert!(output.contains("image/png"));
+    assert_eq!(blocks.len(), 1);
+    assert!(matches!(blocks[0], ContentBlock::Image { .. }));
+}
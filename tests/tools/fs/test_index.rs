@@ -0,0 +1,195 @@
+use anyhow::Result;
+use oli_server::tools::fs::index::{trigrams_of, SearchIndex, TrigramIndex, TrigramQuery};
+use oli_server::tools::fs::search::{GrepOptions, SearchTools};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+fn setup_workspace() -> Result<tempfile::TempDir> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("src"))?;
+    fs::write(
+        dir.path().join("src/config.rs"),
+        "pub struct Config {\n    pub port: u16,\n}\n",
+    )?;
+    fs::write(
+        dir.path().join("src/main.rs"),
+        "fn main() {\n    println!(\"Hello, world!\");\n}\n",
+    )?;
+    fs::write(
+        dir.path().join("README.md"),
+        "# Demo\nUses a Config file.\n",
+    )?;
+    // Ignored by the usual rules, so never indexed but still searched
+    fs::write(dir.path().join("debug.log"), "Config loaded\n")?;
+    Ok(dir)
+}
+
+fn names(paths: &[PathBuf]) -> Vec<String> {
+    let mut names: Vec<String> = paths
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+fn all_files(root: &Path) -> Vec<PathBuf> {
+    SearchTools::glob_search_in_dir(root, "**/*")
+        .unwrap()
+        .into_iter()
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+#[test]
+fn test_query_from_regex() {
+    assert_eq!(
+        TrigramQuery::from_regex("hello", false),
+        TrigramQuery::Trigrams(trigrams_of(b"hello"))
+    );
+    // Case-insensitive patterns use the same lowercased trigrams
+    assert_eq!(
+        TrigramQuery::from_regex("Hello", true),
+        TrigramQuery::Trigrams(trigrams_of(b"hello"))
+    );
+    assert_eq!(
+        TrigramQuery::from_regex("foo|barbaz", false),
+        TrigramQuery::Or(vec![
+            TrigramQuery::Trigrams(trigrams_of(b"barbaz")),
+            TrigramQuery::Trigrams(trigrams_of(b"foo")),
+        ])
+    );
+    // Patterns without a required literal of three bytes match every file
+    assert_eq!(TrigramQuery::from_regex(".*", false), TrigramQuery::All);
+    assert_eq!(TrigramQuery::from_regex("ab|xyz", false), TrigramQuery::All);
+    assert_eq!(TrigramQuery::from_regex(r"\w+", false), TrigramQuery::All);
+    // Either side of a wildcard is still required
+    let mut both = trigrams_of(b"struct");
+    both.extend(trigrams_of(b"config"));
+    both.sort_unstable();
+    assert_eq!(
+        TrigramQuery::from_regex("struct.*Config", false),
+        TrigramQuery::Trigrams(both)
+    );
+}
+
+#[test]
+fn test_index_candidates_and_incremental_update() -> Result<()> {
+    let dir = setup_workspace()?;
+    let root = dir.path().canonicalize()?;
+    let mut index = TrigramIndex::build(&root);
+
+    assert_eq!(index.file_count(), 3);
+    assert!(!index.contains(&root.join("debug.log")));
+
+    // Files the index doesn't know are always candidates
+    let mut files = all_files(&root);
+    files.push(root.join("unknown.txt"));
+    let query = TrigramQuery::from_regex("Config", false);
+    assert_eq!(
+        names(&index.candidates(&query, files.clone())),
+        vec!["README.md", "config.rs", "unknown.txt"]
+    );
+    let query = TrigramQuery::from_regex("println", false);
+    assert_eq!(
+        names(&index.candidates(&query, files.clone())),
+        vec!["main.rs", "unknown.txt"]
+    );
+
+    // Changed, added and deleted files are picked up from the walk
+    fs::write(root.join("src/main.rs"), "fn main() {}\n")?;
+    File::options()
+        .write(true)
+        .open(root.join("src/main.rs"))?
+        .set_modified(SystemTime::now() + Duration::from_secs(10))?;
+    fs::write(root.join("src/log.rs"), "println!(\"log\");\n")?;
+    fs::remove_file(root.join("README.md"))?;
+
+    let files = all_files(&root);
+    let update = index.update(&root, &files);
+    assert_eq!((update.added, update.modified, update.removed), (1, 1, 1));
+    let query = TrigramQuery::from_regex("println", false);
+    assert_eq!(
+        names(&index.candidates(&query, files.clone())),
+        vec!["log.rs"]
+    );
+
+    // Nothing changed since, so nothing is read again
+    assert!(index.update(&root, &files).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_index_save_and_load() -> Result<()> {
+    let dir = setup_workspace()?;
+    let root = dir.path().canonicalize()?;
+    let store = tempfile::tempdir()?;
+    let path = store.path().join("workspace.idx");
+
+    let mut index = TrigramIndex::build(&root);
+    fs::write(root.join("src/main.rs"), "fn main() { run(); }\n")?;
+    File::options()
+        .write(true)
+        .open(root.join("src/main.rs"))?
+        .set_modified(SystemTime::now() + Duration::from_secs(10))?;
+    index.update(&root, &all_files(&root));
+    index.save(&path)?;
+
+    let loaded = TrigramIndex::load(&path, &root)?;
+    assert_eq!(loaded.file_count(), index.file_count());
+    assert_eq!(loaded.trigram_count(), index.trigram_count());
+    let files = all_files(&root);
+    for pattern in ["Config", "run\\(\\)", "Hello"] {
+        let query = TrigramQuery::from_regex(pattern, false);
+        assert_eq!(
+            names(&loaded.candidates(&query, files.clone())),
+            names(&index.candidates(&query, files.clone())),
+            "{pattern}"
+        );
+    }
+
+    // An index is only used for the workspace it was built for
+    assert!(TrigramIndex::load(&path, store.path()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_uses_workspace_index() -> Result<()> {
+    let dir = setup_workspace()?;
+    let store = tempfile::tempdir()?;
+    std::env::set_var("OLI_INDEX_DIR", store.path());
+
+    let status = SearchIndex::enable(dir.path())?;
+    assert_eq!(status.root, dir.path().canonicalize()?);
+    assert!(SearchIndex::wait_until_ready(
+        dir.path(),
+        Duration::from_secs(10)
+    ));
+    assert!(SearchIndex::index_path(&status.root).unwrap().exists());
+
+    // Results match a plain scan
+    let options = GrepOptions::default();
+    let results = SearchTools::grep_search_with("Config", Some(dir.path()), &options)?;
+    let paths: Vec<PathBuf> = results.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(names(&paths), vec!["README.md", "config.rs"]);
+
+    // A file written after the index was built is still found
+    fs::write(dir.path().join("src/new.rs"), "struct Config;\n")?;
+    let results = SearchTools::grep_search_with("struct Config", Some(dir.path()), &options)?;
+    let paths: Vec<PathBuf> = results.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(names(&paths), vec!["config.rs", "new.rs"]);
+
+    // Searching a subdirectory uses the workspace's index too
+    let results =
+        SearchTools::grep_search_with("(?i)hello", Some(&dir.path().join("src")), &options)?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].path.ends_with("main.rs"));
+
+    SearchIndex::disable(dir.path());
+    assert!(!SearchIndex::status().iter().any(|s| s.root == status.root));
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use oli_server::tools::fs::patch::{PatchOperation, PatchTools, DEFAULT_FUZZ};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_parse_git_diff_operations() {
+    let patch = "\
+diff --git a/src/old.rs b/src/new.rs
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+diff --git a/added.txt b/added.txt
+new file mode 100644
+--- /dev/null
++++ b/added.txt
+@@ -0,0 +1,2 @@
++one
++two
+diff --git a/removed.txt b/removed.txt
+deleted file mode 100644
+--- a/removed.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+    let files = PatchTools::parse(patch).unwrap();
+    assert_eq!(files.len(), 3);
+    assert_eq!(
+        files[0].operation(),
+        PatchOperation::Rename {
+            from: "src/old.rs".into()
+        }
+    );
+    assert_eq!(files[0].path(), "src/new.rs");
+    assert_eq!(files[1].operation(), PatchOperation::Create);
+    assert_eq!(files[1].path(), "added.txt");
+    assert_eq!(files[1].hunks[0].lines.len(), 2);
+    assert_eq!(files[2].operation(), PatchOperation::Delete);
+    assert_eq!(files[2].path(), "removed.txt");
+
+    assert!(PatchTools::parse("just some text").is_err());
+}
+
+#[test]
+fn test_apply_patch_with_offset() {
+    let dir = tempdir().unwrap();
+    let content = "header\nextra\nfn main() {\n    println!(\"old\");\n}\n";
+    fs::write(dir.path().join("main.rs"), content).unwrap();
+
+    // The hunk claims line 1, but the file has two lines before it
+    let patch = "\
+--- a/main.rs
++++ b/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"old\");
++    println!(\"new\");
+ }
+";
+    let report = PatchTools::apply(patch, dir.path(), DEFAULT_FUZZ).unwrap();
+    assert!(report.applied);
+    let hunk = &report.files[0].hunks[0];
+    assert!(hunk.applied);
+    assert_eq!(hunk.line, Some(3));
+    assert_eq!(hunk.offset, 2);
+    assert_eq!(hunk.fuzz, 0);
+
+    let result = fs::read_to_string(dir.path().join("main.rs")).unwrap();
+    assert_eq!(
+        result,
+        "header\nextra\nfn main() {\n    println!(\"new\");\n}\n"
+    );
+}
+
+#[test]
+fn test_apply_patch_with_fuzz() {
+    let dir = tempdir().unwrap();
+    let content = "a\nb\nc\nd\ne\n";
+    fs::write(dir.path().join("letters.txt"), content).unwrap();
+
+    // The first context line is wrong, so the hunk needs fuzz to apply
+    let patch = "\
+--- letters.txt
++++ letters.txt
+@@ -1,3 +1,3 @@
+ x
+ b
+-c
++C
+ d
+";
+    let report = PatchTools::check(patch, dir.path(), 0).unwrap();
+    assert!(!report.succeeded());
+    assert!(!report.applied);
+
+    let report = PatchTools::apply(patch, dir.path(), 1).unwrap();
+    assert!(report.applied);
+    assert_eq!(report.files[0].hunks[0].fuzz, 1);
+    let result = fs::read_to_string(dir.path().join("letters.txt")).unwrap();
+    assert_eq!(result, "a\nb\nC\nd\ne\n");
+}
+
+#[test]
+fn test_apply_patch_create_delete_rename() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("removed.txt"), "gone\n").unwrap();
+    fs::write(dir.path().join("old.txt"), "keep\nchange\n").unwrap();
+
+    let patch = "\
+diff --git a/nested/added.txt b/nested/added.txt
+new file mode 100644
+--- /dev/null
++++ b/nested/added.txt
+@@ -0,0 +1,2 @@
++one
++two
+diff --git a/removed.txt b/removed.txt
+deleted file mode 100644
+--- a/removed.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+diff --git a/old.txt b/new.txt
+rename from old.txt
+rename to new.txt
+--- a/old.txt
++++ b/new.txt
+@@ -1,2 +1,2 @@
+ keep
+-change
++changed
+";
+    let report = PatchTools::apply(patch, dir.path(), DEFAULT_FUZZ).unwrap();
+    assert!(report.applied, "{}", report.summary());
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("nested/added.txt")).unwrap(),
+        "one\ntwo\n"
+    );
+    assert!(!dir.path().join("removed.txt").exists());
+    assert!(!dir.path().join("old.txt").exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("new.txt")).unwrap(),
+        "keep\nchanged\n"
+    );
+}
+
+#[test]
+fn test_failed_hunk_changes_nothing() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("first.txt"), "alpha\nbeta\n").unwrap();
+    fs::write(dir.path().join("second.txt"), "gamma\ndelta\n").unwrap();
+
+    // The first file's hunk applies, the second file's hunk doesn't match
+    let patch = "\
+--- a/first.txt
++++ b/first.txt
+@@ -1,2 +1,2 @@
+-alpha
++ALPHA
+ beta
+--- a/second.txt
++++ b/second.txt
+@@ -1,2 +1,2 @@
+-epsilon
++EPSILON
+ zeta
+";
+    let report = PatchTools::apply(patch, dir.path(), 0).unwrap();
+    assert!(!report.applied);
+    assert!(report.files[0].succeeded());
+    assert!(!report.files[1].succeeded());
+
+    let summary = report.summary();
+    assert!(summary.contains("no changes were made"));
+    assert!(summary.contains("second.txt (modify): FAILED"));
+    assert!(summary.contains("hunk 1 @@ -1,2 +1,2 @@: FAILED"));
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("first.txt")).unwrap(),
+        "alpha\nbeta\n"
+    );
+}
+
+#[test]
+fn test_apply_patch_no_newline_at_end() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("plain.txt"), "one\ntwo\n").unwrap();
+
+    let patch = "\
+--- a/plain.txt
++++ b/plain.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++three
+\\ No newline at end of file
+";
+    let report = PatchTools::apply(patch, dir.path(), DEFAULT_FUZZ).unwrap();
+    assert!(report.applied);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("plain.txt")).unwrap(),
+        "one\nthree"
+    );
+}
+
+#[test]
+fn test_patch_paths_stay_in_base_dir() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("project");
+    fs::create_dir(&base).unwrap();
+    fs::write(base.join("inside.txt"), "old\n").unwrap();
+    fs::write(dir.path().join("outside.txt"), "old\n").unwrap();
+
+    let patch = format!(
+        "\
+--- a/inside.txt
++++ b/inside.txt
+@@ -1 +1 @@
+-old
++new
+--- a/../outside.txt
++++ b/../outside.txt
+@@ -1 +1 @@
+-old
++new
+--- {outside}
++++ {outside}
+@@ -1 +1 @@
+-old
++new
+",
+        outside = dir.path().join("outside.txt").display()
+    );
+    let report = PatchTools::apply(&patch, &base, DEFAULT_FUZZ).unwrap();
+    assert!(!report.applied);
+    assert!(report.files[0].succeeded());
+    for file in &report.files[1..] {
+        let error = file.error.as_deref().unwrap_or_default();
+        assert!(error.contains("outside the base directory"), "{error}");
+    }
+
+    // Nothing was written, not even the file inside
+    assert_eq!(
+        fs::read_to_string(base.join("inside.txt")).unwrap(),
+        "old\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("outside.txt")).unwrap(),
+        "old\n"
+    );
+
+    // Paths that only pass through a parent directory are fine
+    let patch = "\
+--- a/sub/../inside.txt
++++ b/sub/../inside.txt
+@@ -1 +1 @@
+-old
++new
+";
+    let report = PatchTools::apply(patch, &base, DEFAULT_FUZZ).unwrap();
+    assert!(report.applied, "{}", report.summary());
+    assert_eq!(
+        fs::read_to_string(base.join("inside.txt")).unwrap(),
+        "new\n"
+    );
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use oli_server::tools::fs::repo_map::{MapSymbol, MappedFile, RepoMap, MAX_FILE_SYMBOLS};
+use std::fs;
+use std::path::Path;
+
+fn outline(path: &str, content: &str) -> Vec<(String, String, usize, bool)> {
+    RepoMap::symbols(Path::new(path), content)
+        .into_iter()
+        .map(|s| (s.name, s.kind, s.line, s.member))
+        .collect()
+}
+
+fn symbol(name: &str, kind: &str, line: usize, member: bool) -> (String, String, usize, bool) {
+    (name.to_string(), kind.to_string(), line, member)
+}
+
+#[test]
+fn test_repo_map_symbols_by_language() {
+    let rust = "use std::fmt;\n\
+                \n\
+                pub struct Config {\n    pub name: String,\n}\n\
+                \n\
+                impl Config {\n    pub fn new() -> Self {\n        let inner = || {};\n    }\n}\n\
+                \n\
+                pub(crate) async fn load(path: &Path) -> Result<Config> {\n}\n\
+                fn main() {\n    helper();\n}\n";
+    assert_eq!(
+        outline("src/config.rs", rust),
+        vec![
+            symbol("Config", "struct", 3, false),
+            symbol("Config", "impl", 7, false),
+            symbol("new", "fn", 8, true),
+            symbol("load", "fn", 13, false),
+            symbol("main", "fn", 15, false),
+        ]
+    );
+    let signatures: Vec<String> = RepoMap::symbols(Path::new("src/config.rs"), rust)
+        .into_iter()
+        .map(|s| s.signature)
+        .collect();
+    assert_eq!(
+        signatures[3],
+        "pub(crate) async fn load(path: &Path) -> Result<Config>"
+    );
+
+    let python = "MAX_USERS = 10\n\
+                  \n\
+                  @dataclass\n\
+                  class User(Base):\n    def greet(self):\n        def inner():\n            pass\n\
+                  \n\
+                  async def fetch(url):\n    if url:\n        return None\n";
+    assert_eq!(
+        outline("app/users.py", python),
+        vec![
+            symbol("MAX_USERS", "const", 1, false),
+            symbol("User", "class", 4, false),
+            symbol("greet", "def", 5, true),
+            symbol("fetch", "def", 9, false),
+        ]
+    );
+
+    let typescript = "export interface Props {\n  name: string;\n}\n\
+                      export default class Widget extends Base {\n  constructor(props: Props) {\n    if (props) {\n    }\n  }\n  async render(): Promise<void> {\n  }\n}\n\
+                      export const useWidget = (id: string) => {\n};\n\
+                      export function mount(el) {\n}\n";
+    assert_eq!(
+        outline("web/widget.tsx", typescript),
+        vec![
+            symbol("Props", "interface", 1, false),
+            symbol("Widget", "class", 4, false),
+            symbol("constructor", "method", 5, true),
+            symbol("render", "method", 9, true),
+            symbol("useWidget", "function", 12, false),
+            symbol("mount", "function", 14, false),
+        ]
+    );
+
+    let go = "package main\n\ntype Server struct {\n}\n\nfunc (s *Server) Start() error {\n}\n\nfunc main() {\n}\n";
+    assert_eq!(
+        outline("cmd/server.go", go),
+        vec![
+            symbol("Server", "struct", 3, false),
+            symbol("Start", "func", 6, false),
+            symbol("main", "func", 9, false),
+        ]
+    );
+
+    // Languages the map doesn't know have no symbols
+    assert!(outline("README.md", "# fn main() {}\n").is_empty());
+}
+
+#[test]
+fn test_repo_map_ranks_referenced_files_first() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join(".git"))?;
+    fs::create_dir_all(dir.path().join("src"))?;
+    fs::write(
+        dir.path().join("src/config.rs"),
+        "pub struct ConfigLoader;\n",
+    )?;
+    fs::write(dir.path().join("src/util.rs"), "pub fn slugify() {}\n")?;
+    fs::write(
+        dir.path().join("src/main.rs"),
+        "fn main() {\n    ConfigLoader::load();\n    slugify();\n}\n",
+    )?;
+    fs::write(
+        dir.path().join("src/server.rs"),
+        "pub fn serve(config: ConfigLoader) {}\n",
+    )?;
+    // Not source, so neither listed nor counted as a reference
+    fs::write(dir.path().join("notes.md"), "ConfigLoader slugify serve\n")?;
+
+    let files = RepoMap::files(dir.path())?;
+    let ranked: Vec<(&str, usize)> = files
+        .iter()
+        .map(|file| (file.path.as_str(), file.referenced_by))
+        .collect();
+    assert_eq!(
+        ranked,
+        vec![
+            ("src/config.rs", 2),
+            ("src/util.rs", 1),
+            ("src/main.rs", 0),
+            ("src/server.rs", 0),
+        ]
+    );
+
+    let outline = RepoMap::outline(dir.path(), 1024)?;
+    assert!(
+        outline.starts_with("src/config.rs:\n  1: pub struct ConfigLoader;\n"),
+        "{outline}"
+    );
+    assert!(!outline.contains("more files"), "{outline}");
+    Ok(())
+}
+
+#[test]
+fn test_repo_map_render_keeps_to_budget() {
+    let file = |path: &str, count: usize| MappedFile {
+        path: path.to_string(),
+        symbols: (1..=count)
+            .map(|line| MapSymbol {
+                name: format!("item_{line}"),
+                kind: "fn".to_string(),
+                line,
+                signature: format!("pub fn item_{line}()"),
+                member: line % 2 == 0,
+            })
+            .collect(),
+        referenced_by: 0,
+        score: 0.0,
+    };
+    let files = vec![file("a.rs", 2), file("b.rs", 20), file("c.rs", 1)];
+
+    let full = RepoMap::render(&files, 1000);
+    assert_eq!(
+        full.lines().take(3).collect::<Vec<_>>(),
+        vec!["a.rs:", "  1: pub fn item_1()", "    2: pub fn item_2()"]
+    );
+    assert!(!full.contains("more"), "{full}");
+
+    // Roughly four characters a token: room for a.rs and part of b.rs
+    let short = RepoMap::render(&files, 40);
+    assert!(short.contains("a.rs:\n"), "{short}");
+    assert!(short.contains("b.rs:\n  1: pub fn item_1()\n"), "{short}");
+    assert!(short.contains("more symbols"), "{short}");
+    assert!(!short.contains("c.rs"), "{short}");
+    assert!(
+        short.ends_with("... 1 more files with symbols not shown\n"),
+        "{short}"
+    );
+
+    // A file with too many symbols shows only its top-level ones
+    let large = RepoMap::render(&[file("large.rs", MAX_FILE_SYMBOLS + 2)], 10_000);
+    assert_eq!(
+        large.lines().count(),
+        1 + MAX_FILE_SYMBOLS / 2 + 1 + 1,
+        "{large}"
+    );
+    assert!(!large.contains("    2: "), "{large}");
+    assert!(
+        large.ends_with(&format!(
+            "  ... {} more symbols\n",
+            MAX_FILE_SYMBOLS / 2 + 1
+        )),
+        "{large}"
+    );
+}
+
+#[test]
+fn test_repo_map_cache_follows_changes() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join(".git"))?;
+    fs::write(dir.path().join("lib.py"), "def first():\n    pass\n")?;
+
+    let before = RepoMap::files(dir.path())?;
+    // Nothing changed, so the cached map is returned as is
+    assert!(std::sync::Arc::ptr_eq(
+        &before,
+        &RepoMap::files(dir.path())?
+    ));
+
+    fs::write(
+        dir.path().join("lib.py"),
+        "def first():\n    pass\n\ndef second():\n    pass\n",
+    )?;
+    fs::write(dir.path().join("app.py"), "from lib import second\n")?;
+    let after = RepoMap::files(dir.path())?;
+    assert_eq!(after.len(), 1);
+    let names: Vec<&str> = after[0].symbols.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["first", "second"]);
+    assert_eq!(after[0].referenced_by, 1);
+
+    RepoMap::invalidate(dir.path());
+    assert_eq!(RepoMap::files(dir.path())?, after);
+    assert!(RepoMap::files(Path::new("/definitely/not/here")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_repo_map_remembers_empty_workspace() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("notes.txt"), "nothing to map\n")?;
+    assert!(RepoMap::files(dir.path())?.is_empty());
+
+    // Not walked again straight away, so the new file isn't seen yet
+    fs::write(dir.path().join("lib.py"), "def first():\n    pass\n")?;
+    assert!(RepoMap::files(dir.path())?.is_empty());
+
+    RepoMap::invalidate(dir.path());
+    assert_eq!(RepoMap::files(dir.path())?.len(), 1);
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use anyhow::Result;
-use oli_server::tools::fs::search::SearchTools;
+use oli_server::tools::fs::search::{GrepOptions, GrepOutputMode, SearchTools};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
+use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
 
 fn setup_test_directory() -> Result<TempDir> {
@@ -1267,3 +1268,163 @@ fn test_very_large_file_handling() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_grep_context_and_output_modes() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let content = (1..=20)
+        .map(|i| {
+            if i == 5 || i == 7 || i == 15 {
+                format!("line {i} TODO")
+            } else {
+                format!("line {i}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    write_file(temp_dir.path().join("notes.txt"), &content)?;
+    write_file(temp_dir.path().join("other.txt"), "todo later\nTODO now")?;
+
+    let options = GrepOptions::default()
+        .with_include("*notes.txt")
+        .with_context(1);
+    let results = SearchTools::grep_search_with("TODO", Some(temp_dir.path()), &options)?;
+    let output = SearchTools::format_grep_results(&results, "TODO", &options);
+    let path = temp_dir.path().join("notes.txt");
+    let path = path.display();
+
+    // Overlapping context is merged; separate groups are split by "--"
+    let expected = format!(
+        "Found 3 matches for pattern 'TODO':\n\n\
+         {path}-4-line 4\n{path}:5:line 5 TODO\n{path}-6-line 6\n{path}:7:line 7 TODO\n{path}-8-line 8\n\
+         --\n\
+         {path}-14-line 14\n{path}:15:line 15 TODO\n{path}-16-line 16\n"
+    );
+    assert_eq!(output, expected);
+
+    // Count and files_with_matches modes, case-insensitively
+    let options = GrepOptions::default()
+        .with_case_insensitive(true)
+        .with_output_mode(GrepOutputMode::Count);
+    let results = SearchTools::grep_search_with("todo", Some(temp_dir.path()), &options)?;
+    let output = SearchTools::format_grep_results(&results, "todo", &options);
+    assert!(output.contains("notes.txt:3\n"), "{output}");
+    assert!(output.contains("other.txt:2\n"), "{output}");
+
+    let options = options.with_output_mode(GrepOutputMode::FilesWithMatches);
+    let output = SearchTools::format_grep_results(&results, "todo", &options);
+    assert!(output.starts_with("Found 2 files with matches"), "{output}");
+    assert!(!output.contains(":3"), "{output}");
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_pagination() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let content = (1..=10)
+        .map(|i| format!("match {i}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    write_file(temp_dir.path().join("many.txt"), &content)?;
+
+    let options = GrepOptions::default().with_head_limit(3).with_offset(4);
+    let results = SearchTools::grep_search_with("match", Some(temp_dir.path()), &options)?;
+    let output = SearchTools::format_grep_results(&results, "match", &options);
+
+    assert!(output.contains(":5:match 5\n"), "{output}");
+    assert!(output.contains(":7:match 7\n"), "{output}");
+    assert!(!output.contains(":4:match 4\n"), "{output}");
+    assert!(!output.contains(":8:match 8\n"), "{output}");
+    assert!(
+        output.contains("[Showing matches 5-7 of 10; 3 more. Pass offset=7"),
+        "{output}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_type_filter_and_multiline() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    write_file(
+        temp_dir.path().join("lib.rs"),
+        "struct Config {\n    port: u16,\n}\n",
+    )?;
+    write_file(
+        temp_dir.path().join("app.py"),
+        "class Config:\n    port = 1\n",
+    )?;
+    write_file(temp_dir.path().join("index.tsx"), "const Config = {};\n")?;
+
+    // The type filter maps to every extension of the language
+    let options = GrepOptions::default().with_file_type("ts");
+    let results = SearchTools::grep_search_with("Config", Some(temp_dir.path()), &options)?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].path.ends_with("index.tsx"));
+
+    let options = GrepOptions::default().with_file_type("rs");
+    let results = SearchTools::grep_search_with("Config", Some(temp_dir.path()), &options)?;
+    assert_eq!(results.len(), 1);
+    assert!(results[0].path.ends_with("lib.rs"));
+
+    // Without multiline a pattern can't cross a line break
+    let pattern = r"struct Config \{.*port";
+    let results = SearchTools::grep_search_with(pattern, Some(temp_dir.path()), &options)?;
+    assert!(results.is_empty());
+
+    let options = options.with_multiline(true);
+    let results = SearchTools::grep_search_with(pattern, Some(temp_dir.path()), &options)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].matches[0].line_number, 1);
+    assert_eq!(
+        results[0].matches[0].text,
+        "struct Config {\n    port: u16,"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_glob_sorted_by_mtime_and_paginated() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let now = SystemTime::now();
+    // file0 is the oldest and file4 the newest
+    for i in 0..5 {
+        let path = temp_dir.path().join(format!("file{i}.md"));
+        write_file(&path, "log")?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(now - Duration::from_secs(100 * (5 - i)))?;
+    }
+
+    let results = SearchTools::glob_search_in_dir(temp_dir.path(), "*.md")?;
+    let names: Vec<String> = results
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["file4.md", "file3.md", "file2.md", "file1.md", "file0.md"]
+    );
+
+    // A page in the middle keeps the global numbering and says what's left
+    let page = SearchTools::paginate_glob_results(results.clone(), Some(2), 1);
+    assert_eq!(page.total, 5);
+    assert_eq!(page.remaining(), 2);
+    let output = SearchTools::format_glob_results(&page, "*.md");
+    assert!(output.starts_with("Found 5 files matching pattern '*.md'"));
+    assert!(output.contains("2. ") && output.contains("file3.md"));
+    assert!(output.contains("3. ") && output.contains("file2.md"));
+    assert!(!output.contains("file4.md") && !output.contains("file1.md"));
+    assert!(output.contains("Results truncated, 2 more. Pass offset=3"));
+
+    // The last page has no marker
+    let page = SearchTools::paginate_glob_results(results, Some(10), 3);
+    assert_eq!(page.paths.len(), 2);
+    let output = SearchTools::format_glob_results(&page, "*.md");
+    assert!(!output.contains("truncated"));
+
+    Ok(())
+}
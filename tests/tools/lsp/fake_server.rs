@@ -0,0 +1,231 @@
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::{tempdir, TempDir};
+
+use oli_server::tools::lsp::{LspServerManager, Position, ServerRegistry, ServerSpec};
+
+/// A language server that logs every message it gets, crashes on hover and
+/// takes a second to answer a workspace symbol search.
+/// It reports one error on the first line of each document it's sent,
+/// formats by inserting a comment naming the options, and offers a code
+/// action of each shape: with an edit, resolved later, a bare command that
+/// asks for an edit while it runs, and disabled. Its call hierarchy has
+/// `main` called by `run` and `retry`, which call each other, and `main`
+/// calling `helper`; its type hierarchy is `Base` <- `Child` <- `Leaf`.
+const FAKE_SERVER: &str = r##"
+import json, sys, time
+
+log = open(sys.argv[1], "a")
+
+def record(message):
+    log.write(json.dumps(message) + "\n")
+    log.flush()
+
+def read():
+    length = 0
+    while True:
+        line = sys.stdin.buffer.readline()
+        if not line:
+            sys.exit(0)
+        line = line.strip()
+        if not line:
+            break
+        name, _, value = line.partition(b":")
+        if name.lower() == b"content-length":
+            length = int(value)
+    return json.loads(sys.stdin.buffer.read(length))
+
+def send(message):
+    message["jsonrpc"] = "2.0"
+    body = json.dumps(message).encode()
+    sys.stdout.buffer.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+    sys.stdout.buffer.flush()
+
+def insert(uri, line, text):
+    position = {"line": line, "character": 0}
+    return {"changes": {uri: [{"range": {"start": position, "end": position}, "newText": text}]}}
+
+def fix_command(uri):
+    return {"title": "Fix", "command": "fake.fix", "arguments": [uri]}
+
+LINES = {"main": 0, "run": 1, "retry": 2, "helper": 3, "Base": 0, "Child": 1, "Leaf": 2}
+CALLERS = {"main": ["run", "retry"], "run": ["retry"], "retry": ["run"]}
+CALLEES = {"main": ["helper"]}
+SUBTYPES = {"Base": ["Child"], "Child": ["Leaf"]}
+SUPERTYPES = {"Child": ["Base"], "Leaf": ["Child"]}
+
+def item(uri, name, kind):
+    line = LINES[name]
+    span = {"start": {"line": line, "character": 4}, "end": {"line": line, "character": 4 + len(name)}}
+    return {"name": name, "kind": kind, "uri": uri, "range": span, "selectionRange": span,
+            "data": name}
+
+def calls(related, field, target):
+    name, uri = target["data"], target["uri"]
+    return [{field: item(uri, other, 12),
+             "fromRanges": [{"start": {"line": LINES[other], "character": 8},
+                             "end": {"line": LINES[other], "character": 9}}]}
+            for other in related.get(name, [])]
+
+def types(related, target):
+    others = related.get(target["data"])
+    return [item(target["uri"], other, 5) for other in others] if others else None
+
+while True:
+    message = read()
+    record(message)
+    method = message.get("method")
+    params = message.get("params") or {}
+    if method == "exit":
+        sys.exit(0)
+    if method in ("textDocument/didOpen", "textDocument/didChange"):
+        error = {
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 3}},
+            "severity": 1,
+            "message": "undefined name",
+            "data": {"fixable": True},
+        }
+        send({"method": "textDocument/publishDiagnostics",
+              "params": {"uri": params["textDocument"]["uri"], "diagnostics": [error]}})
+    if "id" not in message:
+        continue
+
+    if method == "initialize":
+        result = {"capabilities": {
+            "textDocumentSync": {"openClose": True, "change": 2},
+            "codeActionProvider": {"resolveProvider": True},
+            "documentFormattingProvider": True,
+        }}
+    elif method == "textDocument/hover":
+        sys.exit(1)
+    elif method == "shutdown":
+        result = None
+    elif method == "workspace/symbol":
+        time.sleep(1)
+        result = []
+    elif method == "textDocument/formatting":
+        options = params["options"]
+        comment = "# tabSize=%d insertSpaces=%s\n" % (options["tabSize"], options["insertSpaces"])
+        result = insert(params["textDocument"]["uri"], 0, comment)["changes"].popitem()[1]
+    elif method == "textDocument/codeAction":
+        uri = params["textDocument"]["uri"]
+        result = [
+            {"title": "Import name", "kind": "quickfix", "isPreferred": True,
+             "diagnostics": params["context"]["diagnostics"], "edit": insert(uri, 0, "import name\n")},
+            {"title": "Organize imports", "kind": "source.organizeImports", "data": uri},
+            fix_command(uri),
+            {"title": "Extract", "kind": "refactor.extract",
+             "disabled": {"reason": "Select an expression"}},
+        ]
+    elif method == "codeAction/resolve":
+        result = dict(message["params"], edit=insert(message["params"]["data"], 0, "# sorted\n"),
+                      command=fix_command(message["params"]["data"]))
+    elif method == "workspace/executeCommand":
+        send({"id": "apply-1", "method": "workspace/applyEdit",
+              "params": {"edit": insert(params["arguments"][0], 1000, "# fixed\n")}})
+        answer = read()
+        record({"method": "workspace/applyEdit:answer", "result": answer.get("result")})
+        result = None
+    elif method == "textDocument/prepareCallHierarchy":
+        result = [item(params["textDocument"]["uri"], "main", 12)]
+    elif method == "callHierarchy/incomingCalls":
+        result = calls(CALLERS, "from", params["item"])
+    elif method == "callHierarchy/outgoingCalls":
+        result = calls(CALLEES, "to", params["item"])
+    elif method == "textDocument/prepareTypeHierarchy":
+        result = [item(params["textDocument"]["uri"], "Base", 5)]
+    elif method == "typeHierarchy/subtypes":
+        result = types(SUBTYPES, params["item"])
+    elif method == "typeHierarchy/supertypes":
+        result = types(SUPERTYPES, params["item"])
+    else:
+        result = []
+    send({"id": message["id"], "result": result})
+"##;
+
+pub struct FakeProject {
+    dir: TempDir,
+    log: PathBuf,
+    pub manager: LspServerManager,
+}
+
+impl FakeProject {
+    /// A project with a `.fake` file handled by the fake server, or `None`
+    /// when python3 isn't available to run it
+    pub fn new() -> Option<Self> {
+        let python = std::process::Command::new("python3")
+            .arg("--version")
+            .output();
+        if !python.is_ok_and(|output| output.status.success()) {
+            println!("Skipping: python3 not installed");
+            return None;
+        }
+
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let script = root.join("fake_server.py");
+        let log = root.join("messages.log");
+        fs::write(&script, FAKE_SERVER).unwrap();
+        fs::write(root.join("fake.toml"), "").unwrap();
+        fs::write(root.join("main.fake"), "one\ntwo\n").unwrap();
+
+        let spec = ServerSpec::new("fake", "python3")
+            .with_args(&[&script.to_string_lossy(), &log.to_string_lossy()])
+            .with_extensions(&["fake"])
+            .with_root_markers(&["fake.toml"]);
+        let manager = LspServerManager::new()
+            .with_registry(ServerRegistry::builtin().with_servers(vec![spec]));
+        Some(Self { dir, log, manager })
+    }
+
+    pub fn file(&self) -> PathBuf {
+        self.dir.path().canonicalize().unwrap().join("main.fake")
+    }
+
+    pub fn definition(&self) -> anyhow::Result<usize> {
+        let position = Position {
+            line: 0,
+            character: 0,
+        };
+        self.manager
+            .definition(&self.file().to_string_lossy(), &position, None)
+            .map(|locations| locations.len())
+    }
+
+    /// Logged messages with a method
+    pub fn messages(&self, method: &str) -> Vec<Value> {
+        fs::read_to_string(&self.log)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|message| message["method"] == method)
+            .collect()
+    }
+
+    /// Methods of the logged messages, in the order they came
+    pub fn methods(&self) -> Vec<String> {
+        fs::read_to_string(&self.log)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let message = serde_json::from_str::<Value>(line).ok()?;
+                Some(message["method"].as_str()?.to_string())
+            })
+            .collect()
+    }
+
+    /// Wait for the server to log a message, since notifications aren't
+    /// answered
+    pub fn wait_for(&self, method: &str, count: usize) -> Vec<Value> {
+        for _ in 0..200 {
+            let messages = self.messages(method);
+            if messages.len() >= count {
+                return messages;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("server never got {count} {method} messages");
+    }
+}
@@ -1 +1,10 @@
+pub mod fake_server;
+pub mod test_code_actions;
+pub mod test_edits;
+pub mod test_hierarchy;
+pub mod test_initialize;
+pub mod test_lifecycle;
 pub mod test_manager;
+pub mod test_models;
+pub mod test_registry;
+pub mod test_transport;
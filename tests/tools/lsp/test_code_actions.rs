@@ -0,0 +1,198 @@
+use serde_json::json;
+use std::fs;
+
+use super::fake_server::FakeProject;
+use oli_server::agent::tools::ToolCall;
+use oli_server::tools::fs::file_ops::FileOps;
+use oli_server::tools::lsp::{FormatParams, LspServerManager, Range};
+
+fn first_line() -> Range {
+    serde_json::from_value(json!({
+        "start": { "line": 0, "character": 0 },
+        "end": { "line": 0, "character": 3 }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_format_uses_the_file_indentation() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.to_string_lossy();
+
+    fs::write(&file, "def f():\n  pass\n").unwrap();
+    let changes = project.manager.format(&path, None).unwrap();
+    assert!(changes
+        .diff()
+        .unwrap()
+        .contains("# tabSize=2 insertSpaces=True"));
+    // Staged only
+    assert_eq!(fs::read_to_string(&file).unwrap(), "def f():\n  pass\n");
+
+    FileOps::apply_changes(&changes).unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "# tabSize=2 insertSpaces=True\ndef f():\n  pass\n"
+    );
+
+    fs::write(&file, "def f():\n\tpass\n").unwrap();
+    let changes = project.manager.format(&path, None).unwrap();
+    assert!(changes.diff().unwrap().contains("insertSpaces=False"));
+}
+
+#[test]
+fn test_format_tool_writes_the_previewed_changes() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.to_string_lossy();
+
+    // The changes a preview showed are written as they are, without asking
+    // the server again
+    let changes = project.manager.format(&path, None).unwrap();
+    let preview = changes.diff().unwrap();
+    let call = ToolCall::Format(FormatParams {
+        file_path: path.to_string(),
+        server_type: None,
+    });
+    let output = call.execute_with_changes(changes).unwrap();
+    assert!(output.contains(preview.trim_end()), "{output}");
+    assert_eq!(project.messages("textDocument/formatting").len(), 1);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "# tabSize=4 insertSpaces=True\none\ntwo\n"
+    );
+}
+
+#[test]
+fn test_code_actions_are_listed_with_diagnostics_and_applied_by_id() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.to_string_lossy();
+
+    let actions = project
+        .manager
+        .code_actions(&path, &first_line(), None)
+        .unwrap();
+    assert_eq!(
+        LspServerManager::format_code_actions(&actions),
+        "[1] Import name (quickfix, preferred)\n\
+         [2] Organize imports (source.organizeImports)\n\
+         [3] Fix\n\
+         [4] Extract (refactor.extract, disabled: Select an expression)"
+    );
+    // The diagnostic on the range is sent along, with the data the server
+    // attached to it
+    let request = &project.messages("textDocument/codeAction")[0];
+    let diagnostics = &request["params"]["context"]["diagnostics"];
+    assert_eq!(diagnostics[0]["message"], "undefined name");
+    assert_eq!(diagnostics[0]["data"], json!({ "fixable": true }));
+
+    let err = project.manager.apply_code_action(4).unwrap_err();
+    assert!(err.to_string().contains("Select an expression"), "{err}");
+    // A failed action stays listed
+    let err = project.manager.apply_code_action(4).unwrap_err();
+    assert!(err.to_string().contains("Select an expression"), "{err}");
+
+    let applied = project.manager.apply_code_action(1).unwrap();
+    assert_eq!(applied.title, "Import name");
+    assert_eq!(applied.paths, [file.as_path()]);
+    assert!(applied.diff.contains("import name"), "{}", applied.diff);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "import name\none\ntwo\n"
+    );
+
+    // The rest were listed for the old content
+    let err = project.manager.apply_code_action(2).unwrap_err();
+    assert!(err.to_string().contains("Unknown code action 2"), "{err}");
+}
+
+#[test]
+fn test_code_action_is_resolved_and_its_command_edits_applied() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.to_string_lossy();
+
+    let actions = project
+        .manager
+        .code_actions(&path, &first_line(), None)
+        .unwrap();
+    let organize = actions
+        .iter()
+        .find(|action| action.title == "Organize imports")
+        .unwrap();
+
+    // Resolved to an edit and a command, which asks for another edit
+    let applied = project.manager.apply_code_action(organize.id).unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "# sorted\none\ntwo\n# fixed\n"
+    );
+    assert!(applied.diff.contains("# sorted"), "{}", applied.diff);
+    assert!(applied.diff.contains("# fixed"), "{}", applied.diff);
+    assert_eq!(applied.paths, [file.clone(), file.clone()]);
+
+    // The server saw the edit before running the command
+    let methods = project.methods();
+    let position = |method: &str| methods.iter().position(|m| m == method).unwrap();
+    assert!(position("codeAction/resolve") < position("textDocument/didChange"));
+    assert!(position("textDocument/didChange") < position("workspace/executeCommand"));
+    let answer = &project.messages("workspace/applyEdit:answer")[0];
+    assert_eq!(answer["result"]["applied"], true);
+
+    // A bare command, listed again for the new content
+    let actions = project
+        .manager
+        .code_actions(&path, &first_line(), None)
+        .unwrap();
+    let fix = actions.iter().find(|action| action.title == "Fix").unwrap();
+    assert!(fix.id > 4);
+    project.manager.apply_code_action(fix.id).unwrap();
+    assert!(fs::read_to_string(&file)
+        .unwrap()
+        .ends_with("# fixed\n# fixed\n"));
+}
+
+#[test]
+fn test_prepared_code_action_is_previewed_then_applied() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.to_string_lossy();
+
+    let actions = project
+        .manager
+        .code_actions(&path, &first_line(), None)
+        .unwrap();
+    let organize = actions
+        .iter()
+        .find(|action| action.title == "Organize imports")
+        .unwrap();
+
+    // Resolved and staged, but nothing written yet
+    let prepared = project.manager.prepare_code_action(organize.id).unwrap();
+    assert_eq!(prepared.title, "Organize imports");
+    assert!(prepared.changes.diff().unwrap().contains("# sorted"));
+    assert!(prepared.command.is_some());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\ntwo\n");
+
+    // Applying it doesn't resolve it again
+    project
+        .manager
+        .apply_prepared_code_action(prepared)
+        .unwrap();
+    assert_eq!(project.messages("codeAction/resolve").len(), 1);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "# sorted\none\ntwo\n# fixed\n"
+    );
+}
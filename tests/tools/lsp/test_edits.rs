@@ -0,0 +1,181 @@
+use serde_json::json;
+use std::fs;
+use tempfile::tempdir;
+
+use oli_server::tools::fs::file_ops::FileOps;
+use oli_server::tools::lsp::{path_to_uri, TextEdit, WorkspaceEdits};
+
+fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+    serde_json::from_value(json!({
+        "range": {
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 }
+        },
+        "newText": new_text
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_text_edits_apply_in_any_order() {
+    let content = "let x = 1;\nprint(x);\n";
+    let edits = [edit((1, 6), (1, 7), "count"), edit((0, 4), (0, 5), "count")];
+
+    let result = WorkspaceEdits::apply_text_edits(content, &edits).unwrap();
+    assert_eq!(result, "let count = 1;\nprint(count);\n");
+}
+
+#[test]
+fn test_text_edit_positions_count_utf16_units() {
+    // The emoji is two UTF-16 units and four bytes
+    let content = "s = \"😀\"; x = 1\n";
+    let result = WorkspaceEdits::apply_text_edits(content, &[edit((0, 10), (0, 11), "y")]).unwrap();
+    assert_eq!(result, "s = \"😀\"; y = 1\n");
+}
+
+#[test]
+fn test_insertions_at_one_place_keep_their_order() {
+    let result = WorkspaceEdits::apply_text_edits(
+        "b",
+        &[edit((0, 0), (0, 0), "a"), edit((0, 0), (0, 0), "-")],
+    )
+    .unwrap();
+    assert_eq!(result, "a-b");
+
+    // Past the last line means the end of the document
+    let result = WorkspaceEdits::apply_text_edits("a\n", &[edit((5, 0), (5, 0), "b\n")]).unwrap();
+    assert_eq!(result, "a\nb\n");
+}
+
+#[test]
+fn test_overlapping_text_edits_are_rejected() {
+    let err = WorkspaceEdits::apply_text_edits(
+        "abcdef",
+        &[edit((0, 0), (0, 3), "x"), edit((0, 2), (0, 4), "y")],
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("overlap"), "{err}");
+}
+
+#[test]
+fn test_diff_edit_covers_only_the_change() {
+    let edit = WorkspaceEdits::diff_edit("one\ntwo\nthree\n", "one\n2\nthree\n");
+    assert_eq!((edit.range.start.line, edit.range.start.character), (1, 0));
+    assert_eq!((edit.range.end.line, edit.range.end.character), (1, 3));
+    assert_eq!(edit.new_text, "2");
+
+    let cases = [
+        ("", "new file\n"),
+        ("same", "same"),
+        ("s = \"😀\"\n", "s = \"😁\"\n"),
+        ("a\r\nb\r\n", "a\nb\r\n"),
+        ("a\r\n", "a\r\r\n"),
+        ("aaa", "aa"),
+        ("x😀y", "xy"),
+    ];
+    for (old, new) in cases {
+        let edit = WorkspaceEdits::diff_edit(old, new);
+        let result = WorkspaceEdits::apply_text_edits(old, &[edit]).unwrap();
+        assert_eq!(result, new, "{old:?} -> {new:?}");
+    }
+}
+
+#[test]
+fn test_document_changes_with_file_rename_apply_atomically() {
+    let dir = tempdir().unwrap();
+    let lib = dir.path().join("lib.py");
+    let old_module = dir.path().join("helpers.py");
+    let new_module = dir.path().join("utils.py");
+    fs::write(&lib, "from helpers import run\nrun()\n").unwrap();
+    fs::write(&old_module, "def run():\n    pass\n").unwrap();
+
+    let workspace_edit = json!({
+        "documentChanges": [
+            {
+                "textDocument": { "uri": path_to_uri(&lib), "version": 1 },
+                "edits": [
+                    {
+                        "range": {
+                            "start": { "line": 0, "character": 5 },
+                            "end": { "line": 0, "character": 12 }
+                        },
+                        "newText": "utils"
+                    }
+                ]
+            },
+            {
+                "kind": "rename",
+                "oldUri": path_to_uri(&old_module),
+                "newUri": path_to_uri(&new_module)
+            },
+            {
+                // Edits after a rename address the new path
+                "textDocument": { "uri": path_to_uri(&new_module), "version": null },
+                "edits": [
+                    {
+                        "range": {
+                            "start": { "line": 1, "character": 4 },
+                            "end": { "line": 1, "character": 8 }
+                        },
+                        "newText": "return None",
+                        "annotationId": "rename"
+                    }
+                ]
+            }
+        ]
+    });
+
+    let changes = WorkspaceEdits::to_change_set(&workspace_edit).unwrap();
+    assert!(old_module.exists(), "staging must not touch the disk");
+
+    let diff = FileOps::apply_changes(&changes).unwrap();
+    assert!(diff.contains("helpers.py →"), "{diff}");
+    assert_eq!(
+        fs::read_to_string(&lib).unwrap(),
+        "from utils import run\nrun()\n"
+    );
+    assert!(!old_module.exists());
+    assert_eq!(
+        fs::read_to_string(&new_module).unwrap(),
+        "def run():\n    return None\n"
+    );
+}
+
+#[test]
+fn test_changes_map_and_missing_files() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("a.rs");
+    fs::write(&file, "fn a() {}\n").unwrap();
+
+    let changes = WorkspaceEdits::to_change_set(&json!({
+        "changes": {
+            path_to_uri(&file): [{
+                "range": {
+                    "start": { "line": 0, "character": 3 },
+                    "end": { "line": 0, "character": 4 }
+                },
+                "newText": "b"
+            }]
+        }
+    }))
+    .unwrap();
+    assert_eq!(changes.written_paths(), vec![file.clone()]);
+    assert_eq!(changes.content(&file).unwrap().unwrap(), "fn b() {}\n");
+
+    let missing = dir.path().join("missing.rs");
+    let err = WorkspaceEdits::to_change_set(&json!({
+        "documentChanges": [{ "kind": "delete", "uri": path_to_uri(&missing) }]
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("does not exist"), "{err}");
+
+    let ignored = WorkspaceEdits::to_change_set(&json!({
+        "documentChanges": [{
+            "kind": "delete",
+            "uri": path_to_uri(&missing),
+            "options": { "ignoreIfNotExists": true }
+        }]
+    }))
+    .unwrap();
+    assert!(ignored.is_empty());
+}
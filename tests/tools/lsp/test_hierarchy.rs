@@ -0,0 +1,116 @@
+use super::fake_server::FakeProject;
+use oli_server::tools::lsp::{CallDirection, LspServerManager, Position, TypeDirection};
+
+fn start() -> Position {
+    Position {
+        line: 0,
+        character: 4,
+    }
+}
+
+#[test]
+fn test_callers_followed_without_repeating_cycles() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.display();
+
+    let roots = project
+        .manager
+        .call_hierarchy(
+            &file.to_string_lossy(),
+            &start(),
+            CallDirection::Incoming,
+            3,
+            None,
+        )
+        .unwrap();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].descendants(), 4);
+
+    // `run` and `retry` call each other; each is expanded once
+    assert_eq!(
+        LspServerManager::format_hierarchy(&roots, "←"),
+        format!(
+            "main (Function) {path}:1:5\n\
+             ← run (Function) {path}:2:5 at 2:9\n  \
+             ← retry (Function) {path}:3:5 at 3:9\n    \
+             ← run (Function) {path}:2:5 at 2:9 (see above)\n\
+             ← retry (Function) {path}:3:5 at 3:9 (see above)"
+        )
+    );
+
+    // Depth limits how far calls are followed
+    let roots = project
+        .manager
+        .call_hierarchy(
+            &file.to_string_lossy(),
+            &start(),
+            CallDirection::Incoming,
+            1,
+            None,
+        )
+        .unwrap();
+    assert_eq!(roots[0].descendants(), 2);
+    assert!(roots[0]
+        .children
+        .iter()
+        .all(|child| child.children.is_empty()));
+
+    let roots = project
+        .manager
+        .call_hierarchy(
+            &file.to_string_lossy(),
+            &start(),
+            CallDirection::Outgoing,
+            2,
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        LspServerManager::format_hierarchy(&roots, "→"),
+        format!("main (Function) {path}:1:5\n→ helper (Function) {path}:4:5 at 4:9")
+    );
+}
+
+#[test]
+fn test_type_hierarchy_in_both_directions() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+    let path = file.display();
+
+    let roots = project
+        .manager
+        .type_hierarchy(
+            &file.to_string_lossy(),
+            &start(),
+            TypeDirection::Subtypes,
+            5,
+            None,
+        )
+        .unwrap();
+    assert_eq!(
+        LspServerManager::format_hierarchy(&roots, "↓"),
+        format!(
+            "Base (Class) {path}:1:5\n\
+             ↓ Child (Class) {path}:2:5\n  \
+             ↓ Leaf (Class) {path}:3:5"
+        )
+    );
+
+    // A `null` result means there are none
+    let roots = project
+        .manager
+        .type_hierarchy(
+            &file.to_string_lossy(),
+            &start(),
+            TypeDirection::Supertypes,
+            2,
+            None,
+        )
+        .unwrap();
+    assert_eq!(roots[0].descendants(), 0);
+}
@@ -0,0 +1,97 @@
+use serde_json::{json, Value};
+use std::fs;
+use tempfile::tempdir;
+
+use oli_server::tools::lsp::{LspServerManager, Position, ServerRegistry, ServerSpec};
+
+/// A language server that logs the `initialize` request and answers every
+/// other request with nothing
+const RECORDING_SERVER: &str = r#"
+import json, sys
+
+def read():
+    length = 0
+    while True:
+        line = sys.stdin.buffer.readline()
+        if not line:
+            sys.exit(0)
+        line = line.strip()
+        if not line:
+            break
+        name, _, value = line.partition(b":")
+        if name.lower() == b"content-length":
+            length = int(value)
+    return json.loads(sys.stdin.buffer.read(length))
+
+def send(message):
+    message["jsonrpc"] = "2.0"
+    body = json.dumps(message).encode()
+    sys.stdout.buffer.write(b"Content-Length: %d\r\n\r\n" % len(body) + body)
+    sys.stdout.buffer.flush()
+
+while True:
+    message = read()
+    method = message.get("method")
+    if method == "exit":
+        sys.exit(0)
+    if "id" not in message:
+        continue
+    if method == "initialize":
+        with open(sys.argv[1], "w") as log:
+            json.dump(message["params"], log)
+        result = {"capabilities": {"textDocumentSync": 1}}
+    elif method == "shutdown":
+        result = None
+    else:
+        result = []
+    send({"id": message["id"], "result": result})
+"#;
+
+#[test]
+fn test_initialization_options_reach_the_server() {
+    let python = std::process::Command::new("python3")
+        .arg("--version")
+        .output();
+    if !python.is_ok_and(|output| output.status.success()) {
+        println!("Skipping: python3 not installed");
+        return;
+    }
+
+    let dir = tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let script = root.join("server.py");
+    let log = root.join("initialize.json");
+    fs::write(&script, RECORDING_SERVER).unwrap();
+    fs::write(root.join("fake.toml"), "").unwrap();
+    let file = root.join("main.fake");
+    fs::write(&file, "one\n").unwrap();
+
+    let spec = ServerSpec::new("fake", "python3")
+        .with_args(&[&script.to_string_lossy(), &log.to_string_lossy()])
+        .with_extensions(&["fake"])
+        .with_root_markers(&["fake.toml"])
+        .with_initialization_options(json!({ "checkOnSave": { "command": "clippy" } }));
+    let manager =
+        LspServerManager::new().with_registry(ServerRegistry::builtin().with_servers(vec![spec]));
+
+    let position = Position {
+        line: 0,
+        character: 0,
+    };
+    manager
+        .definition(&file.to_string_lossy(), &position, None)
+        .unwrap();
+    manager.stop_all().unwrap();
+
+    // Sent under the names the protocol gives them, or servers ignore them
+    let params: Value = serde_json::from_str(&fs::read_to_string(&log).unwrap()).unwrap();
+    assert_eq!(
+        params["initializationOptions"],
+        json!({ "checkOnSave": { "command": "clippy" } })
+    );
+    assert!(params["rootUri"]
+        .as_str()
+        .is_some_and(|uri| uri.starts_with("file://")));
+    assert!(params.get("initialization_options").is_none());
+    assert!(params.get("root_uri").is_none());
+}
@@ -0,0 +1,161 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::fake_server::FakeProject;
+use oli_server::tools::lsp::Position;
+
+fn assert_uri_is(message: &Value, path: &Path) {
+    let uri = message["params"]["textDocument"]["uri"].as_str().unwrap();
+    assert!(
+        uri.ends_with(&*path.file_name().unwrap().to_string_lossy()),
+        "{uri}"
+    );
+}
+
+#[test]
+fn test_documents_are_synced_incrementally_then_closed() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    let file = project.file();
+
+    assert_eq!(project.definition().unwrap(), 0);
+    let opened = project.wait_for("textDocument/didOpen", 1);
+    assert_eq!(opened[0]["params"]["textDocument"]["version"], 1);
+    assert_uri_is(&opened[0], &file);
+
+    // Initialize params use LSP's field names
+    let initialize = &project.messages("initialize")[0]["params"];
+    assert!(initialize["rootUri"].is_string(), "{initialize}");
+    assert_eq!(
+        initialize["capabilities"]["textDocument"]["codeAction"]["resolveSupport"]["properties"],
+        json!(["edit", "command"])
+    );
+
+    // Unchanged content isn't sent again
+    project.definition().unwrap();
+    assert!(project.messages("textDocument/didChange").is_empty());
+
+    // Only the changed range is sent
+    fs::write(&file, "one\n2\n").unwrap();
+    project.definition().unwrap();
+    let changed = project.wait_for("textDocument/didChange", 1);
+    assert_eq!(changed[0]["params"]["textDocument"]["version"], 2);
+    assert_eq!(
+        changed[0]["params"]["contentChanges"],
+        json!([{
+            "range": {
+                "start": { "line": 1, "character": 0 },
+                "end": { "line": 1, "character": 3 }
+            },
+            "newText": "2"
+        }])
+    );
+
+    let status = project.manager.status();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].name, "fake");
+    assert_eq!(status[0].state, "running");
+    assert_eq!(status[0].open_documents, 1);
+    assert_eq!(status[0].restarts, 0);
+    #[cfg(target_os = "linux")]
+    assert!(status[0].memory_bytes.is_some_and(|bytes| bytes > 0));
+
+    // A deleted file is closed on the server
+    fs::remove_file(&file).unwrap();
+    project
+        .manager
+        .diagnostics_after_edit(std::slice::from_ref(&file));
+    let closed = project.wait_for("textDocument/didClose", 1);
+    assert_uri_is(&closed[0], &file);
+    assert_eq!(project.manager.status()[0].open_documents, 0);
+
+    // Idle servers are shut down
+    assert!(project
+        .manager
+        .stop_idle(Duration::from_secs(60))
+        .is_empty());
+    assert_eq!(project.manager.stop_idle(Duration::ZERO), ["fake"]);
+    assert!(project.manager.status().is_empty());
+    assert_eq!(project.messages("shutdown").len(), 1);
+}
+
+#[test]
+fn test_crashed_server_is_restarted() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+
+    let position = Position {
+        line: 0,
+        character: 0,
+    };
+    let err = project
+        .manager
+        .hover(&project.file().to_string_lossy(), &position, None)
+        .expect_err("the fake server exits on hover");
+    assert!(err.to_string().contains("restarted"), "{err}");
+
+    // The next request starts a new server and reopens the document
+    assert_eq!(project.definition().unwrap(), 0);
+    assert_eq!(project.messages("initialize").len(), 2);
+    assert_eq!(project.messages("textDocument/didOpen").len(), 2);
+    let status = project.manager.status();
+    assert_eq!(status[0].restarts, 1);
+    assert_eq!(status[0].state, "running");
+}
+
+#[test]
+fn test_status_does_not_wait_for_a_request() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    assert_eq!(project.definition().unwrap(), 0);
+    let workspace = project.file().parent().unwrap().to_path_buf();
+
+    std::thread::scope(|scope| {
+        let search = scope.spawn(|| project.manager.workspace_symbol("main", &workspace, None));
+        project.wait_for("workspace/symbol", 1);
+
+        // The server is answering the search, which takes a second
+        let started = Instant::now();
+        let status = project.manager.status();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(status[0].state, "busy");
+        assert_eq!(status[0].open_documents, 1);
+        assert!(status[0].pid > 0);
+
+        assert!(search.join().unwrap().unwrap().is_empty());
+    });
+    assert_eq!(project.manager.status()[0].state, "running");
+}
+
+#[test]
+fn test_diagnostics_after_edit_waits_once_for_all_files() {
+    let Some(project) = FakeProject::new() else {
+        return;
+    };
+    assert_eq!(project.definition().unwrap(), 0);
+    let root = project.file().parent().unwrap().to_path_buf();
+    let files: Vec<_> = (0..8)
+        .map(|i| {
+            let file = root.join(format!("edited_{i}.fake"));
+            fs::write(&file, format!("line {i}\n")).unwrap();
+            file
+        })
+        .collect();
+
+    // Each file settles for a moment; waiting for them one by one would
+    // take seconds
+    let started = Instant::now();
+    let diagnostics = project.manager.diagnostics_after_edit(&files);
+    assert!(started.elapsed() < Duration::from_millis(1500));
+    assert_eq!(diagnostics.len(), files.len());
+    for ((path, diagnostics), file) in diagnostics.iter().zip(&files) {
+        assert_eq!(path, file);
+        assert_eq!(diagnostics[0].message, "undefined name");
+    }
+    project.wait_for("textDocument/didSave", files.len());
+}
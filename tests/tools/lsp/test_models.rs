@@ -0,0 +1,305 @@
+use serde_json::json;
+use std::path::PathBuf;
+
+use oli_server::tools::lsp::{
+    hover_text, path_to_uri, symbol_kind_name, uri_to_path, CallDirection, CallHierarchyParams,
+    CodeAction, Diagnostic, DocumentSymbol, DocumentSymbolResponse, Location, LspServerManager,
+    Range, TypeDirection, TypeHierarchyParams, WorkspaceSymbol,
+};
+
+fn range(start: (u32, u32), end: (u32, u32)) -> serde_json::Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 }
+    })
+}
+
+fn names(symbols: &[DocumentSymbol]) -> Vec<&str> {
+    symbols.iter().map(|s| s.name.as_str()).collect()
+}
+
+#[test]
+fn test_null_document_symbols_are_empty() {
+    let response = DocumentSymbolResponse::from_value(json!(null)).unwrap();
+    assert!(response.is_empty());
+    assert!(response.into_document_symbols().is_empty());
+}
+
+#[test]
+fn test_hierarchical_document_symbols() {
+    let value = json!([{
+        "name": "Config",
+        "detail": "",
+        "kind": 23,
+        "range": range((0, 0), (10, 1)),
+        "selectionRange": range((0, 11), (0, 17)),
+        "children": [{
+            "name": "path",
+            "kind": 8,
+            "range": range((1, 4), (1, 20)),
+            "selectionRange": range((1, 4), (1, 8))
+        }]
+    }]);
+
+    let symbols = DocumentSymbolResponse::from_value(value)
+        .unwrap()
+        .into_document_symbols();
+    assert_eq!(names(&symbols), ["Config"]);
+    assert_eq!(names(symbols[0].children.as_ref().unwrap()), ["path"]);
+}
+
+#[test]
+fn test_flat_symbols_nested_by_range() {
+    let uri = "file:///tmp/example.py";
+    let value = json!([
+        { "name": "greet", "kind": 6, "containerName": "MyClass",
+          "location": { "uri": uri, "range": range((7, 4), (8, 20)) } },
+        { "name": "MyClass", "kind": 5,
+          "location": { "uri": uri, "range": range((1, 0), (8, 20)) } },
+        { "name": "__init__", "kind": 6, "containerName": "MyClass",
+          "location": { "uri": uri, "range": range((2, 4), (5, 0)) } },
+        { "name": "add", "kind": 12,
+          "location": { "uri": uri, "range": range((11, 0), (12, 16)) } },
+        // Missing a location, so skipped rather than failing the response
+        { "name": "broken", "kind": 13 }
+    ]);
+
+    let response = DocumentSymbolResponse::from_value(value).unwrap();
+    assert!(matches!(response, DocumentSymbolResponse::FlatSymbols(_)));
+    let symbols = response.into_document_symbols();
+
+    assert_eq!(names(&symbols), ["MyClass", "add"]);
+    let methods = symbols[0]
+        .children
+        .as_ref()
+        .expect("methods nest under the class");
+    assert_eq!(names(methods), ["__init__", "greet"]);
+    assert!(symbols[1].children.is_none());
+}
+
+#[test]
+fn test_unparseable_document_symbols_are_an_error() {
+    assert!(DocumentSymbolResponse::from_value(json!([{ "bogus": true }])).is_err());
+    assert!(DocumentSymbolResponse::from_value(json!({ "name": "x" })).is_err());
+}
+
+#[test]
+fn test_diagnostic_format() {
+    let diagnostic: Diagnostic = serde_json::from_value(json!({
+        "range": range((4, 8), (4, 12)),
+        "severity": 1,
+        "code": "E0308",
+        "source": "rustc",
+        "message": "mismatched types\nexpected `u32`, found `&str`"
+    }))
+    .unwrap();
+
+    assert_eq!(
+        diagnostic.format("src/lib.rs"),
+        "src/lib.rs:5:9: error[E0308]: mismatched types\n    expected `u32`, found `&str` (rustc)"
+    );
+
+    let hint: Diagnostic = serde_json::from_value(json!({
+        "range": range((0, 0), (0, 1)),
+        "severity": 4,
+        "message": "unused import"
+    }))
+    .unwrap();
+    assert_eq!(hint.format("a.py"), "a.py:1:1: hint: unused import");
+}
+
+#[test]
+fn test_format_diagnostics_puts_errors_first() {
+    let diagnostic = |line: u32, severity: u32, message: &str| -> Diagnostic {
+        serde_json::from_value(json!({
+            "range": range((line, 0), (line, 1)),
+            "severity": severity,
+            "message": message
+        }))
+        .unwrap()
+    };
+    let files = vec![
+        (
+            PathBuf::from("/nowhere/a.rs"),
+            vec![diagnostic(9, 2, "unused"), diagnostic(2, 1, "broken")],
+        ),
+        (
+            PathBuf::from("/nowhere/b.rs"),
+            vec![diagnostic(0, 1, "also broken")],
+        ),
+    ];
+
+    let output = LspServerManager::format_diagnostics(&files, 2);
+    assert_eq!(
+        output,
+        "/nowhere/a.rs:3:1: error: broken\n/nowhere/b.rs:1:1: error: also broken\n... and 1 more"
+    );
+}
+
+#[test]
+fn test_uri_round_trip() {
+    let path = PathBuf::from("/tmp/my project/naïve.rs");
+    let uri = path_to_uri(&path);
+    assert_eq!(uri, "file:///tmp/my%20project/na%C3%AFve.rs");
+    assert_eq!(uri_to_path(&uri), path);
+    assert_eq!(
+        uri_to_path("file:///home/user/src/lib.rs"),
+        PathBuf::from("/home/user/src/lib.rs")
+    );
+}
+
+#[test]
+fn test_locations_from_every_response_shape() {
+    assert!(Location::from_response(json!(null)).is_empty());
+
+    let single = Location::from_response(json!({
+        "uri": "file:///src/a.rs",
+        "range": range((3, 4), (3, 9))
+    }));
+    assert_eq!(single.len(), 1);
+    assert_eq!(single[0].range.start.line, 3);
+
+    // LocationLinks point at the target's name
+    let links = Location::from_response(json!([{
+        "originSelectionRange": range((0, 0), (0, 1)),
+        "targetUri": "file:///src/b.rs",
+        "targetRange": range((10, 0), (20, 1)),
+        "targetSelectionRange": range((10, 7), (10, 12))
+    }]));
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].uri, "file:///src/b.rs");
+    assert_eq!(links[0].range.start.line, 10);
+    assert_eq!(links[0].range.start.character, 7);
+}
+
+#[test]
+fn test_workspace_symbols_without_range() {
+    let symbols = WorkspaceSymbol::from_response(json!([
+        {
+            "name": "Config",
+            "kind": 23,
+            "containerName": "settings",
+            "location": { "uri": "file:///src/settings.rs", "range": range((4, 0), (9, 1)) }
+        },
+        {
+            "name": "load",
+            "kind": 12,
+            "containerName": "",
+            "location": { "uri": "file:///src/load.rs" }
+        }
+    ]));
+
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0].container_name.as_deref(), Some("settings"));
+    assert_eq!(symbols[0].location.range.start.line, 4);
+    assert_eq!(symbols[1].container_name, None);
+    assert_eq!(symbols[1].location.range.start.line, 0);
+    assert_eq!(symbol_kind_name(symbols[0].kind), "Struct");
+}
+
+#[test]
+fn test_hover_text_shapes() {
+    assert_eq!(
+        hover_text(
+            &json!({ "contents": { "kind": "markdown", "value": "```rust\nfn main()\n```" } })
+        ),
+        Some("```rust\nfn main()\n```".to_string())
+    );
+    assert_eq!(
+        hover_text(&json!({ "contents": [
+            { "language": "python", "value": "def add(a, b)" },
+            "Add two numbers."
+        ] })),
+        Some("```python\ndef add(a, b)\n```\n\nAdd two numbers.".to_string())
+    );
+    assert_eq!(hover_text(&json!({ "contents": "" })), None);
+    assert_eq!(hover_text(&json!(null)), None);
+}
+
+#[test]
+fn test_format_locations_with_source_lines() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("lib.rs");
+    std::fs::write(&file, "fn helper() {}\n\nfn main() {\n    helper();\n}\n").unwrap();
+    let uri = path_to_uri(&file);
+
+    let locations = Location::from_response(json!([
+        { "uri": uri, "range": range((0, 3), (0, 9)) },
+        { "uri": uri, "range": range((3, 4), (3, 10)) }
+    ]));
+    let output = LspServerManager::format_locations(&locations);
+
+    let path = file.display();
+    assert_eq!(
+        output,
+        format!("{path}:1:4: fn helper() {{}}\n{path}:4:5: helper();")
+    );
+}
+
+#[test]
+fn test_code_actions_and_bare_commands() {
+    let action = CodeAction::from_value(
+        1,
+        &json!({ "title": "Import os", "kind": "quickfix", "isPreferred": true, "edit": {} }),
+    )
+    .unwrap();
+    assert_eq!(action.format(), "[1] Import os (quickfix, preferred)");
+
+    let command = CodeAction::from_value(
+        2,
+        &json!({ "title": "Run fixer", "command": "fix", "arguments": [] }),
+    )
+    .unwrap();
+    assert_eq!(command.format(), "[2] Run fixer");
+
+    let disabled = CodeAction::from_value(
+        3,
+        &json!({ "title": "Extract", "disabled": { "reason": "Nothing selected" } }),
+    )
+    .unwrap();
+    assert_eq!(
+        disabled.format(),
+        "[3] Extract (disabled: Nothing selected)"
+    );
+
+    assert!(CodeAction::from_value(4, &json!({ "kind": "quickfix" })).is_none());
+}
+
+#[test]
+fn test_range_intersects() {
+    let line: Range = serde_json::from_value(range((2, 0), (2, 10))).unwrap();
+    let inside: Range = serde_json::from_value(range((2, 4), (2, 5))).unwrap();
+    let touching: Range = serde_json::from_value(range((1, 0), (2, 0))).unwrap();
+    let after: Range = serde_json::from_value(range((3, 0), (3, 1))).unwrap();
+
+    assert!(line.intersects(&inside));
+    assert!(inside.intersects(&line));
+    assert!(line.intersects(&touching));
+    assert!(!line.intersects(&after));
+}
+
+#[test]
+fn test_hierarchy_directions_default_to_callers_and_supertypes() {
+    let position = json!({ "line": 3, "character": 4 });
+    let params: CallHierarchyParams =
+        serde_json::from_value(json!({ "file_path": "a.py", "position": position })).unwrap();
+    assert_eq!(params.direction, CallDirection::Incoming);
+    assert_eq!(params.depth, None);
+
+    let params: CallHierarchyParams = serde_json::from_value(
+        json!({ "file_path": "a.py", "position": position, "direction": "outgoing", "depth": 3 }),
+    )
+    .unwrap();
+    assert_eq!(params.direction, CallDirection::Outgoing);
+    assert_eq!(params.depth, Some(3));
+
+    let params: TypeHierarchyParams = serde_json::from_value(
+        json!({ "file_path": "a.py", "position": position, "direction": "subtypes" }),
+    )
+    .unwrap();
+    assert_eq!(params.direction, TypeDirection::Subtypes);
+    assert!(serde_json::from_value::<TypeHierarchyParams>(
+        json!({ "file_path": "a.py", "position": position, "direction": "incoming" })
+    )
+    .is_err());
+}
@@ -0,0 +1,152 @@
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+use oli_server::tools::lsp::{ServerRegistry, ServerSpec};
+
+fn server_for(registry: &ServerRegistry, path: &str) -> Option<String> {
+    registry
+        .for_path(Path::new(path))
+        .map(|server| server.name.clone())
+}
+
+#[test]
+fn test_builtin_servers_are_picked_by_extension() {
+    let registry = ServerRegistry::builtin();
+    assert_eq!(
+        server_for(&registry, "app/models.py"),
+        Some("pyright".into())
+    );
+    assert_eq!(
+        server_for(&registry, "src/main.rs"),
+        Some("rust-analyzer".into())
+    );
+    assert_eq!(
+        server_for(&registry, "web/App.tsx"),
+        Some("typescript-language-server".into())
+    );
+    assert_eq!(server_for(&registry, "cmd/main.go"), Some("gopls".into()));
+    assert_eq!(
+        server_for(&registry, "src/engine.CPP"),
+        Some("clangd".into())
+    );
+    assert_eq!(server_for(&registry, "README.md"), None);
+
+    let typescript = registry.for_path(Path::new("web/App.tsx")).unwrap();
+    assert_eq!(
+        typescript.language_id_for(Path::new("web/App.tsx")),
+        "typescriptreact"
+    );
+    assert_eq!(
+        typescript.language_id_for(Path::new("web/util.js")),
+        "javascript"
+    );
+}
+
+#[test]
+fn test_servers_found_by_name_or_language() {
+    let registry = ServerRegistry::builtin();
+    assert_eq!(registry.find("Python").unwrap().name, "pyright");
+    assert_eq!(registry.find("Rust").unwrap().name, "rust-analyzer");
+    assert_eq!(registry.find("cpp").unwrap().name, "clangd");
+    assert_eq!(registry.find("GOPLS").unwrap().name, "gopls");
+    assert!(registry.find("cobol").is_none());
+
+    let err = registry.resolve(Path::new("notes.txt"), None).unwrap_err();
+    assert!(err.to_string().contains("No language server"), "{err}");
+    assert!(err.to_string().contains(".txt"), "{err}");
+}
+
+#[test]
+fn test_configured_servers_extend_and_override_builtin_ones() {
+    let dir = tempdir().unwrap();
+    let config = dir.path().join("config.json");
+    fs::write(
+        &config,
+        json!({
+            "lsp": {
+                "servers": [
+                    {
+                        "name": "zls",
+                        "command": "zls",
+                        "extensions": ["zig"],
+                        "root_markers": ["build.zig"]
+                    },
+                    {
+                        "name": "gopls",
+                        "command": "/opt/go/bin/gopls",
+                        "args": ["-remote=auto"],
+                        "extensions": ["go"],
+                        "initialization_options": { "staticcheck": true }
+                    },
+                    {
+                        "name": "pylsp",
+                        "command": "pylsp",
+                        "extensions": ["py"]
+                    }
+                ]
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let registry = ServerRegistry::load_from(&config).unwrap();
+    assert_eq!(server_for(&registry, "build.zig"), Some("zls".into()));
+    assert_eq!(
+        registry
+            .for_path(Path::new("build.zig"))
+            .unwrap()
+            .language_id_for(Path::new("build.zig")),
+        "zig"
+    );
+
+    // Same name: replaced, not duplicated
+    let gopls: Vec<&ServerSpec> = registry
+        .servers()
+        .iter()
+        .filter(|server| server.name == "gopls")
+        .collect();
+    assert_eq!(gopls.len(), 1);
+    assert_eq!(gopls[0].command, "/opt/go/bin/gopls");
+    assert_eq!(
+        gopls[0].initialization_options,
+        Some(json!({ "staticcheck": true }))
+    );
+
+    // A configured server wins over a built-in one for the same extension
+    assert_eq!(server_for(&registry, "app.py"), Some("pylsp".into()));
+    assert!(registry.find("pyright").is_some());
+
+    // No config file: only the built-in servers
+    let registry = ServerRegistry::load_from(&dir.path().join("missing.json")).unwrap();
+    assert_eq!(
+        registry.servers().len(),
+        ServerRegistry::builtin().servers().len()
+    );
+}
+
+#[test]
+fn test_workspace_root_uses_the_servers_markers() {
+    let dir = tempdir().unwrap();
+    let module = dir.path().join("service");
+    let package = module.join("internal/api");
+    fs::create_dir_all(&package).unwrap();
+    fs::write(module.join("go.mod"), "module example.com/service\n").unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    let file = package.join("handler.go");
+    fs::write(&file, "package api\n").unwrap();
+
+    let registry = ServerRegistry::builtin();
+    let gopls = registry.find("gopls").unwrap();
+    assert_eq!(gopls.workspace_root(&file).unwrap(), module);
+    assert_eq!(registry.for_workspace(&module).unwrap().name, "gopls");
+
+    // Without its markers, the git repository is the project
+    let clangd = registry.find("clangd").unwrap();
+    assert_eq!(
+        clangd.workspace_root(&package.join("shim.c")).unwrap(),
+        dir.path()
+    );
+}
@@ -0,0 +1,275 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, PipeReader, PipeWriter, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use oli_server::tools::lsp::LspTransport;
+
+/// The server side of a transport: reads what the client writes, writes what
+/// the client reads
+struct FakeServer {
+    reader: BufReader<PipeReader>,
+    writer: PipeWriter,
+}
+
+impl FakeServer {
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        self.writer.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+fn connect() -> (LspTransport, FakeServer) {
+    let (client_reader, server_writer) = std::io::pipe().unwrap();
+    let (server_reader, client_writer) = std::io::pipe().unwrap();
+    let transport = LspTransport::new(client_reader, client_writer);
+    let server = FakeServer {
+        reader: BufReader::new(server_reader),
+        writer: server_writer,
+    };
+    (transport, server)
+}
+
+#[test]
+fn test_response_matched_by_id_past_notifications_and_server_requests() {
+    let (transport, mut server) = connect();
+
+    let server_thread = thread::spawn(move || {
+        let request = server.receive();
+        assert_eq!(request["method"], "textDocument/hover");
+        let id = request["id"].clone();
+
+        // Chatter that used to be mistaken for the response
+        server.send(json!({
+            "jsonrpc": "2.0",
+            "method": "window/logMessage",
+            "params": { "type": 3, "message": "indexing" }
+        }));
+        server.send(json!({
+            "jsonrpc": "2.0",
+            "id": "config-1",
+            "method": "workspace/configuration",
+            "params": { "items": [{ "section": "a" }, { "section": "b" }] }
+        }));
+
+        // The client answers the server's request before getting its response
+        let reply = server.receive();
+        assert_eq!(reply["id"], "config-1");
+        assert_eq!(reply["result"], json!([null, null]));
+
+        server.send(json!({ "jsonrpc": "2.0", "id": id, "result": { "contents": "u32" } }));
+        server
+    });
+
+    let response = transport
+        .request(
+            "textDocument/hover",
+            Some(json!({})),
+            Duration::from_secs(5),
+        )
+        .expect("request should get its response");
+    assert_eq!(response.result, Some(json!({ "contents": "u32" })));
+
+    server_thread.join().unwrap();
+    let notifications = transport.take_notifications();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0].method, "window/logMessage");
+    assert!(transport.take_notifications().is_empty());
+}
+
+#[test]
+fn test_out_of_order_responses() {
+    let (transport, mut server) = connect();
+    let transport = std::sync::Arc::new(transport);
+
+    let first = {
+        let transport = transport.clone();
+        thread::spawn(move || transport.request("first", None, Duration::from_secs(5)))
+    };
+    let first_request = server.receive();
+    let second = {
+        let transport = transport.clone();
+        thread::spawn(move || transport.request("second", None, Duration::from_secs(5)))
+    };
+    let second_request = server.receive();
+
+    // Answer the later request first
+    server.send(json!({ "jsonrpc": "2.0", "id": second_request["id"], "result": 2 }));
+    server.send(json!({ "jsonrpc": "2.0", "id": first_request["id"], "result": 1 }));
+
+    assert_eq!(first.join().unwrap().unwrap().result, Some(json!(1)));
+    assert_eq!(second.join().unwrap().unwrap().result, Some(json!(2)));
+}
+
+#[test]
+fn test_unhandled_server_request_gets_method_not_found() {
+    let (_transport, mut server) = connect();
+
+    server.send(json!({ "jsonrpc": "2.0", "id": 7, "method": "custom/unknown" }));
+    let reply = server.receive();
+    assert_eq!(reply["id"], 7);
+    assert_eq!(reply["error"]["code"], -32601);
+    assert!(reply.get("result").is_none());
+}
+
+#[test]
+fn test_apply_edit_requests_collected_only_when_asked() {
+    let (transport, mut server) = connect();
+    let mut apply_edit = |id: &str| {
+        server.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "workspace/applyEdit",
+            "params": { "edit": { "changes": {} } }
+        }));
+        server.receive()["result"]["applied"].clone()
+    };
+
+    assert_eq!(apply_edit("before"), false);
+
+    transport.collect_requested_edits();
+    assert_eq!(apply_edit("during"), true);
+    assert_eq!(transport.take_requested_edits(), [json!({ "changes": {} })]);
+
+    // Refused again once taken
+    assert_eq!(apply_edit("after"), false);
+    assert!(transport.take_requested_edits().is_empty());
+}
+
+#[test]
+fn test_request_times_out_and_is_cancelled() {
+    let (transport, mut server) = connect();
+
+    let result = transport.request("slow", None, Duration::from_millis(100));
+    let err = result.expect_err("request should time out");
+    assert!(err.to_string().contains("timed out"), "{err}");
+
+    let request = server.receive();
+    let cancel = server.receive();
+    assert_eq!(cancel["method"], "$/cancelRequest");
+    assert_eq!(cancel["params"]["id"], request["id"]);
+}
+
+#[test]
+fn test_server_exit_fails_pending_request() {
+    let (transport, mut server) = connect();
+
+    let server_thread = thread::spawn(move || {
+        server.receive();
+        // Dropping the server closes both pipes
+    });
+
+    let err = transport
+        .request("anything", None, Duration::from_secs(10))
+        .expect_err("request should fail when the server exits");
+    assert!(err.to_string().contains("exited"), "{err}");
+    server_thread.join().unwrap();
+    assert!(transport.is_closed());
+    assert!(transport.notify("exit", None).is_err());
+}
+
+#[test]
+fn test_progress_tracked_until_end() {
+    let (transport, mut server) = connect();
+
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": { "token": "index", "value": { "kind": "begin", "title": "Indexing" } }
+    }));
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": { "token": 3, "value": { "kind": "begin", "title": "Loading" } }
+    }));
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": { "token": "index", "value": { "kind": "report", "percentage": 50 } }
+    }));
+    wait_for(|| transport.active_progress().len() == 2);
+    assert_eq!(transport.active_progress(), ["Indexing", "Loading"]);
+
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": { "token": "index", "value": { "kind": "end" } }
+    }));
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": { "token": 3, "value": { "kind": "end" } }
+    }));
+    wait_for(|| transport.active_progress().is_empty());
+    // Progress notifications are still buffered for callers
+    assert_eq!(transport.take_notifications().len(), 5);
+}
+
+fn wait_for(condition: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("condition not reached");
+}
+
+#[test]
+fn test_published_diagnostics_replace_earlier_ones() {
+    let (transport, mut server) = connect();
+    let path = std::path::Path::new("/work/src/lib.rs");
+    let generation = transport.diagnostics_generation();
+
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+            "uri": "file:///work/src/lib.rs",
+            "diagnostics": [{
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 1, "character": 3 }
+                },
+                "severity": 1,
+                "message": "cannot find value `x`"
+            }]
+        }
+    }));
+    wait_for(|| transport.diagnostics(path).is_some());
+    assert_eq!(transport.diagnostics(path).unwrap().len(), 1);
+
+    let after_first = transport.diagnostics_generation();
+    assert!(after_first > generation);
+    assert_eq!(transport.diagnostics_since(generation).len(), 1);
+    assert!(transport.diagnostics_since(after_first).is_empty());
+
+    // A fix clears them, published under a differently encoded URI
+    server.send(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": "file:///work/src/lib%2Ers", "diagnostics": [] }
+    }));
+    wait_for(|| transport.diagnostics_generation() > after_first);
+    assert_eq!(transport.diagnostics(path).unwrap().len(), 0);
+    assert_eq!(transport.diagnostics_since(after_first)[0].0, path);
+}
@@ -0,0 +1,2 @@
+mod test_process;
+mod test_session;
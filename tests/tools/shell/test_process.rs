@@ -0,0 +1,92 @@
+use oli_server::tools::shell::process::{OutputBuffer, OutputStream};
+use oli_server::tools::shell::ProcessManager;
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+/// Polls until the process has written at least `lines` lines or exited
+fn wait_for_output(id: &str, lines: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let output = ProcessManager::output(id, None).unwrap();
+        if output.next_since >= lines || !output.info.running {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_output_buffer_drops_oldest_lines() {
+    let mut buffer = OutputBuffer::new(3);
+    for i in 0..5 {
+        buffer.push(OutputStream::Stdout, format!("line {i}"));
+    }
+    assert_eq!(buffer.total(), 5);
+    assert_eq!(buffer.first_retained(), 2);
+
+    // Asking for dropped lines reports how many were lost
+    let (lines, dropped) = buffer.since(0);
+    assert_eq!(dropped, 2);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].text, "line 2");
+
+    let (lines, dropped) = buffer.since(4);
+    assert_eq!(dropped, 0);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].text, "line 4");
+
+    let (lines, _) = buffer.since(5);
+    assert!(lines.is_empty());
+}
+
+#[test]
+fn test_background_process_output_and_exit() {
+    let dir = tempdir().unwrap();
+    let info =
+        ProcessManager::start("echo first; echo oops >&2; echo second", Some(dir.path())).unwrap();
+    assert!(info.id.starts_with("bg-"));
+    wait_for_output(&info.id, 3);
+
+    // Wait for the exit status to be collected too
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut output = ProcessManager::output(&info.id, None).unwrap();
+    while output.info.running && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+        output = ProcessManager::output(&info.id, None).unwrap();
+    }
+    assert!(!output.info.running);
+    assert_eq!(output.info.exit_code, Some(0));
+    assert_eq!(output.next_since, 3);
+
+    let stdout: Vec<&str> = output
+        .lines
+        .iter()
+        .filter(|line| line.stream == OutputStream::Stdout)
+        .map(|line| line.text.as_str())
+        .collect();
+    assert_eq!(stdout, vec!["first", "second"]);
+    assert!(output.to_text().contains("[stderr] oops"));
+
+    // Polling from the returned cursor gives only newer output
+    let newer = ProcessManager::output(&info.id, Some(output.next_since)).unwrap();
+    assert!(newer.lines.is_empty());
+    assert!(newer.to_text().contains("No new output"));
+}
+
+#[test]
+fn test_kill_background_process() {
+    let info = ProcessManager::start("echo ready; sleep 30", None).unwrap();
+    wait_for_output(&info.id, 1);
+
+    let listed = ProcessManager::list();
+    assert!(listed.iter().any(|p| p.id == info.id && p.running));
+
+    let killed = ProcessManager::kill(&info.id).unwrap();
+    assert!(!killed.running);
+
+    let listed = ProcessManager::list();
+    assert!(listed.iter().any(|p| p.id == info.id && !p.running));
+
+    assert!(ProcessManager::output("bg-999999", None).is_err());
+    assert!(ProcessManager::kill("not-an-id").is_err());
+}
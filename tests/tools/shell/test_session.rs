@@ -0,0 +1,154 @@
+use oli_server::tools::shell::{PersistentShell, ShellSession};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_state_persists_between_commands() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(dir_path.join("sub")).unwrap();
+    let mut shell = ShellSession::with_cwd(&dir_path).unwrap();
+
+    // Directory changes, variables and functions all carry over
+    let output = shell
+        .run(
+            "cd sub && export GREETING=hello && greet() { echo \"$GREETING $1\"; }",
+            TIMEOUT,
+        )
+        .unwrap();
+    assert!(output.success());
+    assert_eq!(output.cwd.as_deref(), Some(dir_path.join("sub").as_path()));
+
+    let output = shell.run("pwd; greet world", TIMEOUT).unwrap();
+    assert_eq!(
+        output.stdout,
+        format!("{}\nhello world\n", dir_path.join("sub").display())
+    );
+    assert_eq!(shell.cwd(), Some(dir_path.join("sub").as_path()));
+}
+
+#[test]
+fn test_exit_code_and_separate_streams() {
+    let mut shell = ShellSession::new().unwrap();
+
+    let output = shell
+        .run(
+            "echo out; echo err >&2; printf partial; exit_with() { return $1; }; exit_with 3",
+            TIMEOUT,
+        )
+        .unwrap();
+    assert_eq!(output.exit_code, 3);
+    assert!(!output.success());
+    assert_eq!(output.stdout, "out\npartial");
+    assert_eq!(output.stderr, "err\n");
+
+    // A syntax error is reported without killing the shell
+    let output = shell.run("if then", TIMEOUT).unwrap();
+    assert_ne!(output.exit_code, 0);
+    assert!(output.notice.is_none());
+    assert!(shell.is_alive());
+}
+
+#[test]
+fn test_shell_restarts_after_exit() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().canonicalize().unwrap();
+    let mut shell = ShellSession::with_cwd(&dir_path).unwrap();
+    shell.run("export KEPT=no", TIMEOUT).unwrap();
+
+    let output = shell.run("exit 7", TIMEOUT).unwrap();
+    assert_eq!(output.exit_code, 7);
+    assert!(output.notice.unwrap().contains("new shell was started"));
+
+    // The new shell starts in the same directory, without the old variables
+    let output = shell
+        .run("pwd; echo \"kept=${KEPT:-unset}\"", TIMEOUT)
+        .unwrap();
+    assert_eq!(
+        output.stdout,
+        format!("{}\nkept=unset\n", dir_path.display())
+    );
+}
+
+#[test]
+fn test_timeout_restarts_shell() {
+    let mut shell = ShellSession::new().unwrap();
+
+    let result = shell.run("sleep 30", Duration::from_millis(300));
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("timed out after 300 ms"));
+
+    // The shell is usable again straight away
+    let output = shell.run("echo again", TIMEOUT).unwrap();
+    assert_eq!(output.stdout, "again\n");
+}
+
+#[test]
+fn test_commands_do_not_read_the_script() {
+    let mut shell = ShellSession::new().unwrap();
+
+    // A command reading stdin must not swallow the sentinel lines
+    let output = shell.run("cat", TIMEOUT).unwrap();
+    assert!(output.success());
+    assert_eq!(output.stdout, "");
+
+    let output = shell.run("echo 'single '\"'\"' quote'", TIMEOUT).unwrap();
+    assert_eq!(output.stdout, "single ' quote\n");
+}
+
+#[test]
+fn test_persistent_shell_is_opt_in() {
+    let dir = tempdir().unwrap();
+    let dir_path = dir.path().canonicalize().unwrap();
+    std::fs::create_dir(dir_path.join("sub")).unwrap();
+    let shell = PersistentShell::new(Some(dir_path.clone()));
+    assert!(!shell.is_enabled());
+
+    // Off: every command gets a fresh shell in the session's directory
+    shell.run("cd sub", TIMEOUT).unwrap();
+    let output = shell.run("pwd", TIMEOUT).unwrap();
+    assert_eq!(output.stdout, format!("{}\n", dir_path.display()));
+    let error = shell
+        .run("sleep 30", Duration::from_millis(300))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("timed out after 300 ms"), "{error}");
+
+    // On: the directory carries over until the shell is reset
+    shell.set_enabled(true);
+    shell.run("cd sub", TIMEOUT).unwrap();
+    let output = shell.run("pwd", TIMEOUT).unwrap();
+    assert_eq!(
+        output.stdout,
+        format!("{}\n", dir_path.join("sub").display())
+    );
+    assert_eq!(shell.cwd(), Some(dir_path.join("sub")));
+
+    shell.reset();
+    assert_eq!(shell.cwd(), Some(dir_path.clone()));
+    let output = shell.run("pwd", TIMEOUT).unwrap();
+    assert_eq!(output.stdout, format!("{}\n", dir_path.display()));
+}
+
+#[test]
+fn test_persistent_shell_usable_while_command_runs() {
+    let shell = PersistentShell::new(None);
+    shell.set_enabled(true);
+
+    let running = shell.clone();
+    let command = std::thread::spawn(move || running.run("sleep 1; echo done", TIMEOUT));
+    std::thread::sleep(Duration::from_millis(200));
+
+    // Reading the shell's state doesn't wait for the command
+    let start = Instant::now();
+    assert!(shell.is_enabled());
+    shell.cwd();
+    assert!(start.elapsed() < Duration::from_millis(500));
+    let error = shell.run("echo second", TIMEOUT).unwrap_err().to_string();
+    assert!(error.contains("still running"), "{error}");
+
+    assert_eq!(command.join().unwrap().unwrap().stdout, "done\n");
+    assert_eq!(shell.run("echo again", TIMEOUT).unwrap().stdout, "again\n");
+}